@@ -1,9 +0,0 @@
-#version 420
-
-layout(location = 0) out vec4 color;
-
-
-
-void main() {
-	color = vec4(1.0, 0.5, 0.0, 1.0);
-}
\ No newline at end of file
@@ -1,13 +0,0 @@
-#version 420
-
-layout(location = 0) in vec2 pos;
-
-layout(binding = 0) uniform UBO {
-	mat4 mvp;
-} ubo;
-
-
-
-void main() {
-	gl_Position = ubo.mvp * vec4(pos, 0.0, 1.0);
-}
\ No newline at end of file
@@ -1,16 +1,10 @@
-use crate::shader::VertexData;
-use gears::glam::Vec2;
+use gears::{ecs::TransformComponent, glam::Vec2};
 use specs::{
-    prelude::ParallelIterator, Component, Join, ParJoin, ReadStorage, System, VecStorage,
-    WriteStorage,
+    prelude::ParallelIterator, Component, ParJoin, ReadStorage, System, VecStorage, WriteStorage,
 };
 
 //
 
-#[derive(Component)]
-#[storage(VecStorage)]
-pub struct QuadMesh(pub usize);
-
 #[derive(Component)]
 #[storage(VecStorage)]
 pub struct Pos(pub Vec2);
@@ -25,7 +19,8 @@ pub struct Acc(pub Vec2);
 
 pub struct Move;
 pub struct BoundingBox;
-pub struct UpdateMesh<'r>(pub f32, pub &'r mut [VertexData]);
+/// Interpolated transforms for drawing
+pub struct UpdateTransform(pub f32);
 
 impl<'a> System<'a> for Move {
     type SystemData = (
@@ -92,38 +87,30 @@ impl<'a> System<'a> for BoundingBox {
     }
 }
 
-impl<'a, 'r> System<'a> for UpdateMesh<'r> {
+impl<'a> System<'a> for UpdateTransform {
     type SystemData = (
         ReadStorage<'a, Pos>,
         ReadStorage<'a, Vel>,
         ReadStorage<'a, Acc>,
-        ReadStorage<'a, QuadMesh>,
+        WriteStorage<'a, TransformComponent>,
     );
 
-    fn run(&mut self, (pos_storage, vel_storage, acc_storage, quad_storage): Self::SystemData) {
+    fn run(
+        &mut self,
+        (pos_storage, vel_storage, acc_storage, mut transform_storage): Self::SystemData,
+    ) {
         let dt = self.0;
-        for (pos, vel, acc, quad) in
-            (&pos_storage, &vel_storage, &acc_storage, &quad_storage).join()
-        {
-            // x = x0 + v0 * t + 1/2 * a * t^2
-            let o = pos.0 + vel.0 * dt + 0.5 * acc.0 * dt.powf(2.0);
-
-            let new = [
-                VertexData {
-                    pos: (o + Vec2::new(-0.02, -0.02)).to_array(),
-                },
-                VertexData {
-                    pos: (o + Vec2::new(0.02, -0.02)).to_array(),
-                },
-                VertexData {
-                    pos: (o + Vec2::new(0.02, 0.02)).to_array(),
-                },
-                VertexData {
-                    pos: (o + Vec2::new(-0.02, 0.02)).to_array(),
-                },
-            ];
-
-            self.1[quad.0..quad.0 + 4].copy_from_slice(&new);
-        }
+        (
+            &pos_storage,
+            &vel_storage,
+            &acc_storage,
+            &mut transform_storage,
+        )
+            .par_join()
+            .for_each(|(pos, vel, acc, transform)| {
+                // x = x0 + v0 * t + 1/2 * a * t^2
+                let o = pos.0 + vel.0 * dt + 0.5 * acc.0 * dt.powf(2.0);
+                transform.0.translation = o.extend(0.0);
+            });
     }
 }
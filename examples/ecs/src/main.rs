@@ -1,48 +1,46 @@
-use ecs::{Acc, BoundingBox, Move, Pos, QuadMesh, UpdateMesh, Vel};
+use ecs::{Acc, BoundingBox, Move, Pos, UpdateTransform, Vel};
 use gears::{
     context::Context,
+    ecs::{ColorComponent, TransformComponent, Visible},
+    event::InputEvent,
     frame::Frame,
     game_loop::{Event, Runnable, State},
-    glam::{Mat4, Vec2},
+    glam::{Vec2, Vec3, Vec4},
+    gui::Egui,
     io::input_state::{Input, InputState, Triggered},
-    renderer::{buffer::StagedBuffer, query::RecordPerf, simple_renderer::Renderer},
-    SyncMode, UpdateRate,
+    renderer::{
+        query::RecordPerf,
+        simple_renderer::{RenderError, Renderer},
+        sprite::{Sprite, SpriteBatch},
+    },
+    scene::Transform,
+    winit::event::MouseButton,
+    GearsError, SyncMode, UpdateRate,
 };
-use shader::{UniformData, VertexData};
-use specs::{Builder, DispatcherBuilder, World, WorldExt};
+use specs::{Builder, DispatcherBuilder, Join, ReadStorage, RunNow, World, WorldExt};
 use std::{thread, time::Duration};
-use vulkano::{
-    buffer::{BufferUsage, CpuBufferPool},
-    descriptor_set::WriteDescriptorSet,
-    pipeline::{Pipeline, PipelineBindPoint},
-};
 
 //
 
 mod ecs;
-mod shader;
 
 //
 
 const UPDATE_RATE: UpdateRate = UpdateRate::PerSecond(50);
-const MAX_COUNT: usize = 500;
-const VERTICES: usize = 4;
-const INDICES: usize = 6;
-const MAX_IBO_LEN: usize = VERTICES * MAX_COUNT;
-const MAX_VBO_LEN: usize = INDICES * MAX_COUNT;
-
-gears::static_assertions::const_assert!(MAX_VBO_LEN < (std::u16::MAX as usize));
+const MAX_COUNT: usize = 10_000;
+const SPAWN_MANY: usize = 1_000;
+/// Half of the quad size, in the -1..1 space of the simulation
+const QUAD_SIZE: f32 = 0.02;
 
 //
 
 struct App {
     renderer: Renderer,
     input: InputState,
+    gui: Egui,
 
-    shader: shader::DefaultPipeline,
-    spot: usize,
-    vertex_buffer: CpuBufferPool<[VertexData; MAX_VBO_LEN]>,
-    index_buffer: StagedBuffer<[u16]>,
+    sprites: SpriteBatch,
+    count: usize,
 
     // dispatcher: DispatcherWork,
     world: World,
@@ -51,43 +49,117 @@ struct App {
 impl App {
     fn init(renderer: Renderer) -> Self {
         let input = InputState::new();
-        let indices = (0..MAX_COUNT as u16)
-            .map(|i| [i * 4, i * 4 + 1, i * 4 + 2, i * 4, i * 4 + 2, i * 4 + 3])
-            .flatten()
-            .collect::<Vec<u16>>();
-        // clippy warning fix
-        indices.len();
-
-        let shader = shader::DefaultPipeline::build(&renderer);
-        let vertex_buffer = CpuBufferPool::vertex_buffer(renderer.device.logical().clone());
-        let index_buffer = StagedBuffer::from_iter(
-            &renderer.device,
-            BufferUsage::index_buffer(),
-            indices.into_iter(),
-        )
-        .unwrap();
+        let gui = Egui::new(&renderer).unwrap();
+        let sprites = SpriteBatch::new(&renderer).unwrap();
 
         let mut world = World::new();
-        world.register::<QuadMesh>();
         world.register::<Pos>();
         world.register::<Vel>();
         world.register::<Acc>();
+        world.register::<TransformComponent>();
+        world.register::<ColorComponent>();
+        world.register::<Visible>();
 
         Self {
             renderer,
             input,
+            gui,
 
-            shader,
-            spot: 0,
-            vertex_buffer,
-            index_buffer,
+            sprites,
+            count: 0,
 
             world,
         }
     }
+
+    fn spawn(&mut self) {
+        let (x, y): (f32, f32) = rand::random();
+        self.spawn_at(Vec2::new(x * 2.0 - 1.0, y * 2.0 - 1.0));
+    }
+
+    /// Spawn under the cursor
+    ///
+    /// The cursor position and the window size are both in physical
+    /// pixels, so this is the same at any display scaling.
+    fn spawn_at_cursor(&mut self, state: &State) {
+        let cursor = Vec2::new(state.cursor_pos.x as f32, state.cursor_pos.y as f32);
+        let size = Vec2::new(state.size.0, state.size.1);
+        if size.min_element() > 0.0 {
+            self.spawn_at(cursor / size * 2.0 - 1.0);
+        }
+    }
+
+    /// `pos` in the -1..1 space of the simulation
+    fn spawn_at(&mut self, pos: Vec2) {
+        if self.count >= MAX_COUNT {
+            return;
+        }
+        self.count += 1;
+
+        let (r, g, b): (f32, f32, f32) = rand::random();
+
+        self.world
+            .create_entity()
+            .with(TransformComponent(Transform::from_scale(Vec3::new(
+                QUAD_SIZE, QUAD_SIZE, 1.0,
+            ))))
+            .with(ColorComponent(Vec4::new(r, g, b, 1.0)))
+            .with(Visible)
+            .with(Acc {
+                0: Vec2::new(0.0, 0.001),
+            })
+            .with(Vel { 0: Vec2::ZERO })
+            .with(Pos { 0: pos })
+            .build();
+    }
+
+    fn toggle_pause(state: &mut State) {
+        if state.is_paused() {
+            state.resume();
+        } else {
+            state.pause();
+        }
+    }
+
+    /// Spawn and pause controls
+    fn controls(&mut self, state: &mut State) {
+        let count = self.count;
+        let mut paused = state.is_paused();
+        let (mut spawn, mut spawn_many, mut step) = (false, false, false);
+        self.gui
+            .run(state, |ctx| {
+                gears::egui::Window::new("Controls").show(ctx, |ui| {
+                    ui.label(format!("entities: {}/{}", count, MAX_COUNT));
+                    spawn = ui.button("Spawn").clicked();
+                    spawn_many = ui.button(format!("Spawn {}", SPAWN_MANY)).clicked();
+                    ui.checkbox(&mut paused, "Paused");
+                    step = ui
+                        .add_enabled(paused, gears::egui::Button::new("Step"))
+                        .clicked();
+                });
+            })
+            .unwrap();
+
+        if spawn {
+            self.spawn();
+        }
+        if spawn_many {
+            (0..SPAWN_MANY).for_each(|_| self.spawn());
+        }
+        if paused != state.is_paused() {
+            Self::toggle_pause(state);
+        }
+        if step {
+            state.step(1);
+        }
+    }
 }
 
 impl Runnable for App {
+    fn renderers(&mut self) -> Vec<&mut Renderer> {
+        vec![&mut self.renderer]
+    }
+
     fn update(&mut self, state: &mut State, _: f32) {
         if self.input.get_input(Input::Pause, 0).triggered() {
             state.stop = true;
@@ -96,22 +168,8 @@ impl Runnable for App {
             // simulated freeze
             thread::sleep(Duration::from_millis(500));
         }
-        if self.spot < MAX_COUNT * 4 && self.input.get_input(Input::Stats, 0).triggered() {
-            let spot = self.spot;
-            self.spot += 4;
-
-            let (x, y): (f32, f32) = rand::random();
-            let (x, y) = (x * 2.0 - 1.0, y * 2.0 - 1.0);
-
-            self.world
-                .create_entity()
-                .with(QuadMesh { 0: spot })
-                .with(Acc {
-                    0: Vec2::new(0.0, 0.001),
-                })
-                .with(Vel { 0: Vec2::ZERO })
-                .with(Pos { 0: Vec2::new(x, y) })
-                .build();
+        if self.input.get_input(Input::Stats, 0).triggered() {
+            self.spawn();
         }
 
         DispatcherBuilder::new()
@@ -122,58 +180,72 @@ impl Runnable for App {
     }
 
     fn event(&mut self, state: &mut State, event: &Event) {
+        // clicks on the controls window should not spawn
+        let gui = self.gui.event(event);
+        if !gui && state.cursor_in {
+            if let Some(InputEvent::MouseButton {
+                button: MouseButton::Right,
+                pressed: true,
+            }) = InputEvent::from_event(event)
+            {
+                self.spawn_at_cursor(state);
+            }
+        }
         self.input.event(event);
         if self.input.should_close() {
             state.stop = true;
         }
     }
 
-    fn draw(&mut self, state: &mut State, delta: f32) {
-        let mut fd = self.renderer.begin_frame(state);
-        let viewport = fd.viewport_and_scissor().0;
+    fn input_event(&mut self, state: &mut State, event: &InputEvent) {
+        match event {
+            e if e.pressed(Input::Reload) => Self::toggle_pause(state),
+            e if e.pressed(Input::Next) => state.step(1),
+            _ => {}
+        }
+    }
 
-        let mut recorder = fd.recorder;
+    fn draw(&mut self, state: &mut State, alpha: f32) -> Result<(), GearsError> {
+        self.controls(state);
+
+        let mut fd = match self.renderer.begin_frame(state) {
+            Ok(fd) => fd,
+            // e.g. minimized, try again on the next frame
+            Err(RenderError::OutOfDate) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        let rect = fd.rect();
+
+        let recorder = fd.recorder;
         let perf = fd.perf;
 
-        let mut data = [VertexData::default(); MAX_VBO_LEN];
-        DispatcherBuilder::new()
-            .with(UpdateMesh(delta, &mut data), "mesh", &[])
-            .build()
-            .dispatch(&self.world);
-        let vbo = self.vertex_buffer.next(data).unwrap();
-        self.index_buffer.update(&mut recorder).unwrap();
+        // one sprite per entity, uploaded together in one buffer
+        UpdateTransform(alpha).run_now(&self.world);
+        self.sprites.begin();
+        let extent = Vec2::new(rect.extent[0] as f32, rect.extent[1] as f32);
+        let (transforms, colors, visible) = self.world.system_data::<(
+            ReadStorage<TransformComponent>,
+            ReadStorage<ColorComponent>,
+            ReadStorage<Visible>,
+        )>();
+        for (transform, color, _) in (&transforms, &colors, &visible).join() {
+            let position = (transform.0.translation.truncate() + 1.0) * 0.5 * extent;
+            let size = transform.0.scale.truncate() * extent;
+            self.sprites
+                .draw(Sprite::new(position, size).with_color(color.0));
+        }
 
         let mut recorder = recorder.begin_render_pass();
-
-        let mvp = Mat4::orthographic_rh(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0);
-        let ubo = UniformData { mvp };
-        let ubo = self.shader.buffer_pool.next(ubo).unwrap();
-        let set = self
-            .shader
-            .desc_pool
-            .next([WriteDescriptorSet::buffer(0, ubo)])
-            .unwrap();
-        recorder
-            .record()
-            .begin_perf(&perf)
-            .bind_pipeline_graphics(self.shader.pipeline.clone())
-            .bind_descriptor_sets(
-                PipelineBindPoint::Graphics,
-                self.shader.pipeline.layout().clone(),
-                0,
-                vec![set],
-            )
-            .bind_vertex_buffers(0, vbo)
-            .bind_index_buffer(self.index_buffer.local.clone())
-            .set_viewport(0, [viewport])
-            .draw_indexed(MAX_IBO_LEN as u32, 1, 0, 0, 0)
-            .unwrap()
-            .end_perf(&perf);
+        recorder.record().begin_perf(&perf);
+        self.sprites.end(&mut recorder, rect).unwrap();
+        self.gui.draw(&mut recorder).unwrap();
+        recorder.record().end_perf(&perf);
 
         let recorder = recorder.end_render_pass();
         fd.recorder = recorder;
         fd.perf = perf;
         self.renderer.end_frame(fd);
+        Ok(())
     }
 }
 
@@ -187,8 +259,12 @@ fn main() {
         .build()
         .unwrap();
 
-    frame.game_loop().unwrap().run(
+    let result = frame.game_loop().unwrap().run(
         Some(UPDATE_RATE),
         App::init(Renderer::builder(&frame).build().unwrap()),
     );
+    if let Err(err) = result {
+        log::error!("{err}");
+        std::process::exit(1);
+    }
 }
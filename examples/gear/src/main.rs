@@ -1,22 +1,30 @@
 use gears::{
     context::Context,
+    event::InputEvent,
     frame::Frame,
     game_loop::{Event, Runnable, State},
-    glam::{Mat4, Vec3},
-    io::input_state::{Input, InputAxis, InputState, Triggered},
+    glam::{Mat4, Quat, Vec3, Vec4},
+    gui::Egui,
+    io::{
+        input_state::{Input, InputAxis, InputState, Triggered},
+        orbitcam::OrbitCam,
+    },
     renderer::{
-        buffer::StagedBuffer,
-        object::load_obj,
+        mesh::Mesh,
+        object::{load_gltf, load_obj},
+        overlay::DebugOverlay,
         query::RecordPerf,
-        simple_renderer::{FrameData, Renderer},
+        simple_renderer::{FrameData, RenderError, Renderer},
+        viewport::{Rect, SplitScreen},
     },
-    vulkano::buffer::{BufferUsage, TypedBufferAccess},
-    SyncMode,
+    scene::{Camera, NodeId, PerspectiveConfig, SceneGraph, Transform},
+    winit::event::VirtualKeyCode,
+    GearsError, SyncMode,
 };
-use shader::UniformData;
+use shader::{quad_vert, UniformData};
 use std::{sync::Arc, time::Instant};
 use vulkano::{
-    descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
+    descriptor_set::PersistentDescriptorSet,
     pipeline::{Pipeline, PipelineBindPoint},
 };
 
@@ -26,31 +34,65 @@ mod shader;
 
 //
 
+/// Translucent quads (position, color) between and around the gears
+const QUADS: [(Vec3, Vec4); 3] = [
+    (Vec3::new(0.0, 0.0, -0.6), Vec4::new(1.0, 0.2, 0.2, 0.4)),
+    (Vec3::new(0.0, 0.0, 0.0), Vec4::new(0.2, 1.0, 0.2, 0.4)),
+    (Vec3::new(0.0, 0.0, 0.6), Vec4::new(0.2, 0.4, 1.0, 0.4)),
+];
+
 struct App {
-    frame: Frame,
     renderer: Renderer,
     input: InputState,
 
     shader: shader::DefaultPipeline,
-    vb: StagedBuffer<[shader::VertexData]>,
-
-    distance: f32,
-    position: Vec3,
+    mesh: Mesh<shader::VertexData>,
+    quads: shader::QuadPipeline,
+    quad_mesh: Mesh<shader::QuadVertex>,
+    overlay: DebugOverlay,
+    gui: Egui,
+
+    scene: SceneGraph,
+    root: NodeId,
+    gears: [NodeId; 3],
+    // rotation of the gears and its speed in radians per second
+    spin: f32,
+    speed: f32,
+
+    orbit: OrbitCam,
+    tilt: f32,
     dt: Instant,
+    sync: SyncMode,
+    // load gear.glb instead of gear.obj
+    glb: bool,
+
+    frame: Frame,
 }
 
 impl App {
-    fn init(frame: Frame, renderer: Renderer) -> Self {
+    fn init(frame: Frame, renderer: Renderer, glb: bool) -> Self {
         let input = InputState::new();
         let shader = shader::DefaultPipeline::build(&renderer);
 
-        let vertices = Self::vertex_data();
-        let vb = StagedBuffer::from_iter(
-            &renderer.device,
-            BufferUsage::vertex_buffer(),
-            vertices.into_iter(),
-        )
-        .unwrap();
+        let mesh = Mesh::new(&renderer.device, Self::vertex_data(glb)).unwrap();
+
+        let quads = shader::QuadPipeline::build(&renderer);
+        let quad_mesh = Mesh::new(&renderer.device, shader::QuadPipeline::vertices()).unwrap();
+
+        let sync = renderer.current_sync_mode();
+        let overlay = DebugOverlay::new(&renderer).unwrap();
+        let gui = Egui::new(&renderer).unwrap();
+
+        // three gears that follow the root
+        let mut scene = SceneGraph::new();
+        let root = scene.add(Transform::IDENTITY);
+        let gears = [-1.0, 0.0, 1.0].map(|x| {
+            scene.add_child(
+                root,
+                Transform::from_translation(Vec3::new(x * 1.2, 0.0, 0.0))
+                    .with_scale(Vec3::splat(0.5)),
+            )
+        });
 
         Self {
             frame,
@@ -58,15 +100,43 @@ impl App {
             input,
 
             shader,
-            vb,
-
-            distance: 2.5,
-            position: Vec3::new(0.0, 0.0, 0.0),
+            mesh,
+            quads,
+            quad_mesh,
+            overlay,
+            gui,
+
+            scene,
+            root,
+            gears,
+            spin: 0.0,
+            speed: 1.0,
+
+            orbit: OrbitCam::new(Vec3::ZERO, 3.5),
+            tilt: 0.0,
             dt: Instant::now(),
+            sync,
+            glb,
         }
     }
 
-    fn vertex_data() -> Vec<shader::VertexData> {
+    fn vertex_data(glb: bool) -> Vec<shader::VertexData> {
+        if glb {
+            let meshes = load_gltf(include_bytes!("../res/gear.glb"), |pos, norm, _| {
+                shader::VertexData {
+                    vi_pos: pos.to_array(),
+                    vi_norm: norm.to_array(),
+                }
+            })
+            .unwrap();
+
+            // a single mesh at the origin, drawn without indices like the OBJ
+            return meshes
+                .iter()
+                .flat_map(|mesh| mesh.indices.iter().map(move |&i| mesh.vertices[i as usize]))
+                .collect();
+        }
+
         // TODO: make a macro for loading objects at compile time
         load_obj(include_str!("../res/gear.obj"), None, |pos, norm| {
             shader::VertexData {
@@ -76,54 +146,102 @@ impl App {
         })
     }
 
-    fn update_uniform_buffer(&mut self) -> Arc<PersistentDescriptorSet> {
-        let aspect = self.frame.aspect();
-
+    /// Returns the seconds since the previous frame
+    fn update_camera(&mut self) -> f32 {
         let delta = self.dt.elapsed().as_secs_f32();
         self.dt = Instant::now();
 
-        let distance_delta = self.input.get_axis(InputAxis::Roll, 0).x;
-        let velocity = self.input.get_axis(InputAxis::Move, 0);
-        let roll = self.input.get_axis(InputAxis::Trigger, 0).x;
-        let velocity = Vec3::new(-velocity.x, velocity.y, roll);
-
-        self.distance += distance_delta * 3.0 * delta;
-        self.position += velocity * 3.0 * delta;
-        self.position.y = self
-            .position
-            .y
-            .min(std::f32::consts::PI / 2.0 - 0.0001)
-            .max(-std::f32::consts::PI / 2.0 + 0.0001);
-
-        let eye = Vec3::new(
-            self.position.x.sin() * self.position.y.cos(),
-            self.position.y.sin(),
-            self.position.x.cos() * self.position.y.cos(),
-        ) * self.distance;
-        let focus = Vec3::new(0.0, 0.0, 0.0);
-        let up = Vec3::new(0.0, -1.0, 0.0);
+        self.orbit.update(&self.input, delta);
+        self.tilt += self.input.get_axis(InputAxis::Trigger, 0).x * 3.0 * delta;
+        delta
+    }
+
+    fn update_scene(&mut self, delta: f32) {
+        self.spin += self.speed * delta;
+        self.scene.transform_mut(self.root).rotation =
+            Quat::from_rotation_x(self.tilt) * Quat::from_rotation_y(self.spin);
+
+        // only the dirty nodes get recomputed, once per frame
+        self.scene.update();
+    }
+
+    /// `yaw_offset` rotates the camera around the gears
+    fn camera(&self, rect: Rect, yaw_offset: f32) -> Camera {
+        let mut camera = Camera::perspective(PerspectiveConfig::default())
+            .with_position(self.orbit.eye_with_offset(yaw_offset))
+            .with_aspect(rect.aspect());
+        camera.look_at(self.orbit.target, Vec3::Y);
+        camera
+    }
+
+    /// `n`th uniform set of this frame
+    fn uniform_set(
+        &mut self,
+        frame_in_flight: usize,
+        n: usize,
+        rect: Rect,
+        yaw_offset: f32,
+        model_matrix: Mat4,
+    ) -> Arc<PersistentDescriptorSet> {
+        let camera = self.camera(rect, yaw_offset);
 
         let ubo = UniformData {
-            model_matrix: Mat4::from_rotation_x(self.position.z),
-            view_matrix: Mat4::look_at_rh(eye, focus, up),
-            projection_matrix: Mat4::perspective_rh(1.0, aspect, 0.01, 100.0),
+            model_matrix,
+            view_matrix: camera.view(),
+            projection_matrix: camera.projection_matrix(),
             light_dir: Vec3::new(0.2, 2.0, 0.5).normalize(),
         };
 
-        let ubo = self.shader.buffer_pool.next(ubo).unwrap();
-        let layout = self.shader.pipeline.layout().descriptor_set_layouts()[0].clone();
-        PersistentDescriptorSet::new_with_pool(
-            layout,
-            0,
-            &mut self.shader.desc_pool,
-            [WriteDescriptorSet::buffer(0, ubo)],
-        )
-        .unwrap()
+        self.shader
+            .uniforms
+            .update_nth(frame_in_flight, n, ubo)
+            .unwrap()
+    }
+
+    /// Rotation speed slider
+    fn controls(&mut self, state: &State) {
+        let speed = &mut self.speed;
+        self.gui
+            .run(state, |ctx| {
+                gears::egui::Window::new("Gears").show(ctx, |ui| {
+                    ui.add(gears::egui::Slider::new(speed, -5.0..=5.0).text("speed"));
+                });
+            })
+            .unwrap();
+    }
+
+    /// Push constants of the translucent quads, sorted back to front
+    fn quad_constants(&self, rect: Rect, yaw_offset: f32) -> Vec<quad_vert::ty::PushConstants> {
+        let camera = self.camera(rect, yaw_offset);
+        let eye = self.orbit.eye_with_offset(yaw_offset);
+
+        let mut quads = QUADS;
+        quads.sort_by(|(a, _), (b, _)| {
+            let (a, b) = (a.distance_squared(eye), b.distance_squared(eye));
+            b.partial_cmp(&a).unwrap()
+        });
+
+        quads
+            .iter()
+            .map(|&(position, color)| {
+                let model = Mat4::from_translation(position) * Mat4::from_scale(Vec3::splat(1.6));
+                quad_vert::ty::PushConstants {
+                    mvp: (camera.vp() * model).to_cols_array_2d(),
+                    color: color.to_array(),
+                }
+            })
+            .collect()
     }
 }
 
 impl Runnable for App {
-    fn draw(&mut self, state: &mut State, _: f32) {
+    fn renderers(&mut self) -> Vec<&mut Renderer> {
+        vec![&mut self.renderer]
+    }
+
+    fn draw(&mut self, state: &mut State, _: f32) -> Result<(), GearsError> {
+        self.controls(state);
+
         let FrameData {
             mut recorder,
             viewport,
@@ -133,29 +251,82 @@ impl Runnable for App {
             image_index,
             frame_in_flight,
             future,
-        } = self.renderer.begin_frame(state);
+        } = match self.renderer.begin_frame(state) {
+            Ok(frame_data) => frame_data,
+            // e.g. minimized, try again on the next frame
+            Err(RenderError::OutOfDate) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        // the swapchain is recreated with the new present mode in begin_frame
+        let sync = self.renderer.current_sync_mode();
+        if sync != self.sync {
+            log::info!(
+                "Present mode: {:?} (requested {:?})",
+                sync,
+                self.renderer.sync()
+            );
+            self.sync = sync;
+        }
 
         // outside of render pass
-        self.vb.update(&mut recorder).unwrap();
-        let set = self.update_uniform_buffer();
+        self.mesh.update(&mut recorder)?;
+        self.quad_mesh.update(&mut recorder)?;
+        let delta = self.update_camera();
+        self.update_scene(delta);
+        // drawn over the overlay at the end of the render pass
+        self.gui.paint(&mut recorder).unwrap();
+
+        // two cameras side by side, each drawing every gear
+        let rects = SplitScreen::new(scissor.dimensions).columns(2);
+        let models = self.gears.map(|gear| self.scene.world_matrix(gear));
+        let mut sets = Vec::new();
+        let mut quads = Vec::new();
+        for (rect, yaw_offset) in rects.iter().zip([0.0, std::f32::consts::FRAC_PI_2]) {
+            for model in models {
+                let set = self.uniform_set(frame_in_flight, sets.len(), *rect, yaw_offset, model);
+                sets.push((*rect, set));
+            }
+            quads.push((*rect, self.quad_constants(*rect, yaw_offset)));
+        }
 
         // inside of render pass
         let mut recorder = recorder.begin_render_pass();
-        recorder
-            .record()
-            .begin_perf(&perf)
-            .bind_pipeline_graphics(self.shader.pipeline.clone())
-            .bind_descriptor_sets(
-                PipelineBindPoint::Graphics,
-                self.shader.pipeline.layout().clone(),
-                0,
-                vec![set],
+        recorder.record().begin_perf(&perf);
+        for (rect, set) in sets {
+            recorder
+                .set_viewport_and_scissor(rect)
+                .bind_pipeline_graphics(self.shader.pipeline.clone())
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Graphics,
+                    self.shader.pipeline.layout().clone(),
+                    0,
+                    vec![set],
+                );
+            self.mesh.draw(&mut recorder).unwrap();
+        }
+        // translucent after opaque
+        for (rect, constants) in quads {
+            recorder
+                .set_viewport_and_scissor(rect)
+                .bind_pipeline_graphics(self.quads.pipeline.clone());
+            for constants in constants {
+                recorder.record().push_constants(
+                    self.quads.pipeline.layout().clone(),
+                    0,
+                    constants,
+                );
+                self.quad_mesh.draw(&mut recorder).unwrap();
+            }
+        }
+        recorder.record().end_perf(&perf);
+        self.overlay
+            .draw(
+                &mut recorder,
+                state,
+                Rect::new(scissor.origin, scissor.dimensions),
             )
-            .bind_vertex_buffers(0, self.vb.local.clone())
-            .set_viewport(0, [viewport.clone()])
-            .draw(self.vb.local.len() as u32, 1, 0, 0)
-            .unwrap()
-            .end_perf(&perf);
+            .unwrap();
 
         // outside of render pass again
         let recorder = recorder.end_render_pass();
@@ -170,11 +341,35 @@ impl Runnable for App {
             frame_in_flight,
             future,
         });
+        Ok(())
+    }
+
+    /// Recreate the renderer and everything created with its lost device
+    fn device_lost(&mut self, _: &mut State) -> Result<(), GearsError> {
+        self.renderer.rebuild(&self.frame)?;
+        self.shader = shader::DefaultPipeline::build(&self.renderer);
+        self.mesh = Mesh::new(&self.renderer.device, Self::vertex_data(self.glb))?;
+        self.quads = shader::QuadPipeline::build(&self.renderer);
+        self.quad_mesh = Mesh::new(&self.renderer.device, shader::QuadPipeline::vertices())?;
+        self.overlay = DebugOverlay::new(&self.renderer)?;
+        self.gui = Egui::new(&self.renderer)?;
+        Ok(())
+    }
+
+    /// Only the swapchain is rebuilt, the device is still valid
+    fn surface_lost(&mut self, _: &mut State) -> Result<(), GearsError> {
+        self.frame.recreate_surface()?;
+        self.renderer.rebuild_surface(&self.frame)?;
+        Ok(())
     }
 
     fn event(&mut self, state: &mut State, event: &Event) {
         self.frame.event(event);
         self.input.event(event);
+        // dragging a slider should not orbit the camera
+        if !self.gui.event(event) {
+            self.orbit.event(event);
+        }
 
         if self.input.should_close()
             || self.input.get_input(Input::Pause, 0).triggered()
@@ -183,6 +378,52 @@ impl Runnable for App {
             state.stop = true
         }
     }
+
+    fn input_event(&mut self, _: &mut State, event: &InputEvent) {
+        match event {
+            // toggle vsync
+            InputEvent::KeyPressed {
+                key: Some(VirtualKeyCode::V),
+                ..
+            } => {
+                let sync = match self.renderer.sync() {
+                    SyncMode::Immediate => SyncMode::Fifo,
+                    _ => SyncMode::Immediate,
+                };
+                log::info!("Switching to {:?}", sync);
+                self.renderer.set_sync(sync);
+            }
+            // toggle the fps overlay
+            InputEvent::KeyPressed {
+                key: Some(VirtualKeyCode::F3),
+                ..
+            } => self.overlay.toggle(),
+            _ => {}
+        }
+    }
+}
+
+/// Procedural 32x32 gear for the window icon
+fn icon() -> (u32, u32, Vec<u8>) {
+    const SIZE: u32 = 32;
+    let rgba = (0..SIZE * SIZE)
+        .flat_map(|i| {
+            let x = (i % SIZE) as f32 - SIZE as f32 / 2.0 + 0.5;
+            let y = (i / SIZE) as f32 - SIZE as f32 / 2.0 + 0.5;
+            let r = (x * x + y * y).sqrt();
+            let teeth = if (y.atan2(x) * 8.0).sin() > 0.0 {
+                15.0
+            } else {
+                11.0
+            };
+            if r > 4.0 && r < teeth {
+                [200, 200, 210, 255]
+            } else {
+                [0, 0, 0, 0]
+            }
+        })
+        .collect();
+    (SIZE, SIZE, rgba)
 }
 
 fn main() {
@@ -190,9 +431,15 @@ fn main() {
 
     let context = Context::env().unwrap();
 
+    // `--glb` renders the same gear loaded from glTF
+    let glb = std::env::args().any(|arg| arg == "--glb");
+
+    let (icon_width, icon_height, icon_rgba) = icon();
     let mut frame = Frame::builder(context)
         .with_title("Simple Example")
-        .with_size(600, 600)
+        .with_size(1200, 600)
+        .with_min_size(320, 240)
+        .with_icon(icon_width, icon_height, icon_rgba)
         .with_sync(SyncMode::Immediate)
         .build()
         .unwrap();
@@ -201,7 +448,10 @@ fn main() {
 
     let renderer = Renderer::builder(&frame).build().unwrap();
 
-    let app = App::init(frame, renderer);
+    let app = App::init(frame, renderer, glb);
 
-    game_loop.run(None, app);
+    if let Err(err) = game_loop.run(None, app) {
+        log::error!("{err}");
+        std::process::exit(1);
+    }
 }
@@ -1,20 +1,17 @@
 use gears::{
     gears_pipeline::Input,
     glam::{Mat4, Vec3},
-    renderer::simple_renderer::Renderer,
-    vulkano::{buffer::CpuBufferPool, pipeline::GraphicsPipeline, render_pass::Subpass},
-};
-use std::sync::Arc;
-use vulkano::{
-    descriptor_set::pool::StdDescriptorPool,
-    pipeline::graphics::{
-        depth_stencil::DepthStencilState,
-        input_assembly::InputAssemblyState,
-        rasterization::{CullMode, FrontFace, RasterizationState},
-        vertex_input::BuffersDefinition,
-        viewport::ViewportState,
+    renderer::{
+        pipeline::{BlendMode, GPipelineBuilder},
+        simple_renderer::Renderer,
+        uniform::FrameUniformSets,
+    },
+    vulkano::pipeline::{
+        graphics::rasterization::{CullMode, FrontFace},
+        GraphicsPipeline,
     },
 };
+use std::sync::Arc;
 
 #[derive(Input, Debug, PartialEq, Copy, Clone, Default)]
 #[repr(C)]
@@ -32,6 +29,12 @@ pub struct UniformData {
     pub light_dir: Vec3,
 }
 
+#[derive(Input, Debug, PartialEq, Copy, Clone, Default)]
+#[repr(C)]
+pub struct QuadVertex {
+    pub vi_pos: [f32; 3],
+}
+
 mod vert {
     #![allow(clippy::needless_question_mark)]
     vulkano_shaders::shader! {
@@ -48,10 +51,49 @@ mod frag {
     }
 }
 
+pub mod quad_vert {
+    #![allow(clippy::needless_question_mark)]
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+#version 420
+
+layout(location = 0) in vec3 vi_pos;
+
+layout(location = 0) out vec4 fi_color;
+
+layout(push_constant) uniform PushConstants {
+	mat4 mvp;
+	vec4 color;
+} pc;
+
+void main() {
+	gl_Position = pc.mvp * vec4(vi_pos, 1.0);
+	fi_color = pc.color;
+}"
+    }
+}
+
+mod quad_frag {
+    #![allow(clippy::needless_question_mark)]
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 420
+
+layout(location = 0) in vec4 fi_color;
+
+layout(location = 0) out vec4 color;
+
+void main() {
+	color = fi_color;
+}"
+    }
+}
+
 pub struct DefaultPipeline {
     pub pipeline: Arc<GraphicsPipeline>,
-    pub desc_pool: Arc<StdDescriptorPool>,
-    pub buffer_pool: CpuBufferPool<UniformData>,
+    pub uniforms: FrameUniformSets<UniformData>,
 }
 
 impl DefaultPipeline {
@@ -59,36 +101,18 @@ impl DefaultPipeline {
         let vert = vert::load(renderer.device.logical().clone()).unwrap();
         let frag = frag::load(renderer.device.logical().clone()).unwrap();
 
-        let pipeline = GraphicsPipeline::start()
-            //
-            .input_assembly_state(InputAssemblyState::new())
-            //
-            .vertex_input_state(BuffersDefinition::new().vertex::<VertexData>())
-            .vertex_shader(vert.entry_point("main").unwrap(), ())
-            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
-            //
-            .fragment_shader(frag.entry_point("main").unwrap(), ())
-            .depth_stencil_state(DepthStencilState::simple_depth_test())
-            //
-            .rasterization_state(
-                RasterizationState::new()
-                    .cull_mode(CullMode::Back)
-                    .front_face(FrontFace::Clockwise),
-            )
-            .render_pass(Subpass::from(renderer.render_pass(), 0).unwrap())
-            //
-            .build(renderer.device.logical().clone())
+        let pipeline = GPipelineBuilder::new(renderer)
+            .with_input::<VertexData>()
+            .with_vertex_shader(vert.entry_point("main").unwrap())
+            .with_fragment_shader(frag.entry_point("main").unwrap())
+            // gear.obj is exported counter clockwise
+            .with_front_face(FrontFace::CounterClockwise)
+            .build()
             .unwrap();
 
-        let desc_pool = Arc::new(StdDescriptorPool::new(renderer.device.logical().clone()));
-        let buffer_pool =
-            CpuBufferPool::<UniformData>::uniform_buffer(renderer.device.logical().clone());
+        let uniforms = FrameUniformSets::new(&renderer.device, &pipeline).unwrap();
 
-        Self {
-            pipeline,
-            buffer_pool,
-            desc_pool,
-        }
+        Self { pipeline, uniforms }
     }
 }
 
@@ -98,3 +122,43 @@ impl DefaultPipeline {
     mod "VERT" as "vert" where { in UniformData as 0 }
     mod "FRAG" as "frag"
 } */
+
+/// Flat colored translucent quads, drawn after the gears
+pub struct QuadPipeline {
+    pub pipeline: Arc<GraphicsPipeline>,
+}
+
+impl QuadPipeline {
+    pub fn build(renderer: &Renderer) -> Self {
+        let vert = quad_vert::load(renderer.device.logical().clone()).unwrap();
+        let frag = quad_frag::load(renderer.device.logical().clone()).unwrap();
+
+        // depth tested against the gears but not written,
+        // the quads themselves are sorted back to front
+        let pipeline = GPipelineBuilder::new(renderer)
+            .with_input::<QuadVertex>()
+            .with_vertex_shader(vert.entry_point("main").unwrap())
+            .with_fragment_shader(frag.entry_point("main").unwrap())
+            .with_blend(BlendMode::AlphaBlend)
+            .with_depth_write(false)
+            .with_cull_mode(CullMode::None)
+            .build()
+            .unwrap();
+
+        Self { pipeline }
+    }
+
+    /// Unit quad in the XY plane
+    pub fn vertices() -> Vec<QuadVertex> {
+        let corners = [
+            [-0.5, -0.5, 0.0],
+            [0.5, -0.5, 0.0],
+            [0.5, 0.5, 0.0],
+            [-0.5, 0.5, 0.0],
+        ];
+        [0, 1, 2, 0, 2, 3]
+            .iter()
+            .map(|&i| QuadVertex { vi_pos: corners[i] })
+            .collect()
+    }
+}
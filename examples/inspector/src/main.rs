@@ -0,0 +1,173 @@
+use gears::{
+    context::Context,
+    frame::Frame,
+    game_loop::{Event, Runnable, State},
+    glam::Vec3,
+    io::input_state::{Input, InputState, Triggered},
+    renderer::{
+        simple_renderer::{FrameData, RenderError, Renderer},
+        ClearColor,
+    },
+    winit::event::{Event as WinitEvent, WindowEvent},
+    GearsError, UpdateRate,
+};
+use std::time::Instant;
+
+//
+
+/// A `Renderer` and the `Frame` it draws to
+///
+/// The fields drop in order, so the renderer
+/// is dropped before its frame.
+struct View {
+    renderer: Renderer,
+    frame: Frame,
+}
+
+struct App {
+    // the inspector shares the device of the main view
+    inspector: Option<View>,
+    main: View,
+    input: InputState,
+
+    color: Vec3,
+    init: Instant,
+}
+
+impl View {
+    fn new(frame: Frame, renderer: Renderer) -> Self {
+        Self { renderer, frame }
+    }
+
+    fn draw(&mut self, state: &mut State, color: Vec3) -> Result<(), GearsError> {
+        let FrameData {
+            recorder,
+            viewport,
+            scissor,
+            perf,
+
+            image_index,
+            frame_in_flight,
+            future,
+        } = match self.renderer.begin_frame(state) {
+            Ok(frame_data) => frame_data,
+            // e.g. minimized, try again on the next frame
+            Err(RenderError::OutOfDate) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let recorder = recorder
+            .begin_render_pass_with(ClearColor(color.extend(1.0)))
+            .end_render_pass();
+
+        self.renderer.end_frame(FrameData {
+            recorder,
+            viewport,
+            scissor,
+            perf,
+
+            image_index,
+            frame_in_flight,
+            future,
+        });
+        Ok(())
+    }
+}
+
+impl Runnable for App {
+    fn renderers(&mut self) -> Vec<&mut Renderer> {
+        let mut renderers = vec![&mut self.main.renderer];
+        if let Some(inspector) = self.inspector.as_mut() {
+            renderers.push(&mut inspector.renderer);
+        }
+        renderers
+    }
+
+    fn update(&mut self, _: &mut State, _: f32) {
+        let time = self.init.elapsed().as_secs_f32();
+        let wave = |offset: f32| (time + offset).sin() * 0.5 + 0.5;
+        self.color = Vec3::new(wave(0.0), wave(2.0), wave(4.0));
+
+        if let Some(inspector) = &self.inspector {
+            let Vec3 { x, y, z } = self.color;
+            inspector
+                .frame
+                .set_title(&format!("Inspector - rgb({:.2}, {:.2}, {:.2})", x, y, z));
+        }
+    }
+
+    fn draw(&mut self, state: &mut State, _: f32) -> Result<(), GearsError> {
+        // secondary windows are drawn with the primary one
+        self.main.draw(state, self.color)?;
+        if let Some(inspector) = self.inspector.as_mut() {
+            inspector.draw(state, Vec3::splat(0.1))?;
+        }
+        Ok(())
+    }
+
+    fn event(&mut self, state: &mut State, event: &Event) {
+        // route the inspector window events by its id
+        if let Some(inspector) = self.inspector.as_mut() {
+            if event.window_id() == Some(inspector.frame.id()) {
+                inspector.frame.event(event);
+                if let Event::WinitEvent(WinitEvent::WindowEvent {
+                    event: WindowEvent::CloseRequested,
+                    ..
+                }) = event
+                {
+                    // closes only the inspector
+                    self.inspector = None;
+                }
+                return;
+            }
+        }
+
+        self.main.frame.event(event);
+        self.input.event(event);
+
+        if self.input.should_close() || self.input.get_input(Input::Pause, 0).triggered() {
+            state.stop = true
+        }
+    }
+}
+
+fn main() {
+    env_logger::init();
+
+    let context = Context::env().unwrap();
+
+    let mut frame = Frame::builder(context)
+        .with_title("Inspector Example")
+        .with_size(600, 600)
+        .build()
+        .unwrap();
+
+    // secondary frames are built before the game loop takes the event loop
+    let inspector_frame = Frame::builder(frame.context())
+        .with_title("Inspector")
+        .with_size(300, 200)
+        .build_secondary(&frame)
+        .unwrap();
+
+    let game_loop = frame.game_loop().unwrap();
+
+    let renderer = Renderer::builder(&frame).build().unwrap();
+    let inspector_renderer = Renderer::builder(&inspector_frame)
+        .with_device(renderer.device.clone())
+        .build()
+        .unwrap();
+
+    let app = App {
+        inspector: Some(View::new(inspector_frame, inspector_renderer)),
+        main: View::new(frame, renderer),
+        input: InputState::new(),
+
+        color: Vec3::ZERO,
+        init: Instant::now(),
+    };
+
+    if let Err(err) = game_loop.run(Some(UpdateRate::PerSecond(10)), app) {
+        log::error!("{err}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,211 @@
+use gears::{
+    context::Context,
+    frame::Frame,
+    game_loop::{Event, Runnable, State},
+    glam::{Mat4, Vec3},
+    io::input_state::{Input, InputState, Triggered},
+    renderer::{
+        buffer::StagedBuffer,
+        object::load_obj,
+        query::RecordPerf,
+        simple_renderer::{FrameData, RenderError, Renderer},
+    },
+    scene::{Camera, PerspectiveConfig},
+    vulkano::buffer::{BufferUsage, TypedBufferAccess},
+    GearsError, SyncMode,
+};
+use shader::UniformData;
+use std::time::Instant;
+use vulkano::{
+    descriptor_set::{DescriptorSet, PersistentDescriptorSet, WriteDescriptorSet},
+    pipeline::{Pipeline, PipelineBindPoint},
+};
+
+//
+
+mod shader;
+
+//
+
+const GRID: usize = 10;
+
+struct App {
+    renderer: Renderer,
+    input: InputState,
+
+    shader: shader::DefaultPipeline,
+    vb: StagedBuffer<[shader::VertexData]>,
+
+    init: Instant,
+
+    frame: Frame,
+}
+
+impl App {
+    fn init(frame: Frame, renderer: Renderer) -> Self {
+        let input = InputState::new();
+        let shader = shader::DefaultPipeline::build(&renderer);
+
+        let vertices = load_obj(
+            include_str!("../../gear/res/gear.obj"),
+            None,
+            |pos, norm| shader::VertexData {
+                vi_pos: pos.to_array(),
+                vi_norm: norm.to_array(),
+            },
+        );
+        let vb = StagedBuffer::from_iter(
+            &renderer.device,
+            BufferUsage::vertex_buffer(),
+            vertices.into_iter(),
+        )
+        .unwrap();
+
+        Self {
+            frame,
+            renderer,
+            input,
+
+            shader,
+            vb,
+
+            init: Instant::now(),
+        }
+    }
+
+    fn uniform_data(&self) -> impl ExactSizeIterator<Item = UniformData> {
+        let time = self.init.elapsed().as_secs_f32();
+        let mut camera = Camera::perspective(PerspectiveConfig::default())
+            .with_position(Vec3::new(0.0, 0.0, GRID as f32 * 1.5));
+        camera.update_aspect(&self.frame);
+        camera.look_at(Vec3::ZERO, Vec3::Y);
+        let view_matrix = camera.view();
+        let projection_matrix = camera.projection_matrix();
+        let light_dir = Vec3::new(0.2, 2.0, 0.5).normalize();
+
+        (0..GRID * GRID).map(move |i| {
+            let (x, y) = ((i % GRID) as f32, (i / GRID) as f32);
+            let offset = (GRID - 1) as f32 * 0.5;
+            let position = Vec3::new(x - offset, y - offset, 0.0) * 1.2;
+
+            UniformData {
+                model_matrix: Mat4::from_translation(position)
+                    * Mat4::from_rotation_x(time + i as f32 * 0.1)
+                    * Mat4::from_scale(Vec3::splat(0.4)),
+                view_matrix,
+                projection_matrix,
+                light_dir,
+            }
+        })
+    }
+}
+
+impl Runnable for App {
+    fn renderers(&mut self) -> Vec<&mut Renderer> {
+        vec![&mut self.renderer]
+    }
+
+    fn draw(&mut self, state: &mut State, _: f32) -> Result<(), GearsError> {
+        let FrameData {
+            mut recorder,
+            viewport,
+            scissor,
+            perf,
+
+            image_index,
+            frame_in_flight,
+            future,
+        } = match self.renderer.begin_frame(state) {
+            Ok(frame_data) => frame_data,
+            // e.g. minimized, try again on the next frame
+            Err(RenderError::OutOfDate) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        // outside of render pass
+        self.vb.update(&mut recorder)?;
+
+        // one uniform array and one descriptor set for all gears
+        let array = self.shader.uniform_array.next(self.uniform_data()).unwrap();
+        let layout = self.shader.pipeline.layout().descriptor_set_layouts()[0].clone();
+        let set = PersistentDescriptorSet::new_with_pool(
+            layout,
+            0,
+            &mut self.shader.desc_pool,
+            [WriteDescriptorSet::buffer(0, array)],
+        )
+        .unwrap();
+
+        // inside of render pass
+        let mut recorder = recorder.begin_render_pass();
+        recorder
+            .record()
+            .begin_perf(&perf)
+            .bind_pipeline_graphics(self.shader.pipeline.clone())
+            .bind_vertex_buffers(0, self.vb.local.clone())
+            .set_viewport(0, [viewport.clone()])
+            .set_scissor(0, [scissor]);
+        for i in 0..GRID * GRID {
+            let offset = self.shader.uniform_array.offset(i);
+            recorder
+                .record()
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Graphics,
+                    self.shader.pipeline.layout().clone(),
+                    0,
+                    set.clone().offsets([offset]),
+                )
+                .draw(self.vb.local.len() as u32, 1, 0, 0)
+                .unwrap();
+        }
+        recorder.record().end_perf(&perf);
+
+        // outside of render pass again
+        let recorder = recorder.end_render_pass();
+
+        self.renderer.end_frame(FrameData {
+            recorder,
+            viewport,
+            scissor,
+            perf,
+
+            image_index,
+            frame_in_flight,
+            future,
+        });
+        Ok(())
+    }
+
+    fn event(&mut self, state: &mut State, event: &Event) {
+        self.frame.event(event);
+        self.input.event(event);
+
+        if self.input.should_close() || self.input.get_input(Input::Pause, 0).triggered() {
+            state.stop = true
+        }
+    }
+}
+
+fn main() {
+    env_logger::init();
+
+    let context = Context::env().unwrap();
+
+    let mut frame = Frame::builder(context)
+        .with_title("Many Gears Example")
+        .with_size(600, 600)
+        .with_sync(SyncMode::Immediate)
+        .build()
+        .unwrap();
+
+    let game_loop = frame.game_loop().unwrap();
+
+    let renderer = Renderer::builder(&frame).build().unwrap();
+
+    let app = App::init(frame, renderer);
+
+    if let Err(err) = game_loop.run(None, app) {
+        log::error!("{err}");
+        std::process::exit(1);
+    }
+}
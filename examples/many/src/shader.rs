@@ -0,0 +1,72 @@
+use gears::{
+    gears_pipeline::Input,
+    glam::{Mat4, Vec3},
+    renderer::{
+        pipeline::GPipelineBuilder, simple_renderer::Renderer, uniform::UniformArrayBuffer,
+    },
+    vulkano::pipeline::GraphicsPipeline,
+};
+use std::sync::Arc;
+use vulkano::descriptor_set::pool::StdDescriptorPool;
+
+#[derive(Input, Debug, PartialEq, Copy, Clone, Default)]
+#[repr(C)]
+pub struct VertexData {
+    pub vi_pos: [f32; 3],
+    pub vi_norm: [f32; 3],
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+#[repr(C)]
+pub struct UniformData {
+    pub model_matrix: Mat4,
+    pub view_matrix: Mat4,
+    pub projection_matrix: Mat4,
+    pub light_dir: Vec3,
+}
+
+mod vert {
+    #![allow(clippy::needless_question_mark)]
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "gear/res/default.vert.glsl"
+    }
+}
+
+mod frag {
+    #![allow(clippy::needless_question_mark)]
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "gear/res/default.frag.glsl"
+    }
+}
+
+pub struct DefaultPipeline {
+    pub pipeline: Arc<GraphicsPipeline>,
+    pub desc_pool: Arc<StdDescriptorPool>,
+    pub uniform_array: UniformArrayBuffer<UniformData>,
+}
+
+impl DefaultPipeline {
+    pub fn build(renderer: &Renderer) -> Self {
+        let vert = vert::load(renderer.device.logical().clone()).unwrap();
+        let frag = frag::load(renderer.device.logical().clone()).unwrap();
+
+        let pipeline = GPipelineBuilder::new(renderer)
+            .with_input::<VertexData>()
+            .with_vertex_shader(vert.entry_point("main").unwrap())
+            .with_fragment_shader(frag.entry_point("main").unwrap())
+            .with_dynamic_uniform(0, 0)
+            .build()
+            .unwrap();
+
+        let desc_pool = Arc::new(StdDescriptorPool::new(renderer.device.logical().clone()));
+        let uniform_array = UniformArrayBuffer::new(&renderer.device);
+
+        Self {
+            pipeline,
+            desc_pool,
+            uniform_array,
+        }
+    }
+}
@@ -0,0 +1,261 @@
+//! A cube rendered into an extra attachment and inverted
+//! by a fullscreen triangle in a second subpass
+//!
+//! The passes only declare the attachments they read and write,
+//! the `RenderGraph` orders them into the subpasses.
+//!
+//! ### controls:
+//! - Escape to quit
+
+use gears::{
+    context::Context,
+    frame::Frame,
+    game_loop::{Event, Runnable, State},
+    glam::{EulerRot, Mat4, Quat, Vec3},
+    io::input_state::{Input, InputState, Triggered},
+    renderer::{
+        buffer::StagedBuffer,
+        graph::{RenderGraph, DEPTH, SWAPCHAIN},
+        query::RecordPerf,
+        simple_renderer::{ExtraAttachment, FrameData, RenderError, Renderer},
+    },
+    scene::{Camera, PerspectiveConfig},
+    vulkano::{
+        buffer::{BufferUsage, TypedBufferAccess},
+        descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
+        format::Format,
+        image::{view::ImageView, AttachmentImage, ImageUsage},
+        pipeline::{Pipeline, PipelineBindPoint},
+    },
+    GearsError, SyncMode,
+};
+use shader::scene_vert;
+use std::{sync::Arc, time::Instant};
+
+//
+
+mod shader;
+
+//
+
+/// The extra attachment
+const SCENE: &str = "scene";
+
+// passes
+const CUBE: &str = "cube";
+const INVERT: &str = "invert";
+
+type InvertSet = (
+    Arc<ImageView<AttachmentImage>>,
+    Arc<PersistentDescriptorSet>,
+);
+
+struct App {
+    renderer: Renderer,
+    input: InputState,
+    graph: RenderGraph,
+
+    scene: shader::ScenePipeline,
+    invert: shader::InvertPipeline,
+    vb: StagedBuffer<[shader::VertexData]>,
+
+    // input attachment sets per swapchain image,
+    // rebuilt when the swapchain recreates the attachment
+    sets: Vec<Option<InvertSet>>,
+
+    init: Instant,
+
+    frame: Frame,
+}
+
+impl App {
+    fn init(frame: Frame, renderer: Renderer, graph: RenderGraph) -> Self {
+        let input = InputState::new();
+        let scene = shader::ScenePipeline::build(&renderer, graph.subpass(CUBE).unwrap());
+        let invert = shader::InvertPipeline::build(&renderer, graph.subpass(INVERT).unwrap());
+        let vb = StagedBuffer::from_iter(
+            &renderer.device,
+            BufferUsage::vertex_buffer(),
+            shader::ScenePipeline::vertices().into_iter(),
+        )
+        .unwrap();
+
+        Self {
+            frame,
+            renderer,
+            input,
+            graph,
+
+            scene,
+            invert,
+            vb,
+
+            sets: Vec::new(),
+
+            init: Instant::now(),
+        }
+    }
+
+    fn push_constants(&self) -> scene_vert::ty::PushConstants {
+        let time = self.init.elapsed().as_secs_f32();
+        let model = Mat4::from_quat(Quat::from_euler(EulerRot::YXZ, time, time * 0.7, 0.0));
+
+        let mut camera = Camera::perspective(PerspectiveConfig::default())
+            .with_position(Vec3::new(0.0, 0.0, 3.0))
+            .with_depth_mode(self.renderer.depth_mode());
+        camera.update_aspect(&self.frame);
+        camera.look_at(Vec3::ZERO, Vec3::Y);
+
+        scene_vert::ty::PushConstants {
+            mvp: (camera.vp() * model).to_cols_array_2d(),
+        }
+    }
+
+    /// Input attachment set of the extra attachment of `image_index`
+    fn invert_set(&mut self, image_index: usize) -> Arc<PersistentDescriptorSet> {
+        let view = self
+            .renderer
+            .extra_attachment(image_index, SCENE)
+            .expect("Missing extra attachment");
+
+        self.sets.resize(self.renderer.image_count(), None);
+        if let Some((cached, set)) = &self.sets[image_index] {
+            if Arc::ptr_eq(cached, &view) {
+                return set.clone();
+            }
+        }
+
+        let layout = self.invert.pipeline.layout().descriptor_set_layouts()[0].clone();
+        let set =
+            PersistentDescriptorSet::new(layout, [WriteDescriptorSet::image_view(0, view.clone())])
+                .unwrap();
+        self.sets[image_index] = Some((view, set.clone()));
+        set
+    }
+}
+
+impl Runnable for App {
+    fn renderers(&mut self) -> Vec<&mut Renderer> {
+        vec![&mut self.renderer]
+    }
+
+    fn draw(&mut self, state: &mut State, _: f32) -> Result<(), GearsError> {
+        let FrameData {
+            mut recorder,
+            viewport,
+            scissor,
+            perf,
+
+            image_index,
+            frame_in_flight,
+            future,
+        } = match self.renderer.begin_frame(state) {
+            Ok(frame_data) => frame_data,
+            // e.g. minimized, try again on the next frame
+            Err(RenderError::OutOfDate) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        // outside of render pass
+        self.vb.update(&mut recorder)?;
+        let constants = self.push_constants();
+        let set = self.invert_set(image_index);
+
+        // the render pass with one subpass per pass
+        let (scene, invert, vb) = (&self.scene, &self.invert, &self.vb);
+        let recorder = self
+            .graph
+            .record(recorder, |pass, recorder| {
+                match pass {
+                    // the cube into the swapchain image and the extra attachment
+                    CUBE => {
+                        recorder
+                            .record()
+                            .begin_perf(&perf)
+                            .set_viewport(0, [viewport.clone()])
+                            .set_scissor(0, [scissor])
+                            .bind_pipeline_graphics(scene.pipeline.clone())
+                            .push_constants(scene.pipeline.layout().clone(), 0, constants)
+                            .bind_vertex_buffers(0, vb.local.clone())
+                            .draw(vb.local.len() as u32, 1, 0, 0)?;
+                    }
+                    // inverted over the whole swapchain image
+                    INVERT => {
+                        recorder
+                            .record()
+                            .bind_pipeline_graphics(invert.pipeline.clone())
+                            .bind_descriptor_sets(
+                                PipelineBindPoint::Graphics,
+                                invert.pipeline.layout().clone(),
+                                0,
+                                set.clone(),
+                            )
+                            .draw(3, 1, 0, 0)?
+                            .end_perf(&perf);
+                    }
+                    _ => {}
+                }
+                Ok(())
+            })
+            .unwrap();
+
+        self.renderer.end_frame(FrameData {
+            recorder,
+            viewport,
+            scissor,
+            perf,
+
+            image_index,
+            frame_in_flight,
+            future,
+        });
+        Ok(())
+    }
+
+    fn event(&mut self, state: &mut State, event: &Event) {
+        self.frame.event(event);
+        self.input.event(event);
+
+        if self.input.should_close() || self.input.get_input(Input::Pause, 0).triggered() {
+            state.stop = true
+        }
+    }
+}
+
+fn main() {
+    env_logger::init();
+
+    let context = Context::env().unwrap();
+
+    let mut frame = Frame::builder(context)
+        .with_title("Post-process Example")
+        .with_size(800, 600)
+        .with_sync(SyncMode::Immediate)
+        .build()
+        .unwrap();
+
+    let game_loop = frame.game_loop().unwrap();
+
+    // declared in reverse, the graph records the cube first
+    let mut graph = RenderGraph::new().with_extra_attachment(ExtraAttachment {
+        name: SCENE,
+        format: Format::R8G8B8A8_UNORM,
+        usage: ImageUsage::none(),
+    });
+    graph.add_pass(INVERT, &[SCENE], &[SWAPCHAIN]).unwrap();
+    graph
+        .add_pass(CUBE, &[], &[SWAPCHAIN, DEPTH, SCENE])
+        .unwrap();
+
+    let renderer = Renderer::builder(&frame)
+        .with_render_pass_config(graph.render_pass_config().unwrap())
+        .build()
+        .unwrap();
+
+    let app = App::init(frame, renderer, graph);
+
+    if let Err(err) = game_loop.run(None, app) {
+        log::error!("{err}");
+        std::process::exit(1);
+    }
+}
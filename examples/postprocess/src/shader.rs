@@ -0,0 +1,173 @@
+use gears::{
+    gears_pipeline::Input,
+    glam::Vec3,
+    renderer::{pipeline::GPipelineBuilder, simple_renderer::Renderer},
+    vulkano::pipeline::{graphics::rasterization::CullMode, GraphicsPipeline},
+};
+use std::sync::Arc;
+
+//
+
+#[derive(Input, Debug, PartialEq, Copy, Clone, Default)]
+#[repr(C)]
+pub struct VertexData {
+    pub vi_pos: [f32; 3],
+    pub vi_color: [f32; 3],
+}
+
+//
+
+pub mod scene_vert {
+    #![allow(clippy::needless_question_mark)]
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+#version 420
+
+layout(location = 0) in vec3 vi_pos;
+layout(location = 1) in vec3 vi_color;
+
+layout(location = 0) out vec3 fi_color;
+
+layout(push_constant) uniform PushConstants {
+	mat4 mvp;
+} pc;
+
+void main() {
+	gl_Position = pc.mvp * vec4(vi_pos, 1.0);
+	fi_color = vi_color;
+}"
+    }
+}
+
+mod scene_frag {
+    #![allow(clippy::needless_question_mark)]
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 420
+
+layout(location = 0) in vec3 fi_color;
+
+// the swapchain image, overwritten by the invert pass
+layout(location = 0) out vec4 color;
+// the extra attachment
+layout(location = 1) out vec4 scene;
+
+void main() {
+	color = vec4(fi_color, 1.0);
+	scene = vec4(fi_color, 1.0);
+}"
+    }
+}
+
+mod invert_vert {
+    #![allow(clippy::needless_question_mark)]
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+#version 420
+
+// fullscreen triangle without vertex buffers
+void main() {
+	vec2 uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+	gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+}"
+    }
+}
+
+mod invert_frag {
+    #![allow(clippy::needless_question_mark)]
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 420
+
+layout(input_attachment_index = 0, set = 0, binding = 0) uniform subpassInput scene;
+
+layout(location = 0) out vec4 color;
+
+void main() {
+	vec4 texel = subpassLoad(scene);
+	color = vec4(1.0 - texel.rgb, 1.0);
+}"
+    }
+}
+
+//
+
+/// Vertex colored cube, writes the swapchain image and the extra attachment
+pub struct ScenePipeline {
+    pub pipeline: Arc<GraphicsPipeline>,
+}
+
+/// Fullscreen triangle reading the extra attachment in a later subpass
+pub struct InvertPipeline {
+    pub pipeline: Arc<GraphicsPipeline>,
+}
+
+impl ScenePipeline {
+    pub fn build(renderer: &Renderer, subpass: u32) -> Self {
+        let vert = scene_vert::load(renderer.device.logical().clone()).unwrap();
+        let frag = scene_frag::load(renderer.device.logical().clone()).unwrap();
+
+        let pipeline = GPipelineBuilder::new(renderer)
+            .with_subpass(subpass)
+            .with_input::<VertexData>()
+            .with_vertex_shader(vert.entry_point("main").unwrap())
+            .with_fragment_shader(frag.entry_point("main").unwrap())
+            .build()
+            .unwrap();
+
+        Self { pipeline }
+    }
+
+    /// Unit cube with one color per face
+    pub fn vertices() -> Vec<VertexData> {
+        let faces = [
+            (Vec3::X, Vec3::Y, Vec3::new(0.9, 0.2, 0.2)),
+            (-Vec3::X, Vec3::Y, Vec3::new(0.2, 0.9, 0.9)),
+            (Vec3::Y, Vec3::Z, Vec3::new(0.2, 0.9, 0.2)),
+            (-Vec3::Y, Vec3::Z, Vec3::new(0.9, 0.2, 0.9)),
+            (Vec3::Z, Vec3::X, Vec3::new(0.2, 0.2, 0.9)),
+            (-Vec3::Z, Vec3::X, Vec3::new(0.9, 0.9, 0.2)),
+        ];
+
+        faces
+            .iter()
+            .flat_map(|&(normal, up, color)| {
+                // counter clockwise when looking at the face from the outside
+                let right = up.cross(normal);
+                let corner = |x: f32, y: f32| VertexData {
+                    vi_pos: ((normal + right * x + up * y) * 0.5).to_array(),
+                    vi_color: color.to_array(),
+                };
+                [
+                    corner(-1.0, -1.0),
+                    corner(1.0, -1.0),
+                    corner(1.0, 1.0),
+                    corner(-1.0, -1.0),
+                    corner(1.0, 1.0),
+                    corner(-1.0, 1.0),
+                ]
+            })
+            .collect()
+    }
+}
+
+impl InvertPipeline {
+    pub fn build(renderer: &Renderer, subpass: u32) -> Self {
+        let vert = invert_vert::load(renderer.device.logical().clone()).unwrap();
+        let frag = invert_frag::load(renderer.device.logical().clone()).unwrap();
+
+        let pipeline = GPipelineBuilder::new(renderer)
+            .with_subpass(subpass)
+            .with_vertex_shader(vert.entry_point("main").unwrap())
+            .with_fragment_shader(frag.entry_point("main").unwrap())
+            .with_cull_mode(CullMode::None)
+            .build()
+            .unwrap();
+
+        Self { pipeline }
+    }
+}
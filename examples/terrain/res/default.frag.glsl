@@ -0,0 +1,11 @@
+#version 420
+
+layout(location = 0) in vec3 fi_color;
+
+layout(location = 0) out vec4 color;
+
+
+
+void main() {
+	color = vec4(fi_color, 1.0);
+}
@@ -0,0 +1,17 @@
+#version 420
+
+layout(location = 0) in vec3 vi_pos;
+layout(location = 1) in vec3 vi_color;
+
+layout(location = 0) out vec3 fi_color;
+
+layout(binding = 0) uniform UBO {
+	mat4 mvp;
+} ubo;
+
+
+
+void main() {
+	gl_Position = ubo.mvp * vec4(vi_pos, 1.0);
+	fi_color = vi_color;
+}
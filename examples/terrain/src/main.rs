@@ -0,0 +1,266 @@
+//! Height map terrain meshed with the cube mesher of gears,
+//! the vertices are colored by their height
+//!
+//! The terrain is drawn in the first subpass and an
+//! unlit label in a second subpass without depth.
+//!
+//! ### controls:
+//! - Escape to quit
+
+use gears::{
+    context::Context,
+    format::Color,
+    frame::Frame,
+    game_loop::{Event, Runnable, State},
+    glam::{Vec2, Vec3, Vec4},
+    io::input_state::{Input, InputState, Triggered},
+    renderer::{
+        buffer::StagedBuffer,
+        index::{compact_indices, BindIndexBuffer, IndexBuffer},
+        mesh::{cube_indices, cubes, VoxelGrid},
+        query::RecordPerf,
+        simple_renderer::{FrameData, RenderError, RenderPassConfig, Renderer, SubpassConfig},
+        text::TextRenderer,
+        ClearColor,
+    },
+    scene::{Camera, PerspectiveConfig},
+    vulkano::buffer::BufferUsage,
+    GearsError, SyncMode,
+};
+use shader::{UniformData, VertexData};
+use std::{sync::Arc, time::Instant};
+use vulkano::{
+    descriptor_set::single_layout_pool::SingleLayoutDescSet,
+    pipeline::{Pipeline, PipelineBindPoint},
+};
+
+//
+
+mod shader;
+
+//
+
+const WIDTH: usize = 64;
+const HEIGHT: usize = 24;
+const DEPTH: usize = 64;
+
+struct App {
+    renderer: Renderer,
+    input: InputState,
+
+    shader: shader::DefaultPipeline,
+    label: TextRenderer,
+    vb: StagedBuffer<[VertexData]>,
+    ib: IndexBuffer,
+
+    init: Instant,
+
+    frame: Frame,
+}
+
+fn generate_terrain() -> VoxelGrid {
+    VoxelGrid::from_fn([WIDTH, HEIGHT, DEPTH], |x, y, z| {
+        let (x, z) = (x as f32 * 0.15, z as f32 * 0.15);
+        let height = (x.sin() * z.cos() + (x * 0.4 + z * 0.7).sin()) * 0.25 + 0.5;
+        if (y as f32) < height * HEIGHT as f32 {
+            1.0
+        } else {
+            0.0
+        }
+    })
+}
+
+/// Sand, grass, rock and snow from the bottom up
+fn height_color(y: f32) -> Vec3 {
+    let colors = [
+        (0.0, Vec3::new(0.76, 0.7, 0.5)),
+        (0.3, Vec3::new(0.3, 0.6, 0.2)),
+        (0.7, Vec3::new(0.45, 0.4, 0.38)),
+        (0.9, Vec3::new(0.95, 0.95, 0.97)),
+    ];
+
+    let t = (y / HEIGHT as f32).clamp(0.0, 1.0);
+    colors
+        .windows(2)
+        .find(|pair| t <= pair[1].0)
+        .map(|pair| {
+            let ((a, ac), (b, bc)) = (pair[0], pair[1]);
+            ac.lerp(bc, (t - a) / (b - a))
+        })
+        .unwrap_or(colors[colors.len() - 1].1)
+}
+
+impl App {
+    fn init(frame: Frame, renderer: Renderer) -> Self {
+        let input = InputState::new();
+        let shader = shader::DefaultPipeline::build(&renderer);
+        let label = TextRenderer::with_subpass(&renderer, 1).unwrap();
+
+        // the same mesher as the voxel example, with another vertex type
+        let terrain = generate_terrain();
+        let vertices = cubes(&terrain, 0.5, |pos, normal, _| {
+            // a bit of shading so that the cubes can be told apart
+            let shade = 0.75 + 0.25 * normal.dot(Vec3::new(0.3, 0.9, 0.3));
+            VertexData {
+                vi_pos: pos.to_array(),
+                vi_color: (height_color(pos.y) * shade).to_array(),
+            }
+        });
+        let indices = compact_indices(cube_indices(vertices.len()));
+
+        let vb = StagedBuffer::from_iter(
+            &renderer.device,
+            BufferUsage::vertex_buffer(),
+            vertices.into_iter(),
+        )
+        .unwrap();
+        let ib = IndexBuffer::new(&renderer.device, indices).unwrap();
+
+        Self {
+            frame,
+            renderer,
+            input,
+
+            shader,
+            label,
+            vb,
+            ib,
+
+            init: Instant::now(),
+        }
+    }
+
+    fn uniform_set(&mut self) -> Arc<SingleLayoutDescSet> {
+        let time = self.init.elapsed().as_secs_f32() * 0.2;
+        let center = Vec3::new(WIDTH as f32, 0.0, DEPTH as f32) * 0.5;
+        let eye = center + Vec3::new(time.sin() * 60.0, 40.0, time.cos() * 60.0);
+
+        let mut camera = Camera::perspective(PerspectiveConfig {
+            far: 500.0,
+            ..Default::default()
+        })
+        .with_position(eye)
+        .with_depth_mode(self.renderer.depth_mode());
+        camera.update_aspect(&self.frame);
+        camera.look_at(center, Vec3::Y);
+
+        self.shader
+            .uniforms
+            .next(UniformData { mvp: camera.vp() })
+            .unwrap()
+    }
+}
+
+impl Runnable for App {
+    fn renderers(&mut self) -> Vec<&mut Renderer> {
+        vec![&mut self.renderer]
+    }
+
+    fn draw(&mut self, state: &mut State, _: f32) -> Result<(), GearsError> {
+        let FrameData {
+            mut recorder,
+            viewport,
+            scissor,
+            perf,
+
+            image_index,
+            frame_in_flight,
+            future,
+        } = match self.renderer.begin_frame(state) {
+            Ok(frame_data) => frame_data,
+            // e.g. minimized, try again on the next frame
+            Err(RenderError::OutOfDate) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        // outside of render pass
+        self.vb.update(&mut recorder)?;
+        self.ib.update(&mut recorder)?;
+        let set = self.uniform_set();
+
+        // inside of render pass
+        // same sky on sRGB and UNORM swapchains
+        let sky = ClearColor::from(Color::from_srgb(140, 179, 230));
+        let mut recorder = recorder.begin_render_pass_with(sky);
+        recorder.record().begin_perf(&perf);
+        recorder
+            .record()
+            .set_viewport(0, [viewport.clone()])
+            .set_scissor(0, [scissor])
+            .bind_pipeline_graphics(self.shader.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.shader.pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .bind_vertex_buffers(0, self.vb.local.clone());
+        recorder
+            .bind_index_buffer(&self.ib)
+            .draw_indexed(self.ib.len() as u32, 1, 0, 0, 0)
+            .unwrap();
+        recorder.record().end_perf(&perf);
+
+        // unlit overlay in the second subpass, no depth test
+        recorder.next_subpass().unwrap();
+        let label = format!("TERRAIN {}X{}X{}", WIDTH, HEIGHT, DEPTH);
+        self.label
+            .draw(&mut recorder, &label, Vec2::new(8.0, 8.0), 2.0, Vec4::ONE)
+            .unwrap();
+
+        // outside of render pass again
+        let recorder = recorder.end_render_pass();
+
+        self.renderer.end_frame(FrameData {
+            recorder,
+            viewport,
+            scissor,
+            perf,
+
+            image_index,
+            frame_in_flight,
+            future,
+        });
+        Ok(())
+    }
+
+    fn event(&mut self, state: &mut State, event: &Event) {
+        self.frame.event(event);
+        self.input.event(event);
+
+        if self.input.should_close() || self.input.get_input(Input::Pause, 0).triggered() {
+            state.stop = true
+        }
+    }
+}
+
+fn main() {
+    env_logger::init();
+
+    let context = Context::env().unwrap();
+
+    let mut frame = Frame::builder(context)
+        .with_title("Terrain Example")
+        .with_size(800, 600)
+        .with_sync(SyncMode::Immediate)
+        .build()
+        .unwrap();
+
+    let game_loop = frame.game_loop().unwrap();
+
+    let renderer = Renderer::builder(&frame)
+        .with_render_pass_config(
+            RenderPassConfig::new()
+                .with_subpass(SubpassConfig::depth())
+                .with_subpass(SubpassConfig::no_depth()),
+        )
+        .build()
+        .unwrap();
+
+    let app = App::init(frame, renderer);
+
+    if let Err(err) = game_loop.run(None, app) {
+        log::error!("{err}");
+        std::process::exit(1);
+    }
+}
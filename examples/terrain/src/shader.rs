@@ -0,0 +1,65 @@
+use gears::{
+    gears_pipeline::Input,
+    glam::Mat4,
+    renderer::{pipeline::GPipelineBuilder, simple_renderer::Renderer, uniform::UniformSets},
+};
+use std::sync::Arc;
+use vulkano::pipeline::GraphicsPipeline;
+
+//
+
+#[derive(Debug, Input, Clone, PartialEq, Default)]
+#[repr(C)]
+pub struct VertexData {
+    pub vi_pos: [f32; 3],
+    pub vi_color: [f32; 3],
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[repr(C)]
+pub struct UniformData {
+    pub mvp: Mat4,
+}
+
+//
+
+mod vert {
+    #![allow(clippy::needless_question_mark)]
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "terrain/res/default.vert.glsl"
+    }
+}
+
+mod frag {
+    #![allow(clippy::needless_question_mark)]
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "terrain/res/default.frag.glsl"
+    }
+}
+
+//
+
+pub struct DefaultPipeline {
+    pub pipeline: Arc<GraphicsPipeline>,
+    pub uniforms: UniformSets<UniformData>,
+}
+
+impl DefaultPipeline {
+    pub fn build(renderer: &Renderer) -> Self {
+        let vert = vert::load(renderer.device.logical().clone()).unwrap();
+        let frag = frag::load(renderer.device.logical().clone()).unwrap();
+
+        let pipeline = GPipelineBuilder::new(renderer)
+            .with_input::<VertexData>()
+            .with_vertex_shader(vert.entry_point("main").unwrap())
+            .with_fragment_shader(frag.entry_point("main").unwrap())
+            .build()
+            .unwrap();
+
+        let uniforms = UniformSets::new(&renderer.device, &pipeline).unwrap();
+
+        Self { pipeline, uniforms }
+    }
+}
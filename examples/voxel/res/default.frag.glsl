@@ -1,10 +1,18 @@
 #version 420
 
 layout(location = 0) in float fi_exp;
+layout(location = 1) in vec3 fi_pos;
 
 layout(location = 0) out vec4 color;
 
+// directional lights used from `LIGHTS`, each count is its own pipeline
+layout(constant_id = 0) const int LIGHT_COUNT = 1;
 
+const vec3 LIGHTS[3] = vec3[](
+	normalize(vec3(0.3, 1.0, 0.2)),
+	normalize(vec3(-0.8, 0.4, -0.3)),
+	normalize(vec3(0.2, -0.3, 1.0))
+);
 
 void main() {
 	vec3 c = 
@@ -12,6 +20,14 @@ void main() {
 		vec3(fi_exp, fi_exp * 0.5, 0.0);
 #else
 		vec3(fi_exp);
+
+	// flat face normal, lit from either side
+	vec3 normal = normalize(cross(dFdx(fi_pos), dFdy(fi_pos)));
+	float light = 0.0;
+	for (int i = 0; i < min(LIGHT_COUNT, 3); i++) {
+		light += abs(dot(normal, LIGHTS[i]));
+	}
+	c *= 0.5 + 0.5 * light / float(max(LIGHT_COUNT, 1));
 #endif
 	color = vec4(c, 1.0);
 }
\ No newline at end of file
@@ -4,7 +4,9 @@ layout(triangles) in;
 layout(line_strip, max_vertices = 4) out;
 
 layout(location = 0) in float gi_exp[];
+layout(location = 1) in vec3 gi_pos[];
 layout(location = 0) out float fi_exp;
+layout(location = 1) out vec3 fi_pos;
 
 
 
@@ -12,6 +14,7 @@ void vertex(int i)
 {
 	gl_Position = gl_in[i].gl_Position;
 	fi_exp = gi_exp[i];
+	fi_pos = gi_pos[i];
     EmitVertex();
 }
 
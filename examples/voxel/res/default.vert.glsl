@@ -4,6 +4,7 @@ layout(location = 0) in vec3 vi_pos;
 layout(location = 1) in float vi_exp;
 
 layout(location = 0) out float fi_exp;
+layout(location = 1) out vec3 fi_pos;
 
 layout(binding = 0) uniform UBO {
 	mat4 mvp;
@@ -14,4 +15,5 @@ layout(binding = 0) uniform UBO {
 void main() {
 	gl_Position = ubo.mvp * vec4(vi_pos, 1.0);
 	fi_exp = vi_exp;
+	fi_pos = vi_pos;
 }
\ No newline at end of file
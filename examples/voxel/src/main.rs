@@ -1,11 +1,15 @@
 //! ### m&kb controls:
 //! - W,A,S,D,Space,LShift to move around
 //! - Mouse to look around
+//! - Left mouse button to dig the voxel in the center of the view
+//! - Right mouse button to place a voxel in front of it
 //! - R to regenerate voxels with new seed
 //! - Q to generate cube mesh
 //! - E to generate marching cubes mesh
 //! - F to generate smoothed marching cubes mesh
 //! - Tab to toggle wireframe
+//! - L to toggle between 1 and 3 lights
+//! - PageUp,PageDown to change mouse sensitivity
 //!
 //! ### gamepad controls:
 //! - Left stick,X/A,O/B to move around
@@ -15,53 +19,81 @@
 //! - DPadRight to generate marching cubes mesh
 //! - DPadDown to generate smoothed marching cubes mesh
 //! - Select to toggle wireframe
+//!
+//! ### arguments:
+//! - `--list-gpus` to list the available GPUs
+//! - `--gpu <index>` to pick a GPU from the list
+//! - `--serial` to record the draws on the main thread
+//!   instead of secondary command buffers on rayon threads
+//! - `--perf <path>` to write the frame times to `path` on exit,
+//!   as JSON if it ends with `.json` and as CSV otherwise
+//! - `--frames-in-flight <n>` to let the cpu record 1 to 3 frames ahead
+//! - `--fps-limit <n>` to draw at most `n` frames per second
+//! - `--res <n>` for `n`x`n`x`n` voxels, 64 by default. Meshing a
+//!   new volume is spread over several updates, the old chunks
+//!   are drawn until their new meshes are done
 
 use cubes::generate_cubes;
 use gears::{
-    context::Context,
+    context::{Context, ContextGPUPick},
+    event::InputEvent,
     frame::Frame,
-    game_loop::{Event, Runnable, State},
-    glam::{Mat4, Vec2, Vec3},
+    game_loop::{Runnable, State},
+    glam::{Vec2, Vec3},
     io::{
         fpcam::FPCam,
-        input_state::{Input, InputAxis, InputState, Triggered},
+        input_state::{AxisConfig, Curve, Input, InputAxis, InputState, Triggered},
     },
     renderer::{
-        buffer::StagedBuffer,
+        cull::{Aabb, CulledDrawList},
+        device::Dev,
+        index::{compact_indices, IndexData},
+        material::{DrawQueue, Material},
+        mesh::{cube_indices, Mesh, VoxelGrid, VoxelHit, VoxelVolume},
         query::RecordPerf,
-        simple_renderer::{FrameData, Renderer},
+        simple_renderer::{FrameData, RenderError, Renderer},
     },
-    winit::event::ElementState,
-    SyncMode, UpdateRate,
+    scene::{Camera, PerspectiveConfig},
+    time::ScopedTimer,
+    winit::event::{MouseButton, VirtualKeyCode},
+    DepthMode, GearsError, SyncMode, UpdateRate,
 };
-use mcubes::generate_mcubes;
+use mcubes::{generate_mcubes, mcubes_indices, smooth_mcubes};
+use rayon::prelude::*;
 use shader::{DebugPipeline, DefaultPipeline, UniformData, VertexData};
 use simdnoise::NoiseBuilder;
-use std::time::Instant;
-use vulkano::{
-    buffer::{BufferUsage, TypedBufferAccess},
-    descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
-    pipeline::{Pipeline, PipelineBindPoint},
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
 };
 
 //
 
 mod cubes;
 mod mcubes;
-// mod marching_cubes;
 mod shader;
 
 //
 
 const UPDATE_RATE: UpdateRate = UpdateRate::PerSecond(60);
 
+// voxels per side, see `--res`
 const RES: usize = 64;
 const SCALE: f32 = 1.0;
-const WIDTH: usize = RES;
-const HEIGHT: usize = RES;
-const DEPTH: usize = RES;
 const ISLAND: bool = true;
 
+// secondary command buffers per frame
+const SECONDARIES: usize = 8;
+
+// voxels further away than this cannot be edited
+const EDIT_DISTANCE: f32 = 64.0;
+
+// meshing time per update, the rest of the dirty chunks wait for the next ones
+const REMESH_BUDGET: Duration = Duration::from_millis(4);
+
+// frames uploading more than this log a warning
+const UPLOAD_WARNING: u64 = 64 * 1024 * 1024;
+
 //
 
 enum MeshMode {
@@ -70,158 +102,289 @@ enum MeshMode {
     SMarching,
 }
 
+/// Mesh of one voxel chunk
+struct ChunkMesh {
+    aabb: Aabb,
+    mesh: Mesh<VertexData>,
+}
+
 impl MeshMode {
-    fn gen_mesh(&self, voxels: &[f32]) -> (Vec<shader::VertexData>, Vec<u32>) {
-        match &self {
-            MeshMode::Cubes => generate_cubes(voxels),
-            MeshMode::Marching => generate_mcubes(voxels, false),
-            MeshMode::SMarching => generate_mcubes(voxels, true),
+    fn gen_mesh(&self, volume: &VoxelVolume, chunk: [usize; 3]) -> (Vec<VertexData>, IndexData) {
+        let (vertices, indices) = match &self {
+            MeshMode::Cubes => {
+                let vertices = generate_cubes(volume, chunk);
+                let indices = cube_indices(vertices.len());
+                (vertices, indices)
+            }
+            MeshMode::Marching => {
+                let vertices = generate_mcubes(volume, chunk, false);
+                let indices = mcubes_indices(vertices.len());
+                (vertices, indices)
+            }
+            MeshMode::SMarching => smooth_mcubes(&generate_mcubes(volume, chunk, true)),
+        };
+
+        // u16 indices for small meshes
+        (vertices, compact_indices(indices))
+    }
+}
+
+impl ChunkMesh {
+    /// `None` if the chunk has no triangles
+    fn new(device: &Dev, vertices: Vec<VertexData>, indices: IndexData) -> Option<Self> {
+        if vertices.is_empty() {
+            return None;
         }
+
+        let aabb = Aabb::from_points(vertices.iter().map(|v| Vec3::from(v.vi_pos)));
+        let mesh = Mesh::new_indexed(device, vertices, indices).unwrap();
+
+        Some(Self { aabb, mesh })
     }
 }
 
 //
 
 struct App {
-    frame: Frame,
     renderer: Renderer,
 
     shaders: (DefaultPipeline, DebugPipeline),
-    vb: StagedBuffer<[VertexData]>,
-    ib: StagedBuffer<[u32]>,
+    // fill and debug, the sets are replaced every frame
+    materials: (Material, Material),
+
+    // one mesh per chunk of `volume`
+    chunks: Vec<Option<ChunkMesh>>,
+    // dirty chunks still drawn with their old meshes
+    pending: Vec<[usize; 3]>,
+    draws: CulledDrawList<usize>,
 
     input: InputState,
     fpcam: FPCam,
+    camera: Camera,
 
     position: Vec3,
-    velocity: Vec3,
+    prev_position: Vec3,
 
     debug: bool,
-    voxels: Vec<f32>,
+    serial: bool,
+    perf: Option<PathBuf>,
+    res: usize,
+    volume: VoxelVolume,
     mesh: MeshMode,
+
+    // every Renderer has to be dropped before its Frame
+    frame: Frame,
 }
 
-fn generate_voxels(seed: i32) -> Vec<f32> {
-    let voxels = NoiseBuilder::fbm_3d(WIDTH, HEIGHT, DEPTH)
-        .with_freq(SCALE / RES as f32)
+fn generate_voxels(seed: i32, res: usize) -> VoxelGrid {
+    let [width, height, depth] = [res; 3];
+    let voxels = NoiseBuilder::fbm_3d(width, height, depth)
+        .with_freq(SCALE / res as f32)
         .with_octaves(4)
         .with_gain(0.95)
         .with_lacunarity(1.7)
         .with_seed(seed)
         .generate_scaled(0.0, 1.0);
-    voxels
+    let voxels = voxels
         .into_iter()
         .enumerate()
         .map(|(i, v)| {
-            let x = i % WIDTH;
-            let y = (i / WIDTH) % HEIGHT;
-            let z = i / (WIDTH * HEIGHT);
+            let x = i % width;
+            let y = (i / width) % height;
+            let z = i / (width * height);
 
             if ISLAND {
-                let fade_x = 1.0 - (2.0 / WIDTH as f32 * x as f32 - 1.0).powf(4.0);
-                let fade_y = 1.0 - (2.0 / HEIGHT as f32 * y as f32 - 1.0).powf(4.0);
-                let fade_z = 1.0 - (2.0 / DEPTH as f32 * z as f32 - 1.0).powf(4.0);
+                let fade_x = 1.0 - (2.0 / width as f32 * x as f32 - 1.0).powf(4.0);
+                let fade_y = 1.0 - (2.0 / height as f32 * y as f32 - 1.0).powf(4.0);
+                let fade_z = 1.0 - (2.0 / depth as f32 * z as f32 - 1.0).powf(4.0);
 
                 v * fade_x * fade_y * fade_z
             } else {
                 v
             }
         })
-        .collect::<Vec<_>>()
-}
-
-fn point_to_index(x: usize, y: usize, z: usize) -> usize {
-    x + y * WIDTH + z * WIDTH * HEIGHT
+        .collect::<Vec<_>>();
+    VoxelGrid::new(voxels, [width, height, depth])
 }
 
 impl App {
-    fn init(frame: Frame, renderer: Renderer) -> Self {
-        let voxels = generate_voxels(0);
-        let (vertices, indices) = generate_cubes(&voxels);
-
-        let vb = StagedBuffer::from_iter(
-            &renderer.device,
-            BufferUsage::vertex_buffer(),
-            vertices.into_iter(),
-        )
-        .unwrap();
-
-        let ib = StagedBuffer::from_iter(
-            &renderer.device,
-            BufferUsage::index_buffer(),
-            indices.into_iter(),
-        )
-        .unwrap();
+    fn init(
+        frame: Frame,
+        renderer: Renderer,
+        serial: bool,
+        perf: Option<PathBuf>,
+        res: usize,
+    ) -> Self {
+        renderer
+            .device
+            .uploads()
+            .set_threshold(Some(UPLOAD_WARNING));
+        let volume = VoxelVolume::from_grid(&generate_voxels(0, res));
+        let chunks = (0..volume.chunk_count()).map(|_| None).collect();
 
         let fill_shader = DefaultPipeline::build(&renderer);
         let line_shader = DebugPipeline::build(&renderer);
+        let materials = (
+            Material::new(fill_shader.pipeline.clone(), Vec::new()),
+            Material::new(line_shader.pipeline.clone(), Vec::new()),
+        );
 
-        let input = InputState::new();
+        // finer aiming with slow stick movement
+        let mut input = InputState::new();
+        input.set_axis_config(
+            0,
+            InputAxis::Look,
+            AxisConfig {
+                curve: Curve::Power(2.0),
+                ..Default::default()
+            },
+        );
         let fpcam = FPCam::with_dir(Vec2::new(
             -std::f32::consts::FRAC_PI_4 * 3.0,
             -std::f32::consts::PI / 5.0,
         ));
+        let camera = Camera::perspective(PerspectiveConfig {
+            far: 500.0,
+            ..Default::default()
+        })
+        .with_depth_mode(renderer.depth_mode());
 
-        Self {
+        let mut app = Self {
             frame,
             renderer,
 
-            vb,
-            ib,
+            chunks,
+            pending: Vec::new(),
+            draws: CulledDrawList::new(),
             shaders: (fill_shader, line_shader),
+            materials,
 
             input,
             fpcam,
+            camera,
 
-            position: Vec3::new(-26.0, -26.0, -26.0),
-            velocity: Vec3::new(0.0, 0.0, 0.0),
+            position: Vec3::new(-26.0, 90.0, -26.0),
+            prev_position: Vec3::new(-26.0, 90.0, -26.0),
 
             debug: false,
-            voxels,
+            serial,
+            perf,
+            res,
+            volume,
             mesh: MeshMode::Marching,
-        }
-    }
-
-    fn re_mesh(&mut self) {
-        let (vertices, indices) = self.mesh.gen_mesh(&self.voxels);
+        };
 
-        self.vb = StagedBuffer::from_iter(
-            &self.renderer.device,
-            BufferUsage::vertex_buffer(),
-            vertices.into_iter(),
-        )
-        .unwrap();
+        // every chunk of a new volume is dirty
+        // the first frame waits for every chunk
+        app.pending = app.volume.take_dirty();
+        app.re_mesh(Duration::MAX);
+        app
+    }
 
-        self.ib = StagedBuffer::from_iter(
-            &self.renderer.device,
-            BufferUsage::index_buffer(),
-            indices.into_iter(),
-        )
-        .unwrap();
+    fn set_mesh_mode(&mut self, mesh: MeshMode) {
+        self.mesh = mesh;
+        self.volume.mark_all_dirty();
     }
 
-    fn ubo(&self, delta: f32) -> UniformData {
-        let aspect = self.frame.aspect();
+    /// Mesh the `pending` chunks again in parallel batches until
+    /// `budget` has passed, the rest keep their old meshes until then
+    fn re_mesh(&mut self, budget: Duration) {
+        let start = Instant::now();
+        let batch = rayon::current_num_threads().max(1);
+        while !self.pending.is_empty() && start.elapsed() < budget {
+            let count = batch.min(self.pending.len());
+            let chunks: Vec<_> = self.pending.drain(..count).collect();
+
+            let (volume, mesh) = (&self.volume, &self.mesh);
+            let meshes: Vec<_> = chunks
+                .par_iter()
+                .map(|&chunk| (volume.chunk_index(chunk), mesh.gen_mesh(volume, chunk)))
+                .collect();
+
+            for (index, (vertices, indices)) in meshes {
+                self.chunks[index] = ChunkMesh::new(&self.renderer.device, vertices, indices);
+            }
+        }
+    }
 
-        let dir = Vec3::new(
-            self.fpcam.dir(delta).y.cos() * self.fpcam.dir(delta).x.sin(),
-            self.fpcam.dir(delta).y.sin(),
-            self.fpcam.dir(delta).y.cos() * self.fpcam.dir(delta).x.cos(),
+    /// Dig the voxel in the center of the view or place one in front of it
+    fn edit(&mut self, button: MouseButton) {
+        let hit = self.volume.raycast(
+            self.camera.position,
+            self.camera.forward(),
+            EDIT_DISTANCE,
+            0.5,
         );
-        let eye = self.position + self.velocity * delta;
-        let focus = eye - dir;
-        let up = Vec3::new(0.0, 1.0, 0.0);
+        match (button, hit) {
+            (MouseButton::Left, Some(VoxelHit { voxel, .. })) => {
+                let [x, y, z] = voxel;
+                self.volume.set_voxel(x, y, z, 0.0);
+            }
+            (
+                MouseButton::Right,
+                Some(VoxelHit {
+                    previous: Some(previous),
+                    ..
+                }),
+            ) => {
+                let [x, y, z] = previous;
+                self.volume.set_voxel(x, y, z, 1.0);
+            }
+            _ => {}
+        }
+    }
+
+    /// `alpha` interpolates between the last two updates
+    fn ubo(&mut self, alpha: f32) -> UniformData {
+        self.camera.update_aspect(&self.frame);
+        self.camera.position = self.prev_position.lerp(self.position, alpha);
+        self.camera.rotation = self.fpcam.rotation(alpha);
 
         UniformData {
-            mvp: Mat4::perspective_rh(1.0, aspect, 0.01, 500.0)
-                * Mat4::look_at_rh(eye, focus, up)
-                * Mat4::from_scale(Vec3::new(1.0, 1.0, 1.0)),
+            mvp: self.camera.vp(),
         }
     }
 }
 
 impl Runnable for App {
-    fn update(&mut self, _: &mut State, delta: f32) {
+    fn renderers(&mut self) -> Vec<&mut Renderer> {
+        vec![&mut self.renderer]
+    }
+
+    fn update(&mut self, state: &mut State, delta: f32) {
+        if self.input.just_pressed(Input::Stats, 0) {
+            self.debug = !self.debug;
+        }
+        if self.input.just_pressed(Input::Next, 0) {
+            self.fpcam.config_mut().sensitivity *= 1.25;
+        }
+        if self.input.just_pressed(Input::Prev, 0) {
+            self.fpcam.config_mut().sensitivity /= 1.25;
+        }
+        if self.input.just_pressed(Input::Reload, 0) {
+            let _timer = ScopedTimer::new("regen", state.span_reporter("regen"));
+            self.volume = VoxelVolume::from_grid(&generate_voxels(rand::random(), self.res));
+        }
+        if self.input.just_pressed(Input::RollLeft, 0) {
+            self.set_mesh_mode(MeshMode::Cubes);
+        }
+        if self.input.just_pressed(Input::RollRight, 0) {
+            self.set_mesh_mode(MeshMode::Marching);
+        }
+        if self.input.just_pressed(Input::RollDown, 0) {
+            self.set_mesh_mode(MeshMode::SMarching);
+        }
+
+        // only the edited chunks and their neighbors are meshed again,
+        // a new volume or mesher is spread over several updates
+        let dirty = self.volume.take_dirty();
+        self.pending.retain(|chunk| !dirty.contains(chunk));
+        self.pending.splice(0..0, dirty);
+        if !self.pending.is_empty() {
+            let _timer = ScopedTimer::new("remesh", state.span_reporter("remesh"));
+            self.re_mesh(REMESH_BUDGET);
+        }
+
         self.fpcam.update(&self.input, delta);
         let speed = delta
             * if self.input.get_input(Input::Decelerate, 0).triggered() {
@@ -230,61 +393,70 @@ impl Runnable for App {
                 20.0
             };
 
-        self.velocity = Vec3::new(0.0, 0.0, 0.0);
         let local_dir = self.input.get_axis(InputAxis::Move, 0);
         let local_dir = Vec3::new(
             local_dir.x,
             local_dir.y,
             self.input.get_axis(InputAxis::ZMove, 0).x,
         );
-        self.velocity.x = local_dir.x * self.fpcam.dir(delta).x.cos()
-            - local_dir.y * self.fpcam.dir(delta).x.sin();
-        self.velocity.z = -local_dir.x * self.fpcam.dir(delta).x.sin()
-            - local_dir.y * self.fpcam.dir(delta).x.cos();
-        self.velocity.y = -local_dir.z;
-        self.velocity *= speed;
-        self.position += self.velocity;
-    }
+        let yaw = self.fpcam.dir(1.0).x;
+        let velocity = Vec3::new(
+            local_dir.x * yaw.cos() - local_dir.y * yaw.sin(),
+            local_dir.z,
+            -local_dir.x * yaw.sin() - local_dir.y * yaw.cos(),
+        );
 
-    fn event(&mut self, state: &mut State, event: &Event) {
-        self.frame.event(event);
-        self.input.event(event);
-        self.fpcam.event(event, &self.frame);
+        self.prev_position = self.position;
+        self.position += velocity * speed;
+    }
 
-        if self.input.should_close() {
-            state.stop = true;
+    fn shutdown(&mut self, state: &mut State) {
+        if let Some(path) = self.perf.take() {
+            let result = if path.extension().map_or(false, |ext| ext == "json") {
+                state.perf_recorder.dump_json(&path)
+            } else {
+                state.perf_recorder.dump_csv(&path)
+            };
+            match result {
+                Ok(()) => println!("Frame times written to: {}", path.display()),
+                Err(err) => log::error!("Failed to write frame times: {}", err),
+            }
         }
+    }
 
-        if let Some((_, _, ElementState::Pressed)) = self.input.to_input(event, Input::Stats) {
-            self.debug = !self.debug;
-        }
-        if let Some((_, _, ElementState::Pressed)) = self.input.to_input(event, Input::Reload) {
-            let tp = Instant::now();
-            self.voxels = generate_voxels(rand::random());
-            self.re_mesh();
-            println!("Re-gen and re-mesh took: {}ms", tp.elapsed().as_millis());
-        }
-        if let Some((_, _, ElementState::Pressed)) = self.input.to_input(event, Input::RollLeft) {
-            let tp = Instant::now();
-            self.mesh = MeshMode::Cubes;
-            self.re_mesh();
-            println!("Re-mesh took: {}ms", tp.elapsed().as_millis());
-        }
-        if let Some((_, _, ElementState::Pressed)) = self.input.to_input(event, Input::RollRight) {
-            let tp = Instant::now();
-            self.mesh = MeshMode::Marching;
-            self.re_mesh();
-            println!("Re-mesh took: {}ms", tp.elapsed().as_millis());
-        }
-        if let Some((_, _, ElementState::Pressed)) = self.input.to_input(event, Input::RollDown) {
-            let tp = Instant::now();
-            self.mesh = MeshMode::SMarching;
-            self.re_mesh();
-            println!("Re-mesh took: {}ms", tp.elapsed().as_millis());
+    fn input_event(&mut self, state: &mut State, event: &InputEvent) {
+        self.frame.input_event(event);
+        self.input.input_event(event);
+        self.fpcam.input_event(event, &self.frame);
+
+        match event {
+            InputEvent::CloseRequested => state.stop = true,
+            InputEvent::MouseButton {
+                button,
+                pressed: true,
+            } => self.edit(*button),
+            // each light count is a specialized pipeline
+            InputEvent::KeyPressed {
+                key: Some(VirtualKeyCode::L),
+                ..
+            } => {
+                let count = if self.shaders.0.light_count == 1 {
+                    3
+                } else {
+                    1
+                };
+                self.shaders.0.set_light_count(&self.renderer, count);
+                self.materials.0 = Material::new(self.shaders.0.pipeline.clone(), Vec::new());
+            }
+            _ => {}
         }
     }
 
-    fn draw(&mut self, state: &mut State, delta: f32) {
+    fn input_state(&mut self) -> Option<&mut InputState> {
+        Some(&mut self.input)
+    }
+
+    fn draw(&mut self, state: &mut State, alpha: f32) -> Result<(), GearsError> {
         let FrameData {
             mut recorder,
             viewport,
@@ -294,59 +466,88 @@ impl Runnable for App {
             image_index,
             frame_in_flight,
             future,
-        } = self.renderer.begin_frame(state);
+        } = match self.renderer.begin_frame(state) {
+            Ok(frame_data) => frame_data,
+            // e.g. minimized, try again on the next frame
+            Err(RenderError::OutOfDate) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
 
         // outside of render pass
-        self.vb.update(&mut recorder).unwrap();
-        self.ib.update(&mut recorder).unwrap();
+        for chunk in self.chunks.iter().flatten() {
+            chunk.mesh.update(&mut recorder)?;
+        }
 
-        let ubo = self.ubo(delta);
-        let (layout, set, pipeline) = if self.debug {
-            let ubo = self.shaders.1.buffer_pool.next(ubo).unwrap();
-            let layout = self.shaders.1.pipeline.layout().descriptor_set_layouts()[0].clone();
-            (
-                self.shaders.1.pipeline.layout().clone(),
-                PersistentDescriptorSet::new_with_pool(
-                    layout,
-                    0,
-                    &mut self.shaders.1.desc_pool,
-                    [WriteDescriptorSet::buffer(0, ubo)],
-                )
-                .unwrap(),
-                self.shaders.1.pipeline.clone(),
-            )
+        let ubo = self.ubo(alpha);
+        let (material, uniforms) = if self.debug {
+            (&mut self.materials.1, &mut self.shaders.1.uniforms)
         } else {
-            let ubo = self.shaders.0.buffer_pool.next(ubo).unwrap();
-            let layout = self.shaders.0.pipeline.layout().descriptor_set_layouts()[0].clone();
-            (
-                self.shaders.0.pipeline.layout().clone(),
-                PersistentDescriptorSet::new_with_pool(
-                    layout,
-                    0,
-                    &mut self.shaders.0.desc_pool,
-                    [WriteDescriptorSet::buffer(0, ubo)],
-                )
-                .unwrap(),
-                self.shaders.0.pipeline.clone(),
-            )
+            (&mut self.materials.0, &mut self.shaders.0.uniforms)
         };
+        material.set_descriptor_sets(vec![uniforms.update(frame_in_flight, ubo)?]);
+        let material = &*material;
+
+        // skip the chunks outside of the view
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            if let Some(chunk) = chunk {
+                self.draws.push(chunk.aabb, chunk.mesh.len() / 3, index);
+            }
+        }
+        let planes = self.camera.frustum_planes();
+        let visible: Vec<usize> = self.draws.cull(&planes, &mut state.draw_reporter).collect();
+
+        let recorder = if self.serial {
+            // inside of render pass
+            let mut recorder = recorder.begin_render_pass();
+            recorder
+                .record()
+                .begin_perf(&perf)
+                .set_viewport(0, [viewport.clone()])
+                .set_scissor(0, [scissor]);
+            let mut queue = DrawQueue::new();
+            for &index in visible.iter() {
+                let chunk = self.chunks[index].as_ref().unwrap();
+                queue.submit(material, &chunk.mesh, ());
+            }
+            queue.flush(&mut recorder)?;
+            recorder.record().end_perf(&perf);
 
-        // inside of render pass
-        let mut recorder = recorder.begin_render_pass();
-        recorder
-            .record()
-            .begin_perf(&perf)
-            .set_viewport(0, [viewport.clone()])
-            .bind_pipeline_graphics(pipeline)
-            .bind_descriptor_sets(PipelineBindPoint::Graphics, layout, 0, vec![set])
-            .bind_vertex_buffers(0, self.vb.local.clone())
-            .bind_index_buffer(self.ib.local.clone())
-            .draw_indexed(self.ib.len() as _, 1, 0, 0, 0)
-            .unwrap()
-            .end_perf(&perf);
-
-        // outside of render pass again
-        let recorder = recorder.end_render_pass();
+            // outside of render pass again
+            recorder.end_render_pass()
+        } else {
+            // perf queries are not allowed inside of secondary render passes
+            recorder.record().begin_perf(&perf);
+
+            // inside of render pass
+            let mut recorder = recorder.begin_render_pass_secondary();
+            let secondary = recorder.secondary().unwrap();
+
+            // split the visible chunks into groups that are recorded in parallel
+            let chunks = &self.chunks;
+            let per_secondary = ((visible.len() + SECONDARIES - 1) / SECONDARIES).max(1);
+            let secondaries = visible
+                .par_chunks(per_secondary)
+                .map(|draws| {
+                    secondary.record(|cb| {
+                        cb.set_viewport(0, [viewport.clone()])
+                            .set_scissor(0, [scissor]);
+                        material.record(cb);
+                        for &index in draws {
+                            let chunk = chunks[index].as_ref().unwrap();
+                            chunk.mesh.record(cb)?;
+                        }
+                        Ok(())
+                    })
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+                .unwrap();
+            recorder.execute_secondaries(secondaries).unwrap();
+
+            // outside of render pass again
+            let mut recorder = recorder.end_render_pass();
+            recorder.record().end_perf(&perf);
+            recorder
+        };
 
         self.renderer.end_frame(FrameData {
             recorder,
@@ -358,13 +559,56 @@ impl Runnable for App {
             frame_in_flight,
             future,
         });
+
+        // only the re-meshed chunks are uploaded, the uniforms are mapped
+        let uploaded = self.renderer.frame_upload_bytes();
+        if uploaded != 0 {
+            log::debug!("Uploaded {uploaded} bytes");
+        }
+        Ok(())
     }
 }
 
 fn main() {
     env_logger::init();
 
-    let context = Context::env().unwrap();
+    let mut context = Context::env().unwrap();
+
+    let mut serial = false;
+    let mut perf = None;
+    let mut frames_in_flight = 2;
+    let mut fps_limit = None;
+    let mut res = RES;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--list-gpus" => {
+                for gpu in context.list_gpus() {
+                    println!("{}: {} ({:?})", gpu.index, gpu.name, gpu.device_type);
+                }
+                return;
+            }
+            "--gpu" => {
+                let index = args.next().and_then(|index| index.parse().ok());
+                context.pick = ContextGPUPick::Index(index.expect("--gpu expects a GPU index"));
+            }
+            "--serial" => serial = true,
+            "--perf" => perf = Some(PathBuf::from(args.next().expect("--perf expects a path"))),
+            "--frames-in-flight" => {
+                let n = args.next().and_then(|n| n.parse().ok());
+                frames_in_flight = n.expect("--frames-in-flight expects a frame count");
+            }
+            "--fps-limit" => {
+                let n = args.next().and_then(|n| n.parse().ok());
+                fps_limit = Some(n.expect("--fps-limit expects frames per second"));
+            }
+            "--res" => {
+                let n = args.next().and_then(|n| n.parse().ok());
+                res = n.expect("--res expects a voxel count");
+            }
+            other => log::warn!("Ignored unknown argument: {}", other),
+        }
+    }
 
     let mut frame = Frame::builder(context)
         .with_title("Simple Example")
@@ -374,11 +618,19 @@ fn main() {
         .build()
         .unwrap();
 
-    let game_loop = frame.game_loop().unwrap();
+    let game_loop = frame.game_loop().unwrap().fps_limit(fps_limit);
 
-    let renderer = Renderer::builder(&frame).build().unwrap();
+    // reversed-Z removes the z-fighting of far away voxels
+    let renderer = Renderer::builder(&frame)
+        .with_depth_mode(DepthMode::ReversedZ)
+        .with_frames_in_flight(frames_in_flight)
+        .build()
+        .unwrap();
 
-    let app = App::init(frame, renderer);
+    let app = App::init(frame, renderer, serial, perf, res);
 
-    game_loop.run(Some(UPDATE_RATE), app);
+    if let Err(err) = game_loop.run(Some(UPDATE_RATE), app) {
+        log::error!("{err}");
+        std::process::exit(1);
+    }
 }
@@ -1,19 +1,16 @@
-use gears::{gears_pipeline::Input, glam::Mat4, renderer::simple_renderer::Renderer};
+use gears::{
+    gears_pipeline::Input,
+    glam::Mat4,
+    renderer::{
+        pipeline::{GPipelineBuilder, PipelineVariants},
+        simple_renderer::Renderer,
+        uniform::FrameUniformSets,
+    },
+};
 use std::sync::Arc;
 use vulkano::{
-    buffer::CpuBufferPool,
-    descriptor_set::pool::StdDescriptorPool,
-    pipeline::{
-        graphics::{
-            depth_stencil::DepthStencilState,
-            input_assembly::{InputAssemblyState},
-            rasterization::{CullMode, FrontFace, RasterizationState},
-            vertex_input::BuffersDefinition,
-            viewport::ViewportState,
-        },
-        GraphicsPipeline,
-    },
-    render_pass::Subpass,
+    pipeline::{graphics::GraphicsPipelineCreationError, GraphicsPipeline},
+    shader::ShaderModule,
 };
 
 //
@@ -25,7 +22,7 @@ pub struct VertexData {
     pub vi_exp: f32,
 }
 
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
 #[repr(C)]
 pub struct UniformData {
     pub mvp: Mat4,
@@ -70,8 +67,13 @@ mod debug_frag {
 
 pub struct DefaultPipeline {
     pub pipeline: Arc<GraphicsPipeline>,
-    pub desc_pool: Arc<StdDescriptorPool>,
-    pub buffer_pool: CpuBufferPool<UniformData>,
+    pub uniforms: FrameUniformSets<UniformData>,
+    pub light_count: i32,
+
+    // one pipeline per `LIGHT_COUNT` specialization
+    variants: PipelineVariants<i32>,
+    vert: Arc<ShaderModule>,
+    frag: Arc<ShaderModule>,
 }
 
 impl DefaultPipeline {
@@ -79,45 +81,56 @@ impl DefaultPipeline {
         let vert = vert::load(renderer.device.logical().clone()).unwrap();
         let frag = frag::load(renderer.device.logical().clone()).unwrap();
 
-        let pipeline = GraphicsPipeline::start()
-            //
-            .input_assembly_state(InputAssemblyState::new())
-            //
-            .vertex_input_state(BuffersDefinition::new().vertex::<VertexData>())
-            .vertex_shader(vert.entry_point("main").unwrap(), ())
-            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
-            //
-            .fragment_shader(frag.entry_point("main").unwrap(), ())
-            .depth_stencil_state(DepthStencilState::simple_depth_test())
-            //
-            .rasterization_state(
-                RasterizationState::new()
-                    .cull_mode(CullMode::Back)
-                    .front_face(FrontFace::Clockwise),
-            )
-            .render_pass(Subpass::from(renderer.render_pass(), 0).unwrap())
-            //
-            .build(renderer.device.logical().clone())
+        let mut variants = PipelineVariants::new();
+        let pipeline = variants
+            .get_or_build(1, |&count| Self::variant(renderer, &vert, &frag, count))
             .unwrap();
 
-        let desc_pool = Arc::new(StdDescriptorPool::new(renderer.device.logical().clone()));
-        let buffer_pool =
-            CpuBufferPool::<UniformData>::uniform_buffer(renderer.device.logical().clone());
+        let uniforms = FrameUniformSets::new(&renderer.device, &pipeline).unwrap();
 
         Self {
             pipeline,
-            buffer_pool,
-            desc_pool,
+            uniforms,
+            light_count: 1,
+
+            variants,
+            vert,
+            frag,
         }
     }
+
+    /// Switch to the variant with `count` lights, built the first time
+    ///
+    /// The variants share the layout, so `uniforms` stays valid.
+    pub fn set_light_count(&mut self, renderer: &Renderer, count: i32) {
+        let (vert, frag) = (&self.vert, &self.frag);
+        self.pipeline = self
+            .variants
+            .get_or_build(count, |&count| Self::variant(renderer, vert, frag, count))
+            .unwrap();
+        self.light_count = count;
+    }
+
+    fn variant(
+        renderer: &Renderer,
+        vert: &Arc<ShaderModule>,
+        frag: &Arc<ShaderModule>,
+        count: i32,
+    ) -> Result<Arc<GraphicsPipeline>, GraphicsPipelineCreationError> {
+        GPipelineBuilder::new(renderer)
+            .with_input::<VertexData>()
+            .with_vertex_shader(vert.entry_point("main").unwrap())
+            .with_fragment_shader(frag.entry_point("main").unwrap())
+            .with_fragment_specialization(frag::SpecializationConstants { LIGHT_COUNT: count })
+            .build()
+    }
 }
 
 //
 
 pub struct DebugPipeline {
     pub pipeline: Arc<GraphicsPipeline>,
-    pub desc_pool: Arc<StdDescriptorPool>,
-    pub buffer_pool: CpuBufferPool<UniformData>,
+    pub uniforms: FrameUniformSets<UniformData>,
 }
 
 impl DebugPipeline {
@@ -126,37 +139,16 @@ impl DebugPipeline {
         let geom = geom::load(renderer.device.logical().clone()).unwrap();
         let frag = debug_frag::load(renderer.device.logical().clone()).unwrap();
 
-        let pipeline = GraphicsPipeline::start()
-            //
-            .input_assembly_state(InputAssemblyState::new())
-            //
-            .vertex_input_state(BuffersDefinition::new().vertex::<VertexData>())
-            .vertex_shader(vert.entry_point("main").unwrap(), ())
-            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
-            //
-            .geometry_shader(geom.entry_point("main").unwrap(), ())
-            //
-            .fragment_shader(frag.entry_point("main").unwrap(), ())
-            .depth_stencil_state(DepthStencilState::simple_depth_test())
-            //
-            .rasterization_state(
-                RasterizationState::new()
-                    .cull_mode(CullMode::Back)
-                    .front_face(FrontFace::Clockwise),
-            )
-            .render_pass(Subpass::from(renderer.render_pass(), 0).unwrap())
-            //
-            .build(renderer.device.logical().clone())
+        let pipeline = GPipelineBuilder::new(renderer)
+            .with_input::<VertexData>()
+            .with_vertex_shader(vert.entry_point("main").unwrap())
+            .with_geometry_shader(geom.entry_point("main").unwrap())
+            .with_fragment_shader(frag.entry_point("main").unwrap())
+            .build()
             .unwrap();
 
-        let desc_pool = Arc::new(StdDescriptorPool::new(renderer.device.logical().clone()));
-        let buffer_pool =
-            CpuBufferPool::<UniformData>::uniform_buffer(renderer.device.logical().clone());
+        let uniforms = FrameUniformSets::new(&renderer.device, &pipeline).unwrap();
 
-        Self {
-            pipeline,
-            buffer_pool,
-            desc_pool,
-        }
+        Self { pipeline, uniforms }
     }
 }
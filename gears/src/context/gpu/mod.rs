@@ -8,7 +8,14 @@ use colored::Colorize;
 use score::GPUScore;
 use std::{fmt::Write, sync::Arc};
 use suitable::SuitableGPU;
-use vulkano::{device::physical::PhysicalDevice, instance::Instance, swapchain::Surface};
+use vulkano::{
+    device::{
+        physical::{PhysicalDevice, PhysicalDeviceType},
+        Features,
+    },
+    instance::Instance,
+    swapchain::Surface,
+};
 use winit::window::Window;
 
 pub mod any;
@@ -16,20 +23,66 @@ pub mod score;
 pub mod suitable;
 pub mod unsuitable;
 
+/// GPU that has the queue families gears needs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpuInfo {
+    /// Stable for the lifetime of the `Context`,
+    /// see `ContextGPUPick::Index`
+    pub index: usize,
+    pub name: String,
+    pub device_type: PhysicalDeviceType,
+    /// Device local memory in bytes
+    pub memory: u64,
+}
+
+impl GpuInfo {
+    /// Present support can only be checked with a surface,
+    /// so this only checks for a graphics queue family
+    pub fn enumerate(instance: &Arc<Instance>) -> Vec<Self> {
+        PhysicalDevice::enumerate(instance)
+            .filter(|p_device| {
+                p_device
+                    .queue_families()
+                    .any(|queue_family| queue_family.supports_graphics())
+            })
+            .map(|p_device| {
+                let properties = p_device.properties();
+                Self {
+                    index: p_device.index(),
+                    name: properties.device_name.clone(),
+                    device_type: properties.device_type,
+                    memory: GPUScore::new(p_device).memory,
+                }
+            })
+            .collect()
+    }
+}
+
 // pick
 
 impl SuitableGPU {
+    /// GPUs without the queue families or the `features` are not suitable
     pub fn pick(
         instance: &Arc<Instance>,
-        surface: &Arc<Surface<Window>>,
+        surface: &Arc<Surface<Arc<Window>>>,
         pick: ContextGPUPick,
+        features: &Features,
     ) -> Result<Self, ContextError> {
         let p_devices = PhysicalDevice::enumerate(instance)
             .map(|p_device| {
                 let queue_families = QueueFamilies::new(surface, p_device)?;
                 let score = GPUScore::new(p_device);
 
-                if queue_families.is_some() {
+                let supported = p_device.supported_features();
+                if !supported.is_superset_of(features) {
+                    log::warn!(
+                        "GPU {} does not support the required features: {:?}",
+                        p_device.properties().device_name,
+                        features.difference(supported)
+                    );
+                }
+
+                if queue_families.is_some() && supported.is_superset_of(features) {
                     Ok(GPUPicker::Suitable(SuitableGPU {
                         instance: instance.clone(),
                         p_device: p_device.index(),
@@ -95,6 +148,23 @@ impl SuitableGPU {
         let all_iter = p_devices.iter().map(|d| d as &dyn AnyGPU);
         let suitable_iter = suitable.iter().map(|d| d as &dyn AnyGPU);
 
+        if let ContextGPUPick::Index(index) = pick {
+            if let Some(i) = suitable.iter().position(|d| d.p_device == index) {
+                let p_device = suitable.remove(i);
+                log::info!(
+                    "Picked: GPU index: {} ({})",
+                    index,
+                    p_device.device().properties().device_name.blue(),
+                );
+                return Ok(p_device);
+            }
+
+            log::warn!(
+                "ContextGPUPick::Index({}) is not a suitable GPU, picking automatically",
+                index
+            );
+        }
+
         let p_device = if suitable.is_empty() {
             None
         } else if suitable.len() == 1 {
@@ -1,9 +1,10 @@
-use crate::debug;
-use std::{env, sync::Arc};
+use crate::debug::{self, DebugConfig, DebugCounts, DebugFilter, DebugSeverity};
+use gpu::GpuInfo;
+use std::{env, error::Error, fmt, sync::Arc};
 use vulkano::{
-    device::DeviceCreationError,
+    device::{physical::PhysicalDevice, DeviceCreationError, Features, Properties},
     instance::{
-        debug::{DebugCallback, DebugCallbackCreationError},
+        debug::{DebugCallback, DebugCallbackCreationError, MessageType},
         layers_list, Instance, InstanceCreateInfo, InstanceCreationError, InstanceExtensions,
         LayerProperties, LayersListError,
     },
@@ -20,6 +21,11 @@ pub enum ContextGPUPick {
 
     /// Pick the GPU with the commandline.
     Manual,
+
+    /// Pick the GPU with this index from `Context::list_gpus`.
+    ///
+    /// Falls back to `Automatic` if the GPU is not suitable.
+    Index(usize),
 }
 
 impl Default for ContextGPUPick {
@@ -30,6 +36,9 @@ impl Default for ContextGPUPick {
                 let valid = match value.to_lowercase().as_str() {
                     "auto" => ContextGPUPick::Automatic,
                     "pick" => ContextGPUPick::Manual,
+                    other if other.parse::<usize>().is_ok() => {
+                        ContextGPUPick::Index(other.parse().unwrap())
+                    }
                     other => {
                         log::warn!("Ignored invalid value: {}", other);
                         return Err(());
@@ -85,6 +94,74 @@ pub enum ContextError {
     DeviceCreationError(DeviceCreationError),
     SwapchainCreationError(SwapchainCreationError),
     NoSuitableGPUs,
+    /// Required features, see `Context::with_features`, the GPU does not support
+    MissingFeatures(Features),
+    NoDepthFormat,
+    NoEventLoop,
+    SurfaceNotSupported,
+    BadIcon(String),
+}
+
+impl fmt::Display for ContextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContextError::InstanceCreationError(err) => {
+                write!(f, "Failed to create the Vulkan instance: {}", err)
+            }
+            ContextError::LayersListError(err) => {
+                write!(f, "Failed to list the Vulkan layers: {}", err)
+            }
+            ContextError::DebugCallbackCreationError(err) => {
+                write!(f, "Failed to create the debug callback: {}", err)
+            }
+            ContextError::SurfaceCreationError(err) => {
+                write!(f, "Failed to create the window surface: {}", err)
+            }
+            ContextError::CapabilitiesError(err) => {
+                write!(f, "Failed to query the surface capabilities: {}", err)
+            }
+            ContextError::DeviceCreationError(err) => {
+                write!(f, "Failed to create the logical device: {}", err)
+            }
+            ContextError::SwapchainCreationError(err) => {
+                write!(f, "Failed to create the swapchain: {}", err)
+            }
+            ContextError::NoSuitableGPUs => write!(f, "None of the GPUs are suitable"),
+            ContextError::MissingFeatures(features) => {
+                write!(f, "The GPU is missing required features: {:?}", features)
+            }
+            ContextError::NoDepthFormat => {
+                write!(f, "None of the depth formats are supported by the GPU")
+            }
+            ContextError::NoEventLoop => {
+                write!(f, "The event loop was already taken by the game loop")
+            }
+            ContextError::SurfaceNotSupported => {
+                write!(f, "The window surface is not supported by the device")
+            }
+            ContextError::BadIcon(err) => write!(f, "Invalid window icon: {}", err),
+        }
+    }
+}
+
+impl Error for ContextError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ContextError::InstanceCreationError(err) => Some(err),
+            ContextError::LayersListError(err) => Some(err),
+            ContextError::DebugCallbackCreationError(err) => Some(err),
+            ContextError::SurfaceCreationError(err) => Some(err),
+            ContextError::CapabilitiesError(err) => Some(err),
+            ContextError::DeviceCreationError(err) => Some(err),
+            ContextError::SwapchainCreationError(err) => Some(err),
+            ContextError::NoSuitableGPUs => None,
+            ContextError::MissingFeatures(_) => None,
+            ContextError::NoDepthFormat => None,
+            ContextError::NoEventLoop => None,
+            ContextError::SurfaceNotSupported => None,
+            ContextError::BadIcon(_) => None,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -92,7 +169,11 @@ pub struct Context {
     pub pick: ContextGPUPick,
     pub validation: ContextValidation,
     pub debugger: Arc<Option<DebugCallback>>,
+    pub debug_counts: Arc<DebugCounts>,
+    pub debug_filter: Arc<DebugFilter>,
     pub instance: Arc<Instance>,
+    /// Optional device features the GPU must support, see `with_features`
+    pub features: Features,
 }
 
 impl Context {
@@ -145,7 +226,7 @@ impl Context {
     ///
     /// Environment value `GEARS_GPU_PICK` overrides the `ContextGPUPick` if present.
     ///
-    /// Possible values: `auto`, `pick`.
+    /// Possible values: `auto`, `pick` or a GPU index.
     ///
     /// Defaults to `auto`.
     ///
@@ -160,7 +241,62 @@ impl Context {
         Self::new(Default::default(), Default::default())
     }
 
+    /// Require optional device features, e.g. `sampler_anisotropy`,
+    /// `fill_mode_non_solid` or `geometry_shader`
+    ///
+    /// GPUs without them are not suitable and the device is created with
+    /// them enabled. Creating the device fails with
+    /// `ContextError::MissingFeatures` if the GPU does not support them.
+    pub fn with_features(mut self, features: Features) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// GPUs that can be picked with `ContextGPUPick::Index`
+    pub fn list_gpus(&self) -> Vec<GpuInfo> {
+        GpuInfo::enumerate(&self.instance)
+    }
+
+    /// Limits and properties of the GPU at `index`, see `list_gpus`
+    pub fn device_limits(&self, index: usize) -> Option<&Properties> {
+        PhysicalDevice::from_index(&self.instance, index).map(|p_device| p_device.properties())
+    }
+
+    /// Optional features the GPU at `index` supports, see `list_gpus`
+    pub fn supported_features(&self, index: usize) -> Option<&Features> {
+        PhysicalDevice::from_index(&self.instance, index)
+            .map(|p_device| p_device.supported_features())
+    }
+
+    /// Change which validation messages get through
+    ///
+    /// e.g. `DebugSeverity::Warning` to only see errors and warnings
+    pub fn set_debug_filter(&self, min_severity: DebugSeverity, ty: MessageType) {
+        self.debug_filter.set(min_severity, ty);
+    }
+
+    /// Validation errors reported so far
+    ///
+    /// Always zero without validation.
+    pub fn validation_error_count(&self) -> usize {
+        self.debug_counts.errors()
+    }
+
+    /// Validation warnings reported so far
+    pub fn validation_warning_count(&self) -> usize {
+        self.debug_counts.warnings()
+    }
+
     pub fn new(pick: ContextGPUPick, validation: ContextValidation) -> Result<Self, ContextError> {
+        Self::new_with_debug(pick, validation, DebugConfig::default())
+    }
+
+    /// `debug` is ignored without validation
+    pub fn new_with_debug(
+        pick: ContextGPUPick,
+        validation: ContextValidation,
+        debug: DebugConfig,
+    ) -> Result<Self, ContextError> {
         // versions
 
         let engine_version = (
@@ -193,10 +329,16 @@ impl Context {
 
         // debugger
 
+        let debug_counts = Arc::new(DebugCounts::default());
+        let debug_filter = Arc::new(DebugFilter::new(debug.min_severity, debug.ty));
         let debugger = Arc::new(if validation == ContextValidation::WithValidation {
-            let debugger =
-                DebugCallback::new(&instance, debug::SEVERITY, debug::TY, debug::callback)
-                    .map_err(ContextError::DebugCallbackCreationError)?;
+            let debugger = DebugCallback::new(
+                &instance,
+                debug::SEVERITY,
+                debug::TY,
+                debug::callback(debug, debug_counts.clone(), debug_filter.clone()),
+            )
+            .map_err(ContextError::DebugCallbackCreationError)?;
 
             log::warn!("Debugger enabled");
             Some(debugger)
@@ -209,6 +351,9 @@ impl Context {
             validation,
             instance,
             debugger,
+            debug_counts,
+            debug_filter,
+            features: Features::none(),
         })
     }
 }
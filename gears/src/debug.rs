@@ -1,11 +1,14 @@
+use std::{
+    fmt,
+    panic::RefUnwindSafe,
+    sync::{
+        atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering},
+        Arc,
+    },
+};
 use vulkano::instance::debug::{Message, MessageSeverity, MessageType};
 
-pub const SEVERITY: MessageSeverity = MessageSeverity {
-    error: true,
-    warning: true,
-    information: true,
-    verbose: false,
-};
+//
 
 pub const TY: MessageType = MessageType {
     general: true,
@@ -13,21 +16,248 @@ pub const TY: MessageType = MessageType {
     validation: true,
 };
 
-pub fn callback(message: &Message) {
-    let level = if message.severity.error {
-        log::Level::Error
-    } else if message.severity.warning {
-        log::Level::Warn
-    } else if message.severity.information {
-        log::Level::Info
-    } else {
-        log::Level::Trace
-    };
-
-    log::log!(level, "DebugCallback: \n{}", message.description);
-
-    #[cfg(feature = "validation_panic")]
-    if level == log::Level::Error {
-        panic!("Validation error");
+/// The callback is registered for all severities,
+/// `DebugFilter` decides which ones get through
+pub const SEVERITY: MessageSeverity = MessageSeverity {
+    error: true,
+    warning: true,
+    information: true,
+    verbose: true,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum DebugSeverity {
+    Verbose,
+    Info,
+    Warning,
+    Error,
+}
+
+pub type DebugErrorCallback = Arc<dyn Fn(&Message) + Send + Sync + RefUnwindSafe>;
+
+/// What to do with `DebugSeverity::Error` messages
+#[derive(Clone)]
+pub enum OnError {
+    /// Log the error.
+    Log,
+
+    /// Log the error and panic.
+    Panic,
+
+    /// Log the error and call this callback.
+    Callback(DebugErrorCallback),
+}
+
+/// Validation layer message routing
+#[derive(Debug, Clone)]
+pub struct DebugConfig {
+    /// Messages below this severity are ignored.
+    ///
+    /// Defaults to `Info`.
+    pub min_severity: DebugSeverity,
+
+    /// Message types that are not ignored.
+    ///
+    /// Defaults to all types.
+    pub ty: MessageType,
+
+    /// Message ID names (e.g. `UNASSIGNED-BestPractices-vkCreateDevice-specialuse-extension`)
+    /// that are ignored.
+    pub deny_list: Vec<String>,
+
+    /// Defaults to `Panic` with the `validation_panic` feature and to `Log` without it.
+    pub on_error: OnError,
+}
+
+/// Counts of the messages that passed the filters
+#[derive(Debug, Default)]
+pub struct DebugCounts {
+    errors: AtomicUsize,
+    warnings: AtomicUsize,
+}
+
+/// Message filter that can be changed at runtime,
+/// see `Context::set_debug_filter`
+#[derive(Debug)]
+pub struct DebugFilter {
+    min_severity: AtomicU8,
+    general: AtomicBool,
+    performance: AtomicBool,
+    validation: AtomicBool,
+}
+
+//
+
+impl fmt::Debug for OnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OnError::Log => write!(f, "Log"),
+            OnError::Panic => write!(f, "Panic"),
+            OnError::Callback(_) => write!(f, "Callback(..)"),
+        }
+    }
+}
+
+impl Default for OnError {
+    fn default() -> Self {
+        if cfg!(feature = "validation_panic") {
+            OnError::Panic
+        } else {
+            OnError::Log
+        }
+    }
+}
+
+impl Default for DebugConfig {
+    fn default() -> Self {
+        Self {
+            min_severity: DebugSeverity::Info,
+            ty: TY,
+            deny_list: Vec::new(),
+            on_error: OnError::default(),
+        }
+    }
+}
+
+impl DebugConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_min_severity(mut self, min_severity: DebugSeverity) -> Self {
+        self.min_severity = min_severity;
+        self
+    }
+
+    pub fn with_denied<S: Into<String>>(mut self, message_id_name: S) -> Self {
+        self.deny_list.push(message_id_name.into());
+        self
+    }
+
+    pub fn with_on_error(mut self, on_error: OnError) -> Self {
+        self.on_error = on_error;
+        self
+    }
+
+    pub fn with_ty(mut self, ty: MessageType) -> Self {
+        self.ty = ty;
+        self
+    }
+}
+
+impl DebugCounts {
+    pub fn errors(&self) -> usize {
+        self.errors.load(Ordering::SeqCst)
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.warnings.load(Ordering::SeqCst)
+    }
+}
+
+impl DebugFilter {
+    pub fn new(min_severity: DebugSeverity, ty: MessageType) -> Self {
+        let filter = Self {
+            min_severity: AtomicU8::new(0),
+            general: AtomicBool::new(false),
+            performance: AtomicBool::new(false),
+            validation: AtomicBool::new(false),
+        };
+        filter.set(min_severity, ty);
+        filter
+    }
+
+    pub fn set(&self, min_severity: DebugSeverity, ty: MessageType) {
+        self.min_severity
+            .store(min_severity as u8, Ordering::SeqCst);
+        self.general.store(ty.general, Ordering::SeqCst);
+        self.performance.store(ty.performance, Ordering::SeqCst);
+        self.validation.store(ty.validation, Ordering::SeqCst);
+    }
+
+    pub fn min_severity(&self) -> DebugSeverity {
+        match self.min_severity.load(Ordering::SeqCst) {
+            0 => DebugSeverity::Verbose,
+            1 => DebugSeverity::Info,
+            2 => DebugSeverity::Warning,
+            _ => DebugSeverity::Error,
+        }
+    }
+
+    pub fn ty(&self) -> MessageType {
+        MessageType {
+            general: self.general.load(Ordering::SeqCst),
+            performance: self.performance.load(Ordering::SeqCst),
+            validation: self.validation.load(Ordering::SeqCst),
+        }
+    }
+
+    fn allows(&self, severity: DebugSeverity, ty: &MessageType) -> bool {
+        let allowed_ty = self.ty();
+        severity >= self.min_severity()
+            && ((ty.general && allowed_ty.general)
+                || (ty.performance && allowed_ty.performance)
+                || (ty.validation && allowed_ty.validation))
+    }
+}
+
+impl DebugSeverity {
+    fn from_message(severity: &MessageSeverity) -> Self {
+        if severity.error {
+            DebugSeverity::Error
+        } else if severity.warning {
+            DebugSeverity::Warning
+        } else if severity.information {
+            DebugSeverity::Info
+        } else {
+            DebugSeverity::Verbose
+        }
+    }
+
+    fn level(self) -> log::Level {
+        match self {
+            DebugSeverity::Error => log::Level::Error,
+            DebugSeverity::Warning => log::Level::Warn,
+            DebugSeverity::Info => log::Level::Info,
+            DebugSeverity::Verbose => log::Level::Trace,
+        }
+    }
+}
+
+pub fn callback(
+    config: DebugConfig,
+    counts: Arc<DebugCounts>,
+    filter: Arc<DebugFilter>,
+) -> impl Fn(&Message) + Send + RefUnwindSafe + 'static {
+    move |message: &Message| {
+        let severity = DebugSeverity::from_message(&message.severity);
+        if !filter.allows(severity, &message.ty) {
+            return;
+        }
+
+        // vulkano reports the message ID name as the layer prefix
+        if let Some(id) = message.layer_prefix {
+            if config.deny_list.iter().any(|denied| denied == id) {
+                return;
+            }
+        }
+
+        log::log!(severity.level(), "DebugCallback: \n{}", message.description);
+
+        match severity {
+            DebugSeverity::Error => {
+                counts.errors.fetch_add(1, Ordering::SeqCst);
+                match &config.on_error {
+                    OnError::Log => {}
+                    OnError::Panic => panic!("Validation error"),
+                    OnError::Callback(f) => f(message),
+                }
+            }
+            DebugSeverity::Warning => {
+                counts.warnings.fetch_add(1, Ordering::SeqCst);
+            }
+            _ => {}
+        }
     }
 }
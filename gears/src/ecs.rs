@@ -0,0 +1,161 @@
+//! `specs` components and systems for drawing entities
+//!
+//! `CollectInstances` runs with the other systems and uploads the model
+//! matrix and color of every `Visible` entity into a `CpuBufferPool`,
+//! grouped by `MeshHandle`. The result is stored in the `DrawQueue`
+//! resource, which the main thread uses to record the instanced draws.
+//!
+//! ### thread safety
+//! - Components, `DrawQueue` and `CollectInstances` are `Send + Sync`,
+//!   so they can be used from systems on any thread.
+//! - `Renderer` and `Recorder` are not `Send`, systems cannot own them.
+//!   Draws are recorded from the `DrawQueue` on the main thread,
+//!   after the dispatch has finished.
+
+use crate::{renderer::device::Dev, scene::Transform};
+use anyhow::Result;
+use glam::Vec4;
+use specs::{Component, Join, NullStorage, ReadStorage, System, VecStorage, Write};
+use std::{collections::BTreeMap, sync::Arc};
+use vulkano::{
+    buffer::{cpu_pool::CpuBufferPoolChunk, CpuBufferPool},
+    memory::pool::StdMemoryPool,
+};
+
+//
+
+/// Index of the mesh the entity is drawn with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MeshHandle(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TransformComponent(pub Transform);
+
+/// Per entity color, entities without it are white
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorComponent(pub Vec4);
+
+/// Only visible entities are drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Visible;
+
+/// Per instance vertex input
+///
+/// The shader has to declare `i_model` as a `mat4`
+/// and `i_color` as a `vec4` per instance input.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[repr(C)]
+pub struct InstanceData {
+    pub i_model: [[f32; 4]; 4],
+    pub i_color: [f32; 4],
+}
+
+vulkano::impl_vertex!(InstanceData, i_model, i_color);
+
+pub type InstanceChunk = Arc<CpuBufferPoolChunk<InstanceData, Arc<StdMemoryPool>>>;
+
+/// Instances of one mesh
+pub struct InstanceBatch {
+    pub mesh: MeshHandle,
+    pub instances: InstanceChunk,
+    pub count: u32,
+}
+
+/// Draws queued by `CollectInstances`
+///
+/// Read this after the dispatch and bind `InstanceBatch::instances`
+/// as the per instance vertex buffer for each mesh.
+#[derive(Default)]
+pub struct DrawQueue {
+    batches: Vec<InstanceBatch>,
+}
+
+/// Collects the visible entities into the `DrawQueue`
+pub struct CollectInstances {
+    pool: CpuBufferPool<InstanceData>,
+}
+
+//
+
+impl Component for MeshHandle {
+    type Storage = VecStorage<Self>;
+}
+
+impl Component for TransformComponent {
+    type Storage = VecStorage<Self>;
+}
+
+impl Component for ColorComponent {
+    type Storage = VecStorage<Self>;
+}
+
+impl Component for Visible {
+    type Storage = NullStorage<Self>;
+}
+
+impl Default for ColorComponent {
+    fn default() -> Self {
+        Self(Vec4::ONE)
+    }
+}
+
+impl DrawQueue {
+    pub fn batches(&self) -> &[InstanceBatch] {
+        &self.batches
+    }
+
+    pub fn instance_count(&self) -> u32 {
+        self.batches.iter().map(|batch| batch.count).sum()
+    }
+}
+
+impl CollectInstances {
+    pub fn new(device: &Dev) -> Self {
+        Self {
+            pool: CpuBufferPool::vertex_buffer(device.logical().clone()),
+        }
+    }
+
+    fn upload(&self, mesh: MeshHandle, instances: Vec<InstanceData>) -> Result<InstanceBatch> {
+        let count = instances.len() as u32;
+        let instances = self.pool.chunk(instances)?;
+        Ok(InstanceBatch {
+            mesh,
+            instances,
+            count,
+        })
+    }
+}
+
+impl<'a> System<'a> for CollectInstances {
+    type SystemData = (
+        ReadStorage<'a, MeshHandle>,
+        ReadStorage<'a, TransformComponent>,
+        ReadStorage<'a, ColorComponent>,
+        ReadStorage<'a, Visible>,
+        Write<'a, DrawQueue>,
+    );
+
+    fn run(&mut self, (meshes, transforms, colors, visible, mut queue): Self::SystemData) {
+        let mut batches: BTreeMap<MeshHandle, Vec<InstanceData>> = BTreeMap::new();
+        for (mesh, transform, color, _) in
+            (&meshes, &transforms, colors.maybe(), &visible).join()
+        {
+            batches.entry(*mesh).or_default().push(InstanceData {
+                i_model: transform.0.matrix().to_cols_array_2d(),
+                i_color: color.copied().unwrap_or_default().0.to_array(),
+            });
+        }
+
+        queue.batches = batches
+            .into_iter()
+            .filter_map(|(mesh, instances)| match self.upload(mesh, instances) {
+                Ok(batch) => Some(batch),
+                Err(err) => {
+                    log::error!("Instance upload failed: {err}");
+                    None
+                }
+            })
+            .collect();
+    }
+}
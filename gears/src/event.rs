@@ -0,0 +1,226 @@
+use crate::{game_loop::Event, io::input_state::Input};
+use gilrs::{Axis, Button, Event as GilrsEvent, EventType, GamepadId};
+use glam::Vec2;
+use winit::event::{
+    DeviceEvent, ElementState, Event as WinitEvent, KeyboardInput, MouseButton, MouseScrollDelta,
+    ScanCode, VirtualKeyCode, WindowEvent,
+};
+
+//
+
+/// Translated input and window events
+///
+/// Raw events that have no translation are
+/// still passed to `Runnable::event`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    KeyPressed {
+        /// First `Input` bound to this scancode,
+        /// see `InputEvent::pressed` for checking aliased inputs
+        input: Input,
+        scancode: ScanCode,
+        key: Option<VirtualKeyCode>,
+    },
+    KeyReleased {
+        input: Input,
+        scancode: ScanCode,
+        key: Option<VirtualKeyCode>,
+    },
+
+    /// Raw mouse motion, not limited by the window edges
+    MouseMoved {
+        delta: Vec2,
+    },
+    /// Cursor position in physical pixels
+    CursorMoved {
+        position: Vec2,
+    },
+    MouseButton {
+        button: MouseButton,
+        pressed: bool,
+    },
+    /// Scroll wheel in lines, positive y scrolls up (away from the user)
+    ///
+    /// Pixel deltas of touchpads are converted with `PIXELS_PER_LINE`.
+    MouseWheel {
+        delta: Vec2,
+    },
+
+    GamepadPressed {
+        gamepad: GamepadId,
+        input: Input,
+        button: Button,
+    },
+    GamepadReleased {
+        gamepad: GamepadId,
+        input: Input,
+        button: Button,
+    },
+    /// Analog button value
+    GamepadButtonChanged {
+        gamepad: GamepadId,
+        input: Input,
+        button: Button,
+        value: f32,
+    },
+    GamepadAxis {
+        gamepad: GamepadId,
+        input: Input,
+        axis: Axis,
+        value: f32,
+    },
+    GamepadConnected(GamepadId),
+    GamepadDisconnected(GamepadId),
+
+    /// New size in physical pixels
+    WindowResized {
+        width: u32,
+        height: u32,
+    },
+    /// The window moved to a monitor with a different scale factor
+    /// or the scale factor changed, with the new size in physical pixels
+    ScaleFactorChanged {
+        scale_factor: f64,
+        width: u32,
+        height: u32,
+    },
+    FocusChanged(bool),
+    CloseRequested,
+}
+
+/// Touchpad scroll pixels per mouse wheel line
+pub const PIXELS_PER_LINE: f32 = 20.0;
+
+//
+
+impl InputEvent {
+    pub fn from_event(event: &Event) -> Option<Self> {
+        match event {
+            Event::GilrsEvent(event) => Self::from_gilrs(event),
+            Event::WinitEvent(WinitEvent::WindowEvent { event, .. }) => Self::from_window(event),
+            Event::WinitEvent(WinitEvent::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta: (x, y) },
+                ..
+            }) => Some(InputEvent::MouseMoved {
+                delta: Vec2::new(*x as f32, *y as f32),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Was `input` pressed with a key or a gamepad button
+    ///
+    /// Unlike comparing `input` fields, this also
+    /// matches inputs that share the same key.
+    pub fn pressed(&self, input: Input) -> bool {
+        match self {
+            InputEvent::KeyPressed { scancode, .. } => *scancode == input.into_scancode(),
+            InputEvent::GamepadPressed { button, .. } => {
+                *button != Button::Unknown && *button == input.into_button()
+            }
+            _ => false,
+        }
+    }
+
+    /// Was `input` released with a key or a gamepad button
+    pub fn released(&self, input: Input) -> bool {
+        match self {
+            InputEvent::KeyReleased { scancode, .. } => *scancode == input.into_scancode(),
+            InputEvent::GamepadReleased { button, .. } => {
+                *button != Button::Unknown && *button == input.into_button()
+            }
+            _ => false,
+        }
+    }
+
+    pub fn from_gilrs(event: &GilrsEvent) -> Option<Self> {
+        let gamepad = event.id;
+        Some(match event.event {
+            EventType::ButtonPressed(button, _) => InputEvent::GamepadPressed {
+                gamepad,
+                input: Input::from_button(button),
+                button,
+            },
+            EventType::ButtonReleased(button, _) => InputEvent::GamepadReleased {
+                gamepad,
+                input: Input::from_button(button),
+                button,
+            },
+            EventType::ButtonChanged(button, value, _) => InputEvent::GamepadButtonChanged {
+                gamepad,
+                input: Input::from_button(button),
+                button,
+                value,
+            },
+            EventType::AxisChanged(axis, value, _) => InputEvent::GamepadAxis {
+                gamepad,
+                input: Input::from_axis(axis),
+                axis,
+                value,
+            },
+            EventType::Connected => InputEvent::GamepadConnected(gamepad),
+            EventType::Disconnected => InputEvent::GamepadDisconnected(gamepad),
+            EventType::ButtonRepeated(_, _) | EventType::Dropped => return None,
+        })
+    }
+
+    pub fn from_window(event: &WindowEvent) -> Option<Self> {
+        Some(match event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        scancode,
+                        virtual_keycode,
+                        ..
+                    },
+                ..
+            } => {
+                let input = Input::from_scancode(*scancode);
+                let (scancode, key) = (*scancode, *virtual_keycode);
+                match state {
+                    ElementState::Pressed => InputEvent::KeyPressed {
+                        input,
+                        scancode,
+                        key,
+                    },
+                    ElementState::Released => InputEvent::KeyReleased {
+                        input,
+                        scancode,
+                        key,
+                    },
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => InputEvent::CursorMoved {
+                position: Vec2::new(position.x as f32, position.y as f32),
+            },
+            WindowEvent::MouseInput { state, button, .. } => InputEvent::MouseButton {
+                button: *button,
+                pressed: *state == ElementState::Pressed,
+            },
+            WindowEvent::MouseWheel { delta, .. } => InputEvent::MouseWheel {
+                delta: match *delta {
+                    MouseScrollDelta::LineDelta(x, y) => Vec2::new(x, y),
+                    MouseScrollDelta::PixelDelta(p) => {
+                        Vec2::new(p.x as f32, p.y as f32) / PIXELS_PER_LINE
+                    }
+                },
+            },
+            WindowEvent::Resized(size) => InputEvent::WindowResized {
+                width: size.width,
+                height: size.height,
+            },
+            WindowEvent::ScaleFactorChanged {
+                scale_factor,
+                new_inner_size,
+            } => InputEvent::ScaleFactorChanged {
+                scale_factor: *scale_factor,
+                width: new_inner_size.width,
+                height: new_inner_size.height,
+            },
+            WindowEvent::Focused(focused) => InputEvent::FocusChanged(*focused),
+            WindowEvent::CloseRequested => InputEvent::CloseRequested,
+            _ => return None,
+        })
+    }
+}
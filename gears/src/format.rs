@@ -1,6 +1,102 @@
 use glam::{DMat2, DMat3, DMat4, DVec2, DVec3, DVec4, Mat2, Mat3, Mat4, Vec2, Vec3, Vec4};
 use std::mem::size_of;
-use vulkano::format::Format;
+use vulkano::format::{ClearValue, Format, NumericType};
+
+/// RGBA color, stored in linear space
+///
+/// Colors from image editors and color pickers are sRGB encoded,
+/// create those with `from_srgb`. Write `encode` into color
+/// targets and vertex data so that the color looks the same
+/// on sRGB and UNORM swapchains.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Color(Vec4);
+
+impl Color {
+    pub const BLACK: Color = Color(Vec4::new(0.0, 0.0, 0.0, 1.0));
+    pub const WHITE: Color = Color(Vec4::ONE);
+
+    /// Opaque sRGB encoded color
+    pub fn from_srgb(r: u8, g: u8, b: u8) -> Self {
+        Self::from_srgba(r, g, b, u8::MAX)
+    }
+
+    /// sRGB encoded color, alpha is linear
+    pub fn from_srgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        let c = |c: u8| c as f32 / u8::MAX as f32;
+        Self::from_srgb_vec(Vec4::new(c(r), c(g), c(b), c(a)))
+    }
+
+    /// sRGB encoded channels in `0.0..=1.0`, alpha is linear
+    pub fn from_srgb_vec(srgb: Vec4) -> Self {
+        Self(Vec4::new(
+            srgb_to_linear(srgb.x),
+            srgb_to_linear(srgb.y),
+            srgb_to_linear(srgb.z),
+            srgb.w,
+        ))
+    }
+
+    pub fn from_linear(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self(Vec4::new(r, g, b, a))
+    }
+
+    pub fn linear(&self) -> Vec4 {
+        self.0
+    }
+
+    pub fn srgb(&self) -> Vec4 {
+        Vec4::new(
+            linear_to_srgb(self.0.x),
+            linear_to_srgb(self.0.y),
+            linear_to_srgb(self.0.z),
+            self.0.w,
+        )
+    }
+
+    /// Value to write into a target with `srgb_target` encoding
+    ///
+    /// sRGB targets encode on write so they get the linear color,
+    /// UNORM targets are shown as they are so they get the sRGB color.
+    pub fn encode(&self, srgb_target: bool) -> Vec4 {
+        if srgb_target {
+            self.linear()
+        } else {
+            self.srgb()
+        }
+    }
+}
+
+/// sRGB transfer function, decode
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// sRGB transfer function, encode
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Does the format encode to sRGB on write
+pub fn is_srgb(format: Format) -> bool {
+    format.type_color() == Some(NumericType::SRGB)
+}
+
+/// Clear value of a depth attachment, with a 0 stencil if the format has one
+pub fn depth_clear_value(format: Format, depth: f32) -> ClearValue {
+    if format.type_stencil().is_some() {
+        ClearValue::DepthStencil((depth, 0))
+    } else {
+        ClearValue::Depth(depth)
+    }
+}
 
 pub trait FormatOf {
     const FORMAT_OF: Format;
@@ -1,27 +1,47 @@
 use crate::{
-    context::{gpu::suitable::SuitableGPU, Context, ContextError, ContextGPUPick},
+    context::{
+        gpu::{any::AnyGPU, suitable::SuitableGPU},
+        Context, ContextError,
+    },
+    event::InputEvent,
     game_loop::{Event, Loop},
+    renderer::queue::QueueFamilies,
     ExpectLog, SyncMode,
 };
+use glam::Vec2;
 use std::{sync::Arc, time::Instant};
-use vulkano::swapchain::Surface;
-use vulkano_win::VkSurfaceBuild;
+use vulkano::{
+    device::{Features, Properties},
+    swapchain::Surface,
+};
 use winit::{
-    dpi::LogicalSize,
-    event::{Event as WinitEvent, WindowEvent},
-    event_loop::EventLoop,
-    window::{Window, WindowBuilder},
+    dpi::{LogicalSize, PhysicalSize},
+    event_loop::{EventLoop, EventLoopWindowTarget},
+    window::{Icon, Window, WindowBuilder, WindowId},
 };
 
+/// Window, its surface and the GPU picked for it
+///
+/// The first frame owns the event loop. Additional windows, e.g. a
+/// separate inspector view, are built with `FrameBuilder::build_secondary`
+/// before `game_loop` takes the event loop. They share the `Context`
+/// and the GPU of the primary frame and their events are sent through
+/// the same game loop, see `Event::window_id`.
 pub struct Frame {
     context: Context,
-    window: Arc<Surface<Window>>,
+    window: Arc<Surface<Arc<Window>>>,
     p_device: Arc<SuitableGPU>,
     sync: SyncMode,
 
     size: (u32, u32),
     aspect: f32,
 
+    // size change not yet taken with `resized`
+    resized: Option<[u32; 2]>,
+
+    // width / height the resizes are clamped to
+    fixed_aspect: Option<f32>,
+
     event_loop: Option<EventLoop<()>>,
     init_timer: Instant,
 }
@@ -32,6 +52,10 @@ pub struct FrameBuilder<'a> {
     size: (u32, u32),
     min_size: (u32, u32),
     max_size: Option<(u32, u32)>,
+    resizable: bool,
+    decorations: bool,
+    fixed_aspect: Option<f32>,
+    icon: Option<(u32, u32, Vec<u8>)>,
     sync: SyncMode,
 }
 
@@ -43,44 +67,137 @@ impl Frame {
             size: (600, 600),
             min_size: (32, 32),
             max_size: None,
+            resizable: true,
+            decorations: true,
+            fixed_aspect: None,
+            icon: None,
             sync: SyncMode::Mailbox,
         }
     }
 
+    /// `None` for secondary frames and after the first call
     pub fn game_loop(&mut self) -> Option<Loop> {
         Some(Loop::new(
-            self.window.clone(),
+            self.window.window().clone(),
             self.event_loop.take()?,
             self.init_timer,
         ))
     }
 
+    /// Size in logical pixels, same as `logical_size`
+    ///
     /// Won't update unless events are sent to the surface as well
     pub const fn size(&self) -> (u32, u32) {
         self.size
     }
 
+    /// Size in logical pixels, physical pixels divided by `scale_factor`
+    ///
+    /// Won't update unless events are sent to the surface as well
+    pub const fn logical_size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Size in physical pixels, the size of the swapchain images
+    pub fn physical_size(&self) -> (u32, u32) {
+        let size = self.window().inner_size();
+        (size.width, size.height)
+    }
+
     /// Won't update unless events are sent to the surface as well
     pub const fn aspect(&self) -> f32 {
         self.aspect
     }
 
+    /// New size in physical pixels if the window
+    /// was resized since the last call
+    ///
+    /// Won't update unless events are sent to the surface as well
+    pub fn resized(&mut self) -> Option<[u32; 2]> {
+        self.resized.take()
+    }
+
+    /// Requested present mode after the fallback
+    /// for the modes the surface does not support
     pub const fn sync(&self) -> SyncMode {
         self.sync
     }
 
-    pub fn scale(&self) -> f64 {
+    /// Physical pixels per logical pixel, e.g. 2.0 at 200% display scaling
+    pub fn scale_factor(&self) -> f64 {
         self.window.window().scale_factor()
     }
 
+    /// Logical pixels to physical pixels,
+    /// `InputEvent::CursorMoved` is in physical pixels
+    pub fn logical_to_physical(&self, position: Vec2) -> Vec2 {
+        position * self.scale_factor() as f32
+    }
+
+    /// Physical pixels to logical pixels
+    pub fn physical_to_logical(&self, position: Vec2) -> Vec2 {
+        position / self.scale_factor() as f32
+    }
+
     pub fn window(&self) -> &Window {
         self.window.window()
     }
 
-    pub fn surface(&self) -> Arc<Surface<Window>> {
+    pub fn id(&self) -> WindowId {
+        self.window().id()
+    }
+
+    pub fn set_title(&self, title: &str) {
+        self.window().set_title(title);
+    }
+
+    /// `width` x `height` RGBA8 pixels
+    pub fn set_icon(&self, width: u32, height: u32, rgba: Vec<u8>) -> Result<(), ContextError> {
+        self.window()
+            .set_window_icon(Some(to_icon(width, height, rgba)?));
+        Ok(())
+    }
+
+    pub fn set_min_size(&self, width: u32, height: u32) {
+        self.window()
+            .set_min_inner_size(Some(tuple_to_lsize((width, height))));
+    }
+
+    /// `None` removes the limit
+    pub fn set_max_size(&self, size: Option<(u32, u32)>) {
+        self.window().set_max_inner_size(size.map(tuple_to_lsize));
+    }
+
+    pub fn set_resizable(&self, resizable: bool) {
+        self.window().set_resizable(resizable);
+    }
+
+    pub fn set_decorations(&self, decorations: bool) {
+        self.window().set_decorations(decorations);
+    }
+
+    /// Width / height the window is kept at, `None` to resize freely
+    ///
+    /// Emulated by resizing the window back after the user resized it,
+    /// so the events in between see the unclamped size.
+    pub fn set_fixed_aspect(&mut self, aspect: Option<f32>) {
+        self.fixed_aspect = aspect.filter(|aspect| aspect.is_finite() && *aspect > 0.0);
+        self.clamp_aspect();
+    }
+
+    pub fn surface(&self) -> Arc<Surface<Arc<Window>>> {
         self.window.clone()
     }
 
+    /// New surface for the same window, after `RenderError::SurfaceLost`
+    ///
+    /// The renderers drawing to this frame have to be
+    /// moved to it with `Renderer::rebuild_surface`.
+    pub fn recreate_surface(&mut self) -> Result<(), ContextError> {
+        self.window = create_surface(self.window.window().clone(), &self.context)?;
+        Ok(())
+    }
+
     pub fn gpu(&self) -> Arc<SuitableGPU> {
         self.p_device.clone()
     }
@@ -89,23 +206,64 @@ impl Frame {
         self.context.clone()
     }
 
+    /// Limits and properties of the picked GPU
+    pub fn device_limits(&self) -> &Properties {
+        self.p_device.device().properties()
+    }
+
+    /// Optional features the picked GPU supports
+    pub fn supported_features(&self) -> &Features {
+        self.p_device.device().supported_features()
+    }
+
+    /// Events of other windows are ignored
     pub fn event(&mut self, event: &Event) {
-        if let Event::WinitEvent(WinitEvent::WindowEvent {
-            event: WindowEvent::Resized(_),
-            ..
-        }) = event
-        {
-            let (size, aspect) = Self::calc_size_and_aspect(self.window());
-
-            self.size = size;
-            self.aspect = aspect;
+        if event.window_id().map_or(false, |id| id != self.id()) {
+            return;
+        }
+        if let Some(event) = InputEvent::from_event(event) {
+            self.input_event(&event);
         }
     }
 
-    fn calc_size_and_aspect(window: &Window) -> ((u32, u32), f32) {
-        let size = window.inner_size();
-        let scale = window.scale_factor();
+    pub fn input_event(&mut self, event: &InputEvent) {
+        // a new scale factor changes the logical size
+        // even if the physical size stays the same
+        let (width, height, scale_factor) = match *event {
+            InputEvent::WindowResized { width, height } => (width, height, self.scale_factor()),
+            InputEvent::ScaleFactorChanged {
+                scale_factor,
+                width,
+                height,
+            } => (width, height, scale_factor),
+            _ => return,
+        };
+        let (size, aspect) =
+            Self::calc_size_and_aspect(PhysicalSize::new(width, height), scale_factor);
+
+        self.size = size;
+        self.aspect = aspect;
+        self.resized = Some([width, height]);
+
+        self.clamp_aspect();
+    }
 
+    /// Request a size with the fixed aspect, keeping the width
+    fn clamp_aspect(&self) {
+        let aspect = match self.fixed_aspect {
+            Some(aspect) => aspect,
+            None => return,
+        };
+
+        let size = self.window().inner_size();
+        let height = (size.width as f32 / aspect).round() as u32;
+        if height.max(1) != size.height {
+            self.window()
+                .set_inner_size(PhysicalSize::new(size.width, height.max(1)));
+        }
+    }
+
+    fn calc_size_and_aspect(size: PhysicalSize<u32>, scale: f64) -> ((u32, u32), f32) {
         let size = lsize_to_tuple(size.to_logical(scale));
         let mut aspect = (size.0 as f32) / (size.1 as f32);
         aspect = if !aspect.is_finite() { 1.0 } else { aspect };
@@ -135,51 +293,134 @@ impl<'a> FrameBuilder<'a> {
         self
     }
 
-    /// No sync, Fifo or Mailbox
+    /// Can the user resize the window, true by default
+    pub const fn with_resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    /// Title bar and borders, true by default
+    pub const fn with_decorations(mut self, decorations: bool) -> Self {
+        self.decorations = decorations;
+        self
+    }
+
+    /// Keep the window at width / height, see `Frame::set_fixed_aspect`
+    pub const fn with_fixed_aspect(mut self, aspect: f32) -> Self {
+        self.fixed_aspect = Some(aspect);
+        self
+    }
+
+    /// `width` x `height` RGBA8 pixels, checked in `build`
+    pub fn with_icon(mut self, width: u32, height: u32, rgba: Vec<u8>) -> Self {
+        self.icon = Some((width, height, rgba));
+        self
+    }
+
+    /// No sync, Fifo, Mailbox or FifoRelaxed
     pub const fn with_sync(mut self, sync: SyncMode) -> Self {
         self.sync = sync;
         self
     }
 
     pub fn build(self) -> Result<Frame, ContextError> {
+        // events loop
+        let event_loop = EventLoop::new();
+
+        let mut frame = self.build_window(&event_loop, None)?;
+        frame.event_loop = Some(event_loop);
+        Ok(frame)
+    }
+
+    /// Additional window on the event loop and the GPU of `primary`
+    ///
+    /// Has to be called before `primary.game_loop()`, the secondary frame has no
+    /// game loop of its own. Build the builder with `Frame::builder(primary.context())`.
+    ///
+    /// Renderers of secondary frames should share the device of the primary
+    /// renderer with `RendererBuilder::with_device`. The shared device lives
+    /// as long as the last `Renderer` (or buffer, pipeline, ...) using it,
+    /// but every `Renderer` has to be dropped before the `Frame` it draws to.
+    pub fn build_secondary(self, primary: &Frame) -> Result<Frame, ContextError> {
+        let event_loop = primary
+            .event_loop
+            .as_ref()
+            .ok_or(ContextError::NoEventLoop)?;
+
+        // the game loop only shows the primary window
+        let frame = self.build_window(event_loop, Some(primary.gpu()))?;
+        frame.window().set_visible(true);
+        Ok(frame)
+    }
+
+    fn build_window(
+        self,
+        event_loop: &EventLoopWindowTarget<()>,
+        p_device: Option<Arc<SuitableGPU>>,
+    ) -> Result<Frame, ContextError> {
         let FrameBuilder {
             context,
             title,
             size,
             min_size,
             max_size,
+            resizable,
+            decorations,
+            fixed_aspect,
+            icon,
             sync,
         } = self;
 
-        // events loop
-        let event_loop = EventLoop::new();
-
         // window info
         let mut window_builder = WindowBuilder::new()
             .with_min_inner_size(tuple_to_lsize(min_size))
             .with_inner_size(tuple_to_lsize(size))
             .with_title(title)
+            .with_resizable(resizable)
+            .with_decorations(decorations)
             .with_visible(false);
         if let Some(max_size) = max_size {
             window_builder = window_builder.with_max_inner_size(tuple_to_lsize(max_size));
         }
+        if let Some((width, height, rgba)) = icon {
+            window_builder = window_builder.with_window_icon(Some(to_icon(width, height, rgba)?));
+        }
 
         // window itself
         let window = window_builder
-            .build_vk_surface(&event_loop, context.instance.clone())
+            .build(event_loop)
             .expect_log("Window creation failed");
-
-        let (size, aspect) = Frame::calc_size_and_aspect(window.window());
-
-        // physical device
-
-        let p_device = Arc::new(SuitableGPU::pick(
-            &context.instance,
-            &window,
-            ContextGPUPick::default(),
-        )?);
-
-        Ok(Frame {
+        let window = create_surface(Arc::new(window), &context)?;
+
+        let (size, aspect) = Frame::calc_size_and_aspect(
+            window.window().inner_size(),
+            window.window().scale_factor(),
+        );
+
+        // physical device, secondary windows have to be supported by the primary one
+        let p_device = match p_device {
+            Some(p_device) => {
+                if QueueFamilies::new(&window, p_device.device())?.is_none() {
+                    return Err(ContextError::SurfaceNotSupported);
+                }
+                p_device
+            }
+            None => Arc::new(SuitableGPU::pick(
+                &context.instance,
+                &window,
+                context.pick,
+                &context.features,
+            )?),
+        };
+
+        // present mode after the fallback
+        let present_modes = window
+            .capabilities(p_device.device())
+            .map_err(ContextError::CapabilitiesError)?
+            .present_modes;
+        let sync = sync.or_fallback(&present_modes);
+
+        let mut frame = Frame {
             context,
             window,
             p_device,
@@ -188,12 +429,33 @@ impl<'a> FrameBuilder<'a> {
             size,
             aspect,
 
-            event_loop: Some(event_loop),
+            resized: None,
+
+            fixed_aspect: None,
+
+            event_loop: None,
             init_timer: Instant::now(),
-        })
+        };
+        if let Some(aspect) = fixed_aspect {
+            frame.set_fixed_aspect(Some(aspect));
+        }
+
+        Ok(frame)
     }
 }
 
+fn create_surface(
+    window: Arc<Window>,
+    context: &Context,
+) -> Result<Arc<Surface<Arc<Window>>>, ContextError> {
+    vulkano_win::create_surface_from_winit(window, context.instance.clone())
+        .map_err(ContextError::SurfaceCreationError)
+}
+
+fn to_icon(width: u32, height: u32, rgba: Vec<u8>) -> Result<Icon, ContextError> {
+    Icon::from_rgba(rgba, width, height).map_err(|err| ContextError::BadIcon(err.to_string()))
+}
+
 const fn tuple_to_lsize(size: (u32, u32)) -> LogicalSize<u32> {
     LogicalSize {
         width: size.0,
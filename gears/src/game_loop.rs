@@ -1,15 +1,17 @@
-use crate::{report::Reporter, UpdateRate, io::input_state::InputState};
+use crate::{event::InputEvent, report::{DrawReporter, PerfRecorder}, time::{RateReporter, UpdateAccumulator}, GearsError, UpdateRate, io::input_state::InputState, renderer::simple_renderer::{RenderError, Renderer}};
 use std::{
+    collections::BTreeMap,
     sync::Arc,
+    thread,
     time::{Duration, Instant},
 };
 use gilrs::{Event as GilrsEvent, GilrsBuilder};
-use vulkano::swapchain::Surface;
 use winit::{
     dpi::PhysicalPosition,
     event::{ WindowEvent, Event as WinitEvent},
     event_loop::{ControlFlow, EventLoop},
-    window::Window,
+    platform::run_return::EventLoopExtRunReturn,
+    window::{Window, WindowId},
 };
 
 //
@@ -18,11 +20,102 @@ pub trait Runnable {
     #[allow(unused_variables)]
     fn update(&mut self, state: &mut State, delta: f32) {}
 
+    /// Raw events, including the ones that have no `InputEvent` translation
     #[allow(unused_variables)]
     fn event(&mut self, state: &mut State, event: &Event) {}
 
+    /// Translated events, sent after the raw `event`
     #[allow(unused_variables)]
-    fn draw(&mut self, state: &mut State, delta: f32) {}
+    fn input_event(&mut self, state: &mut State, event: &InputEvent) {}
+
+    /// `InputState` whose `begin_frame` the game loop calls
+    /// before each `update`, for `InputState::just_pressed`
+    fn input_state(&mut self) -> Option<&mut InputState> {
+        None
+    }
+
+    /// Draw a frame
+    ///
+    /// `alpha` is the progress from the last fixed update towards the next,
+    /// in `0.0..1.0`. Rendering interpolates between the state before and after
+    /// the last update instead of extrapolating with velocities:
+    ///
+    /// ```ignore
+    /// fn update(&mut self, _: &mut State, delta: f32) {
+    ///     self.prev_position = self.position;
+    ///     self.position += self.velocity * delta;
+    /// }
+    ///
+    /// fn draw(&mut self, _: &mut State, alpha: f32) {
+    ///     let position = self.prev_position.lerp(self.position, alpha);
+    ///     // ..
+    /// }
+    /// ```
+    ///
+    /// Without an update rate or while paused `alpha` is always `1.0`.
+    /// Animations that keep running while paused can use the
+    /// wall-clock `State::frame_delta` instead.
+    ///
+    /// An error stops the game loop, `Loop::run` returns it
+    /// after `shutdown`, e.g. to show it to the user.
+    /// `RenderError::DeviceLost` calls `device_lost` and
+    /// `RenderError::SurfaceLost` calls `surface_lost` instead.
+    #[allow(unused_variables)]
+    fn draw(&mut self, state: &mut State, alpha: f32) -> Result<(), GearsError> {
+        Ok(())
+    }
+
+    /// The primary window was resized to `width` x `height` physical pixels
+    ///
+    /// Called before the raw `event`, the `Renderer` recreates the
+    /// swapchain on the next frame. Not called while minimized.
+    /// Also called if only the scale factor changed, see `State::scale_factor`.
+    /// Secondary windows only get the raw `event`, see `Event::window_id`.
+    #[allow(unused_variables)]
+    fn resized(&mut self, state: &mut State, width: u32, height: u32) {}
+
+    /// `draw` returned `RenderError::DeviceLost`, e.g. after a driver reset
+    ///
+    /// Call `Renderer::rebuild` and recreate everything that was
+    /// created with the old device (buffers, pipelines, descriptor
+    /// sets, ...), then the next frame is drawn as usual. An error
+    /// stops the game loop, the default returns the device loss.
+    #[allow(unused_variables)]
+    fn device_lost(&mut self, state: &mut State) -> Result<(), GearsError> {
+        Err(RenderError::DeviceLost.into())
+    }
+
+    /// `draw` returned `RenderError::SurfaceLost`, e.g. after the display
+    /// was disconnected
+    ///
+    /// Call `Frame::recreate_surface` and then `Renderer::rebuild_surface`
+    /// with the frame, the device and everything created with it stay
+    /// valid. An error stops the game loop, the default returns the
+    /// surface loss.
+    #[allow(unused_variables)]
+    fn surface_lost(&mut self, state: &mut State) -> Result<(), GearsError> {
+        Err(RenderError::SurfaceLost.into())
+    }
+
+    /// Deterministic mode fell behind by more than `max_catchup` updates
+    ///
+    /// The `skipped` updates are not run.
+    #[allow(unused_variables)]
+    fn lag(&mut self, state: &mut State, skipped: u64) {}
+
+    /// Renderers of the app, the game loop calls `Renderer::wait_idle`
+    /// on them after `State::stop` was set
+    ///
+    /// Buffers, pipelines, ... dropped with the app could
+    /// otherwise still be in use by the last frames.
+    fn renderers(&mut self) -> Vec<&mut Renderer> {
+        Vec::new()
+    }
+
+    /// Called once after `State::stop` was set and the `renderers`
+    /// are idle, before the app is dropped.
+    #[allow(unused_variables)]
+    fn shutdown(&mut self, state: &mut State) {}
 }
 
 //
@@ -37,21 +130,51 @@ pub enum Event<'e> {
     WinitEvent(WinitEvent<'e, ()>)
 }
 
+impl Event<'_> {
+    /// Window the event was sent to, `None` for gamepad and device events
+    ///
+    /// Events of every `Frame` go through the same game loop,
+    /// compare with `Frame::id` to route them.
+    pub fn window_id(&self) -> Option<WindowId> {
+        match self {
+            Event::WinitEvent(WinitEvent::WindowEvent { window_id, .. })
+            | Event::WinitEvent(WinitEvent::RedrawRequested(window_id)) => Some(*window_id),
+            _ => None,
+        }
+    }
+}
+
 //
 
 pub struct Loop {
-    window: Arc<Surface<Window>>,
+    window: Arc<Window>,
     event_loop: Option<EventLoop<()>>,
     init_timer: Instant,
+
+    deterministic: bool,
+    max_catchup: u64,
+    fps_limit: Option<u32>,
 }
 
 pub struct State {
     //
-    pub cpu_frame_reporter: Reporter,
-    pub gpu_frame_reporter: Reporter,
-    pub update_reporter: Reporter,
+    pub cpu_frame_reporter: RateReporter,
+    pub gpu_frame_reporter: RateReporter,
+    pub update_reporter: RateReporter,
+
+    // app timed spans, see `span_reporter`
+    pub span_reporters: BTreeMap<&'static str, RateReporter>,
+
+    // draw and cull counts of the last frame
+    pub draw_reporter: DrawReporter,
+
+    // cpu and gpu times of the recent frames
+    pub perf_recorder: PerfRecorder,
 
-    // window size
+    // the following window fields are
+    // of the primary window, see `Frame`
+
+    // window size in physical pixels
     pub size: (f32, f32),
 
     // window aspect ratio
@@ -60,7 +183,7 @@ pub struct State {
     // is cursor inside the window?
     pub cursor_in: bool,
 
-    // cursor position
+    // cursor position in physical pixels, like `size`
     pub cursor_pos: PhysicalPosition<f64>,
 
     // window scaling factor
@@ -71,13 +194,163 @@ pub struct State {
 
     // the loop should stop
     pub stop: bool,
+
+    // multiplier for the update delta
+    time_scale: f32,
+
+    // fixed updates are not run
+    paused: bool,
+
+    // fixed updates to run while paused
+    steps: usize,
+
+    // fixed updates run so far
+    tick: u64,
+
+    // constant update delta, not affected by the time scale
+    deterministic: bool,
+
+    // most frames drawn per second
+    fps_limit: Option<u32>,
+
+    // earliest start of the next frame with the fps limit
+    next_frame: Option<Instant>,
+
+    // wall-clock time between the last two frames
+    frame_delta: Duration,
 }
 
 //
 
+impl State {
+    /// Scale the delta passed to `Runnable::update`, 0.5 runs the simulation at half speed
+    ///
+    /// Clamped to non-negative. 0.0 effectively pauses the simulation while
+    /// the fixed updates keep ticking (unlike `pause`). The update rate, events
+    /// and the interpolation alpha passed to `Runnable::draw` are not affected,
+    /// alpha is the progress between ticks which keep their cadence.
+    /// Ignored by `Loop::deterministic`.
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.max(0.0);
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Stop running fixed updates, frames are still drawn
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.steps = 0;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Wall-clock seconds since the previous frame
+    ///
+    /// Not affected by pausing or the time scale.
+    pub fn frame_delta(&self) -> f32 {
+        self.frame_delta.as_secs_f32()
+    }
+
+    /// Reporter for timing an app span like remeshing, created on first use
+    ///
+    /// Shown by the `DebugOverlay` next to the frame times,
+    /// see `time::ScopedTimer`.
+    pub fn span_reporter(&mut self, name: &'static str) -> &mut RateReporter {
+        self.span_reporters.entry(name).or_default()
+    }
+
+    /// Run exactly `n` fixed updates on the next frame while paused
+    pub fn step(&mut self, n: usize) {
+        if self.paused {
+            self.steps += n;
+        }
+    }
+
+    /// Fixed updates run so far, the first update is tick 0
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Most frames drawn per second, `None` (or 0) draws as fast as the present mode allows
+    ///
+    /// Independent of the `SyncMode`, the fixed updates keep their own rate.
+    pub fn set_fps_limit(&mut self, fps_limit: Option<u32>) {
+        self.fps_limit = fps_limit.filter(|&fps| fps > 0);
+        self.next_frame = None;
+    }
+
+    pub fn fps_limit(&self) -> Option<u32> {
+        self.fps_limit
+    }
+
+    /// Wait until the next frame is allowed to start
+    ///
+    /// Frames are spaced from the previous frame instead of the end of the wait,
+    /// so short stalls are caught up without drifting below the limit.
+    fn limit_frame_rate(&mut self) {
+        let period = match self.fps_limit {
+            Some(fps) => Duration::from_secs_f64(1.0 / fps as f64),
+            None => return,
+        };
+
+        if let Some(next_frame) = self.next_frame {
+            sleep_until(next_frame);
+        }
+
+        // restart the schedule after falling more than a frame behind
+        let now = Instant::now();
+        let next_frame = self
+            .next_frame
+            .map_or(now, |next| next.max(now.checked_sub(period).unwrap_or(now)))
+            + period;
+        self.next_frame = Some(next_frame);
+    }
+}
+
+/// Sleep most of the way and spin the last
+/// millisecond for an accurate wake up time
+fn sleep_until(deadline: Instant) {
+    const SPIN: Duration = Duration::from_millis(1);
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        } else if remaining > SPIN {
+            thread::sleep(remaining - SPIN);
+        } else {
+            std::hint::spin_loop();
+        }
+    }
+}
+
+fn fixed_update(app: &mut impl Runnable, state: &mut State, interval: Duration) {
+    let delta = if state.deterministic {
+        interval.as_secs_f32()
+    } else {
+        interval.as_secs_f32() * state.time_scale
+    };
+
+    if let Some(input) = app.input_state() {
+        input.begin_frame();
+    }
+
+    let timer = state.update_reporter.begin();
+    app.update(state, delta);
+    state.update_reporter.end(timer);
+    state.tick += 1;
+}
+
 impl Loop {
     pub fn new(
-        window: Arc<Surface<Window>>,
+        window: Arc<Window>,
         event_loop: EventLoop<()>,
         init_timer: Instant,
     ) -> Self {
@@ -85,24 +358,62 @@ impl Loop {
             window,
             event_loop: Some(event_loop),
             init_timer,
+
+            deterministic: false,
+            max_catchup: 20,
+            fps_limit: None,
         }
     }
 
-    pub fn run(mut self, update_rate: Option<UpdateRate>, app: impl Runnable + 'static) -> ! {
+    /// Initial `State::set_fps_limit`, no limit by default
+    pub fn fps_limit(mut self, fps_limit: Option<u32>) -> Self {
+        self.fps_limit = fps_limit;
+        self
+    }
+
+    /// Deterministic fixed updates for replays and lockstep networking
+    ///
+    /// `Runnable::update` always gets the exact update interval
+    /// (the time scale is ignored) and updates are never dropped
+    /// silently: falling behind by more than `max_catchup` updates
+    /// calls `Runnable::lag` with the skipped count.
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Most updates to run in a single frame to catch up, 20 by default
+    ///
+    /// The rest of the updates are dropped, see `deterministic`.
+    pub fn max_catchup(mut self, max_catchup: u64) -> Self {
+        self.max_catchup = max_catchup;
+        self
+    }
+
+    /// Run until `State::stop` is set or `Runnable::draw` fails
+    ///
+    /// The app is shut down and dropped before this returns.
+    pub fn run(mut self, update_rate: Option<UpdateRate>, app: impl Runnable) -> Result<(), GearsError> {
         log::debug!("Initialization took: {:?}", self.init_timer.elapsed());
 
-        let window = self.window.window();
+        let window = &self.window;
+        let primary = window.id();
         let size = window.inner_size().into();
         let scale_factor = window.scale_factor();
         let interval = update_rate.map(|rate| rate.to_interval());
         window.set_visible(true);
 
         let mut previous = Instant::now();
-        let mut lag = Duration::from_secs_f64(0.0);
+        let mut previous_frame = Instant::now();
+        // the updates are counted from the rate, the rounded interval would drift
+        let mut lag = update_rate.map(UpdateAccumulator::new);
         let mut state = State {
-            cpu_frame_reporter: Reporter::new(),
-            gpu_frame_reporter: Reporter::new(),
-            update_reporter: Reporter::new(),
+            cpu_frame_reporter: RateReporter::new(),
+            gpu_frame_reporter: RateReporter::new(),
+            update_reporter: RateReporter::new(),
+            span_reporters: BTreeMap::new(),
+            draw_reporter: DrawReporter::new(),
+            perf_recorder: PerfRecorder::new(),
             size,
             aspect: size.0 / size.1,
             cursor_in: false,
@@ -110,8 +421,19 @@ impl Loop {
             scale_factor,
             interval,
             stop: false,
+            time_scale: 1.0,
+            paused: false,
+            steps: 0,
+            tick: 0,
+            deterministic: self.deterministic,
+            fps_limit: None,
+            next_frame: None,
+            frame_delta: Duration::ZERO,
         };
+        state.set_fps_limit(self.fps_limit);
+        let max_catchup = self.max_catchup;
         let mut opt_app = Some(app);
+        let mut result = Ok(());
 
         let mut gilrs = match GilrsBuilder::new()/* .with_default_filters(false) */.build() {
             Ok(gilrs) => Some(gilrs),
@@ -121,10 +443,10 @@ impl Loop {
             }
         };
 
-        self.event_loop
-            .take()
-            .unwrap()
-            .run(move |event, _, control| {
+        let mut event_loop = self.event_loop.take().unwrap();
+        let window = self.window.clone();
+        event_loop
+            .run_return(|event, _, control| {
                 let app = if let Some(app) = opt_app.as_mut() {
                     app
                 }  else {
@@ -134,6 +456,10 @@ impl Loop {
                 *control = ControlFlow::Poll;
                 if state.stop {
                     *control = ControlFlow::Exit;
+                    for renderer in app.renderers() {
+                        renderer.wait_idle();
+                    }
+                    app.shutdown(&mut state);
                     log::debug!("Dropping app");
                     {
                         opt_app.take().unwrap();
@@ -146,64 +472,144 @@ impl Loop {
                     let event = gilrs.next_event();
                     let event = InputState::deadzone(event, gilrs);
                     if let Some(event) = event {
+                        let input_event = InputEvent::from_gilrs(&event);
                         app.event(&mut state, &Event::GilrsEvent(event));
+                        if let Some(event) = input_event {
+                            app.input_event(&mut state, &event);
+                        }
                     };
                 }
 
                 match &event {
                     WinitEvent::WindowEvent {
                         event: WindowEvent::CursorEntered { .. },
-                        ..
-                    } => state.cursor_in = true,
+                        window_id,
+                    } if *window_id == primary => state.cursor_in = true,
                     WinitEvent::WindowEvent {
                         event: WindowEvent::CursorLeft { .. },
-                        ..
-                    } => state.cursor_in = false,
+                        window_id,
+                    } if *window_id == primary => state.cursor_in = false,
                     WinitEvent::WindowEvent {
                         event: WindowEvent::CursorMoved { position, .. },
-                        ..
-                    } => {
+                        window_id,
+                    } if *window_id == primary => {
                         state.cursor_pos = *position;
                     }
                     WinitEvent::WindowEvent {
                         event: WindowEvent::Resized(s),
-                        ..
-                    } => {
+                        window_id,
+                    } if *window_id == primary => {
+                        state.size = (s.width as f32, s.height as f32);
+                        let (width, height) = (s.width, s.height);
+                        let s = s.to_logical::<f32>(state.scale_factor);
+                        state.aspect = s.width / s.height;
+                        if width != 0 && height != 0 {
+                            app.resized(&mut state, width, height);
+                        }
+                    }
+                    // the physical size may stay the same, so there
+                    // might be no Resized event for the new scale factor
+                    WinitEvent::WindowEvent {
+                        event: WindowEvent::ScaleFactorChanged { scale_factor, new_inner_size },
+                        window_id,
+                    } if *window_id == primary => {
+                        state.scale_factor = *scale_factor;
+                        let s = **new_inner_size;
                         state.size = (s.width as f32, s.height as f32);
+                        let (width, height) = (s.width, s.height);
                         let s = s.to_logical::<f32>(state.scale_factor);
                         state.aspect = s.width / s.height;
+                        if width != 0 && height != 0 {
+                            app.resized(&mut state, width, height);
+                        }
                     }
+                    // secondary windows are drawn with the primary one
+                    WinitEvent::RedrawRequested(window_id) if *window_id != primary => return,
                     WinitEvent::RedrawRequested(_) => {
                         // main game loop source:
                         //  - https://gameprogrammingpatterns.com/game-loop.html
-                        if let Some(interval) = state.interval {
+                        state.limit_frame_rate();
+                        state.frame_delta = previous_frame.elapsed();
+                        previous_frame = Instant::now();
+
+                        if let (Some(interval), Some(lag)) = (state.interval, lag.as_mut()) {
                             let elapsed = previous.elapsed();
                             previous = Instant::now();
-                            lag += elapsed;
-    
-                            // updates
-                            // stop after 20 to avoid freezing completely caused by the input 
-                            // if those updates take longer than they should
-                            let mut i = 0;
-                            while lag >= interval && i <= 20 {
-                                i += 1;
-                                let timer = state.update_reporter.begin();
-                                app.update(&mut state, interval.as_secs_f32());
-                                state.update_reporter.end(timer);
-                                lag -= interval;
+
+                            if state.paused {
+                                // drop the paused time so that
+                                // resuming doesn't trigger a catch-up burst
+                                lag.reset();
+                                for _ in 0..std::mem::take(&mut state.steps) {
+                                    fixed_update(app, &mut state, interval);
+                                }
+                            } else {
+                                lag.add(elapsed);
+
+                                // skip the updates that are too far behind
+                                // (only reported in the deterministic mode)
+                                let behind = lag.due();
+                                if state.deterministic && behind > max_catchup {
+                                    let skipped = behind - max_catchup;
+                                    lag.skip(skipped);
+                                    app.lag(&mut state, skipped);
+                                }
+
+                                // updates
+                                // stop after max_catchup to avoid freezing completely caused by the input 
+                                // if those updates take longer than they should
+                                let mut i = 0;
+                                while i < max_catchup && lag.tick() {
+                                    i += 1;
+                                    fixed_update(app, &mut state, interval);
+                                }
+
+                                // drop the updates that did not fit, keep the fraction
+                                // for alpha, otherwise a frame spike would keep
+                                // every following frame running max_catchup updates
+                                lag.skip_due();
                             }
                         }
 
                         // frames
+                        if state.interval.is_none() {
+                            if let Some(input) = app.input_state() {
+                                input.begin_frame();
+                            }
+                        }
                         let timer = state.cpu_frame_reporter.begin();
                         {
-							let dt = if let Some(interval) = state.interval {lag.as_secs_f32() / interval.as_secs_f32() } else {self.init_timer.elapsed().as_secs_f32()};
-                            app.draw(
+							let alpha = match &lag {
+                                // paused frames show the latest update
+                                Some(lag) if !state.paused => lag.alpha(),
+                                _ => 1.0,
+                            };
+                            let draw = match app.draw(
                                 &mut state,
-                                dt,
-                            );
+                                alpha.clamp(0.0, 1.0),
+                            ) {
+                                Err(GearsError::Render(RenderError::DeviceLost)) => {
+                                    log::warn!("Device lost, recovering");
+                                    app.device_lost(&mut state)
+                                }
+                                Err(GearsError::Render(RenderError::SurfaceLost)) => {
+                                    log::warn!("Surface lost, recovering");
+                                    app.surface_lost(&mut state)
+                                }
+                                draw => draw,
+                            };
+                            if let Err(err) = draw {
+                                log::error!("Stopping the game loop: {err}");
+                                result = Err(err);
+                                state.stop = true;
+                            }
                         }
                         let should_report = state.cpu_frame_reporter.end(timer);
+                        state.draw_reporter.end_frame();
+                        if let Some(cpu) = state.cpu_frame_reporter.take_sample() {
+                            let gpu = state.gpu_frame_reporter.take_sample();
+                            state.perf_recorder.push(cpu, gpu);
+                        }
 
                         // reports
                         if should_report {
@@ -211,6 +617,7 @@ impl Loop {
                             let (u_int, u_per_sec) = state.update_reporter.last_string();
                             let (cf_int, cf_per_sec) = state.cpu_frame_reporter.last_string();
                             let (gf_int, gf_per_sec) = state.gpu_frame_reporter.last_string();
+                            let draws = state.draw_reporter.last_string();
 
                             #[cfg(debug_assertions)]
                             const DEBUG: &str = "debug build";
@@ -218,7 +625,7 @@ impl Loop {
                             const DEBUG: &str = "release build";
 
                             log::debug!(
-                                "Report ({:?})({})\n              per second @ time per\nUPDATES:{:>16} @ {}\nCPU FRAMES:{:>13} @ {}\nGPU FRAMES:{:>13} @ {}",
+                                "Report ({:?})({})\n              per second @ time per\nUPDATES:{:>16} @ {}\nCPU FRAMES:{:>13} @ {}\nGPU FRAMES:{:>13} @ {}\nDRAWS: {}",
                                 int,
                                 DEBUG,
                                 u_per_sec,
@@ -226,19 +633,31 @@ impl Loop {
                                 cf_per_sec,
                                 cf_int,
 								gf_per_sec,
-								gf_int
+								gf_int,
+								draws
                             );
+                            for (name, reporter) in state.span_reporters.iter() {
+                                if let Some(average) = reporter.average() {
+                                    log::debug!("SPAN {}: {:?} average", name, average);
+                                }
+                            }
                         }
 
                         return;
                     }
                     WinitEvent::MainEventsCleared => {
-                        self.window.window().request_redraw();
+                        window.request_redraw();
                     }
                     _ => {}
                 }
 
-                app.event(&mut state, &Event::WinitEvent(event));
-            })
+                let event = Event::WinitEvent(event);
+                app.event(&mut state, &event);
+                if let Some(event) = InputEvent::from_event(&event) {
+                    app.input_event(&mut state, &event);
+                }
+            });
+
+        result
     }
 }
@@ -0,0 +1,522 @@
+use crate::{
+    game_loop::{Event, State},
+    renderer::{simple_renderer::Renderer, viewport::Rect, Recorder},
+};
+use anyhow::{anyhow, Result};
+use egui::{
+    epaint::{ClippedMesh, Color32, ImageData, ImageDelta},
+    pos2, vec2, Context, Key, Modifiers, PointerButton, Pos2, RawInput, Rgba, TextureId,
+    TexturesDelta,
+};
+use std::{collections::HashMap, sync::Arc, time::Instant};
+use vulkano::{
+    buffer::{cpu_pool::CpuBufferPoolChunk, BufferUsage, CpuBufferPool},
+    descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
+    device::Queue,
+    format::Format,
+    image::{view::ImageView, ImageDimensions, ImmutableImage, MipmapsCount},
+    memory::pool::StdMemoryPool,
+    pipeline::{
+        graphics::{
+            color_blend::{AttachmentBlend, BlendFactor, BlendOp, ColorBlendState},
+            depth_stencil::DepthStencilState,
+            input_assembly::InputAssemblyState,
+            rasterization::RasterizationState,
+            vertex_input::BuffersDefinition,
+            viewport::ViewportState,
+        },
+        GraphicsPipeline, Pipeline, PipelineBindPoint,
+    },
+    render_pass::Subpass,
+    sampler::{Filter, Sampler, SamplerAddressMode},
+    sync::GpuFuture,
+};
+use winit::event::{
+    ElementState, Event as WinitEvent, KeyboardInput, ModifiersState, MouseButton,
+    MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+//
+
+/// Immediate mode ui with `egui`
+///
+/// Feed every `Runnable::event` to `event`, build the ui with `run`
+/// before the render pass and record it with `draw` as the last
+/// thing in the render pass, or with `paint` anywhere in the frame.
+/// The ui is drawn over everything with no depth test.
+pub struct Egui {
+    context: Context,
+    input: RawInput,
+    init: Instant,
+
+    pointer: Option<Pos2>,
+    pixels_per_point: f32,
+    meshes: Vec<ClippedMesh>,
+    free: Vec<TextureId>,
+
+    queue: Arc<Queue>,
+    pipeline: Arc<GraphicsPipeline>,
+    sampler: Arc<Sampler>,
+    textures: HashMap<TextureId, GuiTexture>,
+    vertex_pool: CpuBufferPool<GuiVertex>,
+    index_pool: CpuBufferPool<u32>,
+}
+
+/// Ui meshes of one frame, ready to be recorded
+struct GuiDraws {
+    pipeline: Arc<GraphicsPipeline>,
+    extent: [u32; 2],
+    draws: Vec<GuiDraw>,
+}
+
+struct GuiDraw {
+    scissor: Rect,
+    set: Arc<PersistentDescriptorSet>,
+    vertices: Arc<CpuBufferPoolChunk<GuiVertex, Arc<StdMemoryPool>>>,
+    indices: Arc<CpuBufferPoolChunk<u32, Arc<StdMemoryPool>>>,
+    count: u32,
+}
+
+/// Texture uploaded from `egui`, the pixels are kept for partial updates
+struct GuiTexture {
+    size: [usize; 2],
+    pixels: Vec<Color32>,
+    set: Arc<PersistentDescriptorSet>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[repr(C)]
+struct GuiVertex {
+    vi_pos: [f32; 2],
+    vi_uv: [f32; 2],
+    vi_color: [f32; 4],
+}
+
+vulkano::impl_vertex!(GuiVertex, vi_pos, vi_uv, vi_color);
+
+mod vert {
+    #![allow(clippy::needless_question_mark)]
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+#version 420
+
+layout(location = 0) in vec2 vi_pos;
+layout(location = 1) in vec2 vi_uv;
+layout(location = 2) in vec4 vi_color;
+
+layout(location = 0) out vec2 fi_uv;
+layout(location = 1) out vec4 fi_color;
+
+void main() {
+	gl_Position = vec4(vi_pos, 0.0, 1.0);
+	fi_uv = vi_uv;
+	fi_color = vi_color;
+}"
+    }
+}
+
+mod frag {
+    #![allow(clippy::needless_question_mark)]
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 420
+
+layout(location = 0) in vec2 fi_uv;
+layout(location = 1) in vec4 fi_color;
+
+layout(location = 0) out vec4 color;
+
+layout(binding = 0) uniform sampler2D tex;
+
+void main() {
+	color = fi_color * texture(tex, fi_uv);
+}"
+    }
+}
+
+//
+
+impl Egui {
+    pub fn new(renderer: &Renderer) -> Result<Self> {
+        let device = renderer.device.logical().clone();
+
+        let vert = vert::load(device.clone())?;
+        let frag = frag::load(device.clone())?;
+        // egui outputs premultiplied alpha
+        let blend = AttachmentBlend {
+            color_op: BlendOp::Add,
+            color_source: BlendFactor::One,
+            color_destination: BlendFactor::OneMinusSrcAlpha,
+            alpha_op: BlendOp::Add,
+            alpha_source: BlendFactor::OneMinusDstAlpha,
+            alpha_destination: BlendFactor::One,
+        };
+        let pipeline = GraphicsPipeline::start()
+            .input_assembly_state(InputAssemblyState::new())
+            .vertex_input_state(BuffersDefinition::new().vertex::<GuiVertex>())
+            .vertex_shader(
+                vert.entry_point("main")
+                    .ok_or_else(|| anyhow!("Egui vertex shader has no main"))?,
+                (),
+            )
+            .viewport_state(ViewportState::viewport_dynamic_scissor_dynamic(1))
+            .fragment_shader(
+                frag.entry_point("main")
+                    .ok_or_else(|| anyhow!("Egui fragment shader has no main"))?,
+                (),
+            )
+            .depth_stencil_state(DepthStencilState::disabled())
+            .color_blend_state(ColorBlendState::new(1).blend(blend))
+            .rasterization_state(RasterizationState::new())
+            .render_pass(
+                Subpass::from(renderer.render_pass(), 0)
+                    .ok_or_else(|| anyhow!("Render pass has no subpasses"))?,
+            )
+            .build(device.clone())?;
+
+        let sampler = Sampler::start(device.clone())
+            .filter(Filter::Linear)
+            .address_mode(SamplerAddressMode::ClampToEdge)
+            .build()?;
+
+        Ok(Self {
+            context: Context::default(),
+            input: RawInput::default(),
+            init: Instant::now(),
+
+            pointer: None,
+            pixels_per_point: 1.0,
+            meshes: Vec::new(),
+            free: Vec::new(),
+
+            queue: renderer.device.queues.graphics.clone(),
+            pipeline,
+            sampler,
+            textures: HashMap::new(),
+            vertex_pool: CpuBufferPool::vertex_buffer(device.clone()),
+            index_pool: CpuBufferPool::new(device, BufferUsage::index_buffer()),
+        })
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Queue a window event for the next `run`
+    ///
+    /// Returns true if egui wants the event for itself,
+    /// e.g. the cursor is over a window or a text field has focus.
+    pub fn event(&mut self, event: &Event) -> bool {
+        let event = match event {
+            Event::WinitEvent(WinitEvent::WindowEvent { event, .. }) => event,
+            _ => return false,
+        };
+
+        let ppp = self.pixels_per_point;
+        let modifiers = self.input.modifiers;
+        let events = &mut self.input.events;
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                let pos = pos2(position.x as f32 / ppp, position.y as f32 / ppp);
+                self.pointer = Some(pos);
+                events.push(egui::Event::PointerMoved(pos));
+                self.context.wants_pointer_input()
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.pointer = None;
+                events.push(egui::Event::PointerGone);
+                false
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                if let (Some(pos), Some(button)) = (self.pointer, translate_button(*button)) {
+                    events.push(egui::Event::PointerButton {
+                        pos,
+                        button,
+                        pressed: *state == ElementState::Pressed,
+                        modifiers,
+                    });
+                }
+                self.context.wants_pointer_input()
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let delta = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => vec2(*x, *y) * 50.0,
+                    MouseScrollDelta::PixelDelta(p) => vec2(p.x as f32, p.y as f32) / ppp,
+                };
+                events.push(egui::Event::Scroll(delta));
+                self.context.wants_pointer_input()
+            }
+            WindowEvent::ReceivedCharacter(c) => {
+                if !c.is_control() {
+                    events.push(egui::Event::Text(c.to_string()));
+                }
+                self.context.wants_keyboard_input()
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(keycode),
+                        ..
+                    },
+                ..
+            } => {
+                let pressed = *state == ElementState::Pressed;
+                match (*keycode, pressed, modifiers.command) {
+                    (VirtualKeyCode::C, true, true) => events.push(egui::Event::Copy),
+                    (VirtualKeyCode::X, true, true) => events.push(egui::Event::Cut),
+                    _ => {}
+                }
+                if let Some(key) = translate_key(*keycode) {
+                    events.push(egui::Event::Key {
+                        key,
+                        pressed,
+                        modifiers,
+                    });
+                }
+                self.context.wants_keyboard_input()
+            }
+            WindowEvent::ModifiersChanged(state) => {
+                self.input.modifiers = translate_modifiers(*state);
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Build the ui of this frame with `ui`
+    ///
+    /// Uploads the new and changed textures, waits for the uploads to finish.
+    pub fn run<F: FnOnce(&Context)>(&mut self, state: &State, ui: F) -> Result<()> {
+        self.pixels_per_point = state.scale_factor as f32;
+        let (width, height) = state.size;
+
+        let mut input = self.input.take();
+        input.screen_rect = Some(egui::Rect::from_min_size(
+            Pos2::ZERO,
+            vec2(width, height) / self.pixels_per_point,
+        ));
+        input.pixels_per_point = Some(self.pixels_per_point);
+        input.time = Some(self.init.elapsed().as_secs_f64());
+
+        let output = self.context.run(input, ui);
+        self.update_textures(output.textures_delta)?;
+        self.meshes = self.context.tessellate(output.shapes);
+
+        Ok(())
+    }
+
+    /// Record the ui built by the last `run`
+    ///
+    /// Sets the viewport to the whole frame and the scissor to each clip rect.
+    pub fn draw(&mut self, recorder: &mut Recorder<true>) -> Result<()> {
+        let draws = self.prepare(recorder.extent())?;
+        draws.record(recorder)
+    }
+
+    /// `draw` the ui built by the last `run` at the end of the render pass
+    ///
+    /// The ui is recorded with `Recorder::post_draw`, after the user draws.
+    pub fn paint<const IN_RENDER_PASS: bool>(
+        &mut self,
+        recorder: &mut Recorder<IN_RENDER_PASS>,
+    ) -> Result<()> {
+        let draws = self.prepare(recorder.extent())?;
+        recorder.post_draw(move |recorder| draws.record(recorder));
+        Ok(())
+    }
+
+    /// Upload the meshes of the last `run`
+    fn prepare(&mut self, extent: Option<[u32; 2]>) -> Result<GuiDraws> {
+        let extent =
+            extent.ok_or_else(|| anyhow!("Recorder has no framebuffer to draw egui into"))?;
+        let size = pos2(extent[0].max(1) as f32, extent[1].max(1) as f32);
+        let ppp = self.pixels_per_point;
+
+        let mut draws = Vec::with_capacity(self.meshes.len());
+        for ClippedMesh(clip, mesh) in self.meshes.drain(..) {
+            let set = match self.textures.get(&mesh.texture_id) {
+                Some(texture) => texture.set.clone(),
+                None => continue,
+            };
+            if mesh.indices.is_empty() {
+                continue;
+            }
+
+            // clip rect in points to scissor in pixels
+            let clamp = |p: Pos2| {
+                (p.to_vec2() * ppp)
+                    .round()
+                    .max(vec2(0.0, 0.0))
+                    .min(size.to_vec2())
+            };
+            let (min, max) = (clamp(clip.min), clamp(clip.max));
+            if max.x <= min.x || max.y <= min.y {
+                continue;
+            }
+            let scissor = Rect::new(
+                [min.x as u32, min.y as u32],
+                [(max.x - min.x) as u32, (max.y - min.y) as u32],
+            );
+
+            let vertices = mesh.vertices.iter().map(|v| GuiVertex {
+                vi_pos: [
+                    v.pos.x * ppp / size.x * 2.0 - 1.0,
+                    v.pos.y * ppp / size.y * 2.0 - 1.0,
+                ],
+                vi_uv: [v.uv.x, v.uv.y],
+                vi_color: Rgba::from(v.color).to_array(),
+            });
+            let count = mesh.indices.len() as u32;
+            draws.push(GuiDraw {
+                scissor,
+                set,
+                vertices: self.vertex_pool.chunk(vertices)?,
+                indices: self.index_pool.chunk(mesh.indices)?,
+                count,
+            });
+        }
+
+        // the draws keep their own references
+        for id in self.free.drain(..) {
+            self.textures.remove(&id);
+        }
+
+        Ok(GuiDraws {
+            pipeline: self.pipeline.clone(),
+            extent,
+            draws,
+        })
+    }
+
+    fn update_textures(&mut self, delta: TexturesDelta) -> Result<()> {
+        for (id, delta) in delta.set {
+            let ImageDelta { image, pos } = delta;
+            let (size, pixels) = match image {
+                ImageData::Color(image) => (image.size, image.pixels),
+                ImageData::Alpha(image) => (image.size, image.srgba_pixels(1.0).collect()),
+            };
+
+            let (size, pixels) = match (pos, self.textures.remove(&id)) {
+                (Some([x, y]), Some(mut texture)) => {
+                    let width = texture.size[0];
+                    for (row, src) in pixels.chunks(size[0]).enumerate() {
+                        let start = (y + row) * width + x;
+                        texture.pixels[start..start + src.len()].copy_from_slice(src);
+                    }
+                    (texture.size, texture.pixels)
+                }
+                (Some(_), None) => return Err(anyhow!("Partial update of unknown texture")),
+                (None, _) => (size, pixels),
+            };
+
+            let set = self.upload(size, &pixels)?;
+            self.textures.insert(id, GuiTexture { size, pixels, set });
+        }
+        self.free.extend(delta.free);
+
+        Ok(())
+    }
+
+    fn upload(&self, size: [usize; 2], pixels: &[Color32]) -> Result<Arc<PersistentDescriptorSet>> {
+        let (image, future) = ImmutableImage::from_iter(
+            pixels.iter().flat_map(|c| c.to_array()),
+            ImageDimensions::Dim2d {
+                width: size[0] as u32,
+                height: size[1] as u32,
+                array_layers: 1,
+            },
+            MipmapsCount::One,
+            Format::R8G8B8A8_SRGB,
+            self.queue.clone(),
+        )?;
+        future.then_signal_fence_and_flush()?.wait(None)?;
+
+        let layout = self.pipeline.layout().descriptor_set_layouts()[0].clone();
+        Ok(PersistentDescriptorSet::new(
+            layout,
+            [WriteDescriptorSet::image_view_sampler(
+                0,
+                ImageView::new(image)?,
+                self.sampler.clone(),
+            )],
+        )?)
+    }
+}
+
+impl GuiDraws {
+    fn record(self, recorder: &mut Recorder<true>) -> Result<()> {
+        recorder
+            .set_viewport_and_scissor(Rect::from_extent(self.extent))
+            .bind_pipeline_graphics(self.pipeline.clone());
+
+        for draw in self.draws {
+            recorder
+                .record()
+                .set_scissor(0, [draw.scissor.scissor()])
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Graphics,
+                    self.pipeline.layout().clone(),
+                    0,
+                    draw.set,
+                )
+                .bind_vertex_buffers(0, draw.vertices)
+                .bind_index_buffer(draw.indices)
+                .draw_indexed(draw.count, 1, 0, 0, 0)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn translate_button(button: MouseButton) -> Option<PointerButton> {
+    match button {
+        MouseButton::Left => Some(PointerButton::Primary),
+        MouseButton::Right => Some(PointerButton::Secondary),
+        MouseButton::Middle => Some(PointerButton::Middle),
+        MouseButton::Other(_) => None,
+    }
+}
+
+fn translate_modifiers(state: ModifiersState) -> Modifiers {
+    Modifiers {
+        alt: state.alt(),
+        ctrl: state.ctrl(),
+        shift: state.shift(),
+        mac_cmd: cfg!(target_os = "macos") && state.logo(),
+        command: if cfg!(target_os = "macos") {
+            state.logo()
+        } else {
+            state.ctrl()
+        },
+    }
+}
+
+fn translate_key(keycode: VirtualKeyCode) -> Option<Key> {
+    Some(match keycode {
+        VirtualKeyCode::Down => Key::ArrowDown,
+        VirtualKeyCode::Left => Key::ArrowLeft,
+        VirtualKeyCode::Right => Key::ArrowRight,
+        VirtualKeyCode::Up => Key::ArrowUp,
+        VirtualKeyCode::Escape => Key::Escape,
+        VirtualKeyCode::Tab => Key::Tab,
+        VirtualKeyCode::Back => Key::Backspace,
+        VirtualKeyCode::Return => Key::Enter,
+        VirtualKeyCode::Space => Key::Space,
+        VirtualKeyCode::Insert => Key::Insert,
+        VirtualKeyCode::Delete => Key::Delete,
+        VirtualKeyCode::Home => Key::Home,
+        VirtualKeyCode::End => Key::End,
+        VirtualKeyCode::PageUp => Key::PageUp,
+        VirtualKeyCode::PageDown => Key::PageDown,
+        VirtualKeyCode::A => Key::A,
+        VirtualKeyCode::C => Key::C,
+        VirtualKeyCode::V => Key::V,
+        VirtualKeyCode::X => Key::X,
+        VirtualKeyCode::Y => Key::Y,
+        VirtualKeyCode::Z => Key::Z,
+        _ => return None,
+    })
+}
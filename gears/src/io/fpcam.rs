@@ -1,20 +1,124 @@
 use super::input_state::{InputAxis, InputState};
-use crate::{frame::Frame, game_loop::Event};
-use glam::Vec2;
-use winit::event::{DeviceEvent, Event as WinitEvent, WindowEvent};
+use crate::{
+    event::InputEvent,
+    frame::Frame,
+    game_loop::Event,
+    scene::{CameraView, Projection},
+    DepthMode,
+};
+use glam::{Mat4, Quat, Vec2, Vec3};
 
 //
 
 /// First person camera controller
 #[derive(Debug, Clone, Copy, Default)]
 pub struct FPCam {
+    /// Eye position for `CameraView`, not moved by the controller
+    pub position: Vec3,
+    pub projection: Projection,
+    /// Has to match the `DepthMode` of the renderer
+    pub depth_mode: DepthMode,
+
     focused: bool,
+    config: FPCamConfig,
+
+    // look direction the input is driving towards
+    target: Vec2,
+    // smoothed look direction (yaw, pitch)
     dir: Vec2,
-    vel: Vec2,
+    // look direction from the previous update (for interpolation)
+    prev_dir: Vec2,
+
+    roll: f32,
+    prev_roll: f32,
+}
+
+/// First person camera controller configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FPCamConfig {
+    /// Mouse look sensitivity in radians per mouse motion unit.
+    ///
+    /// Defaults to `0.001` on both axes.
+    pub sensitivity: Vec2,
+
+    /// Gamepad look speed in radians per second at full stick deflection.
+    ///
+    /// Defaults to `3.0` on both axes.
+    pub gamepad_speed: Vec2,
+
+    /// Invert the vertical look direction.
+    pub invert_y: bool,
+
+    /// Exponential smoothing time constant of the look direction in seconds.
+    ///
+    /// `0.0` disables smoothing.
+    pub smoothing: f32,
+
+    /// Pitch (min, max) in radians.
+    ///
+    /// `max` wins if `min > max`, NaN limits are ignored.
+    pub pitch_limits: (f32, f32),
+
+    /// Roll speed in radians per second driven by `InputAxis::Roll`.
+    ///
+    /// `None` disables roll.
+    pub roll_speed: Option<f32>,
 }
 
 //
 
+impl Default for FPCamConfig {
+    fn default() -> Self {
+        Self {
+            sensitivity: Vec2::new(0.001, 0.001),
+            gamepad_speed: Vec2::new(3.0, 3.0),
+            invert_y: false,
+            smoothing: 0.0,
+            pitch_limits: (
+                -std::f32::consts::PI / 2.0 + 0.01,
+                std::f32::consts::PI / 2.0 - 0.01,
+            ),
+            roll_speed: None,
+        }
+    }
+}
+
+impl FPCamConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub const fn with_sensitivity(mut self, sensitivity: Vec2) -> Self {
+        self.sensitivity = sensitivity;
+        self
+    }
+
+    pub const fn with_gamepad_speed(mut self, gamepad_speed: Vec2) -> Self {
+        self.gamepad_speed = gamepad_speed;
+        self
+    }
+
+    pub const fn with_invert_y(mut self, invert_y: bool) -> Self {
+        self.invert_y = invert_y;
+        self
+    }
+
+    pub const fn with_smoothing(mut self, smoothing: f32) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    pub const fn with_pitch_limits(mut self, min: f32, max: f32) -> Self {
+        self.pitch_limits = (min, max);
+        self
+    }
+
+    pub const fn with_roll_speed(mut self, roll_speed: Option<f32>) -> Self {
+        self.roll_speed = roll_speed;
+        self
+    }
+}
+
 impl FPCam {
     pub fn new() -> Self {
         Self::default()
@@ -22,35 +126,163 @@ impl FPCam {
 
     pub fn with_dir(dir: Vec2) -> Self {
         Self {
+            target: dir,
             dir,
+            prev_dir: dir,
             ..Default::default()
         }
     }
 
-    pub fn dir(&self, delta: f32) -> Vec2 {
-        Self::clamp2(self.dir + self.vel * delta)
+    pub fn with_config(mut self, config: FPCamConfig) -> Self {
+        self.config = config;
+        self.clamp();
+        self
+    }
+
+    pub fn with_position(mut self, position: Vec3) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    /// Use `Renderer::depth_mode`
+    pub fn with_depth_mode(mut self, depth_mode: DepthMode) -> Self {
+        self.depth_mode = depth_mode;
+        self
+    }
+
+    pub fn config(&self) -> &FPCamConfig {
+        &self.config
+    }
+
+    /// Changes take effect immediately
+    pub fn config_mut(&mut self) -> &mut FPCamConfig {
+        &mut self.config
+    }
+
+    /// Mouse look sensitivity on both axes in radians per mouse motion unit.
+    ///
+    /// Defaults to `0.001`, see `FPCamConfig::sensitivity` for separate axes.
+    pub fn with_sensitivity(mut self, sensitivity: f32) -> Self {
+        self.set_sensitivity(sensitivity);
+        self
+    }
+
+    /// Applied from the next mouse motion event
+    pub fn set_sensitivity(&mut self, sensitivity: f32) {
+        self.config.sensitivity = Vec2::splat(sensitivity);
+    }
+
+    /// Gamepad look speed on both axes in radians per second at full stick deflection.
+    ///
+    /// Defaults to `3.0`, independent of the mouse sensitivity.
+    pub fn with_gamepad_speed(mut self, gamepad_speed: f32) -> Self {
+        self.set_gamepad_speed(gamepad_speed);
+        self
+    }
+
+    /// Applied from the next update
+    pub fn set_gamepad_speed(&mut self, gamepad_speed: f32) {
+        self.config.gamepad_speed = Vec2::splat(gamepad_speed);
+    }
+
+    /// Invert the vertical look of both the mouse and the gamepad, off by default.
+    pub fn with_invert_y(mut self, invert_y: bool) -> Self {
+        self.set_invert_y(invert_y);
+        self
+    }
+
+    /// Only flips the direction of the following input,
+    /// the current pitch and its limits are kept.
+    pub fn set_invert_y(&mut self, invert_y: bool) {
+        self.config.invert_y = invert_y;
+    }
+
+    pub fn invert_y(&self) -> bool {
+        self.config.invert_y
+    }
+
+    /// Look direction (yaw, pitch) interpolated between the last two updates.
+    ///
+    /// `alpha` is the interpolation factor given to `Runnable::draw`.
+    pub fn dir(&self, alpha: f32) -> Vec2 {
+        self.prev_dir.lerp(self.dir, alpha.clamp(0.0, 1.0))
+    }
+
+    /// Roll interpolated between the last two updates.
+    pub fn roll(&self, alpha: f32) -> f32 {
+        self.prev_roll + (self.roll - self.prev_roll) * alpha.clamp(0.0, 1.0)
+    }
+
+    /// Unit vector pointing where the camera is looking.
+    pub fn forward(&self) -> Vec3 {
+        Self::dir_to_forward(self.dir)
+    }
+
+    /// View matrix for a camera located at `position`.
+    ///
+    /// `alpha` interpolates the direction and roll like `dir` and `roll`.
+    pub fn view_matrix(&self, position: Vec3, alpha: f32) -> Mat4 {
+        let forward = Self::dir_to_forward(self.dir(alpha));
+        let up = Quat::from_axis_angle(forward, self.roll(alpha)) * Vec3::Y;
+        Mat4::look_at_rh(position, position + forward, up)
+    }
+
+    /// Camera orientation for `scene::Camera::rotation`, see `view_matrix`
+    pub fn rotation(&self, alpha: f32) -> Quat {
+        Quat::from_mat4(&self.view_matrix(Vec3::ZERO, alpha).inverse())
     }
 
     pub fn update(&mut self, input: &InputState, delta: f32) {
-        self.vel = delta * Vec2::new(-3.0, 3.0) * input.get_axis(InputAxis::Look, 0);
-        self.dir += self.vel;
+        self.prev_dir = self.dir;
+        self.prev_roll = self.roll;
+
+        // gamepad look
+        let look = input.get_axis(InputAxis::Look, 0);
+        let speed = self.config.gamepad_speed * Vec2::new(-1.0, self.invert_sign());
+        self.target += delta * speed * look;
+
+        // roll
+        if let Some(roll_speed) = self.config.roll_speed {
+            self.roll += delta * roll_speed * input.get_axis(InputAxis::Roll, 0).x;
+        }
+
         self.clamp();
+
+        // smoothing
+        self.dir = if self.config.smoothing > 0.0 {
+            let t = 1.0 - (-delta / self.config.smoothing).exp();
+            self.dir.lerp(self.target, t)
+        } else {
+            self.target
+        };
     }
 
     pub fn event(&mut self, event: &Event, frame: &Frame) {
-        match event {
-            Event::WinitEvent(WinitEvent::DeviceEvent {
-                event: DeviceEvent::MouseMotion { delta: (x, y) },
-                ..
-            }) if self.focused => {
-                self.dir -= Vec2::new(*x as f32 * 0.001, *y as f32 * 0.001);
+        if let Some(event) = InputEvent::from_event(event) {
+            self.input_event(&event, frame);
+        }
+    }
+
+    pub fn input_event(&mut self, event: &InputEvent, frame: &Frame) {
+        match *event {
+            InputEvent::MouseMoved { delta } if self.focused => {
+                let delta = delta * Vec2::new(1.0, self.invert_sign()) * self.config.sensitivity;
+                self.target -= delta;
+
+                // mouse look is applied immediately when not smoothed
+                if self.config.smoothing <= 0.0 {
+                    self.dir -= delta;
+                    self.prev_dir -= delta;
+                }
                 self.clamp();
             }
-            Event::WinitEvent(WinitEvent::WindowEvent {
-                event: WindowEvent::Focused(focused),
-                ..
-            }) => {
-                self.focused = *focused;
+            InputEvent::FocusChanged(focused) => {
+                self.focused = focused;
                 let _ = frame.window().set_cursor_grab(self.focused);
                 frame.window().set_cursor_visible(!self.focused);
             }
@@ -60,15 +292,44 @@ impl FPCam {
 
     //
 
+    fn invert_sign(&self) -> f32 {
+        if self.config.invert_y {
+            -1.0
+        } else {
+            1.0
+        }
+    }
+
     fn clamp(&mut self) {
-        self.dir = Self::clamp2(self.dir);
+        self.target = self.clamp2(self.target);
+        self.dir = self.clamp2(self.dir);
+        self.prev_dir = self.clamp2(self.prev_dir);
     }
 
-    fn clamp2(mut dir: Vec2) -> Vec2 {
-        dir.y = dir.y.clamp(
-            -std::f32::consts::PI / 2.0 + 0.01,
-            std::f32::consts::PI / 2.0 - 0.01,
-        );
+    fn clamp2(&self, mut dir: Vec2) -> Vec2 {
+        let (min, max) = self.config.pitch_limits;
+        // unlike `clamp`, does not panic with bad limits
+        dir.y = dir.y.max(min).min(max);
         dir
     }
+
+    /// Positive pitch looks up (+Y)
+    fn dir_to_forward(dir: Vec2) -> Vec3 {
+        Vec3::new(
+            -dir.y.cos() * dir.x.sin(),
+            dir.y.sin(),
+            -dir.y.cos() * dir.x.cos(),
+        )
+    }
+}
+
+impl CameraView for FPCam {
+    /// The direction of the latest update
+    fn view(&self) -> Mat4 {
+        self.view_matrix(self.position, 1.0)
+    }
+
+    fn projection(&self, aspect: f32) -> Mat4 {
+        self.projection.matrix(aspect, self.depth_mode)
+    }
 }
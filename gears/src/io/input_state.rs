@@ -1,7 +1,10 @@
-use crate::game_loop::Event;
+use crate::{event::InputEvent, game_loop::Event};
 use gilrs::{Axis, Button, Event as GilrsEvent, EventType, GamepadId, Gilrs};
 use glam::Vec2;
-use std::collections::{hash_map::Entry, HashMap};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    time::{Duration, Instant},
+};
 use winit::event::{
     ElementState, Event as WinitEvent, KeyboardInput, ScanCode, VirtualKeyCode, WindowEvent,
 };
@@ -15,9 +18,20 @@ pub struct InputState {
 
     players: Vec<Option<GamepadId>>,
     gamepads: HashMap<GamepadId, Gamepad>,
+    axis_configs: HashMap<(InputAxis, usize), AxisConfig>,
 
     window_focused: bool,
     should_close: bool,
+
+    // edges since the previous `begin_frame`, per (input, player)
+    held_since: HashMap<(Input, usize), Instant>,
+    just_pressed: HashSet<(Input, usize)>,
+    just_released: HashSet<(Input, usize)>,
+
+    // button edges from events, latched until the next `begin_frame`
+    // so a press and release between two frames is not lost
+    pressed_edges: HashSet<(Input, usize)>,
+    released_edges: HashSet<(Input, usize)>,
 }
 
 #[derive(Debug, Default)]
@@ -155,6 +169,39 @@ pub enum InputAxis {
     ZMove,
 }
 
+/// Response of the gamepad stick or buttons of an `InputAxis`
+///
+/// Set per player with `InputState::set_axis_config`,
+/// keyboard inputs are not affected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisConfig {
+    /// Values below this are 0.0, values above it are rescaled to
+    /// start from 0.0 instead of jumping to the threshold
+    ///
+    /// The gamepad filter of the game loop still cuts values
+    /// below the gamepad deadzone (0.2 if the gamepad has none).
+    pub deadzone: f32,
+
+    /// Applied to the rescaled value
+    pub curve: Curve,
+
+    /// Negate y, or x of the one dimensional `Trigger` and `ZMove`
+    ///
+    /// Only applies to `InputState::get_axis`.
+    pub invert: bool,
+}
+
+/// Maps the `0.0..=1.0` magnitude after the deadzone
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Curve {
+    Linear,
+
+    /// `value.powf(n)`, over 1.0 makes slow stick movement finer
+    Power(f32),
+
+    Custom(fn(f32) -> f32),
+}
+
 pub trait Triggered {
     fn triggered(self) -> bool;
 }
@@ -167,6 +214,47 @@ impl Triggered for f32 {
 
 //
 
+impl Default for AxisConfig {
+    fn default() -> Self {
+        Self {
+            deadzone: 0.2,
+            curve: Curve::Linear,
+            invert: false,
+        }
+    }
+}
+
+impl AxisConfig {
+    /// Deadzone and curve of a single value, keeps the sign
+    pub fn apply(&self, value: f32) -> f32 {
+        let magnitude = value.abs();
+        if magnitude <= self.deadzone {
+            return 0.0;
+        }
+        let rescaled = (magnitude - self.deadzone) / (1.0 - self.deadzone).max(f32::EPSILON);
+        self.curve.apply(rescaled.min(1.0)).copysign(value)
+    }
+
+    /// Deadzone and curve of the length of `value`, keeps the direction
+    pub fn apply_vec(&self, value: Vec2) -> Vec2 {
+        let length = value.length();
+        if length <= self.deadzone {
+            return Vec2::ZERO;
+        }
+        value / length * self.apply(length)
+    }
+}
+
+impl Curve {
+    pub fn apply(self, value: f32) -> f32 {
+        match self {
+            Curve::Linear => value,
+            Curve::Power(n) => value.powf(n),
+            Curve::Custom(f) => f(value),
+        }
+    }
+}
+
 impl Default for InputState {
     fn default() -> Self {
         Self {
@@ -175,14 +263,77 @@ impl Default for InputState {
 
             players: Default::default(),
             gamepads: Default::default(),
+            axis_configs: Default::default(),
 
             window_focused: Default::default(),
             should_close: Default::default(),
+
+            held_since: Default::default(),
+            just_pressed: Default::default(),
+            just_released: Default::default(),
+
+            pressed_edges: Default::default(),
+            released_edges: Default::default(),
         }
     }
 }
 
 impl Input {
+    pub const ALL: [Input; 23] = [
+        Input::MoveUp,
+        Input::MoveDown,
+        Input::MoveLeft,
+        Input::MoveRight,
+        Input::LookUp,
+        Input::LookDown,
+        Input::LookLeft,
+        Input::LookRight,
+        Input::RollUp,
+        Input::RollDown,
+        Input::RollLeft,
+        Input::RollRight,
+        Input::Jump,
+        Input::Crouch,
+        Input::Reload,
+        Input::Accelerate,
+        Input::Decelerate,
+        Input::Next,
+        Input::Prev,
+        Input::Stats,
+        Input::Pause,
+        Input::Mode,
+        Input::Undefined,
+    ];
+
+    /// First input bound to `scancode`
+    pub fn from_scancode(scancode: ScanCode) -> Input {
+        Self::find(|input| input.into_scancode() == scancode)
+    }
+
+    /// First input bound to `button`
+    pub fn from_button(button: Button) -> Input {
+        if button == Button::Unknown {
+            return Input::Undefined;
+        }
+        Self::find(|input| input.into_button() == button)
+    }
+
+    /// First input bound to `axis`
+    pub fn from_axis(axis: Axis) -> Input {
+        if axis == Axis::Unknown {
+            return Input::Undefined;
+        }
+        Self::find(|input| input.into_axis() == axis)
+    }
+
+    fn find(f: impl Fn(Input) -> bool) -> Input {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|input| f(*input))
+            .unwrap_or(Input::Undefined)
+    }
+
     pub fn from_name(name: &'static str) -> Input {
         match name {
             "move-up" => Input::MoveUp,
@@ -301,6 +452,24 @@ impl Input {
         }
     }
 
+    /// `InputAxis` whose `AxisConfig` applies to this input
+    pub const fn axis(self) -> Option<InputAxis> {
+        match self {
+            Input::MoveUp | Input::MoveDown | Input::MoveLeft | Input::MoveRight => {
+                Some(InputAxis::Move)
+            }
+            Input::LookUp | Input::LookDown | Input::LookLeft | Input::LookRight => {
+                Some(InputAxis::Look)
+            }
+            Input::RollUp | Input::RollDown | Input::RollLeft | Input::RollRight => {
+                Some(InputAxis::Roll)
+            }
+            Input::Accelerate | Input::Decelerate => Some(InputAxis::Trigger),
+            Input::Jump | Input::Crouch => Some(InputAxis::ZMove),
+            _ => None,
+        }
+    }
+
     /// TODO: rebinding
     pub const fn is_reverse(self) -> bool {
         match self {
@@ -439,63 +608,84 @@ impl InputState {
             input.scancode
         ); */
         let state = matches!(input.state, ElementState::Pressed);
-        if let Some(scancode) = self.scancode_keymap.get_mut(input.scancode as usize) {
-            *scancode = state;
+        self.set_key(input.scancode, input.virtual_keycode, state);
+    }
+
+    fn set_key(&mut self, scancode: ScanCode, keycode: Option<VirtualKeyCode>, state: bool) {
+        if let Some(held) = self.scancode_keymap.get_mut(scancode as usize) {
+            let was = std::mem::replace(held, state);
+            self.latch_edge(0, was, state, |input| input.into_scancode() == scancode);
         }
-        if let Some(keycode) = input.virtual_keycode {
+        if let Some(keycode) = keycode {
             self.virtual_keymap.insert(keycode, state);
         }
     }
 
-    pub fn update_joystrick(&mut self, event: &GilrsEvent) {
-        match event.event {
-            EventType::ButtonPressed(button, _) => {
-                *self
-                    .gamepad_entry(event.id)
-                    .buttons
-                    .entry(button)
-                    .or_default() = 1.0;
-            }
-            EventType::ButtonRepeated(_, _) => {}
-            EventType::ButtonReleased(button, _) => {
-                *self
-                    .gamepad_entry(event.id)
-                    .buttons
-                    .entry(button)
-                    .or_default() = 0.0;
-            }
-            EventType::ButtonChanged(button, val, _) => {
-                *self
-                    .gamepad_entry(event.id)
-                    .buttons
-                    .entry(button)
-                    .or_default() = val;
-            }
-            EventType::AxisChanged(axis, val, _) => {
-                *self.gamepad_entry(event.id).axis.entry(axis).or_default() = val;
-            }
-            EventType::Connected => {}
-            EventType::Disconnected => {}
-            EventType::Dropped => {}
+    fn set_button(&mut self, id: GamepadId, button: Button, value: f32) {
+        let gamepad = self.gamepad_entry(id);
+        let player = gamepad.player;
+        let was = std::mem::replace(gamepad.buttons.entry(button).or_default(), value);
+        if button != Button::Unknown {
+            self.latch_edge(player, was.triggered(), value.triggered(), |input| {
+                input.into_button() == button
+            });
+        }
+    }
+
+    fn latch_edge(&mut self, player: usize, was: bool, now: bool, bound: impl Fn(Input) -> bool) {
+        if was == now {
+            return;
+        }
+        let edges = if now {
+            &mut self.pressed_edges
+        } else {
+            &mut self.released_edges
         };
+        for input in Input::ALL {
+            if input != Input::Undefined && bound(input) {
+                edges.insert((input, player));
+            }
+        }
+    }
+
+    pub fn update_joystrick(&mut self, event: &GilrsEvent) {
+        if let Some(event) = InputEvent::from_gilrs(event) {
+            self.input_event(&event);
+        }
     }
 
     pub fn event(&mut self, event: &Event) {
-        match event {
-            Event::GilrsEvent(event) => self.update_joystrick(event),
-            Event::WinitEvent(WinitEvent::WindowEvent {
-                event: WindowEvent::KeyboardInput { input, .. },
-                ..
-            }) => self.update_key(input),
-            // Event::WinitEvent(WinitEvent::DeviceEvent { event, .. }) => log::debug!("device event: {event:?}"),
-            Event::WinitEvent(WinitEvent::WindowEvent {
-                event: WindowEvent::Focused(f),
+        if let Some(event) = InputEvent::from_event(event) {
+            self.input_event(&event);
+        }
+    }
+
+    pub fn input_event(&mut self, event: &InputEvent) {
+        match *event {
+            InputEvent::KeyPressed { scancode, key, .. } => self.set_key(scancode, key, true),
+            InputEvent::KeyReleased { scancode, key, .. } => self.set_key(scancode, key, false),
+            InputEvent::GamepadPressed {
+                gamepad, button, ..
+            } => self.set_button(gamepad, button, 1.0),
+            InputEvent::GamepadReleased {
+                gamepad, button, ..
+            } => self.set_button(gamepad, button, 0.0),
+            InputEvent::GamepadButtonChanged {
+                gamepad,
+                button,
+                value,
                 ..
-            }) => self.window_focused = *f,
-            Event::WinitEvent(WinitEvent::WindowEvent {
-                event: WindowEvent::CloseRequested,
+            } => self.set_button(gamepad, button, value),
+            InputEvent::GamepadAxis {
+                gamepad,
+                axis,
+                value,
                 ..
-            }) => self.should_close = true,
+            } => {
+                *self.gamepad_entry(gamepad).axis.entry(axis).or_default() = value;
+            }
+            InputEvent::FocusChanged(focused) => self.window_focused = focused,
+            InputEvent::CloseRequested => self.should_close = true,
             _ => (),
         }
     }
@@ -527,10 +717,17 @@ impl InputState {
 
     /// player 0 is keyboard/mouse/controller/gamepad/joystick
     /// players 1.. are the other controllers/gamepads/joysticks
+    ///
+    /// The gamepad value goes through the `AxisConfig` of `Input::axis`,
+    /// per input instead of the stick length like in `get_axis`.
     pub fn get_input(&self, input: Input, player: usize) -> f32 {
         let mut val = 0.0;
         if let Some(gamepad) = self.get_gamepad(player) {
-            val += gamepad.get_value(input).unwrap_or(0.0);
+            let pad = gamepad.get_value(input).unwrap_or(0.0);
+            val += match input.axis() {
+                Some(axis) => self.axis_config(axis, player).apply(pad),
+                None => pad,
+            };
         }
         if player == 0 && self.key_held(input) {
             val += 1.0
@@ -540,24 +737,42 @@ impl InputState {
 
     /// player 0 is keyboard/mouse/controller/gamepad/joystick
     /// players 1.. are the other controllers/gamepads/joysticks
-    fn get_input_vec(&self, x_input: AxisInputs, y_input: AxisInputs, player: usize) -> Vec2 {
+    fn get_input_vec(
+        &self,
+        axis: InputAxis,
+        x_input: AxisInputs,
+        y_input: AxisInputs,
+        player: usize,
+    ) -> Vec2 {
         let mut neg_x = 0.0;
         let mut pos_x = 0.0;
         let mut neg_y = 0.0;
         let mut pos_y = 0.0;
         if let Some(gamepad) = self.get_gamepad(player) {
+            let mut pad = Vec2::ZERO;
             if x_input.2 {
-                pos_x += gamepad.get_value(x_input.1).unwrap_or(0.0);
+                pad.x += gamepad.get_value(x_input.1).unwrap_or(0.0);
             } else {
-                neg_x -= gamepad.get_value(x_input.0).unwrap_or(0.0);
-                pos_x += gamepad.get_value(x_input.1).unwrap_or(0.0);
+                pad.x -= gamepad.get_value(x_input.0).unwrap_or(0.0);
+                pad.x += gamepad.get_value(x_input.1).unwrap_or(0.0);
             }
             if y_input.2 {
-                pos_y += gamepad.get_value(y_input.1).unwrap_or(0.0);
+                pad.y += gamepad.get_value(y_input.1).unwrap_or(0.0);
             } else {
-                neg_y -= gamepad.get_value(y_input.0).unwrap_or(0.0);
-                pos_y += gamepad.get_value(y_input.1).unwrap_or(0.0);
+                pad.y -= gamepad.get_value(y_input.0).unwrap_or(0.0);
+                pad.y += gamepad.get_value(y_input.1).unwrap_or(0.0);
             }
+
+            let config = self.axis_config(axis, player);
+            let mut pad = config.apply_vec(pad);
+            if config.invert {
+                match axis {
+                    InputAxis::Trigger | InputAxis::ZMove => pad.x = -pad.x,
+                    _ => pad.y = -pad.y,
+                }
+            }
+            pos_x += pad.x;
+            pos_y += pad.y;
         }
         if player == 0 {
             neg_x -= Self::btof(self.key_held(x_input.0));
@@ -570,29 +785,36 @@ impl InputState {
 
     /// player 0 is keyboard/mouse
     /// players 1.. are controllers/gamepads/joysticks
+    ///
+    /// The gamepad stick goes through the `AxisConfig` of `input`.
     pub fn get_axis(&self, input: InputAxis, player: usize) -> Vec2 {
         match input {
             InputAxis::Move => self.get_input_vec(
+                input,
                 (Input::MoveLeft, Input::MoveRight, true),
                 (Input::MoveDown, Input::MoveUp, true),
                 player,
             ),
             InputAxis::Look => self.get_input_vec(
+                input,
                 (Input::LookLeft, Input::LookRight, true),
                 (Input::LookDown, Input::LookUp, true),
                 player,
             ),
             InputAxis::Roll => self.get_input_vec(
+                input,
                 (Input::RollLeft, Input::RollRight, true),
                 (Input::RollDown, Input::RollUp, true),
                 player,
             ),
             InputAxis::Trigger => self.get_input_vec(
+                input,
                 (Input::Decelerate, Input::Accelerate, false),
                 (Input::Undefined, Input::Undefined, true),
                 player,
             ),
             InputAxis::ZMove => self.get_input_vec(
+                input,
                 (Input::Crouch, Input::Jump, false),
                 (Input::Undefined, Input::Undefined, true),
                 player,
@@ -600,10 +822,97 @@ impl InputState {
         }
     }
 
+    /// Deadzone, curve and inversion of the gamepad `axis` of `player`
+    pub fn set_axis_config(&mut self, player: usize, axis: InputAxis, config: AxisConfig) {
+        self.axis_configs.insert((axis, player), config);
+    }
+
+    /// `AxisConfig::default` unless set with `set_axis_config`
+    pub fn axis_config(&self, axis: InputAxis, player: usize) -> AxisConfig {
+        self.axis_configs
+            .get(&(axis, player))
+            .copied()
+            .unwrap_or_default()
+    }
+
     pub fn should_close(&self) -> bool {
         self.should_close
     }
 
+    /// Compare the inputs to the previous call for `just_pressed`
+    /// and `just_released`
+    ///
+    /// Key and button edges are latched from the events, so a tap
+    /// shorter than a frame is both just pressed and just released.
+    ///
+    /// The game loop calls this before each `Runnable::update` (or each
+    /// `Runnable::draw` without an update rate) for the `InputState`
+    /// returned by `Runnable::input_state`.
+    pub fn begin_frame(&mut self) {
+        self.just_pressed.clear();
+        self.just_released.clear();
+        let pressed_edges = std::mem::take(&mut self.pressed_edges);
+        let released_edges = std::mem::take(&mut self.released_edges);
+
+        let now = Instant::now();
+        for player in 0..self.players.len().max(1) {
+            for input in Input::ALL {
+                if input == Input::Undefined {
+                    continue;
+                }
+
+                let key = (input, player);
+                let held = self.get_input(input, player).triggered();
+                let was_held = self.held_since.contains_key(&key);
+                let pressed = pressed_edges.contains(&key);
+                let released = released_edges.contains(&key);
+
+                if (held && !was_held) || pressed {
+                    self.just_pressed.insert(key);
+                }
+                if (!held && was_held) || released {
+                    self.just_released.insert(key);
+                }
+
+                if !held {
+                    self.held_since.remove(&key);
+                } else if !was_held || (pressed && released) {
+                    // released and pressed again since the previous frame
+                    self.held_since.insert(key, now);
+                }
+            }
+        }
+    }
+
+    /// `input` started being held since the previous `begin_frame`
+    pub fn just_pressed(&self, input: Input, player: usize) -> bool {
+        self.just_pressed.contains(&(input, player))
+    }
+
+    /// `input` stopped being held since the previous `begin_frame`
+    pub fn just_released(&self, input: Input, player: usize) -> bool {
+        self.just_released.contains(&(input, player))
+    }
+
+    /// All of `inputs` are held and at least one of them was just pressed
+    ///
+    /// Ex: `just_pressed_chord(&[Input::Mode, Input::Reload], 0)` for Alt+R
+    pub fn just_pressed_chord(&self, inputs: &[Input], player: usize) -> bool {
+        inputs
+            .iter()
+            .all(|&input| self.held_since.contains_key(&(input, player)))
+            && inputs.iter().any(|&input| self.just_pressed(input, player))
+    }
+
+    /// Time `input` has been held, zero if it is not held
+    ///
+    /// Measured from the `begin_frame` that saw it pressed.
+    pub fn held_duration(&self, input: Input, player: usize) -> Duration {
+        self.held_since
+            .get(&(input, player))
+            .map_or(Duration::ZERO, Instant::elapsed)
+    }
+
     //
 
     fn gamepad_entry(&mut self, id: GamepadId) -> &'_ mut Gamepad {
@@ -700,3 +1009,59 @@ impl InputState {
 //
 
 type AxisInputs = (Input, Input, bool);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(state: &mut InputState, input: Input, pressed: bool) {
+        let (scancode, key) = (input.into_scancode(), None);
+        state.input_event(&if pressed {
+            InputEvent::KeyPressed {
+                input,
+                scancode,
+                key,
+            }
+        } else {
+            InputEvent::KeyReleased {
+                input,
+                scancode,
+                key,
+            }
+        });
+    }
+
+    #[test]
+    fn tap_between_frames() {
+        let mut state = InputState::new();
+        state.begin_frame();
+
+        key(&mut state, Input::Jump, true);
+        key(&mut state, Input::Jump, false);
+        state.begin_frame();
+        assert!(state.just_pressed(Input::Jump, 0));
+        assert!(state.just_released(Input::Jump, 0));
+        assert_eq!(state.held_duration(Input::Jump, 0), Duration::ZERO);
+
+        state.begin_frame();
+        assert!(!state.just_pressed(Input::Jump, 0));
+        assert!(!state.just_released(Input::Jump, 0));
+    }
+
+    #[test]
+    fn hold_across_frames() {
+        let mut state = InputState::new();
+        key(&mut state, Input::Jump, true);
+        state.begin_frame();
+        assert!(state.just_pressed(Input::Jump, 0));
+
+        state.begin_frame();
+        assert!(!state.just_pressed(Input::Jump, 0));
+        assert!(state.get_input(Input::Jump, 0).triggered());
+
+        key(&mut state, Input::Jump, false);
+        state.begin_frame();
+        assert!(state.just_released(Input::Jump, 0));
+        assert!(!state.just_pressed(Input::Jump, 0));
+    }
+}
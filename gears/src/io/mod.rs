@@ -1,2 +1,4 @@
 pub mod fpcam;
 pub mod input_state;
+pub mod orbitcam;
+pub mod snapshot;
@@ -0,0 +1,255 @@
+use super::input_state::{InputAxis, InputState};
+use crate::{
+    event::InputEvent,
+    game_loop::Event,
+    scene::{CameraView, Projection},
+    DepthMode,
+};
+use glam::{Mat4, Vec2, Vec3};
+use winit::event::MouseButton;
+
+//
+
+/// Orbit camera controller for model viewers
+///
+/// Looks at `target` from `distance` away. Dragging with the
+/// mouse or the look axis rotates and scrolling or the roll
+/// axis zooms.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitCam {
+    pub target: Vec3,
+    pub distance: f32,
+    /// Radians around +Y, 0 looks from +Z towards the target
+    pub azimuth: f32,
+    /// Radians above the XZ plane
+    pub elevation: f32,
+
+    pub projection: Projection,
+    /// Has to match the `DepthMode` of the renderer
+    pub depth_mode: DepthMode,
+
+    config: OrbitCamConfig,
+    dragging: bool,
+}
+
+/// Orbit camera controller configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitCamConfig {
+    /// Drag rotation in radians per mouse motion unit.
+    ///
+    /// Defaults to `0.005` on both axes.
+    pub sensitivity: Vec2,
+
+    /// Gamepad and arrow key rotation in radians per second.
+    ///
+    /// Defaults to `2.0` on both axes.
+    pub gamepad_speed: Vec2,
+
+    /// Distance multiplier per scroll line, in and out.
+    ///
+    /// Defaults to `0.1`, 10% per line.
+    pub zoom_speed: f32,
+
+    /// Mouse button that rotates while held.
+    ///
+    /// Defaults to the left button.
+    pub drag_button: MouseButton,
+
+    /// Distance (min, max).
+    pub distance_limits: (f32, f32),
+
+    /// Elevation (min, max) in radians.
+    pub elevation_limits: (f32, f32),
+}
+
+//
+
+impl Default for OrbitCamConfig {
+    fn default() -> Self {
+        Self {
+            sensitivity: Vec2::new(0.005, 0.005),
+            gamepad_speed: Vec2::new(2.0, 2.0),
+            zoom_speed: 0.1,
+            drag_button: MouseButton::Left,
+            distance_limits: (0.1, 1000.0),
+            elevation_limits: (
+                -std::f32::consts::PI / 2.0 + 0.01,
+                std::f32::consts::PI / 2.0 - 0.01,
+            ),
+        }
+    }
+}
+
+impl OrbitCamConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub const fn with_sensitivity(mut self, sensitivity: Vec2) -> Self {
+        self.sensitivity = sensitivity;
+        self
+    }
+
+    pub const fn with_gamepad_speed(mut self, gamepad_speed: Vec2) -> Self {
+        self.gamepad_speed = gamepad_speed;
+        self
+    }
+
+    pub const fn with_zoom_speed(mut self, zoom_speed: f32) -> Self {
+        self.zoom_speed = zoom_speed;
+        self
+    }
+
+    pub const fn with_drag_button(mut self, drag_button: MouseButton) -> Self {
+        self.drag_button = drag_button;
+        self
+    }
+
+    pub const fn with_distance_limits(mut self, min: f32, max: f32) -> Self {
+        self.distance_limits = (min, max);
+        self
+    }
+
+    pub const fn with_elevation_limits(mut self, min: f32, max: f32) -> Self {
+        self.elevation_limits = (min, max);
+        self
+    }
+}
+
+impl Default for OrbitCam {
+    fn default() -> Self {
+        Self::new(Vec3::ZERO, 5.0)
+    }
+}
+
+impl OrbitCam {
+    pub fn new(target: Vec3, distance: f32) -> Self {
+        Self {
+            target,
+            distance,
+            azimuth: 0.0,
+            elevation: 0.0,
+
+            projection: Projection::default(),
+            depth_mode: DepthMode::default(),
+
+            config: OrbitCamConfig::default(),
+            dragging: false,
+        }
+    }
+
+    pub fn with_angles(mut self, azimuth: f32, elevation: f32) -> Self {
+        self.azimuth = azimuth;
+        self.elevation = elevation;
+        self.clamp();
+        self
+    }
+
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    /// Use `Renderer::depth_mode`
+    pub fn with_depth_mode(mut self, depth_mode: DepthMode) -> Self {
+        self.depth_mode = depth_mode;
+        self
+    }
+
+    pub fn with_config(mut self, config: OrbitCamConfig) -> Self {
+        self.config = config;
+        self.clamp();
+        self
+    }
+
+    pub fn config(&self) -> &OrbitCamConfig {
+        &self.config
+    }
+
+    /// Changes take effect immediately
+    pub fn config_mut(&mut self) -> &mut OrbitCamConfig {
+        &mut self.config
+    }
+
+    /// Camera position
+    pub fn eye(&self) -> Vec3 {
+        self.eye_with_offset(0.0)
+    }
+
+    /// Camera position rotated `azimuth_offset` radians further around the target
+    pub fn eye_with_offset(&self, azimuth_offset: f32) -> Vec3 {
+        let azimuth = self.azimuth + azimuth_offset;
+        let dir = Vec3::new(
+            azimuth.sin() * self.elevation.cos(),
+            self.elevation.sin(),
+            azimuth.cos() * self.elevation.cos(),
+        );
+        self.target + dir * self.distance
+    }
+
+    /// View matrix looking at `target`
+    pub fn view_matrix(&self) -> Mat4 {
+        Mat4::look_at_rh(self.eye(), self.target, Vec3::Y)
+    }
+
+    /// Keyboard and gamepad rotation and zoom
+    pub fn update(&mut self, input: &InputState, delta: f32) {
+        let look = input.get_axis(InputAxis::Look, 0);
+        self.rotate(delta * self.config.gamepad_speed * Vec2::new(-look.x, look.y));
+
+        let zoom = input.get_axis(InputAxis::Roll, 0).y;
+        self.zoom(delta * zoom * 10.0);
+    }
+
+    pub fn event(&mut self, event: &Event) {
+        if let Some(event) = InputEvent::from_event(event) {
+            self.input_event(&event);
+        }
+    }
+
+    pub fn input_event(&mut self, event: &InputEvent) {
+        match *event {
+            InputEvent::MouseButton { button, pressed } if button == self.config.drag_button => {
+                self.dragging = pressed;
+            }
+            InputEvent::MouseMoved { delta } if self.dragging => {
+                self.rotate(-delta * self.config.sensitivity);
+            }
+            InputEvent::MouseWheel { delta } => self.zoom(delta.y),
+            InputEvent::FocusChanged(false) => self.dragging = false,
+            _ => {}
+        }
+    }
+
+    //
+
+    /// Add (azimuth, elevation)
+    fn rotate(&mut self, delta: Vec2) {
+        self.azimuth += delta.x;
+        self.elevation += delta.y;
+        self.clamp();
+    }
+
+    /// Positive lines zoom in
+    fn zoom(&mut self, lines: f32) {
+        self.distance *= (1.0 - self.config.zoom_speed).powf(lines);
+        self.clamp();
+    }
+
+    fn clamp(&mut self) {
+        let (min, max) = self.config.elevation_limits;
+        self.elevation = self.elevation.clamp(min, max);
+        let (min, max) = self.config.distance_limits;
+        self.distance = self.distance.clamp(min, max);
+    }
+}
+
+impl CameraView for OrbitCam {
+    fn view(&self) -> Mat4 {
+        self.view_matrix()
+    }
+
+    fn projection(&self, aspect: f32) -> Mat4 {
+        self.projection.matrix(aspect, self.depth_mode)
+    }
+}
@@ -0,0 +1,260 @@
+use super::input_state::{Input, InputAxis, InputState, Triggered};
+use glam::Vec2;
+
+//
+
+/// Compact copy of the player 0 input state
+///
+/// Digital inputs are stored as bits (`1 << Input as u32`),
+/// analog axes are quantized to `i8` so that every
+/// machine decodes the exact same values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct InputSnapshot {
+    pub buttons: u32,
+
+    /// Move x, Move y, Look x, Look y, Trigger x
+    pub axes: [i8; AXES],
+}
+
+/// Changes between two `InputSnapshot`s,
+/// see `InputSnapshot::diff`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct InputDelta {
+    /// Bit 0: buttons changed, bits 1..: axes changed
+    changed: u8,
+    /// Buttons that toggled
+    buttons: u32,
+    axes: [i8; AXES],
+}
+
+//
+
+const AXES: usize = 5;
+
+impl InputState {
+    /// Snapshot of the player 0 inputs
+    pub fn snapshot(&self) -> InputSnapshot {
+        let buttons = Input::ALL
+            .iter()
+            .filter(|&&input| input != Input::Undefined && self.get_input(input, 0).triggered())
+            .fold(0, |buttons, &input| buttons | InputSnapshot::bit(input));
+
+        let movement = self.get_axis(InputAxis::Move, 0);
+        let look = self.get_axis(InputAxis::Look, 0);
+        let trigger = self.get_axis(InputAxis::Trigger, 0);
+
+        InputSnapshot {
+            buttons,
+            axes: [
+                quantize(movement.x),
+                quantize(movement.y),
+                quantize(look.x),
+                quantize(look.y),
+                quantize(trigger.x),
+            ],
+        }
+    }
+}
+
+/// Deterministic quantization of an axis value in -1.0..=1.0
+pub fn quantize(value: f32) -> i8 {
+    if value.is_nan() {
+        0
+    } else {
+        (value.clamp(-1.0, 1.0) * 127.0).round() as i8
+    }
+}
+
+pub fn dequantize(value: i8) -> f32 {
+    (value as f32 / 127.0).max(-1.0)
+}
+
+impl InputSnapshot {
+    /// Byte length of `to_bytes`
+    pub const SIZE: usize = 4 + AXES;
+
+    fn bit(input: Input) -> u32 {
+        1 << input as u32
+    }
+
+    pub fn pressed(&self, input: Input) -> bool {
+        input != Input::Undefined && self.buttons & Self::bit(input) != 0
+    }
+
+    /// Dequantized axis, `Roll` and `ZMove` are not stored
+    pub fn axis(&self, axis: InputAxis) -> Vec2 {
+        let a = self.axes;
+        match axis {
+            InputAxis::Move => Vec2::new(dequantize(a[0]), dequantize(a[1])),
+            InputAxis::Look => Vec2::new(dequantize(a[2]), dequantize(a[3])),
+            InputAxis::Trigger => Vec2::new(dequantize(a[4]), 0.0),
+            InputAxis::Roll | InputAxis::ZMove => Vec2::ZERO,
+        }
+    }
+
+    /// `None` if nothing changed since `prev`
+    pub fn diff(&self, prev: &InputSnapshot) -> Option<InputDelta> {
+        let mut delta = InputDelta::default();
+
+        if self.buttons != prev.buttons {
+            delta.changed |= 1;
+            delta.buttons = self.buttons ^ prev.buttons;
+        }
+        for (i, (&now, &then)) in self.axes.iter().zip(prev.axes.iter()).enumerate() {
+            if now != then {
+                delta.changed |= 1 << (i + 1);
+                delta.axes[i] = now;
+            }
+        }
+
+        if delta.changed == 0 {
+            None
+        } else {
+            Some(delta)
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[..4].copy_from_slice(&self.buttons.to_le_bytes());
+        for (byte, &axis) in bytes[4..].iter_mut().zip(self.axes.iter()) {
+            *byte = axis as u8;
+        }
+        bytes
+    }
+
+    /// `None` if `bytes` is too short
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.get(..Self::SIZE)?;
+        let mut buttons = [0; 4];
+        buttons.copy_from_slice(&bytes[..4]);
+
+        let mut snapshot = Self {
+            buttons: u32::from_le_bytes(buttons),
+            ..Default::default()
+        };
+        for (axis, &byte) in snapshot.axes.iter_mut().zip(bytes[4..].iter()) {
+            *axis = byte as i8;
+        }
+        Some(snapshot)
+    }
+}
+
+impl InputDelta {
+    /// `prev` with the changes applied
+    pub fn apply(&self, prev: &InputSnapshot) -> InputSnapshot {
+        let mut snapshot = *prev;
+        if self.changed & 1 != 0 {
+            snapshot.buttons ^= self.buttons;
+        }
+        for (i, axis) in snapshot.axes.iter_mut().enumerate() {
+            if self.changed & (1 << (i + 1)) != 0 {
+                *axis = self.axes[i];
+            }
+        }
+        snapshot
+    }
+
+    /// One byte of change flags followed by only the changed values
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.changed];
+        if self.changed & 1 != 0 {
+            bytes.extend_from_slice(&self.buttons.to_le_bytes());
+        }
+        for (i, &axis) in self.axes.iter().enumerate() {
+            if self.changed & (1 << (i + 1)) != 0 {
+                bytes.push(axis as u8);
+            }
+        }
+        bytes
+    }
+
+    /// `None` if `bytes` is too short or has unknown change flags
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (&changed, mut bytes) = bytes.split_first()?;
+        if changed >> (AXES + 1) != 0 {
+            return None;
+        }
+
+        let mut delta = Self {
+            changed,
+            ..Default::default()
+        };
+        if changed & 1 != 0 {
+            let mut buttons = [0; 4];
+            buttons.copy_from_slice(bytes.get(..4)?);
+            delta.buttons = u32::from_le_bytes(buttons);
+            bytes = &bytes[4..];
+        }
+        for (i, axis) in delta.axes.iter_mut().enumerate() {
+            if changed & (1 << (i + 1)) != 0 {
+                let (&byte, rest) = bytes.split_first()?;
+                *axis = byte as i8;
+                bytes = rest;
+            }
+        }
+        Some(delta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> InputSnapshot {
+        InputSnapshot {
+            buttons: InputSnapshot::bit(Input::MoveUp) | InputSnapshot::bit(Input::Reload),
+            axes: [
+                quantize(1.0),
+                quantize(-1.0),
+                quantize(0.5),
+                0,
+                quantize(-0.25),
+            ],
+        }
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let snapshot = snapshot();
+        let bytes = snapshot.to_bytes();
+        assert_eq!(InputSnapshot::from_bytes(&bytes), Some(snapshot));
+        assert_eq!(
+            InputSnapshot::from_bytes(&bytes[..InputSnapshot::SIZE - 1]),
+            None
+        );
+    }
+
+    #[test]
+    fn unchanged_delta_is_empty() {
+        let snapshot = snapshot();
+        assert_eq!(snapshot.diff(&snapshot), None);
+        // only the change flags, no payload
+        assert_eq!(InputDelta::default().to_bytes(), [0]);
+    }
+
+    #[test]
+    fn delta_round_trip() {
+        let prev = snapshot();
+        let mut next = prev;
+        next.buttons ^= InputSnapshot::bit(Input::Jump);
+        next.axes[2] = quantize(-0.5);
+
+        let bytes = next.diff(&prev).unwrap().to_bytes();
+        // flags, 4 button bytes and one axis
+        assert_eq!(bytes.len(), 1 + 4 + 1);
+        let delta = InputDelta::from_bytes(&bytes).unwrap();
+        assert_eq!(delta.apply(&prev), next);
+        assert_eq!(InputDelta::from_bytes(&bytes[..bytes.len() - 1]), None);
+    }
+
+    #[test]
+    fn quantize_deterministic() {
+        assert_eq!(quantize(1.0), 127);
+        assert_eq!(quantize(-1.0), -127);
+        assert_eq!(quantize(2.0), 127);
+        assert_eq!(quantize(f32::NAN), 0);
+        assert_eq!(dequantize(quantize(0.5)), 64.0 / 127.0);
+        assert_eq!(dequantize(i8::MIN), -1.0);
+    }
+}
@@ -1,24 +1,44 @@
 pub use async_trait;
+#[cfg(feature = "egui")]
+pub use egui;
 pub use gears_pipeline;
 pub use glam;
+#[cfg(feature = "specs")]
+pub use specs;
 pub use static_assertions;
 pub use vulkano;
 pub use vulkano_shaders;
 pub use winit;
 
+use context::ContextError;
 use log::error;
-use std::{fmt, time};
+use renderer::simple_renderer::RenderError;
+use std::{error::Error, fmt, time::Duration};
+use vulkano::{
+    pipeline::{
+        graphics::depth_stencil::{CompareOp, DepthStencilState},
+        StateMode,
+    },
+    swapchain::{PresentMode, SupportedPresentModes},
+};
 
 //
 
 pub mod context;
 pub mod debug;
+#[cfg(feature = "specs")]
+pub mod ecs;
+pub mod event;
 pub mod format;
 pub mod frame;
 pub mod game_loop;
+#[cfg(feature = "egui")]
+pub mod gui;
 pub mod io;
 pub mod renderer;
 pub mod report;
+pub mod scene;
+pub mod time;
 //
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
@@ -55,6 +75,37 @@ pub enum SyncMode {
     /// - Consumes more power
     /// - Might not be supported (fallback to Fifo)
     Mailbox,
+
+    /// FIFO relaxed: sync unless the frame was late
+    ///
+    /// Pros:
+    /// + Eliminates screen tearing when the frame rate is above the refresh rate
+    /// + Less stutter than Fifo when a frame is late
+    ///
+    /// Cons:
+    /// - Late frames will tear
+    /// - Might not be supported (fallback to Fifo)
+    FifoRelaxed,
+}
+
+/// Depth buffer convention
+///
+/// Set on the `RendererBuilder`, pipelines built with
+/// `GPipelineBuilder` and the clear value of the render
+/// pass follow it. Cameras have to be given the same mode.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum DepthMode {
+    /// Depth 0 is near and 1 is far
+    ///
+    /// Cleared to 1, `Less` passes.
+    Standard,
+
+    /// Depth 1 is near and 0 is infinitely far
+    ///
+    /// Cleared to 0, `GreaterOrEqual` passes.
+    /// Float precision is spread more evenly over the distance,
+    /// which removes z-fighting far away from the camera.
+    ReversedZ,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
@@ -69,7 +120,20 @@ pub enum UpdateRate {
 
     /// _t_ update interval
     /// Ex: Update every 2 seconds = ```UpdateRate::Interval::(Duration::from_secs(2))```
-    Interval(time::Duration),
+    Interval(Duration),
+}
+
+/// Errors that stop the game loop, see `Loop::run`
+#[derive(Debug)]
+pub enum GearsError {
+    /// Creating the context, frame or renderer failed
+    Context(ContextError),
+
+    /// Rendering a frame failed, e.g. the device was lost
+    Render(RenderError),
+
+    /// Buffer, pipeline and other errors from the `anyhow` based apis
+    Other(anyhow::Error),
 }
 
 impl Default for SyncMode {
@@ -78,16 +142,115 @@ impl Default for SyncMode {
     }
 }
 
+impl SyncMode {
+    /// This mode if it is supported, Fifo otherwise
+    pub fn or_fallback(self, supported: &SupportedPresentModes) -> Self {
+        let is_supported = match self {
+            SyncMode::Immediate => supported.immediate,
+            SyncMode::Fifo => true,
+            SyncMode::Mailbox => supported.mailbox,
+            SyncMode::FifoRelaxed => supported.relaxed,
+        };
+
+        if is_supported {
+            self
+        } else {
+            SyncMode::Fifo
+        }
+    }
+
+    pub fn present_mode(self) -> PresentMode {
+        match self {
+            SyncMode::Immediate => PresentMode::Immediate,
+            SyncMode::Fifo => PresentMode::Fifo,
+            SyncMode::Mailbox => PresentMode::Mailbox,
+            SyncMode::FifoRelaxed => PresentMode::Relaxed,
+        }
+    }
+}
+
+impl Default for DepthMode {
+    fn default() -> Self {
+        DepthMode::Standard
+    }
+}
+
+impl DepthMode {
+    /// Depth the depth buffer is cleared to
+    pub fn clear_value(self) -> f32 {
+        match self {
+            DepthMode::Standard => 1.0,
+            DepthMode::ReversedZ => 0.0,
+        }
+    }
+
+    pub fn compare_op(self) -> CompareOp {
+        match self {
+            DepthMode::Standard => CompareOp::Less,
+            DepthMode::ReversedZ => CompareOp::GreaterOrEqual,
+        }
+    }
+
+    /// Depth test and write with `compare_op`
+    pub fn depth_stencil_state(self) -> DepthStencilState {
+        let mut state = DepthStencilState::simple_depth_test();
+        if let Some(depth) = state.depth.as_mut() {
+            depth.compare_op = StateMode::Fixed(self.compare_op());
+        }
+        state
+    }
+}
+
 impl UpdateRate {
-    pub fn to_interval(&self) -> time::Duration {
+    /// Rounded to whole nanoseconds, the game loop
+    /// counts the updates with `time::UpdateAccumulator`
+    pub fn to_interval(&self) -> Duration {
         match *self {
-            UpdateRate::PerSecond(n) => time::Duration::from_secs_f64(1.0).div_f64(n as f64),
-            UpdateRate::PerMinute(n) => time::Duration::from_secs_f64(60.0).div_f64(n as f64),
+            UpdateRate::PerSecond(n) => Duration::from_secs_f64(1.0).div_f64(n as f64),
+            UpdateRate::PerMinute(n) => Duration::from_secs_f64(60.0).div_f64(n as f64),
             UpdateRate::Interval(i) => i,
         }
     }
 }
 
+impl fmt::Display for GearsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GearsError::Context(err) => write!(f, "Context error: {err}"),
+            GearsError::Render(err) => write!(f, "Render error: {err}"),
+            GearsError::Other(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl Error for GearsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GearsError::Context(err) => Some(err),
+            GearsError::Render(err) => Some(err),
+            GearsError::Other(err) => Some(err.as_ref()),
+        }
+    }
+}
+
+impl From<ContextError> for GearsError {
+    fn from(err: ContextError) -> Self {
+        GearsError::Context(err)
+    }
+}
+
+impl From<RenderError> for GearsError {
+    fn from(err: RenderError) -> Self {
+        GearsError::Render(err)
+    }
+}
+
+impl From<anyhow::Error> for GearsError {
+    fn from(err: anyhow::Error) -> Self {
+        GearsError::Other(err)
+    }
+}
+
 // Internal helper traits:
 
 trait ExpectLog<T> {
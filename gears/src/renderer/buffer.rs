@@ -1,7 +1,15 @@
-use super::{device::Dev, Recorder};
-use anyhow::Result;
+use super::{
+    device::Dev,
+    memory::{self, Allocation, UploadTracker},
+    readback::ReadbackHandle,
+    upload::{UploadHandle, Uploader},
+    Recorder,
+};
+use anyhow::{bail, Result};
+use parking_lot::Mutex;
 use std::{
-    ops::{Deref, DerefMut},
+    any, mem,
+    ops::{Deref, DerefMut, Range},
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
@@ -10,8 +18,9 @@ use std::{
 use vulkano::{
     buffer::{
         cpu_access::{ReadLock, WriteLock},
-        CpuAccessibleBuffer, DeviceLocalBuffer,
+        BufferAccess, CpuAccessibleBuffer, DeviceLocalBuffer,
     },
+    device::{physical::QueueFamily, DeviceOwned},
     memory::Content,
     DeviceSize,
 };
@@ -21,7 +30,20 @@ pub use vulkano::buffer::BufferUsage;
 pub struct StagedBuffer<T: ?Sized> {
     pub stage: Arc<CpuAccessibleBuffer<T>>,
     pub local: Arc<DeviceLocalBuffer<T>>,
+    usage: BufferUsage,
     updates: AtomicBool,
+    // element ranges written with `mark_dirty`, see `update`
+    dirty: Mutex<Vec<Range<DeviceSize>>>,
+    pending: Option<PendingReplace<T>>,
+    allocation: Allocation,
+    uploads: UploadTracker,
+}
+
+struct PendingReplace<T: ?Sized> {
+    stage: Arc<CpuAccessibleBuffer<T>>,
+    local: Arc<DeviceLocalBuffer<T>>,
+    handle: UploadHandle,
+    allocation: Allocation,
 }
 
 impl<T: ?Sized> Deref for StagedBuffer<T> {
@@ -57,11 +79,17 @@ where
         let stage =
             CpuAccessibleBuffer::from_data(device.logical().clone(), stage_usage, false, data)?;
         let local = make_local(device, local_usage)?;
+        let allocation = track(device, &stage, &local);
 
         let buffer = Self {
             stage,
             local,
+            usage,
             updates: AtomicBool::new(true),
+            dirty: Mutex::new(Vec::new()),
+            pending: None,
+            allocation,
+            uploads: device.uploads().clone(),
         };
 
         Ok(buffer)
@@ -82,15 +110,84 @@ where
         let stage =
             CpuAccessibleBuffer::from_iter(device.logical().clone(), stage_usage, false, data)?;
         let local = make_local_array(device, local_usage, len as u64)?;
+        let allocation = track(device, &stage, &local);
 
         let buffer = Self {
             stage,
             local,
+            usage,
             updates: AtomicBool::new(true),
+            dirty: Mutex::new(Vec::new()),
+            pending: None,
+            allocation,
+            uploads: device.uploads().clone(),
         };
 
         Ok(buffer)
     }
+
+    /// record an upload of `data` into new buffers
+    ///
+    /// the current buffers stay in use until
+    /// the upload has finished and `poll_replace` swaps them
+    pub fn replace_async<I>(&mut self, uploader: &mut Uploader, data: I) -> Result<()>
+    where
+        I: ExactSizeIterator<Item = T>,
+    {
+        let device = uploader.device().clone();
+        let (stage_usage, local_usage) = make_usage(self.usage);
+        let len = data.len();
+
+        let stage =
+            CpuAccessibleBuffer::from_iter(device.logical().clone(), stage_usage, false, data)?;
+        let local = make_local_array(&device, local_usage, len as u64)?;
+        let allocation = track(&device, &stage, &local);
+
+        uploader
+            .record()
+            .copy_buffer(stage.clone(), local.clone())?;
+        self.uploads.upload(any::type_name::<Self>(), stage.size());
+
+        self.pending = Some(PendingReplace {
+            stage,
+            local,
+            handle: uploader.handle(),
+            allocation,
+        });
+
+        Ok(())
+    }
+}
+
+impl<T> StagedBuffer<[T]>
+where
+    T: Copy + Send + Sync + 'static,
+{
+    /// record a copy of the device local buffer into a new host visible buffer
+    ///
+    /// record this after the commands that write to the buffer,
+    /// the handle becomes ready when the frame has finished
+    pub fn read_back(&self, recorder: &mut Recorder<false>) -> Result<ReadbackHandle<T>> {
+        // the stage would overwrite the device local buffer
+        // after the copy in the same frame
+        if self.updates.load(Ordering::SeqCst) || !self.dirty.lock().is_empty() {
+            bail!("StagedBuffer has a pending write, call update before read_back");
+        }
+
+        let buffer = unsafe {
+            CpuAccessibleBuffer::uninitialized_array(
+                self.local.device().clone(),
+                self.local.len(),
+                BufferUsage::transfer_destination(),
+                true,
+            )?
+        };
+        recorder
+            .record()
+            .copy_buffer(self.local.clone(), buffer.clone())?;
+
+        Ok(ReadbackHandle::new(buffer, recorder.readback_fence()))
+    }
 }
 
 impl<T> StagedBuffer<T>
@@ -114,9 +211,41 @@ where
         recorder
             .record()
             .copy_buffer(self.stage.clone(), self.local.clone())?;
+        self.uploads
+            .upload(any::type_name::<Self>(), self.stage.size());
         Ok(())
     }
 
+    /// record the copy of the stage buffer to the device local
+    /// buffer with `uploader` instead of the next `update`
+    pub fn upload(&self, uploader: &mut Uploader) -> Result<()> {
+        self.updates.store(false, Ordering::SeqCst);
+        self.dirty.lock().clear();
+        uploader
+            .record()
+            .copy_buffer(self.stage.clone(), self.local.clone())?;
+        self.uploads
+            .upload(any::type_name::<Self>(), self.stage.size());
+        Ok(())
+    }
+
+    /// multiple writes will result in multiple copy operations
+    pub fn write(&self, recorder: &mut Recorder<false>) -> Result<WriteLock<T>> {
+        // the whole buffer is copied, which covers the dirty ranges too
+        self.updates.store(false, Ordering::SeqCst);
+        self.dirty.lock().clear();
+        self.copy_to_local(recorder)?;
+
+        // acquire the write lock
+        let lock = self.stage.write()?;
+        Ok(lock)
+    }
+}
+
+impl<T> StagedBuffer<T>
+where
+    T: Content + Send + Sync + 'static,
+{
     /// update sends data from the stage to the device local buffer
     ///
     /// must be called after creation
@@ -130,17 +259,86 @@ where
             Ok(())
         }
     }
+}
 
-    /// multiple writes will result in multiple copy operations
-    pub fn write(&self, recorder: &mut Recorder<false>) -> Result<WriteLock<T>> {
-        // store here and swap in the update will cancel out
-        // (unless it gets updated between the store and swap but it wont matter)
-        self.updates.store(true, Ordering::SeqCst);
-        self.update(recorder)?;
+impl<T> StagedBuffer<[T]>
+where
+    T: Send + Sync + 'static,
+{
+    /// update sends data from the stage to the device local buffer
+    ///
+    /// must be called after creation, copies only the ranges
+    /// marked with `mark_dirty` if the whole buffer is not dirty
+    pub fn update(&self, recorder: &mut Recorder<false>) -> Result<()> {
+        let ranges = coalesce(std::mem::take(&mut *self.dirty.lock()));
+        let full = ranges.first().map_or(false, |range| {
+            ranges.len() == 1 && range.start == 0 && range.end >= self.local.len()
+        });
 
-        // acquire the write lock
-        let lock = self.stage.write()?;
-        Ok(lock)
+        if self.updates.swap(false, Ordering::SeqCst) || full {
+            return self.copy_to_local(recorder);
+        }
+        for range in ranges {
+            self.copy_range(recorder, range)?;
+        }
+        Ok(())
+    }
+
+    /// copy only `len` elements from `offset` to the device local buffer
+    pub fn update_range(
+        &self,
+        recorder: &mut Recorder<false>,
+        offset: usize,
+        len: usize,
+    ) -> Result<()> {
+        let range = self.checked_range(offset, len)?;
+        self.copy_range(recorder, range)
+    }
+
+    /// mark `len` elements from `offset` as written to the stage buffer
+    ///
+    /// the next `update` copies the marked ranges, merged
+    /// where they overlap or touch, instead of the whole buffer
+    pub fn mark_dirty(&self, offset: usize, len: usize) -> Result<()> {
+        let range = self.checked_range(offset, len)?;
+        self.dirty.lock().push(range);
+        Ok(())
+    }
+
+    /// acquire the stage buffer to write `len` elements from `offset`
+    ///
+    /// unlike `write`, the copy is recorded by the next `update`
+    pub fn write_range(&self, offset: usize, len: usize) -> Result<WriteLock<[T]>> {
+        self.mark_dirty(offset, len)?;
+        Ok(self.stage.write()?)
+    }
+
+    fn checked_range(&self, offset: usize, len: usize) -> Result<Range<DeviceSize>> {
+        let range = offset as DeviceSize..(offset + len) as DeviceSize;
+        if range.end > self.local.len() {
+            bail!(
+                "Range {:?} is out of the buffer bounds 0..{}",
+                range,
+                self.local.len()
+            );
+        }
+        Ok(range)
+    }
+
+    fn copy_range(&self, recorder: &mut Recorder<false>, range: Range<DeviceSize>) -> Result<()> {
+        if range.is_empty() {
+            return Ok(());
+        }
+        recorder.record().copy_buffer_dimensions(
+            self.stage.clone(),
+            range.start,
+            self.local.clone(),
+            range.start,
+            range.end - range.start,
+        )?;
+        let bytes = (range.end - range.start) * mem::size_of::<T>() as DeviceSize;
+        self.uploads.upload(any::type_name::<Self>(), bytes);
+        Ok(())
     }
 }
 
@@ -153,6 +351,45 @@ where
     }
 }
 
+impl<T: ?Sized> StagedBuffer<T> {
+    /// is there an unfinished `replace_async`
+    pub fn replace_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// swap in the buffers from `replace_async` if the upload has finished
+    ///
+    /// returns true if the buffers were swapped
+    pub fn poll_replace(&mut self) -> bool {
+        match &self.pending {
+            Some(pending) if pending.handle.is_ready() => {}
+            _ => return false,
+        }
+
+        let pending = self.pending.take().unwrap();
+        self.stage = pending.stage;
+        self.local = pending.local;
+        self.allocation = pending.allocation;
+        // the new local buffer is already up to date
+        self.updates.store(false, Ordering::SeqCst);
+        self.dirty.lock().clear();
+        true
+    }
+}
+
+// sorted ranges with the overlapping and touching ones merged
+fn coalesce(mut ranges: Vec<Range<DeviceSize>>) -> Vec<Range<DeviceSize>> {
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<DeviceSize>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
 fn make_usage(usage: BufferUsage) -> (BufferUsage, BufferUsage) {
     (
         BufferUsage {
@@ -160,17 +397,43 @@ fn make_usage(usage: BufferUsage) -> (BufferUsage, BufferUsage) {
             ..usage
         },
         BufferUsage {
+            transfer_source: true,
             transfer_destination: true,
             ..usage
         },
     )
 }
 
+// stage and device local buffer sizes
+fn track<T>(
+    device: &Dev,
+    stage: &CpuAccessibleBuffer<T>,
+    local: &DeviceLocalBuffer<T>,
+) -> Allocation
+where
+    T: ?Sized + Send + Sync + 'static,
+{
+    device
+        .memory()
+        .track(memory::BUFFERS, stage.size() + local.size())
+}
+
+// device local buffers are shared between the graphics and transfer queues
+fn local_families(device: &Dev) -> Vec<QueueFamily<'_>> {
+    let graphics = device.queues.graphics.family();
+    let transfer = device.queues.transfer.family();
+    if graphics == transfer {
+        vec![graphics]
+    } else {
+        vec![graphics, transfer]
+    }
+}
+
 fn make_local<T>(device: &Dev, local_usage: BufferUsage) -> Result<Arc<DeviceLocalBuffer<T>>> {
     Ok(DeviceLocalBuffer::new(
         device.logical().clone(),
         local_usage,
-        [device.queues.graphics.family()].iter().cloned(),
+        local_families(device),
     )?)
 }
 
@@ -183,7 +446,7 @@ fn make_local_array<T>(
         device.logical().clone(),
         len,
         local_usage,
-        [device.queues.graphics.family()].iter().cloned(),
+        local_families(device),
     )?)
 }
 
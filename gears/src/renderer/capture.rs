@@ -0,0 +1,62 @@
+use anyhow::{bail, Result};
+use std::sync::Arc;
+use vulkano::{buffer::CpuAccessibleBuffer, format::Format};
+
+//
+
+/// Pixels of a captured frame
+///
+/// Pixels are tightly packed RGBA8 rows, top to bottom.
+#[derive(Debug, Clone)]
+pub struct FrameCapture {
+    pub extent: [u32; 2],
+    pub pixels: Vec<u8>,
+}
+
+pub(super) struct CaptureBuffer {
+    pub(super) buffer: Arc<CpuAccessibleBuffer<[u8]>>,
+    pub(super) extent: [u32; 2],
+}
+
+//
+
+impl FrameCapture {
+    /// Converts raw image bytes of `format` to RGBA8
+    pub fn from_raw(extent: [u32; 2], format: Format, mut pixels: Vec<u8>) -> Result<Self> {
+        match format {
+            Format::R8G8B8A8_UNORM
+            | Format::R8G8B8A8_SRGB
+            | Format::A8B8G8R8_UNORM_PACK32
+            | Format::A8B8G8R8_SRGB_PACK32 => {}
+            Format::B8G8R8A8_UNORM | Format::B8G8R8A8_SRGB => {
+                for pixel in pixels.chunks_exact_mut(4) {
+                    pixel.swap(0, 2);
+                }
+            }
+            other => bail!("Frame capture from format {:?} is not supported", other),
+        }
+
+        Ok(Self { extent, pixels })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.extent[0]
+    }
+
+    pub fn height(&self) -> u32 {
+        self.extent[1]
+    }
+
+    #[cfg(feature = "image")]
+    pub fn save_png<P: AsRef<std::path::Path>>(&self, path: P) -> Result<()> {
+        image::save_buffer_with_format(
+            path,
+            &self.pixels,
+            self.extent[0],
+            self.extent[1],
+            image::ColorType::Rgba8,
+            image::ImageFormat::Png,
+        )?;
+        Ok(())
+    }
+}
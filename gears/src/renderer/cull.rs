@@ -0,0 +1,144 @@
+use crate::report::{DrawReporter, DrawStats};
+use glam::{Mat4, Vec3, Vec4};
+
+//
+
+/// Axis aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+/// Draws that are tested against the view frustum
+/// before they are recorded
+///
+/// `T` is whatever records the draw, like a closure,
+/// an index range or an indirect command index.
+/// The list is meant to be refilled every frame.
+#[derive(Debug)]
+pub struct CulledDrawList<T> {
+    draws: Vec<(Aabb, u32, T)>,
+}
+
+//
+
+impl Aabb {
+    /// Contains nothing, not even the origin
+    pub const EMPTY: Self = Self {
+        min: Vec3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+        max: Vec3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+    };
+
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// `Aabb::EMPTY` if there are no points
+    pub fn from_points<I: IntoIterator<Item = Vec3>>(points: I) -> Self {
+        points.into_iter().fold(Self::EMPTY, |aabb, point| Self {
+            min: aabb.min.min(point),
+            max: aabb.max.max(point),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.cmpgt(self.max).any()
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Vec3::new(a.x, a.y, a.z),
+            Vec3::new(b.x, a.y, a.z),
+            Vec3::new(a.x, b.y, a.z),
+            Vec3::new(b.x, b.y, a.z),
+            Vec3::new(a.x, a.y, b.z),
+            Vec3::new(b.x, a.y, b.z),
+            Vec3::new(a.x, b.y, b.z),
+            Vec3::new(b.x, b.y, b.z),
+        ]
+    }
+
+    /// Smallest `Aabb` that contains this one transformed by `matrix`
+    pub fn transform(&self, matrix: Mat4) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+        Self::from_points(
+            self.corners()
+                .iter()
+                .map(|&corner| matrix.transform_point3(corner)),
+        )
+    }
+
+    /// Conservative test against `Camera::frustum_planes`
+    ///
+    /// Boxes near the frustum corners might pass
+    /// even if they are not visible.
+    pub fn intersects_frustum(&self, planes: &[Vec4; 6]) -> bool {
+        !self.is_empty()
+            && planes.iter().all(|plane| {
+                // corner furthest along the plane normal
+                let normal = plane.truncate();
+                let corner = Vec3::select(normal.cmpge(Vec3::ZERO), self.max, self.min);
+                normal.dot(corner) + plane.w >= 0.0
+            })
+    }
+}
+
+impl<T> Default for CulledDrawList<T> {
+    fn default() -> Self {
+        Self { draws: Vec::new() }
+    }
+}
+
+impl<T> CulledDrawList<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `triangles` is only used for the draw stats
+    pub fn push(&mut self, aabb: Aabb, triangles: u32, draw: T) {
+        self.draws.push((aabb, triangles, draw));
+    }
+
+    pub fn len(&self) -> usize {
+        self.draws.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.draws.clear();
+    }
+
+    /// Empties the list, adds the visible and culled
+    /// counts to `stats` and returns the visible draws
+    pub fn cull(
+        &mut self,
+        planes: &[Vec4; 6],
+        stats: &mut DrawReporter,
+    ) -> impl Iterator<Item = T> + '_ {
+        let mut counts = DrawStats::default();
+        self.draws.retain(|(aabb, triangles, _)| {
+            let visible = aabb.intersects_frustum(planes);
+            if visible {
+                counts.visible += 1;
+                counts.triangles += *triangles as u64;
+            } else {
+                counts.culled += 1;
+            }
+            visible
+        });
+        stats.add(counts);
+
+        self.draws.drain(..).map(|(_, _, draw)| draw)
+    }
+}
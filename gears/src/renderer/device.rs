@@ -1,12 +1,21 @@
-use super::queue::{QueueFamilies, Queues};
+use super::{
+    memory::{MemoryTracker, UploadTracker},
+    queue::{QueueFamilies, Queues},
+};
 use crate::{
-    context::{gpu::any::AnyGPU, Context, ContextError},
+    context::{
+        gpu::{any::AnyGPU, score::GPUScore},
+        Context, ContextError, ContextGPUPick,
+    },
     frame::Frame,
 };
 use std::sync::Arc;
-use vulkano::device::{
-    physical::{MemoryType, PhysicalDevice},
-    Device, DeviceCreateInfo, DeviceExtensions, Features,
+use vulkano::{
+    device::{
+        physical::{MemoryType, PhysicalDevice},
+        Device, DeviceCreateInfo, DeviceExtensions, Features,
+    },
+    format::Format,
 };
 
 //
@@ -18,6 +27,10 @@ pub struct RenderDevice {
     p_device: usize,
 
     pub queues: Queues,
+
+    depth_format: Format,
+    memory: MemoryTracker,
+    uploads: UploadTracker,
 }
 
 //
@@ -27,6 +40,15 @@ pub type Dev = Arc<RenderDevice>;
 //
 
 impl RenderDevice {
+    /// Depth attachment formats from the most preferred
+    pub const DEPTH_FORMATS: [Format; 5] = [
+        Format::D32_SFLOAT,
+        Format::D32_SFLOAT_S8_UINT,
+        Format::D24_UNORM_S8_UINT,
+        Format::D16_UNORM_S8_UINT,
+        Format::D16_UNORM,
+    ];
+
     pub fn logical(&self) -> &'_ Arc<Device> {
         &self.device
     }
@@ -35,6 +57,27 @@ impl RenderDevice {
         PhysicalDevice::from_index(&self.context.instance, self.p_device).unwrap()
     }
 
+    /// Format of every depth attachment created with this device,
+    /// the first supported one of the preferred format and `DEPTH_FORMATS`
+    pub fn depth_format(&self) -> Format {
+        self.depth_format
+    }
+
+    /// Buffer and image allocations made through gears
+    pub fn memory(&self) -> &MemoryTracker {
+        &self.memory
+    }
+
+    /// Bytes uploaded through gears in the current frame
+    pub fn uploads(&self) -> &UploadTracker {
+        &self.uploads
+    }
+
+    /// Features the device was created with, see `Context::with_features`
+    pub fn enabled_features(&self) -> &Features {
+        self.device.enabled_features()
+    }
+
     pub fn memory_types(&self) -> impl ExactSizeIterator<Item = MemoryType<'_>> {
         self.physical().memory_types()
     }
@@ -42,32 +85,149 @@ impl RenderDevice {
     fn device_extensions(p_device: PhysicalDevice) -> DeviceExtensions {
         DeviceExtensions {
             khr_swapchain: true,
+            // for u8 index buffers
+            ext_index_type_uint8: p_device.supported_extensions().ext_index_type_uint8,
             ..*p_device.required_extensions()
         }
     }
 
+    fn pick_depth_format(
+        p_device: PhysicalDevice,
+        preferred: Option<Format>,
+    ) -> Result<Format, ContextError> {
+        let mut candidates = preferred.into_iter().chain(Self::DEPTH_FORMATS);
+        let first = candidates.clone().next();
+        let format = candidates
+            .find(|&format| {
+                p_device
+                    .format_properties(format)
+                    .optimal_tiling_features
+                    .depth_stencil_attachment
+            })
+            .ok_or(ContextError::NoDepthFormat)?;
+
+        if let Some(first) = first.filter(|&first| first != format) {
+            log::info!(
+                "Depth format {:?} is not supported, falling back to {:?}",
+                first,
+                format
+            );
+        }
+        Ok(format)
+    }
+
     pub fn from_frame(frame: &Frame) -> Result<Dev, ContextError> {
-        let context = frame.context();
+        Self::from_frame_with_depth_format(frame, None)
+    }
+
+    /// Use `depth_format` for the depth attachments if it is supported
+    pub fn from_frame_with_depth_format(
+        frame: &Frame,
+        depth_format: Option<Format>,
+    ) -> Result<Dev, ContextError> {
         let gpu = frame.gpu();
         let p_device = gpu.device();
-        let surface = frame.surface();
+        let queue_families = QueueFamilies::new(&frame.surface(), p_device)?
+            .expect("Selected physical device was not suitable");
+        let enabled_extensions = Self::device_extensions(p_device);
 
-        // device extensions
+        Self::new(
+            frame.context(),
+            p_device,
+            queue_families,
+            enabled_extensions,
+            depth_format,
+        )
+    }
 
-        let enabled_extensions = Self::device_extensions(p_device);
+    /// Device without a window surface, see `HeadlessRenderer`
+    ///
+    /// Uses the GPU of `ContextGPUPick::Index` if it can render, the
+    /// best scoring one otherwise, preferring the ones with the required
+    /// `Context::features`. Presenting goes to the graphics queue.
+    pub fn headless(context: &Context, depth_format: Option<Format>) -> Result<Dev, ContextError> {
+        let candidates: Vec<_> = PhysicalDevice::enumerate(&context.instance)
+            .filter(|&p_device| QueueFamilies::headless(p_device).is_some())
+            .collect();
+        let picked = match context.pick {
+            ContextGPUPick::Index(index) => candidates
+                .iter()
+                .copied()
+                .find(|p_device| p_device.index() == index),
+            _ => None,
+        };
+        let p_device = picked
+            .or_else(|| {
+                candidates
+                    .iter()
+                    .copied()
+                    // the GPUs with the required features first
+                    .max_by_key(|&p_device| {
+                        let supported = p_device.supported_features();
+                        (
+                            supported.is_superset_of(&context.features),
+                            GPUScore::new(p_device),
+                        )
+                    })
+            })
+            .ok_or(ContextError::NoSuitableGPUs)?;
+
+        let queue_families =
+            QueueFamilies::headless(p_device).ok_or(ContextError::NoSuitableGPUs)?;
+        let enabled_extensions = DeviceExtensions {
+            khr_swapchain: false,
+            ..Self::device_extensions(p_device)
+        };
+
+        Self::new(
+            context.clone(),
+            p_device,
+            queue_families,
+            enabled_extensions,
+            depth_format,
+        )
+    }
+
+    fn new(
+        context: Context,
+        p_device: PhysicalDevice,
+        queue_families: QueueFamilies,
+        enabled_extensions: DeviceExtensions,
+        depth_format: Option<Format>,
+    ) -> Result<Dev, ContextError> {
+        // depth format
+
+        let depth_format = Self::pick_depth_format(p_device, depth_format)?;
+        log::debug!("Depth format: {:?}", depth_format);
 
         // queue infos
 
-        let queue_families = QueueFamilies::new(&surface, p_device)?
-            .expect("Selected physical device was not suitable");
         let queue_create_infos = queue_families.get();
 
         // features
 
-        let enabled_features = Features {
+        let supported_features = p_device.supported_features();
+        if !supported_features.is_superset_of(&context.features) {
+            return Err(ContextError::MissingFeatures(
+                context.features.difference(supported_features),
+            ));
+        }
+
+        // the ones gears uses where supported
+        let gears_features = Features {
             geometry_shader: true,
-            ..Default::default()
+            index_type_uint8: enabled_extensions.ext_index_type_uint8
+                && supported_features.index_type_uint8,
+            // for the optional PipelineStatsQuery
+            pipeline_statistics_query: supported_features.pipeline_statistics_query,
+            // for DrawIndirectBuffers with more than one command
+            multi_draw_indirect: supported_features.multi_draw_indirect,
+            draw_indirect_first_instance: supported_features.draw_indirect_first_instance,
+            ..Features::none()
         };
+        // a requested `index_type_uint8` is supported, so is its extension
+        // and `device_extensions` has enabled it
+        let enabled_features = context.features.union(&gears_features);
 
         // device
 
@@ -91,6 +251,10 @@ impl RenderDevice {
             p_device: p_device.index(),
 
             queues,
+
+            depth_format,
+            memory: MemoryTracker::new(),
+            uploads: UploadTracker::new(),
         }))
     }
 }
@@ -0,0 +1,215 @@
+use super::{
+    simple_renderer::{ExtraAccess, ExtraAttachment, RenderPassConfig, SubpassConfig},
+    Recorder,
+};
+use anyhow::{anyhow, bail, Result};
+
+//
+
+/// The swapchain image, written by every pass
+pub const SWAPCHAIN: &str = "swapchain";
+
+/// The shared depth buffer, passes that use it get depth testing
+pub const DEPTH: &str = "depth";
+
+/// Passes of the main render pass and the attachments they use
+///
+/// The graph orders the passes so that every attachment is written
+/// before it is read, and turns them into the subpasses of a
+/// `RenderPassConfig`. The subpass dependencies of the render pass do
+/// the layout transitions between the passes, and `record` begins the
+/// render pass and moves to the next subpass between the passes.
+///
+/// Attachments are `SWAPCHAIN`, `DEPTH` and the names of the
+/// extra attachments. A pass uses the extra attachments together, see
+/// `ExtraAccess`, so it can write or read them but not both.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenderGraph {
+    passes: Vec<Pass>,
+    extra_attachments: Vec<ExtraAttachment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pass {
+    name: &'static str,
+    reads: Vec<&'static str>,
+    writes: Vec<&'static str>,
+}
+
+//
+
+impl RenderGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Offscreen color attachment the passes can write and read by name
+    pub fn with_extra_attachment(mut self, extra_attachment: ExtraAttachment) -> Self {
+        self.extra_attachments.push(extra_attachment);
+        self
+    }
+
+    /// Declare a pass, passes that do not depend on
+    /// each other keep the order they were added in
+    pub fn add_pass(
+        &mut self,
+        name: &'static str,
+        reads: &[&'static str],
+        writes: &[&'static str],
+    ) -> Result<()> {
+        if self.passes.iter().any(|pass| pass.name == name) {
+            bail!("Render graph already has a pass {name:?}");
+        }
+        for &attachment in reads.iter().chain(writes) {
+            if !self.is_attachment(attachment) {
+                bail!("Pass {name:?} uses an unknown attachment {attachment:?}");
+            }
+        }
+        if let Some(attachment) = reads.iter().find(|read| writes.contains(read)) {
+            bail!("Pass {name:?} reads {attachment:?} which it also writes");
+        }
+        if reads.contains(&SWAPCHAIN) {
+            bail!("Pass {name:?} reads the swapchain image, which is not an input attachment");
+        }
+        let extra_reads = reads.iter().any(|&read| self.is_extra(read));
+        let extra_writes = writes.iter().any(|&write| self.is_extra(write));
+        if extra_reads && extra_writes {
+            bail!("Pass {name:?} both reads and writes the extra attachments");
+        }
+
+        self.passes.push(Pass {
+            name,
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+        });
+        Ok(())
+    }
+
+    /// Pass names in the order they are recorded
+    pub fn order(&self) -> Result<Vec<&'static str>> {
+        Ok(self
+            .sorted()?
+            .into_iter()
+            .map(|index| self.passes[index].name)
+            .collect())
+    }
+
+    /// Subpass index of the pass `name`, for `GPipelineBuilder::with_subpass`
+    pub fn subpass(&self, name: &str) -> Result<u32> {
+        self.order()?
+            .iter()
+            .position(|&pass| pass == name)
+            .map(|index| index as u32)
+            .ok_or_else(|| anyhow!("Render graph has no pass {name:?}"))
+    }
+
+    /// Render pass config with one subpass per pass, in order
+    pub fn render_pass_config(&self) -> Result<RenderPassConfig> {
+        if self.passes.is_empty() {
+            bail!("Render graph has no passes");
+        }
+
+        let mut config = RenderPassConfig::new();
+        for &extra_attachment in self.extra_attachments.iter() {
+            config = config.with_extra_attachment(extra_attachment);
+        }
+        for index in self.sorted()? {
+            let pass = &self.passes[index];
+            let uses =
+                |attachment| pass.reads.contains(&attachment) || pass.writes.contains(&attachment);
+            let extra = if pass.writes.iter().any(|&write| self.is_extra(write)) {
+                ExtraAccess::Write
+            } else if pass.reads.iter().any(|&read| self.is_extra(read)) {
+                ExtraAccess::Read
+            } else {
+                ExtraAccess::None
+            };
+            let subpass = if uses(DEPTH) {
+                SubpassConfig::depth()
+            } else {
+                SubpassConfig::no_depth()
+            };
+            config = config.with_subpass(subpass.with_extra(extra));
+        }
+        Ok(config)
+    }
+
+    /// Record every pass in order with `f(pass name, recorder)`
+    ///
+    /// The renderer has to be built with `render_pass_config`.
+    pub fn record<F>(&self, recorder: Recorder<false>, mut f: F) -> Result<Recorder<false>>
+    where
+        F: FnMut(&'static str, &mut Recorder<true>) -> Result<()>,
+    {
+        let order = self.order()?;
+        let mut recorder = recorder.begin_render_pass();
+        for (i, name) in order.into_iter().enumerate() {
+            if i != 0 {
+                recorder.next_subpass()?;
+            }
+            f(name, &mut recorder).map_err(|err| anyhow!("Pass {name:?} failed: {err}"))?;
+        }
+        Ok(recorder.end_render_pass())
+    }
+
+    //
+
+    fn is_extra(&self, attachment: &str) -> bool {
+        self.extra_attachments
+            .iter()
+            .any(|extra| extra.name == attachment)
+    }
+
+    fn is_attachment(&self, attachment: &str) -> bool {
+        attachment == SWAPCHAIN || attachment == DEPTH || self.is_extra(attachment)
+    }
+
+    /// Pass indices with the writers of an attachment before its readers
+    fn sorted(&self) -> Result<Vec<usize>> {
+        let count = self.passes.len();
+
+        // reads after writes, and writes of the same attachment in the
+        // order they were added unless one pass reads from the other
+        let reads_from = |a: &Pass, b: &Pass| a.reads.iter().any(|read| b.writes.contains(read));
+        let mut after = vec![Vec::new(); count];
+        for (i, pass) in self.passes.iter().enumerate() {
+            for (j, other) in self.passes.iter().enumerate() {
+                let ordered = j < i
+                    && !reads_from(other, pass)
+                    && pass.writes.iter().any(|write| other.writes.contains(write));
+                if i != j && (reads_from(pass, other) || ordered) {
+                    after[i].push(j);
+                }
+            }
+        }
+
+        for (i, pass) in self.passes.iter().enumerate() {
+            for &read in pass.reads.iter().filter(|&&read| read != DEPTH) {
+                if !after[i]
+                    .iter()
+                    .any(|&j| self.passes[j].writes.contains(&read))
+                {
+                    bail!("Pass {:?} reads {read:?} which no pass writes", pass.name);
+                }
+            }
+        }
+
+        // the first pass whose dependencies are done, every time
+        let mut order = Vec::with_capacity(count);
+        let mut done = vec![false; count];
+        while order.len() < count {
+            let next = (0..count)
+                .find(|&i| !done[i] && after[i].iter().all(|&j| done[j]))
+                .ok_or_else(|| {
+                    let names: Vec<_> = (0..count)
+                        .filter(|&i| !done[i])
+                        .map(|i| self.passes[i].name)
+                        .collect();
+                    anyhow!("Render graph has a dependency cycle between {names:?}")
+                })?;
+            done[next] = true;
+            order.push(next);
+        }
+        Ok(order)
+    }
+}
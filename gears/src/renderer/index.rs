@@ -0,0 +1,292 @@
+use super::{
+    buffer::StagedBuffer,
+    device::Dev,
+    upload::{UploadHandle, Uploader},
+    Recorder,
+};
+use anyhow::{anyhow, Result};
+use vulkano::{
+    buffer::{BufferUsage, TypedBufferAccess},
+    command_buffer::AutoCommandBufferBuilder,
+    pipeline::graphics::input_assembly::Index as VkIndex,
+};
+
+//
+
+/// Vertex index type usable in index buffers
+pub trait Index: VkIndex + Copy + Send + Sync + 'static {
+    /// Primitive restart value,
+    /// vertex indices have to be smaller than this
+    const MAX: u32;
+
+    fn from_u32(index: u32) -> Self;
+
+    /// Can `device` bind index buffers of this type
+    fn supported(_: &Dev) -> bool {
+        true
+    }
+}
+
+/// Indices in the smallest type that fits,
+/// see `compact_indices`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexData {
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+/// Staged index buffer of any `Index` type
+pub struct IndexBuffer {
+    buffer: Buffers,
+    pending: Option<(Buffers, UploadHandle)>,
+}
+
+/// Buffers that `Recorder::bind_index_buffer` accepts,
+/// also usable with secondary command buffer builders
+pub trait BindIndexBuffer {
+    fn bind_index_buffer<'a, L, P>(
+        &self,
+        builder: &'a mut AutoCommandBufferBuilder<L, P>,
+    ) -> &'a mut AutoCommandBufferBuilder<L, P>;
+
+    fn index_count(&self) -> u32;
+}
+
+enum Buffers {
+    U8(StagedBuffer<[u8]>),
+    U16(StagedBuffer<[u16]>),
+    U32(StagedBuffer<[u32]>),
+}
+
+//
+
+impl Index for u8 {
+    const MAX: u32 = u8::MAX as u32;
+
+    fn from_u32(index: u32) -> Self {
+        index as u8
+    }
+
+    /// 8 bit indices need `VK_EXT_index_type_uint8`
+    fn supported(device: &Dev) -> bool {
+        device.logical().enabled_features().index_type_uint8
+    }
+}
+
+impl Index for u16 {
+    const MAX: u32 = u16::MAX as u32;
+
+    fn from_u32(index: u32) -> Self {
+        index as u16
+    }
+}
+
+impl Index for u32 {
+    const MAX: u32 = u32::MAX;
+
+    fn from_u32(index: u32) -> Self {
+        index
+    }
+}
+
+/// Pick u16 or u32 indices, whichever is smaller and still fits
+///
+/// u8 indices need a device feature, use
+/// `compact_indices_for` to allow them.
+pub fn compact_indices(indices: Vec<u32>) -> IndexData {
+    compact(indices, false)
+}
+
+/// Same as `compact_indices` but also picks
+/// u8 indices if `device` supports them
+pub fn compact_indices_for(device: &Dev, indices: Vec<u32>) -> IndexData {
+    compact(indices, u8::supported(device))
+}
+
+fn compact(indices: Vec<u32>, allow_u8: bool) -> IndexData {
+    let max = indices.iter().copied().max().unwrap_or(0);
+    if allow_u8 && max < u8::MAX as u32 {
+        IndexData::U8(convert(indices))
+    } else if max < u16::MAX as u32 {
+        IndexData::U16(convert(indices))
+    } else {
+        IndexData::U32(indices)
+    }
+}
+
+fn convert<I: Index>(indices: Vec<u32>) -> Vec<I> {
+    indices.into_iter().map(I::from_u32).collect()
+}
+
+impl IndexData {
+    pub fn len(&self) -> usize {
+        match self {
+            IndexData::U8(i) => i.len(),
+            IndexData::U16(i) => i.len(),
+            IndexData::U32(i) => i.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl From<Vec<u32>> for IndexData {
+    fn from(indices: Vec<u32>) -> Self {
+        compact_indices(indices)
+    }
+}
+
+impl Buffers {
+    fn new(device: &Dev, data: IndexData) -> Result<Self> {
+        let usage = BufferUsage::index_buffer();
+        Ok(match data {
+            IndexData::U8(i) => Buffers::U8(from_vec(device, usage, i)?),
+            IndexData::U16(i) => Buffers::U16(from_vec(device, usage, i)?),
+            IndexData::U32(i) => Buffers::U32(from_vec(device, usage, i)?),
+        })
+    }
+
+    fn update(&self, recorder: &mut Recorder<false>) -> Result<()> {
+        match self {
+            Buffers::U8(b) => b.update(recorder),
+            Buffers::U16(b) => b.update(recorder),
+            Buffers::U32(b) => b.update(recorder),
+        }
+    }
+
+    fn upload(&self, uploader: &mut Uploader) -> Result<()> {
+        match self {
+            Buffers::U8(b) => b.upload(uploader),
+            Buffers::U16(b) => b.upload(uploader),
+            Buffers::U32(b) => b.upload(uploader),
+        }
+    }
+
+    fn poll_replace(&mut self) -> bool {
+        match self {
+            Buffers::U8(b) => b.poll_replace(),
+            Buffers::U16(b) => b.poll_replace(),
+            Buffers::U32(b) => b.poll_replace(),
+        }
+    }
+
+    fn replace_pending(&self) -> bool {
+        match self {
+            Buffers::U8(b) => b.replace_pending(),
+            Buffers::U16(b) => b.replace_pending(),
+            Buffers::U32(b) => b.replace_pending(),
+        }
+    }
+}
+
+fn from_vec<I: Index>(
+    device: &Dev,
+    usage: BufferUsage,
+    indices: Vec<I>,
+) -> Result<StagedBuffer<[I]>> {
+    if !I::supported(device) {
+        return Err(anyhow!(
+            "Index type {:?} is not supported by the device",
+            I::ty()
+        ));
+    }
+    StagedBuffer::from_iter(device, usage, indices.into_iter())
+}
+
+impl IndexBuffer {
+    pub fn new(device: &Dev, data: IndexData) -> Result<Self> {
+        Ok(Self {
+            buffer: Buffers::new(device, data)?,
+            pending: None,
+        })
+    }
+
+    /// Same as `StagedBuffer::update`
+    pub fn update(&self, recorder: &mut Recorder<false>) -> Result<()> {
+        self.buffer.update(recorder)
+    }
+
+    /// Same as `StagedBuffer::replace_async`
+    ///
+    /// If the index type changes, a new buffer is
+    /// uploaded and swapped in by `poll_replace` instead.
+    pub fn replace_async(&mut self, uploader: &mut Uploader, data: IndexData) -> Result<()> {
+        self.pending = None;
+        match (&mut self.buffer, data) {
+            (Buffers::U8(b), IndexData::U8(i)) => b.replace_async(uploader, i.into_iter()),
+            (Buffers::U16(b), IndexData::U16(i)) => b.replace_async(uploader, i.into_iter()),
+            (Buffers::U32(b), IndexData::U32(i)) => b.replace_async(uploader, i.into_iter()),
+            (_, data) => {
+                let buffer = Buffers::new(uploader.device(), data)?;
+                buffer.upload(uploader)?;
+                self.pending = Some((buffer, uploader.handle()));
+                Ok(())
+            }
+        }
+    }
+
+    /// Same as `StagedBuffer::poll_replace`
+    pub fn poll_replace(&mut self) -> bool {
+        match self.pending.take() {
+            Some((buffer, handle)) if handle.is_ready() => {
+                self.buffer = buffer;
+                true
+            }
+            Some(pending) => {
+                self.pending = Some(pending);
+                false
+            }
+            None => self.buffer.poll_replace(),
+        }
+    }
+
+    /// Same as `StagedBuffer::replace_pending`
+    pub fn replace_pending(&self) -> bool {
+        self.pending.is_some() || self.buffer.replace_pending()
+    }
+
+    pub fn len(&self) -> usize {
+        self.index_count() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<I: Index> BindIndexBuffer for StagedBuffer<[I]> {
+    fn bind_index_buffer<'a, L, P>(
+        &self,
+        builder: &'a mut AutoCommandBufferBuilder<L, P>,
+    ) -> &'a mut AutoCommandBufferBuilder<L, P> {
+        builder.bind_index_buffer(self.local.clone())
+    }
+
+    fn index_count(&self) -> u32 {
+        self.local.len() as u32
+    }
+}
+
+impl BindIndexBuffer for IndexBuffer {
+    fn bind_index_buffer<'a, L, P>(
+        &self,
+        builder: &'a mut AutoCommandBufferBuilder<L, P>,
+    ) -> &'a mut AutoCommandBufferBuilder<L, P> {
+        match &self.buffer {
+            Buffers::U8(b) => b.bind_index_buffer(builder),
+            Buffers::U16(b) => b.bind_index_buffer(builder),
+            Buffers::U32(b) => b.bind_index_buffer(builder),
+        }
+    }
+
+    fn index_count(&self) -> u32 {
+        match &self.buffer {
+            Buffers::U8(b) => b.index_count(),
+            Buffers::U16(b) => b.index_count(),
+            Buffers::U32(b) => b.index_count(),
+        }
+    }
+}
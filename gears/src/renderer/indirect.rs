@@ -0,0 +1,98 @@
+use super::{buffer::StagedBuffer, device::Dev, Recorder};
+use anyhow::Result;
+use std::sync::Arc;
+use vulkano::{
+    buffer::{cpu_access::WriteLock, BufferUsage, DeviceLocalBuffer},
+    command_buffer::AutoCommandBufferBuilder,
+};
+
+pub use vulkano::command_buffer::{DrawIndexedIndirectCommand, DrawIndirectCommand};
+
+//
+
+/// Staged buffer of indirect draw commands
+///
+/// The commands are written by the cpu with `write` or by compute
+/// shaders through `local`, which is also usable as a storage buffer,
+/// e.g. to cull on the gpu and only issue the visible draws.
+///
+/// More than one command per draw needs the `multi_draw_indirect`
+/// device feature and a nonzero `first_instance` needs
+/// `draw_indirect_first_instance`, both are enabled if supported.
+pub struct DrawIndirectBuffer<C = DrawIndirectCommand> {
+    buffer: StagedBuffer<[C]>,
+}
+
+/// `DrawIndirectBuffer` for `draw_indexed_indirect`
+pub type DrawIndexedIndirectBuffer = DrawIndirectBuffer<DrawIndexedIndirectCommand>;
+
+//
+
+impl<C> DrawIndirectBuffer<C>
+where
+    C: Default + Copy + Send + Sync + 'static,
+{
+    pub fn new<I>(device: &Dev, commands: I) -> Result<Self>
+    where
+        I: ExactSizeIterator<Item = C>,
+    {
+        let usage = BufferUsage {
+            indirect_buffer: true,
+            storage_buffer: true,
+            ..BufferUsage::none()
+        };
+        Ok(Self {
+            buffer: StagedBuffer::from_iter(device, usage, commands)?,
+        })
+    }
+
+    /// `count` zeroed commands, e.g. to be filled by a compute shader
+    pub fn with_len(device: &Dev, count: usize) -> Result<Self> {
+        Self::new(device, (0..count).map(|_| C::default()))
+    }
+
+    /// Same as `StagedBuffer::update`
+    pub fn update(&self, recorder: &mut Recorder<false>) -> Result<()> {
+        self.buffer.update(recorder)
+    }
+
+    /// Same as `StagedBuffer::write`
+    pub fn write(&self, recorder: &mut Recorder<false>) -> Result<WriteLock<[C]>> {
+        self.buffer.write(recorder)
+    }
+
+    /// The device local buffer the draws read the commands from
+    pub fn local(&self) -> &Arc<DeviceLocalBuffer<[C]>> {
+        &self.buffer.local
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.local.len() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl DrawIndirectBuffer<DrawIndirectCommand> {
+    /// Record a draw of every command, also usable
+    /// with secondary command buffer builders
+    pub fn draw<'a, L, P>(
+        &self,
+        builder: &'a mut AutoCommandBufferBuilder<L, P>,
+    ) -> Result<&'a mut AutoCommandBufferBuilder<L, P>> {
+        Ok(builder.draw_indirect(self.buffer.local.clone())?)
+    }
+}
+
+impl DrawIndirectBuffer<DrawIndexedIndirectCommand> {
+    /// Same as `DrawIndirectBuffer::draw` but with
+    /// the bound index buffer
+    pub fn draw_indexed<'a, L, P>(
+        &self,
+        builder: &'a mut AutoCommandBufferBuilder<L, P>,
+    ) -> Result<&'a mut AutoCommandBufferBuilder<L, P>> {
+        Ok(builder.draw_indexed_indirect(self.buffer.local.clone())?)
+    }
+}
@@ -0,0 +1,89 @@
+use super::{
+    device::Dev,
+    memory::{self, Allocation},
+    simple_renderer::Renderer,
+};
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+use vulkano::buffer::{
+    cpu_access::WriteLock, BufferAccess, BufferUsage, CpuAccessibleBuffer, TypedBufferAccess,
+};
+
+//
+
+/// Host visible buffer per frame in flight, written in place
+///
+/// For data that changes every frame, like instances or streamed
+/// vertices. The memory stays mapped for the lifetime of the buffer
+/// and `write` gives the `&mut [T]` of it, without a staging copy
+/// or a new allocation per frame.
+///
+/// Synchronization: the buffer of a frame in flight may still be read
+/// by the gpu until `Renderer::begin_frame` returns that frame in flight
+/// again, which waits for its previous submit. Only write the buffer of
+/// the current `FrameData::frame_in_flight`, `write` fails if the gpu
+/// still holds it. Non-coherent memory is flushed when the lock is
+/// dropped, so drop it before the frame is submitted.
+pub struct MappedBuffer<T> {
+    buffers: Vec<Arc<CpuAccessibleBuffer<[T]>>>,
+    _allocation: Allocation,
+}
+
+//
+
+impl<T> MappedBuffer<T>
+where
+    T: Default + Copy + Send + Sync + 'static,
+{
+    /// `len` default elements per frame in flight of `renderer`
+    pub fn new(renderer: &Renderer, usage: BufferUsage, len: usize) -> Result<Self> {
+        Self::new_with_frames(&renderer.device, renderer.frame_count(), usage, len)
+    }
+
+    /// `len` default elements for each of `frames` frames in flight
+    pub fn new_with_frames(
+        device: &Dev,
+        frames: usize,
+        usage: BufferUsage,
+        len: usize,
+    ) -> Result<Self> {
+        let buffers = (0..frames.max(1))
+            .map(|_| {
+                CpuAccessibleBuffer::from_iter(
+                    device.logical().clone(),
+                    usage,
+                    false,
+                    (0..len).map(|_| T::default()),
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let size = buffers.iter().map(|buffer| buffer.size()).sum();
+        let _allocation = device.memory().track(memory::BUFFERS, size);
+
+        Ok(Self {
+            buffers,
+            _allocation,
+        })
+    }
+
+    /// The mapped elements of `frame_in_flight`
+    pub fn write(&self, frame_in_flight: usize) -> Result<WriteLock<[T]>> {
+        Ok(self.buffer(frame_in_flight)?.write()?)
+    }
+
+    /// The buffer of `frame_in_flight`, to bind it for the draws of that frame
+    pub fn buffer(&self, frame_in_flight: usize) -> Result<&Arc<CpuAccessibleBuffer<[T]>>> {
+        self.buffers
+            .get(frame_in_flight)
+            .ok_or_else(|| anyhow!("MappedBuffer has no frame in flight {frame_in_flight}"))
+    }
+
+    /// Elements per frame in flight
+    pub fn len(&self) -> usize {
+        self.buffers[0].len() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -0,0 +1,217 @@
+use super::{
+    mesh::{Mesh, MeshIndices},
+    Recorder,
+};
+use anyhow::Result;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    mem,
+    sync::Arc,
+};
+use vulkano::{
+    command_buffer::AutoCommandBufferBuilder,
+    descriptor_set::PersistentDescriptorSet,
+    pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint},
+};
+
+//
+
+/// Pipeline pointer and a hash of the descriptor set pointers
+pub type SortKey = (usize, u64);
+
+/// Pipeline and the descriptor sets it is drawn with
+///
+/// `bind` binds both in one call. Materials sharing the pipeline
+/// or the sets have sort keys next to each other, see `DrawQueue`.
+#[derive(Clone)]
+pub struct Material {
+    pipeline: Arc<GraphicsPipeline>,
+    sets: Vec<Arc<PersistentDescriptorSet>>,
+    key: SortKey,
+}
+
+/// Anything a `DrawQueue` can draw after binding a `Material`
+pub trait Drawable {
+    fn draw(&self, recorder: &mut Recorder<true>) -> Result<()>;
+}
+
+/// Draws of a frame, sorted by material when flushed
+///
+/// Draws with the same pipeline or descriptor sets as the previous
+/// draw skip binding them again. `P` is the push constant data
+/// of each draw, nothing is pushed for `()`.
+pub struct DrawQueue<'a, P = ()> {
+    draws: Vec<QueuedDraw<'a, P>>,
+}
+
+/// Binds and draws recorded by `DrawQueue::flush`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct QueueStats {
+    pub draws: u32,
+    pub pipeline_binds: u32,
+    pub set_binds: u32,
+}
+
+struct QueuedDraw<'a, P> {
+    material: &'a Material,
+    mesh: &'a dyn Drawable,
+    push_data: P,
+}
+
+//
+
+impl Material {
+    /// `sets` are bound to the set numbers `0..`
+    pub fn new(pipeline: Arc<GraphicsPipeline>, sets: Vec<Arc<PersistentDescriptorSet>>) -> Self {
+        let key = Self::key(&pipeline, &sets);
+        Self {
+            pipeline,
+            sets,
+            key,
+        }
+    }
+
+    /// Replace the descriptor sets, e.g. with the ones of the current frame in flight
+    pub fn set_descriptor_sets(&mut self, sets: Vec<Arc<PersistentDescriptorSet>>) {
+        self.key = Self::key(&self.pipeline, &sets);
+        self.sets = sets;
+    }
+
+    pub fn pipeline(&self) -> &Arc<GraphicsPipeline> {
+        &self.pipeline
+    }
+
+    pub fn descriptor_sets(&self) -> &[Arc<PersistentDescriptorSet>] {
+        &self.sets
+    }
+
+    pub fn sort_key(&self) -> SortKey {
+        self.key
+    }
+
+    /// Bind the pipeline and the descriptor sets
+    pub fn bind(&self, recorder: &mut Recorder<true>) {
+        self.record(recorder.record());
+    }
+
+    /// Same as `bind`, also usable with secondary command buffer builders
+    pub fn record<L, P>(&self, builder: &mut AutoCommandBufferBuilder<L, P>) {
+        builder.bind_pipeline_graphics(self.pipeline.clone());
+        self.record_sets(builder);
+    }
+
+    //
+
+    fn record_sets<L, P>(&self, builder: &mut AutoCommandBufferBuilder<L, P>) {
+        if !self.sets.is_empty() {
+            builder.bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.pipeline.layout().clone(),
+                0,
+                self.sets.clone(),
+            );
+        }
+    }
+
+    fn same_pipeline(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.pipeline, &other.pipeline)
+    }
+
+    fn same_sets(&self, other: &Self) -> bool {
+        self.sets.len() == other.sets.len()
+            && self
+                .sets
+                .iter()
+                .zip(other.sets.iter())
+                .all(|(a, b)| Arc::ptr_eq(a, b))
+    }
+
+    fn key(pipeline: &Arc<GraphicsPipeline>, sets: &[Arc<PersistentDescriptorSet>]) -> SortKey {
+        let mut hasher = DefaultHasher::new();
+        for set in sets {
+            Arc::as_ptr(set).hash(&mut hasher);
+        }
+        (Arc::as_ptr(pipeline) as usize, hasher.finish())
+    }
+}
+
+impl<V, I> Drawable for Mesh<V, I>
+where
+    V: Send + Sync + 'static,
+    I: MeshIndices,
+{
+    fn draw(&self, recorder: &mut Recorder<true>) -> Result<()> {
+        Mesh::draw(self, recorder)
+    }
+}
+
+impl<'a, P> Default for DrawQueue<'a, P> {
+    fn default() -> Self {
+        Self { draws: Vec::new() }
+    }
+}
+
+impl<'a, P> DrawQueue<'a, P>
+where
+    P: Copy + Send + Sync + 'static,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Draw `mesh` with `material` on the next `flush`
+    pub fn submit(&mut self, material: &'a Material, mesh: &'a dyn Drawable, push_data: P) {
+        self.draws.push(QueuedDraw {
+            material,
+            mesh,
+            push_data,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.draws.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    /// Record the draws sorted by material and empty the queue
+    ///
+    /// Draws of the same material keep their submit order.
+    pub fn flush(&mut self, recorder: &mut Recorder<true>) -> Result<QueueStats> {
+        self.draws.sort_by_key(|draw| draw.material.sort_key());
+
+        let mut stats = QueueStats::default();
+        let mut bound: Option<&Material> = None;
+        for draw in self.draws.drain(..) {
+            let material = draw.material;
+            // binding another pipeline may disturb the bound sets
+            let same_pipeline = bound.map_or(false, |bound| bound.same_pipeline(material));
+            let same_sets = same_pipeline && bound.map_or(false, |bound| bound.same_sets(material));
+            if !same_pipeline {
+                recorder
+                    .record()
+                    .bind_pipeline_graphics(material.pipeline.clone());
+                stats.pipeline_binds += 1;
+            }
+            if !same_sets && !material.sets.is_empty() {
+                material.record_sets(recorder.record());
+                stats.set_binds += 1;
+            }
+            bound = Some(material);
+
+            if mem::size_of::<P>() != 0 {
+                recorder.record().push_constants(
+                    material.pipeline.layout().clone(),
+                    0,
+                    draw.push_data,
+                );
+            }
+            draw.mesh.draw(recorder)?;
+            stats.draws += 1;
+        }
+        Ok(stats)
+    }
+}
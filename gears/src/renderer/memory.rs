@@ -0,0 +1,215 @@
+use parking_lot::Mutex;
+use std::{collections::HashMap, fmt, sync::Arc};
+
+//
+
+/// Category of `StagedBuffer`s and other gears buffers
+pub const BUFFERS: &str = "buffers";
+
+/// Category of `Texture`s, font atlases and render target images
+pub const IMAGES: &str = "images";
+
+/// Buffer and image memory allocated through gears
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// Bytes
+    pub total_allocated: u64,
+    /// Bytes per category, see `BUFFERS` and `IMAGES`
+    pub by_category: HashMap<&'static str, u64>,
+    pub allocation_count: usize,
+}
+
+/// Records the buffer and image allocations of a `RenderDevice`
+///
+/// Only the allocations of gears types (`StagedBuffer`, `Texture`,
+/// render targets, ...) are counted, vulkano buffer pools and
+/// swapchain images are not. Sizes are the requested sizes
+/// without alignment or driver overhead.
+#[derive(Clone, Default)]
+pub struct MemoryTracker {
+    inner: Arc<Mutex<TrackerInner>>,
+}
+
+/// Registered allocation, deregistered when dropped
+pub struct Allocation {
+    tracker: MemoryTracker,
+    category: &'static str,
+    size: u64,
+}
+
+/// Counts the bytes copied from the host to the gpu in the current frame
+///
+/// `StagedBuffer` copies and the uniform, sprite and text
+/// buffer pools are counted, `MappedBuffer` and other writes
+/// to host visible memory are not. The count is reset
+/// by `Renderer::begin_frame`.
+#[derive(Clone, Default)]
+pub struct UploadTracker {
+    inner: Arc<Mutex<UploadInner>>,
+}
+
+#[derive(Default)]
+struct TrackerInner {
+    stats: MemoryStats,
+    watermark: Option<u64>,
+    above_watermark: bool,
+}
+
+//
+
+impl MemoryTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `size` bytes under `category` until the `Allocation` is dropped
+    pub fn track(&self, category: &'static str, size: u64) -> Allocation {
+        let mut inner = self.inner.lock();
+        inner.stats.total_allocated += size;
+        inner.stats.allocation_count += 1;
+        *inner.stats.by_category.entry(category).or_default() += size;
+        inner.check_watermark();
+
+        Allocation {
+            tracker: self.clone(),
+            category,
+            size,
+        }
+    }
+
+    pub fn stats(&self) -> MemoryStats {
+        self.inner.lock().stats.clone()
+    }
+
+    /// Log a warning when the total allocation grows over `watermark` bytes
+    ///
+    /// Warns once per crossing, no watermark by default.
+    pub fn set_watermark(&self, watermark: Option<u64>) {
+        let mut inner = self.inner.lock();
+        inner.watermark = watermark;
+        inner.above_watermark = false;
+        inner.check_watermark();
+    }
+
+    pub fn watermark(&self) -> Option<u64> {
+        self.inner.lock().watermark
+    }
+
+    fn release(&self, category: &'static str, size: u64) {
+        let mut inner = self.inner.lock();
+        inner.stats.total_allocated -= size;
+        inner.stats.allocation_count -= 1;
+        if let Some(bytes) = inner.stats.by_category.get_mut(category) {
+            *bytes -= size;
+        }
+        inner.check_watermark();
+    }
+}
+
+#[derive(Default)]
+struct UploadInner {
+    frame_bytes: u64,
+    threshold: Option<u64>,
+    above_threshold: bool,
+}
+
+impl TrackerInner {
+    fn check_watermark(&mut self) {
+        let above = self
+            .watermark
+            .map_or(false, |watermark| self.stats.total_allocated > watermark);
+
+        if above && !self.above_watermark {
+            log::warn!(
+                "GPU memory usage over the watermark: {} ({})",
+                mib(self.stats.total_allocated),
+                self.stats
+            );
+        }
+        self.above_watermark = above;
+    }
+}
+
+impl UploadTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count `bytes` uploaded into a buffer of `type_name`
+    pub fn upload(&self, type_name: &'static str, bytes: u64) {
+        let mut inner = self.inner.lock();
+        inner.frame_bytes += bytes;
+
+        let above = inner
+            .threshold
+            .map_or(false, |threshold| inner.frame_bytes > threshold);
+        if above && !inner.above_threshold {
+            log::warn!(
+                "Frame uploads over the threshold: {} after {} of {type_name}",
+                mib(inner.frame_bytes),
+                mib(bytes),
+            );
+        }
+        inner.above_threshold = above;
+    }
+
+    /// Bytes uploaded since the last `reset`
+    pub fn frame_bytes(&self) -> u64 {
+        self.inner.lock().frame_bytes
+    }
+
+    pub fn reset(&self) {
+        let mut inner = self.inner.lock();
+        inner.frame_bytes = 0;
+        inner.above_threshold = false;
+    }
+
+    /// Log a warning naming the buffer type when the uploads
+    /// of a single frame grow over `threshold` bytes
+    ///
+    /// Warns once per frame, no threshold by default.
+    pub fn set_threshold(&self, threshold: Option<u64>) {
+        self.inner.lock().threshold = threshold;
+    }
+
+    pub fn threshold(&self) -> Option<u64> {
+        self.inner.lock().threshold
+    }
+}
+
+impl Allocation {
+    pub fn category(&self) -> &'static str {
+        self.category
+    }
+
+    /// Bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        self.tracker.release(self.category, self.size);
+    }
+}
+
+/// `buffers: 12.0 MiB, images: 34.0 MiB`
+impl fmt::Display for MemoryStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut categories: Vec<_> = self.by_category.iter().collect();
+        categories.sort_unstable_by_key(|(category, _)| **category);
+
+        for (i, (category, &bytes)) in categories.into_iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{category}: {}", mib(bytes))?;
+        }
+        Ok(())
+    }
+}
+
+fn mib(bytes: u64) -> String {
+    format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+}
@@ -0,0 +1,149 @@
+use super::VoxelGrid;
+use crate::renderer::index::Index;
+use glam::Vec3;
+
+//
+
+const VERT_PER_QUAD: usize = 4;
+const INDX_PER_QUAD: usize = 6;
+
+//
+
+/// One quad for every face between a solid and an empty voxel
+///
+/// Voxels above `iso` are solid and the voxels outside of the grid are empty.
+/// Voxel `[x, y, z]` is the cube from `(x, y, z)` to `(x + 1, y + 1, z + 1)`.
+///
+/// `f` gets the position, the outward face normal and the value of
+/// the solid voxel for each vertex. Four vertices per quad, see `cube_indices`.
+pub fn cubes<V, F>(grid: &VoxelGrid, iso: f32, f: F) -> Vec<V>
+where
+    F: FnMut(Vec3, Vec3, f32) -> V,
+{
+    cubes_in(grid, iso, [0; 3], grid.dims(), f)
+}
+
+/// `cubes` for the voxels from `min` to `max` (exclusive)
+///
+/// The voxels around the region are only used for hiding
+/// the faces between them, e.g. the border of a chunk.
+pub fn cubes_in<V, F>(
+    grid: &VoxelGrid,
+    iso: f32,
+    min: [usize; 3],
+    max: [usize; 3],
+    mut f: F,
+) -> Vec<V>
+where
+    F: FnMut(Vec3, Vec3, f32) -> V,
+{
+    let exists = |v: f32| v > iso;
+    let empty = |v: Option<f32>| !v.map_or(false, exists);
+
+    let mut vertices = Vec::new();
+    let [width, height, depth] = grid.dims();
+    for z in min[2]..max[2].min(depth) {
+        for y in min[1]..max[1].min(height) {
+            for x in min[0]..max[0].min(width) {
+                let value = grid[[x, y, z]];
+                if !exists(value) {
+                    continue;
+                }
+
+                let faces = Faces {
+                    neg_x: x == 0 || empty(grid.get(x - 1, y, z)),
+                    pos_x: empty(grid.get(x + 1, y, z)),
+                    neg_y: y == 0 || empty(grid.get(x, y - 1, z)),
+                    pos_y: empty(grid.get(x, y + 1, z)),
+                    neg_z: z == 0 || empty(grid.get(x, y, z - 1)),
+                    pos_z: empty(grid.get(x, y, z + 1)),
+                };
+
+                cube([x, y, z], faces, value, &mut f, &mut vertices);
+            }
+        }
+    }
+
+    vertices
+}
+
+/// Two triangles per quad of `cubes`
+pub fn cube_indices<I: Index>(vertex_count: usize) -> Vec<I> {
+    // 0 1 2   0 2 3
+    // 4 5 6   4 6 7
+    // ...
+    let quad_indices = [0, 1, 2, 0, 2, 3];
+    (0..vertex_count * INDX_PER_QUAD / VERT_PER_QUAD)
+        .map(|i| {
+            let quad_index = (i / INDX_PER_QUAD) as u32;
+            I::from_u32(quad_index * VERT_PER_QUAD as u32 + quad_indices[i % INDX_PER_QUAD])
+        })
+        .collect()
+}
+
+/// Visible faces of a cube
+struct Faces {
+    neg_x: bool,
+    pos_x: bool,
+    neg_y: bool,
+    pos_y: bool,
+    neg_z: bool,
+    pos_z: bool,
+}
+
+fn cube<V, F>(p: [usize; 3], faces: Faces, value: f32, f: &mut F, vertices: &mut Vec<V>)
+where
+    F: FnMut(Vec3, Vec3, f32) -> V,
+{
+    let [x, y, z] = p;
+    let mut face = |corner: [usize; 3], axes: [u8; 3], inv: bool, normal: Vec3| {
+        quad(corner, axes, inv, |pos| {
+            vertices.push(f(pos, normal, value))
+        })
+    };
+
+    if faces.neg_x {
+        face([x, y, z], [0, 1, 2], false, -Vec3::X);
+    }
+    if faces.pos_x {
+        face([x + 1, y, z], [0, 1, 2], true, Vec3::X);
+    }
+    if faces.neg_y {
+        face([x, y, z], [1, 0, 2], true, -Vec3::Y);
+    }
+    if faces.pos_y {
+        face([x, y + 1, z], [1, 0, 2], false, Vec3::Y);
+    }
+    if faces.neg_z {
+        face([x, y, z], [1, 2, 0], false, -Vec3::Z);
+    }
+    if faces.pos_z {
+        face([x, y, z + 1], [1, 2, 0], true, Vec3::Z);
+    }
+}
+
+/// Four corners of the unit quad at `corner`
+///
+/// `axes[i]` is 0 for the axis the quad is facing,
+/// 1 and 2 for the two axes it spans.
+fn quad<F: FnMut(Vec3)>(corner: [usize; 3], axes: [u8; 3], inv: bool, mut push: F) {
+    let (al, bl, ar, br) = if inv { (0, 1, 1, 0) } else { (1, 0, 1, 0) };
+
+    let vertex = |l: usize, r: usize| {
+        let offset = |axis: usize| match axes[axis] {
+            1 => l,
+            2 => r,
+            _ => 0,
+        };
+        Vec3::new(
+            (corner[0] + offset(0)) as f32,
+            (corner[1] + offset(1)) as f32,
+            (corner[2] + offset(2)) as f32,
+        )
+    };
+
+    push(vertex(al, ar));
+    push(vertex(al, br));
+    push(vertex(bl, br));
+    push(vertex(bl, ar));
+}
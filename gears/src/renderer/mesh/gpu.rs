@@ -0,0 +1,182 @@
+use crate::renderer::{
+    buffer::StagedBuffer,
+    device::Dev,
+    index::{BindIndexBuffer, Index, IndexBuffer, IndexData},
+    Recorder,
+};
+use anyhow::{bail, Result};
+use std::ops::Range;
+use vulkano::{
+    buffer::{BufferUsage, TypedBufferAccess},
+    command_buffer::AutoCommandBufferBuilder,
+};
+
+//
+
+/// Vertex buffer, optional index buffer and the draw call for them
+///
+/// The indices are an `IndexBuffer` by default,
+/// or a `StagedBuffer<[I]>` of one `Index` type.
+pub struct Mesh<V, I = IndexBuffer> {
+    pub vertices: StagedBuffer<[V]>,
+    pub indices: Option<I>,
+}
+
+/// Index buffers usable in a `Mesh`
+pub trait MeshIndices: BindIndexBuffer {
+    /// Same as `StagedBuffer::update`
+    fn update(&self, recorder: &mut Recorder<false>) -> Result<()>;
+}
+
+//
+
+impl<I: Index> MeshIndices for StagedBuffer<[I]> {
+    fn update(&self, recorder: &mut Recorder<false>) -> Result<()> {
+        StagedBuffer::update(self, recorder)
+    }
+}
+
+impl MeshIndices for IndexBuffer {
+    fn update(&self, recorder: &mut Recorder<false>) -> Result<()> {
+        IndexBuffer::update(self, recorder)
+    }
+}
+
+impl<V, I> Mesh<V, I>
+where
+    V: Send + Sync + 'static,
+    I: MeshIndices,
+{
+    /// Mesh drawn without indices
+    pub fn new(device: &Dev, vertices: Vec<V>) -> Result<Self> {
+        let vertices =
+            StagedBuffer::from_iter(device, BufferUsage::vertex_buffer(), vertices.into_iter())?;
+        Ok(Self::from_buffers(vertices, None))
+    }
+
+    pub fn from_buffers(vertices: StagedBuffer<[V]>, indices: Option<I>) -> Self {
+        Self { vertices, indices }
+    }
+
+    /// Send both buffers to the device, see `StagedBuffer::update`
+    pub fn update(&self, recorder: &mut Recorder<false>) -> Result<()> {
+        self.vertices.update(recorder)?;
+        if let Some(indices) = &self.indices {
+            indices.update(recorder)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_indexed(&self) -> bool {
+        self.indices.is_some()
+    }
+
+    /// Indices drawn, or vertices if the mesh has no indices
+    pub fn len(&self) -> u32 {
+        match &self.indices {
+            Some(indices) => indices.index_count(),
+            None => self.vertices.local.len() as u32,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Bind the buffers and draw the whole mesh
+    ///
+    /// The pipeline and descriptor sets have to be bound already.
+    pub fn draw(&self, recorder: &mut Recorder<true>) -> Result<()> {
+        self.draw_range(recorder, 0..self.len())
+    }
+
+    /// Same as `draw` but only the indices, or vertices if the
+    /// mesh has no indices, in `range`, e.g. one part of a mesh
+    pub fn draw_range(&self, recorder: &mut Recorder<true>, range: Range<u32>) -> Result<()> {
+        let count = range.end.saturating_sub(range.start);
+        self.record_range(recorder.record(), range)?;
+        recorder.count_draw(count, 1);
+        Ok(())
+    }
+
+    /// Same as `draw`, also usable with secondary command buffer builders
+    ///
+    /// Not counted in `Recorder::stats`.
+    pub fn record<L, P>(&self, builder: &mut AutoCommandBufferBuilder<L, P>) -> Result<()> {
+        self.record_range(builder, 0..self.len())
+    }
+
+    /// Same as `draw_range`, also usable with secondary command buffer builders
+    pub fn record_range<L, P>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L, P>,
+        range: Range<u32>,
+    ) -> Result<()> {
+        if range.start > range.end || range.end > self.len() {
+            bail!(
+                "Draw range {:?} is out of the mesh bounds 0..{}",
+                range,
+                self.len()
+            );
+        }
+
+        builder.bind_vertex_buffers(0, self.vertices.local.clone());
+        let count = range.end - range.start;
+        match &self.indices {
+            Some(indices) => {
+                indices
+                    .bind_index_buffer(builder)
+                    .draw_indexed(count, 1, range.start, 0, 0)?;
+            }
+            None => {
+                builder.draw(count, 1, range.start, 0)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<V> Mesh<V, IndexBuffer>
+where
+    V: Send + Sync + 'static,
+{
+    /// Mesh drawn with `indices`, in the type `IndexData` picked
+    pub fn new_indexed(device: &Dev, vertices: Vec<V>, indices: IndexData) -> Result<Self> {
+        let mut mesh = Self::new(device, vertices)?;
+        mesh.indices = Some(IndexBuffer::new(device, indices)?);
+        Ok(mesh)
+    }
+}
+
+impl<V, I> Mesh<V, StagedBuffer<[I]>>
+where
+    V: Send + Sync + 'static,
+    I: Index,
+{
+    /// Mesh drawn with `indices` converted to `I`
+    ///
+    /// Fails if an index does not fit into `I`, instead of
+    /// wrapping around, e.g. with u16 indices of a large mesh.
+    pub fn new_with_indices(device: &Dev, vertices: Vec<V>, indices: Vec<u32>) -> Result<Self> {
+        if !I::supported(device) {
+            bail!("Index type {:?} is not supported by the device", I::ty());
+        }
+        if let Some(&index) = indices.iter().find(|&&index| index >= I::MAX) {
+            bail!(
+                "Index {} does not fit into {:?} indices, the max is {}",
+                index,
+                I::ty(),
+                I::MAX - 1
+            );
+        }
+        let indices = StagedBuffer::from_iter(
+            device,
+            BufferUsage::index_buffer(),
+            indices.into_iter().map(I::from_u32),
+        )?;
+
+        let mut mesh = Self::new(device, vertices)?;
+        mesh.indices = Some(indices);
+        Ok(mesh)
+    }
+}
@@ -0,0 +1,192 @@
+use glam::{Vec2, Vec3, Vec4};
+use std::collections::HashMap;
+
+//
+
+pub mod cubes;
+pub mod gpu;
+pub mod mcubes;
+pub mod volume;
+pub mod voxel;
+
+pub use cubes::{cube_indices, cubes, cubes_in};
+pub use gpu::{Mesh, MeshIndices};
+pub use mcubes::{
+    marching_cubes, marching_cubes_parallel, marching_cubes_with, JobStatus, MeshJob,
+};
+pub use volume::{VoxelHit, VoxelVolume, CHUNK_SIZE};
+pub use voxel::VoxelGrid;
+
+//
+
+/// Vertices split by the normal generation
+///
+/// Output vertex `i` is made from the input vertex
+/// `source[i]` and has the normal `normals[i]`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NormalMesh {
+    pub source: Vec<u32>,
+    pub normals: Vec<Vec3>,
+    pub indices: Vec<u32>,
+}
+
+//
+
+impl NormalMesh {
+    /// Build the output vertices from the input vertices
+    pub fn vertices<V, T, F>(&self, vertices: &[V], mut f: F) -> Vec<T>
+    where
+        F: FnMut(&V, Vec3) -> T,
+    {
+        self.source
+            .iter()
+            .zip(self.normals.iter())
+            .map(|(&source, &normal)| f(&vertices[source as usize], normal))
+            .collect()
+    }
+
+    fn push(&mut self, source: u32, normal: Vec3) -> u32 {
+        let index = self.source.len() as u32;
+        self.source.push(source);
+        self.normals.push(normal);
+        index
+    }
+}
+
+/// Counter clockwise face normal scaled by twice the triangle area
+fn face_normal(positions: &[Vec3], triangle: &[u32]) -> Vec3 {
+    let a = positions[triangle[0] as usize];
+    let b = positions[triangle[1] as usize];
+    let c = positions[triangle[2] as usize];
+    (b - a).cross(c - a)
+}
+
+fn position_key(position: Vec3) -> [u32; 3] {
+    [
+        position.x.to_bits(),
+        position.y.to_bits(),
+        position.z.to_bits(),
+    ]
+}
+
+/// Every triangle gets its own vertices
+/// with the face normal
+pub fn compute_flat_normals<V, P>(vertices: &[V], indices: &[u32], position: P) -> NormalMesh
+where
+    P: Fn(&V) -> Vec3,
+{
+    let positions: Vec<Vec3> = vertices.iter().map(position).collect();
+
+    let mut mesh = NormalMesh::default();
+    for triangle in indices.chunks_exact(3) {
+        let normal = face_normal(&positions, triangle).normalize_or_zero();
+        for &source in triangle {
+            let index = mesh.push(source, normal);
+            mesh.indices.push(index);
+        }
+    }
+    mesh
+}
+
+/// Area weighted vertex normals
+///
+/// Faces are smoothed over if they share a vertex position,
+/// so unwelded meshes (like marching cubes output) are fine.
+/// Vertices are split where the angle between
+/// the face normals exceeds `angle_threshold` (radians).
+pub fn compute_smooth_normals<V, P>(
+    vertices: &[V],
+    indices: &[u32],
+    angle_threshold: f32,
+    position: P,
+) -> NormalMesh
+where
+    P: Fn(&V) -> Vec3,
+{
+    let cos_threshold = angle_threshold.cos();
+    let positions: Vec<Vec3> = vertices.iter().map(position).collect();
+    let faces: Vec<Vec3> = indices
+        .chunks_exact(3)
+        .map(|triangle| face_normal(&positions, triangle))
+        .collect();
+    let corners = &indices[..faces.len() * 3];
+
+    // faces around each vertex position
+    let mut shared: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+    for (corner, &source) in corners.iter().enumerate() {
+        shared
+            .entry(position_key(positions[source as usize]))
+            .or_default()
+            .push(corner / 3);
+    }
+
+    let mut mesh = NormalMesh::default();
+    let mut split: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+    for (corner, &source) in corners.iter().enumerate() {
+        let face = faces[corner / 3].normalize_or_zero();
+        let normal = shared[&position_key(positions[source as usize])]
+            .iter()
+            .map(|&other| faces[other])
+            .filter(|other| other.normalize_or_zero().dot(face) >= cos_threshold)
+            .fold(Vec3::ZERO, |sum, other| sum + other)
+            .normalize_or_zero();
+
+        // corners of the same vertex that ended up
+        // with the same normal share the output vertex
+        let index = *split
+            .entry((source, position_key(normal)))
+            .or_insert_with(|| mesh.push(source, normal));
+        mesh.indices.push(index);
+    }
+    mesh
+}
+
+/// Per vertex tangents for normal mapping
+///
+/// `attributes` returns the position, normal and uv of a vertex.
+/// The `w` component is the bitangent sign.
+pub fn generate_tangents<V, A>(vertices: &[V], indices: &[u32], attributes: A) -> Vec<Vec4>
+where
+    A: Fn(&V) -> (Vec3, Vec3, Vec2),
+{
+    let attributes: Vec<(Vec3, Vec3, Vec2)> = vertices.iter().map(attributes).collect();
+    let mut tangents = vec![Vec3::ZERO; vertices.len()];
+    let mut bitangents = vec![Vec3::ZERO; vertices.len()];
+
+    for triangle in indices.chunks_exact(3) {
+        let (p0, _, uv0) = attributes[triangle[0] as usize];
+        let (p1, _, uv1) = attributes[triangle[1] as usize];
+        let (p2, _, uv2) = attributes[triangle[2] as usize];
+
+        let (e1, e2) = (p1 - p0, p2 - p0);
+        let (d1, d2) = (uv1 - uv0, uv2 - uv0);
+        let det = d1.x * d2.y - d2.x * d1.y;
+        if det.abs() <= f32::EPSILON {
+            // degenerate uvs
+            continue;
+        }
+        let r = 1.0 / det;
+        let tangent = (e1 * d2.y - e2 * d1.y) * r;
+        let bitangent = (e2 * d1.x - e1 * d2.x) * r;
+
+        for &i in triangle {
+            tangents[i as usize] += tangent;
+            bitangents[i as usize] += bitangent;
+        }
+    }
+
+    attributes
+        .iter()
+        .zip(tangents.into_iter().zip(bitangents.into_iter()))
+        .map(|(&(_, normal, _), (tangent, bitangent))| {
+            // Gram-Schmidt orthogonalize
+            let tangent = (tangent - normal * normal.dot(tangent)).normalize_or_zero();
+            let w = if normal.cross(tangent).dot(bitangent) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            tangent.extend(w)
+        })
+        .collect()
+}
@@ -0,0 +1,320 @@
+use super::{cubes_in, marching_cubes_with, VoxelGrid};
+use glam::Vec3;
+
+//
+
+/// Voxels per chunk axis of a `VoxelVolume`
+pub const CHUNK_SIZE: usize = 16;
+
+/// Voxel values stored in `CHUNK_SIZE`³ chunks
+///
+/// `set_voxel` marks the chunks whose mesh depends on the voxel as dirty,
+/// so only those have to be meshed again, see `take_dirty`. The chunk
+/// meshes are in the coordinates of the whole volume, like the mesh of a
+/// `VoxelGrid` of the same voxels, and line up without offsets.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VoxelVolume {
+    chunks: Vec<VoxelGrid>,
+    dirty: Vec<bool>,
+    dims: [usize; 3],
+    chunk_dims: [usize; 3],
+}
+
+/// Result of `VoxelVolume::raycast`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VoxelHit {
+    /// The first solid voxel along the ray
+    pub voxel: [usize; 3],
+
+    /// The voxel the ray came from, e.g. for placing a voxel
+    ///
+    /// `None` if the ray started in `voxel` or came from outside of the volume.
+    pub previous: Option<[usize; 3]>,
+}
+
+//
+
+impl VoxelVolume {
+    /// Volume of zeros, every chunk starts dirty
+    pub fn new(dims: [usize; 3]) -> Self {
+        let chunk_dims = dims.map(|dim| (dim + CHUNK_SIZE - 1) / CHUNK_SIZE);
+        let count = chunk_dims[0] * chunk_dims[1] * chunk_dims[2];
+        Self {
+            chunks: vec![VoxelGrid::from_fn([CHUNK_SIZE; 3], |_, _, _| 0.0); count],
+            dirty: vec![true; count],
+            dims,
+            chunk_dims,
+        }
+    }
+
+    /// Volume filled with `f(x, y, z)`
+    pub fn from_fn<F>(dims: [usize; 3], mut f: F) -> Self
+    where
+        F: FnMut(usize, usize, usize) -> f32,
+    {
+        let mut volume = Self::new(dims);
+        for z in 0..dims[2] {
+            for y in 0..dims[1] {
+                for x in 0..dims[0] {
+                    let (chunk, local) = volume.locate(x, y, z);
+                    volume.chunks[chunk][local] = f(x, y, z);
+                }
+            }
+        }
+        volume
+    }
+
+    pub fn from_grid(grid: &VoxelGrid) -> Self {
+        Self::from_fn(grid.dims(), |x, y, z| grid[[x, y, z]])
+    }
+
+    /// Size in voxels
+    pub fn dims(&self) -> [usize; 3] {
+        self.dims
+    }
+
+    /// Chunks per axis
+    pub fn chunk_dims(&self) -> [usize; 3] {
+        self.chunk_dims
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Chunk coordinates of chunk `index`
+    pub fn chunk(&self, index: usize) -> [usize; 3] {
+        let [width, height, _] = self.chunk_dims;
+        [
+            index % width,
+            (index / width) % height,
+            index / (width * height),
+        ]
+    }
+
+    /// Index of the chunk at chunk coordinates `chunk`
+    pub fn chunk_index(&self, [x, y, z]: [usize; 3]) -> usize {
+        let [width, height, _] = self.chunk_dims;
+        x + (y + z * height) * width
+    }
+
+    /// First voxel of a chunk
+    pub fn chunk_origin(&self, chunk: [usize; 3]) -> [usize; 3] {
+        chunk.map(|c| c * CHUNK_SIZE)
+    }
+
+    /// `None` if the voxel is outside of the volume
+    pub fn get(&self, x: usize, y: usize, z: usize) -> Option<f32> {
+        if !self.contains(x, y, z) {
+            return None;
+        }
+        let (chunk, local) = self.locate(x, y, z);
+        Some(self.chunks[chunk][local])
+    }
+
+    /// Set a voxel and mark the chunks that mesh it as dirty
+    ///
+    /// Voxels on a chunk border are also meshed by the neighboring
+    /// chunks. Returns `false` if the voxel is outside of the volume.
+    pub fn set_voxel(&mut self, x: usize, y: usize, z: usize, value: f32) -> bool {
+        if !self.contains(x, y, z) {
+            return false;
+        }
+        let (chunk, local) = self.locate(x, y, z);
+        if self.chunks[chunk][local] == value {
+            return true;
+        }
+        self.chunks[chunk][local] = value;
+
+        // the neighbors before the border for the marching cubes
+        // cells and after it for the faces of the cube mesher
+        let neighbors = |v: usize, count: usize| {
+            let (chunk, local) = (v / CHUNK_SIZE, v % CHUNK_SIZE);
+            let first = if local == 0 {
+                chunk.saturating_sub(1)
+            } else {
+                chunk
+            };
+            let last = if local == CHUNK_SIZE - 1 {
+                (chunk + 1).min(count - 1)
+            } else {
+                chunk
+            };
+            first..=last
+        };
+        for cz in neighbors(z, self.chunk_dims[2]) {
+            for cy in neighbors(y, self.chunk_dims[1]) {
+                for cx in neighbors(x, self.chunk_dims[0]) {
+                    let index = self.chunk_index([cx, cy, cz]);
+                    self.dirty[index] = true;
+                }
+            }
+        }
+        true
+    }
+
+    pub fn is_dirty(&self, chunk: [usize; 3]) -> bool {
+        self.dirty[self.chunk_index(chunk)]
+    }
+
+    /// Mesh every chunk again, e.g. after changing the mesher
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty.iter_mut().for_each(|dirty| *dirty = true);
+    }
+
+    /// Dirty chunks since the last call, marked clean
+    pub fn take_dirty(&mut self) -> Vec<[usize; 3]> {
+        let dirty = (0..self.dirty.len())
+            .filter(|&index| self.dirty[index])
+            .map(|index| self.chunk(index))
+            .collect();
+        self.dirty.iter_mut().for_each(|dirty| *dirty = false);
+        dirty
+    }
+
+    /// `marching_cubes_with` for the cells of one chunk
+    ///
+    /// The cells on the far border read the voxels of the next chunks.
+    pub fn marching_cubes_chunk<V, F>(
+        &self,
+        chunk: [usize; 3],
+        iso: f32,
+        smooth: bool,
+        mut f: F,
+    ) -> Vec<V>
+    where
+        F: FnMut(Vec3, Vec3, f32) -> V,
+    {
+        let min = self.chunk_origin(chunk);
+        let max = self.clamp_max(min.map(|v| v + CHUNK_SIZE + 1));
+        let grid = self.region(min, max);
+
+        let offset = to_vec3(min);
+        marching_cubes_with(&grid, iso, smooth, |pos, normal, value| {
+            f(pos + offset, normal, value)
+        })
+    }
+
+    /// `cubes_in` for the voxels of one chunk
+    ///
+    /// The faces between chunks are hidden like inside of a chunk.
+    pub fn cubes_chunk<V, F>(&self, chunk: [usize; 3], iso: f32, mut f: F) -> Vec<V>
+    where
+        F: FnMut(Vec3, Vec3, f32) -> V,
+    {
+        let origin = self.chunk_origin(chunk);
+        let end = self.clamp_max(origin.map(|v| v + CHUNK_SIZE));
+
+        // one voxel of the neighbors around the chunk
+        let min = origin.map(|v| v.saturating_sub(1));
+        let max = self.clamp_max(end.map(|v| v + 1));
+        let grid = self.region(min, max);
+
+        let offset = to_vec3(min);
+        let sub = |a: [usize; 3]| [a[0] - min[0], a[1] - min[1], a[2] - min[2]];
+        cubes_in(&grid, iso, sub(origin), sub(end), |pos, normal, value| {
+            f(pos + offset, normal, value)
+        })
+    }
+
+    /// First voxel above `iso` along a ray, up to `max_distance` away
+    ///
+    /// Voxel `[x, y, z]` is the cube from `(x, y, z)` to
+    /// `(x + 1, y + 1, z + 1)`, like with the cube mesher.
+    pub fn raycast(
+        &self,
+        origin: Vec3,
+        dir: Vec3,
+        max_distance: f32,
+        iso: f32,
+    ) -> Option<VoxelHit> {
+        let dir = dir.normalize_or_zero();
+        if dir == Vec3::ZERO {
+            return None;
+        }
+
+        // http://www.cse.yorku.ca/~amana/research/grid.pdf
+        let mut voxel = origin.floor().to_array().map(|v| v as i64);
+        let dir = dir.to_array();
+        let origin = origin.to_array();
+        let mut step = [0_i64; 3];
+        let mut t_max = [f32::INFINITY; 3];
+        let mut t_delta = [f32::INFINITY; 3];
+        for axis in 0..3 {
+            if dir[axis] > 0.0 {
+                step[axis] = 1;
+                t_max[axis] = (voxel[axis] as f32 + 1.0 - origin[axis]) / dir[axis];
+            } else if dir[axis] < 0.0 {
+                step[axis] = -1;
+                t_max[axis] = (origin[axis] - voxel[axis] as f32) / -dir[axis];
+            }
+            if dir[axis] != 0.0 {
+                t_delta[axis] = 1.0 / dir[axis].abs();
+            }
+        }
+
+        let mut previous = None;
+        let mut t = 0.0;
+        while t <= max_distance {
+            let inside = voxel
+                .iter()
+                .zip(self.dims)
+                .all(|(&v, dim)| v >= 0 && (v as usize) < dim);
+            if inside {
+                let [x, y, z] = voxel.map(|v| v as usize);
+                if self.get(x, y, z).map_or(false, |value| value > iso) {
+                    return Some(VoxelHit {
+                        voxel: [x, y, z],
+                        previous,
+                    });
+                }
+                previous = Some([x, y, z]);
+            } else {
+                previous = None;
+            }
+
+            // step over the closest voxel border
+            let axis = (0..3)
+                .min_by(|&a, &b| t_max[a].partial_cmp(&t_max[b]).unwrap())
+                .unwrap();
+            t = t_max[axis];
+            voxel[axis] += step[axis];
+            t_max[axis] += t_delta[axis];
+        }
+
+        None
+    }
+
+    //
+
+    fn contains(&self, x: usize, y: usize, z: usize) -> bool {
+        x < self.dims[0] && y < self.dims[1] && z < self.dims[2]
+    }
+
+    /// Chunk index and the position inside of the chunk
+    fn locate(&self, x: usize, y: usize, z: usize) -> (usize, [usize; 3]) {
+        let chunk = self.chunk_index([x / CHUNK_SIZE, y / CHUNK_SIZE, z / CHUNK_SIZE]);
+        (chunk, [x % CHUNK_SIZE, y % CHUNK_SIZE, z % CHUNK_SIZE])
+    }
+
+    fn clamp_max(&self, max: [usize; 3]) -> [usize; 3] {
+        [
+            max[0].min(self.dims[0]),
+            max[1].min(self.dims[1]),
+            max[2].min(self.dims[2]),
+        ]
+    }
+
+    /// Copy of the voxels from `min` to `max` (exclusive)
+    fn region(&self, min: [usize; 3], max: [usize; 3]) -> VoxelGrid {
+        let dims = [max[0] - min[0], max[1] - min[1], max[2] - min[2]];
+        VoxelGrid::from_fn(dims, |x, y, z| {
+            self.get(min[0] + x, min[1] + y, min[2] + z)
+                .unwrap_or_default()
+        })
+    }
+}
+
+fn to_vec3(v: [usize; 3]) -> Vec3 {
+    Vec3::new(v[0] as f32, v[1] as f32, v[2] as f32)
+}
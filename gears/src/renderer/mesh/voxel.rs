@@ -0,0 +1,135 @@
+use glam::Vec3;
+use std::ops::{Index, IndexMut};
+
+//
+
+/// Dense 3D grid of voxel values
+///
+/// Stored x first, then y, then z.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VoxelGrid {
+    data: Vec<f32>,
+    dims: [usize; 3],
+}
+
+//
+
+impl VoxelGrid {
+    /// Panics if `data` does not have exactly `dims[0] * dims[1] * dims[2]` values
+    pub fn new(data: Vec<f32>, dims: [usize; 3]) -> Self {
+        assert_eq!(
+            data.len(),
+            dims[0] * dims[1] * dims[2],
+            "VoxelGrid data length does not match its dimensions"
+        );
+        Self { data, dims }
+    }
+
+    /// Grid filled with `f(x, y, z)`
+    pub fn from_fn<F>(dims: [usize; 3], mut f: F) -> Self
+    where
+        F: FnMut(usize, usize, usize) -> f32,
+    {
+        let mut data = Vec::with_capacity(dims[0] * dims[1] * dims[2]);
+        for z in 0..dims[2] {
+            for y in 0..dims[1] {
+                for x in 0..dims[0] {
+                    data.push(f(x, y, z));
+                }
+            }
+        }
+        Self { data, dims }
+    }
+
+    pub fn dims(&self) -> [usize; 3] {
+        self.dims
+    }
+
+    pub fn data(&self) -> &[f32] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [f32] {
+        &mut self.data
+    }
+
+    /// `None` if the point is outside of the grid
+    pub fn get(&self, x: usize, y: usize, z: usize) -> Option<f32> {
+        self.offset(x, y, z).map(|i| self.data[i])
+    }
+
+    /// `None` if the point is outside of the grid
+    pub fn get_mut(&mut self, x: usize, y: usize, z: usize) -> Option<&mut f32> {
+        self.offset(x, y, z).map(move |i| &mut self.data[i])
+    }
+
+    /// Index into `data`, `None` if the point is outside of the grid
+    pub fn offset(&self, x: usize, y: usize, z: usize) -> Option<usize> {
+        let [width, height, depth] = self.dims;
+        if x < width && y < height && z < depth {
+            Some(x + (y + z * height) * width)
+        } else {
+            None
+        }
+    }
+
+    /// Trilinear interpolation between the voxels around `pos`
+    ///
+    /// `pos` is in grid coordinates and clamped to the grid.
+    /// Empty grids sample as 0.
+    pub fn sample(&self, pos: Vec3) -> f32 {
+        if self.data.is_empty() {
+            return 0.0;
+        }
+
+        let max = Vec3::new(
+            (self.dims[0] - 1) as f32,
+            (self.dims[1] - 1) as f32,
+            (self.dims[2] - 1) as f32,
+        );
+        let pos = pos.clamp(Vec3::ZERO, max);
+        let base = pos.floor();
+        let t = pos - base;
+
+        // the upper corner is clamped back to the grid on the edges
+        let lo = [base.x as usize, base.y as usize, base.z as usize];
+        let hi = [
+            (lo[0] + 1).min(self.dims[0] - 1),
+            (lo[1] + 1).min(self.dims[1] - 1),
+            (lo[2] + 1).min(self.dims[2] - 1),
+        ];
+        let v = |x: usize, y: usize, z: usize| self[[x, y, z]];
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+        let x00 = lerp(v(lo[0], lo[1], lo[2]), v(hi[0], lo[1], lo[2]), t.x);
+        let x10 = lerp(v(lo[0], hi[1], lo[2]), v(hi[0], hi[1], lo[2]), t.x);
+        let x01 = lerp(v(lo[0], lo[1], hi[2]), v(hi[0], lo[1], hi[2]), t.x);
+        let x11 = lerp(v(lo[0], hi[1], hi[2]), v(hi[0], hi[1], hi[2]), t.x);
+
+        let y0 = lerp(x00, x10, t.y);
+        let y1 = lerp(x01, x11, t.y);
+
+        lerp(y0, y1, t.z)
+    }
+}
+
+/// Panics if the point is outside of the grid
+impl Index<[usize; 3]> for VoxelGrid {
+    type Output = f32;
+
+    fn index(&self, [x, y, z]: [usize; 3]) -> &Self::Output {
+        let i = self
+            .offset(x, y, z)
+            .unwrap_or_else(|| panic!("Voxel {:?} is outside of {:?}", [x, y, z], self.dims));
+        &self.data[i]
+    }
+}
+
+impl IndexMut<[usize; 3]> for VoxelGrid {
+    fn index_mut(&mut self, [x, y, z]: [usize; 3]) -> &mut Self::Output {
+        let i = self
+            .offset(x, y, z)
+            .unwrap_or_else(|| panic!("Voxel {:?} is outside of {:?}", [x, y, z], self.dims));
+        &mut self.data[i]
+    }
+}
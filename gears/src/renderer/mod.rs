@@ -1,20 +1,54 @@
+use crate::{format::Color, report::FrameStats};
+use anyhow::{anyhow, Result};
 use glam::Vec4;
+use index::BindIndexBuffer;
+use indirect::{DrawIndexedIndirectBuffer, DrawIndirectBuffer};
+use readback::ReadbackFence;
+use secondary::SecondaryRecorder;
 use std::{
     ops::{Deref, DerefMut},
+    sync::Arc,
     time::Duration,
 };
-use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use viewport::Rect;
+use vulkano::{
+    command_buffer::{
+        AutoCommandBufferBuilder, ClearAttachment, ClearRect, PrimaryAutoCommandBuffer,
+        SecondaryAutoCommandBuffer, SubpassContents,
+    },
+    device::Queue,
+    format::ClearValue,
+    render_pass::{Framebuffer, Subpass},
+};
 
 //
 
 pub mod buffer;
+pub mod capture;
+pub mod cull;
 pub mod device;
+pub mod graph;
+pub mod index;
+pub mod indirect;
+pub mod mapped;
+pub mod material;
+pub mod memory;
+pub mod mesh;
 pub mod object;
+pub mod overlay;
 pub mod pipeline;
 pub mod query;
 pub mod queue;
+pub mod readback;
+pub mod secondary;
 pub mod simple_renderer;
+pub mod sprite;
 pub mod target;
+pub mod text;
+pub mod texture;
+pub mod uniform;
+pub mod upload;
+pub mod viewport;
 
 //
 
@@ -26,14 +60,23 @@ pub struct FramePerfReport {
 pub type BeginInfoRecorder<'a> = (
     &'a mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
     ClearColor,
+    SubpassContents,
 );
 
+/// Draws recorded by `Recorder::end_render_pass` after everything else,
+/// see `Recorder::post_draw`
+pub type PostDraw = Box<dyn FnOnce(&mut Recorder<true>) -> Result<()>>;
+
 struct RecorderInner {
     command_buffer: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
     modified: bool,
 
     image_index: usize,
     /* frame_in_flight: usize, */
+    // fences for the readbacks recorded into this command buffer
+    readbacks: Vec<ReadbackFence>,
+    post_draws: Vec<PostDraw>,
+    stats: FrameStats,
 }
 
 impl RecorderInner {
@@ -64,6 +107,10 @@ impl<'a> DerefMut for RecorderTarget<'a> {
 pub struct Recorder<const IN_RENDER_PASS: bool> {
     inner: RecorderInner,
     begin_info: Box<dyn Fn(BeginInfoRecorder)>,
+    secondary: Option<(Arc<Queue>, Arc<Framebuffer>)>,
+    contents: SubpassContents,
+    subpass: u32,
+    srgb_target: bool,
 }
 
 impl<const IN_RENDER_PASS: bool> Recorder<IN_RENDER_PASS> {
@@ -82,15 +129,42 @@ impl<const IN_RENDER_PASS: bool> Recorder<IN_RENDER_PASS> {
 
                 image_index,
                 /* frame_in_flight, */
+                readbacks: Vec::new(),
+                post_draws: Vec::new(),
+                stats: FrameStats::default(),
             },
             begin_info,
+            secondary: None,
+            contents: SubpassContents::Inline,
+            subpass: 0,
+            srgb_target: false,
         }
     }
 
+    /// Allow `SecondaryRecorder`s that render into `framebuffer`
+    pub fn with_secondary(mut self, queue: Arc<Queue>, framebuffer: Arc<Framebuffer>) -> Self {
+        self.secondary = Some((queue, framebuffer));
+        self
+    }
+
+    /// Color encoding of the target, `ClearColor`s are converted to match it
+    pub fn with_srgb_target(mut self, srgb_target: bool) -> Self {
+        self.srgb_target = srgb_target;
+        self
+    }
+
     pub fn image_index(&self) -> usize {
         self.inner.image_index
     }
 
+    /// Size of the framebuffer this recorder renders into, if it is known
+    pub fn extent(&self) -> Option<[u32; 2]> {
+        self.secondary.as_ref().map(|(_, framebuffer)| {
+            let [width, height, _] = framebuffer.dimensions();
+            [width, height]
+        })
+    }
+
     /* pub fn frame_in_flight(&self) -> usize {
         self.inner.frame_in_flight
     } */
@@ -98,6 +172,58 @@ impl<const IN_RENDER_PASS: bool> Recorder<IN_RENDER_PASS> {
     pub fn record(&mut self) -> &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer> {
         self.inner.record()
     }
+
+    /// Draw calls counted so far, see `count_draw`
+    pub fn stats(&self) -> FrameStats {
+        self.inner.stats
+    }
+
+    /// Add a draw call to `stats`
+    ///
+    /// `Recorder::draw`, `Recorder::draw_indexed` and `Mesh::draw` count
+    /// their draws, draws recorded directly with `record` or into
+    /// secondary command buffers can be counted with this.
+    pub fn count_draw(&mut self, vertices: u32, instances: u32) {
+        self.inner.stats += FrameStats::draw(vertices, instances);
+    }
+
+    /// Fence that gets signaled with this command buffer
+    pub(super) fn readback_fence(&mut self) -> ReadbackFence {
+        let fence = ReadbackFence::default();
+        self.inner.readbacks.push(fence.clone());
+        fence
+    }
+
+    /// Record `f` at the end of the next render pass, after the user draws
+    ///
+    /// For overlays like `gui::Egui::paint`, which can then be set up
+    /// before or during the render pass. The hooks run in the order they
+    /// were added, in the last subpass, and are skipped if that subpass
+    /// was started with `begin_render_pass_secondary`.
+    pub fn post_draw<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut Recorder<true>) -> Result<()> + 'static,
+    {
+        self.inner.post_draws.push(Box::new(f));
+    }
+
+    /// Set the dynamic viewport and scissor to `rect`
+    pub fn set_viewport_and_scissor(
+        &mut self,
+        rect: Rect,
+    ) -> &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer> {
+        self.record()
+            .set_viewport(0, [rect.viewport()])
+            .set_scissor(0, [rect.scissor()])
+    }
+
+    /// Bind a `StagedBuffer` or an `IndexBuffer` of any `Index` type
+    pub fn bind_index_buffer<B: BindIndexBuffer>(
+        &mut self,
+        buffer: &B,
+    ) -> &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer> {
+        buffer.bind_index_buffer(self.record())
+    }
 }
 
 impl Recorder<false> {
@@ -105,33 +231,212 @@ impl Recorder<false> {
         self.begin_render_pass_with(ClearColor::default())
     }
 
-    pub fn begin_render_pass_with(mut self, cc: ClearColor) -> Recorder<true> {
+    pub fn begin_render_pass_with(self, cc: ClearColor) -> Recorder<true> {
+        self.begin(cc, SubpassContents::Inline)
+    }
+
+    /// Begin a render pass that is recorded with `Recorder::secondary`
+    ///
+    /// Only `execute_secondaries` is allowed inside this render pass,
+    /// perf queries have to be recorded outside of it.
+    pub fn begin_render_pass_secondary(self) -> Recorder<true> {
+        self.begin_render_pass_secondary_with(ClearColor::default())
+    }
+
+    pub fn begin_render_pass_secondary_with(self, cc: ClearColor) -> Recorder<true> {
+        self.begin(cc, SubpassContents::SecondaryCommandBuffers)
+    }
+
+    fn begin(mut self, cc: ClearColor, contents: SubpassContents) -> Recorder<true> {
         let f = self.begin_info;
-        f((self.inner.record(), cc));
+        f((self.inner.record(), cc, contents));
         self.begin_info = f;
         Recorder::<true> {
             inner: self.inner,
             begin_info: self.begin_info,
+            secondary: self.secondary,
+            contents,
+            subpass: 0,
+            srgb_target: self.srgb_target,
         }
     }
 }
 
 impl Recorder<true> {
     pub fn end_render_pass(mut self) -> Recorder<false> {
+        let post_draws = std::mem::take(&mut self.inner.post_draws);
+        if matches!(self.contents, SubpassContents::SecondaryCommandBuffers) {
+            if !post_draws.is_empty() {
+                log::warn!("Skipped post draws in a secondary subpass");
+            }
+        } else {
+            for post_draw in post_draws {
+                if let Err(err) = post_draw(&mut self) {
+                    log::error!("Post draw failed: {err}");
+                }
+            }
+        }
+
         self.record().end_render_pass().unwrap();
         Recorder::<false> {
             inner: self.inner,
             begin_info: self.begin_info,
+            secondary: self.secondary,
+            contents: SubpassContents::Inline,
+            subpass: 0,
+            srgb_target: self.srgb_target,
         }
     }
+
+    /// Index of the subpass being recorded, see `RenderPassConfig`
+    pub fn subpass(&self) -> u32 {
+        self.subpass
+    }
+
+    /// Move on to the next subpass of the render pass
+    pub fn next_subpass(&mut self) -> Result<()> {
+        self.next_subpass_with(SubpassContents::Inline)
+    }
+
+    /// Move on to the next subpass, recorded with `Recorder::secondary`
+    pub fn next_subpass_secondary(&mut self) -> Result<()> {
+        self.next_subpass_with(SubpassContents::SecondaryCommandBuffers)
+    }
+
+    /// Clear a part of the attachments of the current subpass
+    ///
+    /// `color` clears the color attachment and `depth` the depth
+    /// attachment, see `DepthMode::clear_value` for the usual value.
+    pub fn clear_attachments(
+        &mut self,
+        rect: Rect,
+        color: Option<ClearColor>,
+        depth: Option<f32>,
+    ) -> Result<()> {
+        let srgb_target = self.srgb_target;
+        let attachments = color
+            .map(|cc| ClearAttachment::Color(ClearValue::Float(cc.c(srgb_target)), 0))
+            .into_iter()
+            .chain(depth.map(ClearAttachment::Depth));
+        let rect = ClearRect {
+            rect_offset: rect.origin,
+            rect_extent: rect.extent,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        self.record().clear_attachments(attachments, [rect])?;
+        Ok(())
+    }
+
+    /// Draw with the bound vertex buffers and count it in `stats`
+    pub fn draw(
+        &mut self,
+        vertex_count: u32,
+        instance_count: u32,
+        first_vertex: u32,
+        first_instance: u32,
+    ) -> Result<&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>> {
+        self.count_draw(vertex_count, instance_count);
+        Ok(self
+            .record()
+            .draw(vertex_count, instance_count, first_vertex, first_instance)?)
+    }
+
+    /// Draw with the bound index buffer and count it in `stats`
+    pub fn draw_indexed(
+        &mut self,
+        index_count: u32,
+        instance_count: u32,
+        first_index: u32,
+        vertex_offset: i32,
+        first_instance: u32,
+    ) -> Result<&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>> {
+        self.count_draw(index_count, instance_count);
+        Ok(self.record().draw_indexed(
+            index_count,
+            instance_count,
+            first_index,
+            vertex_offset,
+            first_instance,
+        )?)
+    }
+
+    /// Draw every command of `buffer` with the bound vertex buffers
+    ///
+    /// Not counted in `stats`, the commands may be written by the gpu.
+    pub fn draw_indirect(
+        &mut self,
+        buffer: &DrawIndirectBuffer,
+    ) -> Result<&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>> {
+        buffer.draw(self.record())
+    }
+
+    /// Draw every command of `buffer` with the bound index buffer
+    ///
+    /// Not counted in `stats` either.
+    pub fn draw_indexed_indirect(
+        &mut self,
+        buffer: &DrawIndexedIndirectBuffer,
+    ) -> Result<&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>> {
+        buffer.draw_indexed(self.record())
+    }
+
+    fn next_subpass_with(&mut self, contents: SubpassContents) -> Result<()> {
+        self.record().next_subpass(contents)?;
+        self.contents = contents;
+        self.subpass += 1;
+        Ok(())
+    }
+
+    /// Secondary recorder for the current render pass
+    ///
+    /// The render pass has to be started with `begin_render_pass_secondary`.
+    pub fn secondary(&self) -> Result<SecondaryRecorder> {
+        if !matches!(self.contents, SubpassContents::SecondaryCommandBuffers) {
+            return Err(anyhow!(
+                "Render pass was not started with begin_render_pass_secondary"
+            ));
+        }
+
+        let (queue, framebuffer) = self
+            .secondary
+            .clone()
+            .ok_or_else(|| anyhow!("Recorder has no secondary target"))?;
+        let subpass = Subpass::from(framebuffer.render_pass().clone(), self.subpass)
+            .ok_or_else(|| anyhow!("Render pass has no subpass {}", self.subpass))?;
+
+        Ok(SecondaryRecorder::new(queue, subpass, framebuffer))
+    }
+
+    /// Execute command buffers recorded with `SecondaryRecorder`s in order
+    pub fn execute_secondaries(
+        &mut self,
+        secondaries: Vec<SecondaryAutoCommandBuffer>,
+    ) -> Result<()> {
+        if secondaries.is_empty() {
+            return Ok(());
+        }
+        self.record().execute_commands_from_vec(secondaries)?;
+        Ok(())
+    }
 }
 
+/// sRGB encoded clear color, as it is shown on the screen
+///
+/// Linearized for sRGB targets, so the same value
+/// looks the same on sRGB and UNORM swapchains.
 #[derive(Debug, Clone, Copy)]
 pub struct ClearColor(pub Vec4);
 
 impl ClearColor {
-    fn c(&self) -> [f32; 4] {
-        self.0.to_array()
+    fn c(&self, srgb_target: bool) -> [f32; 4] {
+        Color::from_srgb_vec(self.0).encode(srgb_target).to_array()
+    }
+}
+
+impl From<Color> for ClearColor {
+    fn from(color: Color) -> Self {
+        Self(color.srgb())
     }
 }
 
@@ -1,40 +1,247 @@
-use glam::Vec3;
-use wavefront_obj::obj::Primitive;
+use super::mesh::compute_smooth_normals;
+#[cfg(feature = "gltf")]
+use anyhow::anyhow;
+use anyhow::{bail, Result};
+#[cfg(feature = "gltf")]
+use glam::{Mat4, Vec4};
+use glam::{Vec2, Vec3};
+#[cfg(feature = "gltf")]
+use std::path::Path;
+use std::{f32::consts::PI, ops::Range};
+use wavefront_obj::{mtl::Color, obj::Primitive};
 
+//
+
+/// Material from a `.mtl` file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    pub name: String,
+    pub ambient: Vec3,
+    pub diffuse: Vec3,
+    pub alpha: f32,
+    /// Diffuse texture path (`map_Kd`)
+    pub texture: Option<String>,
+}
+
+/// Vertices that use the same material
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MeshGroup {
+    /// Index into `MaterialMesh::materials`,
+    /// `None` if the group has no known material
+    pub material: Option<usize>,
+    pub vertices: Range<usize>,
+}
+
+/// See `load_obj_mtl`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialMesh<V> {
+    pub vertices: Vec<V>,
+    pub groups: Vec<MeshGroup>,
+    pub materials: Vec<Material>,
+}
+
+/// Triangles of one glTF mesh primitive, see `load_gltf`
+#[cfg(feature = "gltf")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct GltfMesh<V> {
+    pub vertices: Vec<V>,
+    pub indices: Vec<u32>,
+    /// `baseColorFactor` of the material, white without a material
+    pub base_color: Vec4,
+    /// Transform of the node that uses the mesh, it is not applied to the vertices
+    pub transform: Mat4,
+}
+
+//
+
+/// Triangles of the first object in `obj_data`
+///
+/// Panics if `obj_data` is not a valid obj of only
+/// triangles, `load_obj_mtl` returns the error instead.
 pub fn load_obj<V>(
     obj_data: &str,
-    _: Option<&str>,
+    _mtl_data: Option<&str>,
+    construct_vertex: fn(position: Vec3, normal: Vec3) -> V,
+) -> Vec<V> {
+    load_obj_with(obj_data, false, |position, normal, _, _| {
+        construct_vertex(position, normal)
+    })
+    .expect("Invalid obj")
+    .0
+}
+
+/// `load_obj` that smooths the normals missing from the file
+///
+/// `load_obj` uses the face normal for vertices without `vn`,
+/// this averages the face normals around each shared vertex instead.
+pub fn load_obj_smooth<V>(
+    obj_data: &str,
+    _mtl_data: Option<&str>,
     construct_vertex: fn(position: Vec3, normal: Vec3) -> V,
 ) -> Vec<V> {
-    let objset = wavefront_obj::obj::parse(obj_data).unwrap();
-    // TODO: let mtlset = wavefront_obj::mtl::parse(mtl_data).unwrap();
-    let obj = &objset.objects[0];
+    load_obj_with(obj_data, true, |position, normal, _, _| {
+        construct_vertex(position, normal)
+    })
+    .expect("Invalid obj")
+    .0
+}
+
+/// `load_obj` with texture coordinates
+///
+/// `uv` is zero for vertices without a `vt` coordinate.
+/// `v` is flipped to match the Vulkan image origin (top left).
+pub fn load_obj_uv<V>(
+    obj_data: &str,
+    _mtl_data: Option<&str>,
+    construct_vertex: fn(position: Vec3, normal: Vec3, uv: Vec2) -> V,
+) -> Vec<V> {
+    load_obj_with(obj_data, false, |position, normal, uv, _| {
+        construct_vertex(position, normal, uv)
+    })
+    .expect("Invalid obj")
+    .0
+}
+
+/// `load_obj_uv` with `.mtl` materials
+///
+/// Vertices are grouped by the `usemtl` material and
+/// `construct_vertex` receives the material of the vertex.
+pub fn load_obj_mtl<V>(
+    obj_data: &str,
+    mtl_data: &str,
+    construct_vertex: fn(position: Vec3, normal: Vec3, uv: Vec2, material: Option<&Material>) -> V,
+) -> Result<MaterialMesh<V>> {
+    let materials: Vec<Material> = wavefront_obj::mtl::parse(mtl_data)?
+        .materials
+        .into_iter()
+        .map(|material| Material {
+            name: material.name,
+            ambient: color(material.color_ambient),
+            diffuse: color(material.color_diffuse),
+            alpha: material.alpha as f32,
+            texture: material.uv_map,
+        })
+        .collect();
+    let find = |name: Option<&str>| materials.iter().position(|m| Some(m.name.as_str()) == name);
+
+    let (vertices, groups) = load_obj_with(obj_data, false, |position, normal, uv, name| {
+        construct_vertex(position, normal, uv, find(name).map(|i| &materials[i]))
+    })?;
+    let groups = groups
+        .into_iter()
+        .map(|(name, vertices)| MeshGroup {
+            material: find(name.as_deref()),
+            vertices,
+        })
+        .collect();
+
+    Ok(MaterialMesh {
+        vertices,
+        groups,
+        materials,
+    })
+}
+
+/// Load the triangle meshes of the default scene of a `.glb` or `.gltf`
+///
+/// Every primitive of every node becomes one `GltfMesh`, meshes used by
+/// several nodes are loaded once per node. Missing normals are smoothed
+/// like in `load_obj_smooth`, missing texture coordinates are zero.
+/// Buffers have to be in the `.glb` or embedded, `load_gltf_file` also
+/// reads buffers next to the file.
+#[cfg(feature = "gltf")]
+pub fn load_gltf<V>(
+    data: &[u8],
+    construct_vertex: fn(position: Vec3, normal: Vec3, uv: Vec2) -> V,
+) -> Result<Vec<GltfMesh<V>>> {
+    load_gltf_with(data, None, construct_vertex)
+}
+
+/// `load_gltf` from a file
+#[cfg(feature = "gltf")]
+pub fn load_gltf_file<V, P: AsRef<Path>>(
+    path: P,
+    construct_vertex: fn(position: Vec3, normal: Vec3, uv: Vec2) -> V,
+) -> Result<Vec<GltfMesh<V>>> {
+    let path = path.as_ref();
+    let data = std::fs::read(path)?;
+    load_gltf_with(&data, path.parent(), construct_vertex)
+}
+
+fn color(color: Color) -> Vec3 {
+    Vec3::new(color.r as f32, color.g as f32, color.b as f32)
+}
+
+type Groups = Vec<(Option<String>, Range<usize>)>;
+
+fn load_obj_with<V>(
+    obj_data: &str,
+    smooth: bool,
+    construct_vertex: impl Fn(Vec3, Vec3, Vec2, Option<&str>) -> V,
+) -> Result<(Vec<V>, Groups)> {
+    let objset = wavefront_obj::obj::parse(obj_data)?;
+    let obj = match objset.objects.first() {
+        Some(obj) => obj,
+        None => bail!("Obj has no objects"),
+    };
     let i_count = obj
         .geometry
         .iter()
-        .map(|g| {
-            g.shapes
-                .iter()
-                .map(|s| match &s.primitive {
-                    Primitive::Triangle(_, _, _) => 3,
-                    _ => panic!("Only triangles"),
-                })
-                .sum::<usize>()
+        .flat_map(|g| g.shapes.iter())
+        .map(|s| match &s.primitive {
+            Primitive::Triangle(_, _, _) => Ok(3),
+            _ => bail!("Obj has points or lines, only triangles are supported"),
         })
-        .sum::<usize>();
+        .sum::<Result<usize>>()?;
+
+    let position = |id: usize| {
+        let vert = obj.vertices[id];
+        Vec3::new(vert.x as f32, vert.y as f32, vert.z as f32)
+    };
+    // counter clockwise winding, scaled by the area
+    let face_normal = |a: usize, b: usize, c: usize| {
+        let a = position(a);
+        (position(b) - a).cross(position(c) - a)
+    };
+
+    let mut smooth_normals = Vec::new();
+    if smooth {
+        let positions: Vec<Vec3> = (0..obj.vertices.len()).map(position).collect();
+        let indices: Vec<u32> = obj
+            .geometry
+            .iter()
+            .flat_map(|g| g.shapes.iter())
+            .flat_map(|s| match s.primitive {
+                Primitive::Triangle((a, _, _), (b, _, _), (c, _, _)) => [a, b, c],
+                _ => unreachable!(),
+            })
+            .map(|id| id as u32)
+            .collect();
+        smooth_normals = vertex_normals(&positions, &indices);
+    }
 
     // fill vertex&index buffer
     let mut vertices = Vec::<V>::with_capacity(i_count);
+    let mut groups = Groups::new();
     for g in obj.geometry.iter() {
+        let material = g.material_name.as_deref();
+        let first = vertices.len();
         for s in g.shapes.iter() {
             match s.primitive {
                 Primitive::Triangle(
-                    (a_vert_id, _, a_norm_id),
-                    (b_vert_id, _, b_norm_id),
-                    (c_vert_id, _, c_norm_id),
+                    (a_vert_id, a_uv_id, a_norm_id),
+                    (b_vert_id, b_uv_id, b_norm_id),
+                    (c_vert_id, c_uv_id, c_norm_id),
                 ) => {
-                    let id_to_vertex = |vert: usize, norm: Option<usize>| -> V {
-                        let vert = obj.vertices[vert];
+                    let id_to_vertex = |id: usize, uv: Option<usize>, norm: Option<usize>| -> V {
+                        let vert = obj.vertices[id];
+
+                        let uv = if let Some(uv_id) = uv {
+                            let uv = obj.tex_vertices[uv_id];
+                            Vec2::new(uv.u as f32, 1.0 - uv.v as f32)
+                        } else {
+                            Vec2::ZERO
+                        };
 
                         let norm = if let Some(norm_id) = norm {
                             Vec3::new(
@@ -42,36 +249,159 @@ pub fn load_obj<V>(
                                 obj.normals[norm_id].y as f32,
                                 obj.normals[norm_id].z as f32,
                             )
+                        } else if smooth {
+                            smooth_normals[id]
                         } else {
-                            let ab = Vec3::new(
-                                (obj.vertices[b_vert_id].x - obj.vertices[a_vert_id].x) as f32,
-                                (obj.vertices[b_vert_id].y - obj.vertices[a_vert_id].y) as f32,
-                                (obj.vertices[b_vert_id].z - obj.vertices[a_vert_id].z) as f32,
-                            );
-
-                            let ac = Vec3::new(
-                                (obj.vertices[c_vert_id].x - obj.vertices[a_vert_id].x) as f32,
-                                (obj.vertices[c_vert_id].y - obj.vertices[a_vert_id].y) as f32,
-                                (obj.vertices[c_vert_id].z - obj.vertices[a_vert_id].z) as f32,
-                            );
-
-                            ab.normalize().cross(ac.normalize())
+                            face_normal(a_vert_id, b_vert_id, c_vert_id).normalize_or_zero()
                         };
 
                         construct_vertex(
                             Vec3::new(vert.x as f32, vert.y as f32, vert.z as f32),
                             Vec3::new(norm.x as f32, norm.y as f32, norm.z as f32),
+                            uv,
+                            material,
                         )
                     };
 
-                    vertices.push(id_to_vertex(a_vert_id, a_norm_id));
-                    vertices.push(id_to_vertex(b_vert_id, b_norm_id));
-                    vertices.push(id_to_vertex(c_vert_id, c_norm_id));
+                    vertices.push(id_to_vertex(a_vert_id, a_uv_id, a_norm_id));
+                    vertices.push(id_to_vertex(b_vert_id, b_uv_id, b_norm_id));
+                    vertices.push(id_to_vertex(c_vert_id, c_uv_id, c_norm_id));
                 }
-                _ => panic!("Only triangles"),
+                _ => bail!("Obj has points or lines, only triangles are supported"),
             }
         }
+        if first != vertices.len() {
+            groups.push((g.material_name.clone(), first..vertices.len()));
+        }
+    }
+
+    Ok((vertices, groups))
+}
+
+/// Smooth normal of each vertex, the faces around
+/// a vertex position are averaged by their area
+fn vertex_normals(positions: &[Vec3], indices: &[u32]) -> Vec<Vec3> {
+    let mesh = compute_smooth_normals(positions, indices, PI, |&position| position);
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+    for (&source, &normal) in mesh.source.iter().zip(mesh.normals.iter()) {
+        normals[source as usize] = normal;
+    }
+    normals
+}
+
+#[cfg(feature = "gltf")]
+fn load_gltf_with<V>(
+    data: &[u8],
+    base: Option<&Path>,
+    construct_vertex: fn(Vec3, Vec3, Vec2) -> V,
+) -> Result<Vec<GltfMesh<V>>> {
+    let gltf::Gltf { document, blob } = gltf::Gltf::from_slice(data)?;
+    // external buffers are an error without a base path
+    let buffers = gltf::import_buffers(&document, base, blob)?;
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| anyhow!("glTF has no scenes"))?;
+
+    let mut meshes = Vec::new();
+    for node in scene.nodes() {
+        load_gltf_node(
+            &node,
+            Mat4::IDENTITY,
+            &buffers,
+            construct_vertex,
+            &mut meshes,
+        )?;
+    }
+    Ok(meshes)
+}
+
+#[cfg(feature = "gltf")]
+fn load_gltf_node<V>(
+    node: &gltf::Node,
+    parent: Mat4,
+    buffers: &[gltf::buffer::Data],
+    construct_vertex: fn(Vec3, Vec3, Vec2) -> V,
+    meshes: &mut Vec<GltfMesh<V>>,
+) -> Result<()> {
+    let transform = parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+
+    for primitive in node.mesh().iter().flat_map(|mesh| mesh.primitives()) {
+        if primitive.mode() != gltf::mesh::Mode::Triangles {
+            log::warn!("Skipped a glTF primitive that is not a triangle list");
+            continue;
+        }
+
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+        let positions: Vec<Vec3> = reader
+            .read_positions()
+            .ok_or_else(|| anyhow!("glTF primitive has no positions"))?
+            .map(Vec3::from)
+            .collect();
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        if let Some(index) = indices.iter().find(|&&i| i as usize >= positions.len()) {
+            return Err(anyhow!("glTF index {index} is out of bounds"));
+        }
+
+        let normals: Vec<Vec3> = match reader.read_normals() {
+            Some(normals) => normals.map(Vec3::from).collect(),
+            None => vertex_normals(&positions, &indices),
+        };
+        let uvs: Vec<Vec2> = match reader.read_tex_coords(0) {
+            Some(uvs) => uvs.into_f32().map(Vec2::from).collect(),
+            None => vec![Vec2::ZERO; positions.len()],
+        };
+
+        let vertices = positions
+            .into_iter()
+            .zip(normals)
+            .zip(uvs)
+            .map(|((position, normal), uv)| construct_vertex(position, normal, uv))
+            .collect();
+        let base_color = primitive
+            .material()
+            .pbr_metallic_roughness()
+            .base_color_factor();
+
+        meshes.push(GltfMesh {
+            vertices,
+            indices,
+            base_color: Vec4::from(base_color),
+            transform,
+        });
+    }
+
+    for child in node.children() {
+        load_gltf_node(&child, transform, buffers, construct_vertex, meshes)?;
     }
+    Ok(())
+}
 
-    vertices
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // counter clockwise seen from +z, without normals
+    const QUAD: &str = "o quad
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+f 1 2 3
+f 1 3 4
+";
+
+    #[test]
+    fn missing_normals_face_the_winding() {
+        let flat = load_obj(QUAD, None, |_, normal| normal);
+        let smooth = load_obj_smooth(QUAD, None, |_, normal| normal);
+        assert_eq!(flat.len(), 6);
+        assert_eq!(smooth.len(), 6);
+        for normal in flat.into_iter().chain(smooth) {
+            assert!(normal.abs_diff_eq(Vec3::Z, 1e-6), "{normal}");
+        }
+    }
 }
@@ -0,0 +1,116 @@
+use super::{
+    simple_renderer::Renderer,
+    text::{TextBatch, TextRenderer},
+    viewport::Rect,
+    Recorder,
+};
+use crate::{game_loop::State, report::FrameStats, time::RateReporter};
+use anyhow::Result;
+use glam::{Vec2, Vec4};
+use std::time::Duration;
+
+//
+
+/// On-screen FPS, frame time, triangle and draw call count
+///
+/// Followed by the average time of each `State::span_reporter`.
+///
+/// Drawn in the top left corner with a `TextRenderer`.
+/// Nothing is uploaded or recorded while the overlay is disabled.
+pub struct DebugOverlay {
+    text: TextRenderer,
+
+    enabled: bool,
+    scale: u32,
+    color: Vec4,
+}
+
+//
+
+impl DebugOverlay {
+    /// Disabled by default
+    pub fn new(renderer: &Renderer) -> Result<Self> {
+        Ok(Self {
+            text: TextRenderer::new(renderer)?,
+
+            enabled: false,
+            scale: 2,
+            color: Vec4::ONE,
+        })
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Screen pixels per font pixel, 2 by default
+    pub fn set_scale(&mut self, scale: u32) {
+        self.scale = scale.max(1);
+    }
+
+    /// White by default
+    pub fn set_color(&mut self, color: Vec4) {
+        self.color = color;
+    }
+
+    /// Draw the overlay in the top left corner of `rect`
+    ///
+    /// Sets the viewport and scissor to the whole frame
+    /// and binds its own pipeline, draw it after everything else.
+    pub fn draw(&self, recorder: &mut Recorder<true>, state: &State, rect: Rect) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        // the draws recorded before the overlay
+        let text = Self::lines(state, recorder.stats()).join("\n");
+        let scale = self.scale as f32;
+        let position = Vec2::new(rect.origin[0] as f32, rect.origin[1] as f32) + scale;
+
+        // one font pixel drop shadow for readability
+        let shadow = Vec4::new(0.0, 0.0, 0.0, self.color.w);
+        let mut batch = TextBatch::new();
+        batch
+            .push(&text, position + scale, scale, shadow)
+            .push(&text, position, scale, self.color);
+
+        self.text.draw_batch(recorder, &batch)
+    }
+
+    fn lines(state: &State, stats: FrameStats) -> Vec<String> {
+        let ms = |interval: Option<Duration>| {
+            interval
+                .map(|interval| format!("{:.2}MS", interval.as_secs_f64() * 1000.0))
+                .unwrap_or_else(|| "...".into())
+        };
+        let last = |reporter: &RateReporter| reporter.last().map(|(interval, _)| interval);
+        let fps = state
+            .cpu_frame_reporter
+            .last()
+            .map(|(_, per_second)| format!("{:.1}", per_second))
+            .unwrap_or_else(|| "...".into());
+
+        let mut lines = vec![
+            format!("FPS {}", fps),
+            format!("CPU {}", ms(last(&state.cpu_frame_reporter))),
+            format!("GPU {}", ms(last(&state.gpu_frame_reporter))),
+            format!("TRIS {}", state.draw_reporter.last().triangles),
+            format!("DRAWS {}", stats.draw_calls),
+        ];
+        // app spans are timed rarely, so show the recent average
+        lines.extend(
+            state.span_reporters.iter().map(|(name, reporter)| {
+                format!("{} {}", name.to_uppercase(), ms(reporter.average()))
+            }),
+        );
+        lines
+    }
+}
@@ -1,13 +1,533 @@
-/* use vulkano::pipeline::{GraphicsPipeline, shader::GraphicsEntryPoint};
+use super::{device::Dev, simple_renderer::Renderer};
+use crate::DepthMode;
+use std::{collections::HashMap, hash::Hash, sync::Arc};
+use vulkano::{
+    descriptor_set::layout::{DescriptorSetLayoutCreateInfo, DescriptorType},
+    device::Device,
+    pipeline::{
+        graphics::{
+            color_blend::{AttachmentBlend, BlendFactor, BlendOp, ColorBlendState},
+            depth_stencil::{CompareOp, DepthStencilState},
+            input_assembly::{InputAssemblyState, PrimitiveTopology},
+            rasterization::{CullMode, FrontFace, RasterizationState},
+            vertex_input::{BuffersDefinition, Vertex},
+            viewport::ViewportState,
+            GraphicsPipelineCreationError,
+        },
+        GraphicsPipeline, StateMode,
+    },
+    render_pass::{RenderPass, Subpass},
+    shader::{EntryPoint, SpecializationConstants},
+};
 
-pub struct Pipeline {}
+//
 
-impl Pipeline {
-    pub fn graphics(modules: &[GraphicsEntryPoint]) -> GraphicsPipeline {
-        let
+/// Viewport and scissor are dynamic state,
+/// see `Recorder::set_viewport_and_scissor`.
+///
+/// Back faces are culled and front faces are counter clockwise
+/// with the Y flipping projection of `scene::Camera` by default,
+/// see `with_cull_mode` and `with_front_face`.
+///
+/// The depth test follows the `DepthMode` of the renderer and
+/// the `DepthConfig`, it is disabled in subpasses without a depth attachment.
+///
+/// The `BlendMode` applies to every color attachment of the subpass.
+///
+/// `Vs`, `Gs` and `Fs` are the specialization constants of the
+/// shaders, see `with_fragment_specialization`.
+pub struct GPipelineBuilder<'a, Vs = (), Gs = (), Fs = ()> {
+    device: Arc<Device>,
+    subpass: Subpass,
 
-        GraphicsPipeline::start()
-        .vertex_shader(shader, specialization_constants)
-        Self {}
+    vert: Option<EntryPoint<'a>>,
+    geom: Option<EntryPoint<'a>>,
+    frag: Option<EntryPoint<'a>>,
+
+    vertex_input: BuffersDefinition,
+    topology: PrimitiveTopology,
+    indexed: bool,
+    depth_mode: DepthMode,
+    depth: DepthConfig,
+    blend: BlendMode,
+    cull_mode: CullMode,
+    front_face: FrontFace,
+
+    // (set, binding)
+    dynamic_uniforms: Vec<(u32, u32)>,
+
+    specialization: (Vs, Gs, Fs),
+}
+
+/// Pipelines built once per key
+///
+/// Specialization constants are baked into the pipeline, so every
+/// combination of constants needs its own pipeline. Key the variants
+/// with the constant values, e.g. a light count.
+pub struct PipelineVariants<K> {
+    pipelines: HashMap<K, Arc<GraphicsPipeline>>,
+}
+
+/// Depth test and write of a pipeline
+///
+/// Defaults to testing with the `DepthMode` compare op and writing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepthConfig {
+    /// Reject fragments that fail `compare`,
+    /// disable it to draw over the scene, e.g. a HUD
+    pub test: bool,
+
+    /// Write the depth of the fragments that pass,
+    /// disable it for translucent geometry
+    pub write: bool,
+
+    /// `None` uses the compare op of the `DepthMode`
+    pub compare: Option<CompareOp>,
+}
+
+/// How fragment colors are combined with the color attachments
+///
+/// Translucent geometry should be drawn after the opaque geometry,
+/// sorted back to front and usually without depth writes,
+/// see `GPipelineBuilder::with_depth`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// No blending, the fragment replaces the color
+    Opaque,
+
+    /// `src * src_alpha + dst * (1 - src_alpha)`
+    AlphaBlend,
+
+    /// `src + dst * (1 - src_alpha)`, for colors already multiplied with their alpha
+    PremultipliedAlpha,
+
+    /// `src * src_alpha + dst`, e.g. for particles and glow
+    Additive,
+
+    /// `op(src * src_factor, dst * dst_factor)` for color and alpha separately
+    Custom {
+        src: BlendFactor,
+        dst: BlendFactor,
+        op: BlendOp,
+        alpha_src: BlendFactor,
+        alpha_dst: BlendFactor,
+        alpha_op: BlendOp,
+    },
+}
+
+//
+
+impl Default for DepthConfig {
+    fn default() -> Self {
+        Self {
+            test: true,
+            write: true,
+            compare: None,
+        }
+    }
+}
+
+impl DepthConfig {
+    /// Test without writing, for translucent geometry
+    pub const READ_ONLY: Self = Self {
+        test: true,
+        write: false,
+        compare: None,
+    };
+
+    /// No depth test or write, draws over everything drawn before
+    pub const DISABLED: Self = Self {
+        test: false,
+        write: false,
+        compare: None,
+    };
+
+    fn depth_stencil_state(self, depth_mode: DepthMode) -> DepthStencilState {
+        if !self.test && !self.write {
+            return DepthStencilState::disabled();
+        }
+
+        // writes need the test enabled, so always pass it instead
+        let compare = if self.test {
+            self.compare.unwrap_or_else(|| depth_mode.compare_op())
+        } else {
+            CompareOp::Always
+        };
+
+        let mut state = depth_mode.depth_stencil_state();
+        if let Some(depth) = state.depth.as_mut() {
+            depth.write_enable = StateMode::Fixed(self.write);
+            depth.compare_op = StateMode::Fixed(compare);
+        }
+        state
+    }
+}
+
+impl Default for BlendMode {
+    fn default() -> Self {
+        BlendMode::Opaque
+    }
+}
+
+impl BlendMode {
+    /// `None` for `Opaque`
+    pub fn attachment_blend(self) -> Option<AttachmentBlend> {
+        let (src, dst, op, alpha_src, alpha_dst, alpha_op) = match self {
+            BlendMode::Opaque => return None,
+            BlendMode::AlphaBlend => return Some(AttachmentBlend::alpha()),
+            BlendMode::PremultipliedAlpha => (
+                BlendFactor::One,
+                BlendFactor::OneMinusSrcAlpha,
+                BlendOp::Add,
+                BlendFactor::One,
+                BlendFactor::OneMinusSrcAlpha,
+                BlendOp::Add,
+            ),
+            BlendMode::Additive => (
+                BlendFactor::SrcAlpha,
+                BlendFactor::One,
+                BlendOp::Add,
+                BlendFactor::One,
+                BlendFactor::One,
+                BlendOp::Add,
+            ),
+            BlendMode::Custom {
+                src,
+                dst,
+                op,
+                alpha_src,
+                alpha_dst,
+                alpha_op,
+            } => (src, dst, op, alpha_src, alpha_dst, alpha_op),
+        };
+
+        Some(AttachmentBlend {
+            color_op: op,
+            color_source: src,
+            color_destination: dst,
+            alpha_op,
+            alpha_source: alpha_src,
+            alpha_destination: alpha_dst,
+        })
+    }
+
+    /// Blend state for `attachments` color attachments
+    pub fn color_blend_state(self, attachments: u32) -> ColorBlendState {
+        let state = ColorBlendState::new(attachments);
+        match self.attachment_blend() {
+            Some(blend) => state.blend(blend),
+            None => state,
+        }
+    }
+}
+
+impl<'a> GPipelineBuilder<'a> {
+    pub fn new(renderer: &Renderer) -> Self {
+        Self::new_with_render_pass(&renderer.device, renderer.render_pass())
+            .with_depth_mode(renderer.depth_mode())
+    }
+
+    /// Pipeline for the first subpass of `render_pass` without a `Renderer`,
+    /// e.g. for a `HeadlessRenderer`
+    ///
+    /// The depth mode defaults to `DepthMode::Standard`.
+    pub fn new_with_render_pass(device: &Dev, render_pass: Arc<RenderPass>) -> Self {
+        Self {
+            device: device.logical().clone(),
+            subpass: Subpass::from(render_pass, 0).unwrap(),
+
+            vert: None,
+            geom: None,
+            frag: None,
+
+            vertex_input: BuffersDefinition::new(),
+            topology: PrimitiveTopology::TriangleList,
+            indexed: false,
+            depth_mode: DepthMode::default(),
+            depth: DepthConfig::default(),
+            blend: BlendMode::Opaque,
+            cull_mode: CullMode::Back,
+            front_face: FrontFace::CounterClockwise,
+
+            dynamic_uniforms: Vec::new(),
+
+            specialization: ((), (), ()),
+        }
+    }
+}
+
+impl<'a, Vs, Gs, Fs> GPipelineBuilder<'a, Vs, Gs, Fs> {
+    /// Draw into another render pass, e.g. a `RenderTexture`
+    ///
+    /// Resets the subpass to 0.
+    pub fn with_render_pass(mut self, render_pass: Arc<RenderPass>) -> Self {
+        self.subpass = Subpass::from(render_pass, 0).unwrap();
+        self
+    }
+
+    /// Draw in another subpass of the render pass, defaults to 0
+    ///
+    /// See `RenderPassConfig`.
+    pub fn with_subpass(mut self, subpass: u32) -> Self {
+        self.subpass = Subpass::from(self.subpass.render_pass().clone(), subpass)
+            .expect("Subpass index out of range");
+        self
+    }
+
+    pub fn with_vertex_shader(mut self, entry_point: EntryPoint<'a>) -> Self {
+        self.vert = Some(entry_point);
+        self
+    }
+
+    pub fn with_geometry_shader(mut self, entry_point: EntryPoint<'a>) -> Self {
+        self.geom = Some(entry_point);
+        self
+    }
+
+    pub fn with_fragment_shader(mut self, entry_point: EntryPoint<'a>) -> Self {
+        self.frag = Some(entry_point);
+        self
+    }
+
+    /// Per vertex input buffer
+    pub fn with_input<V: Vertex>(mut self) -> Self {
+        self.vertex_input = self.vertex_input.vertex::<V>();
+        self
+    }
+
+    /// Defaults to `TriangleList`
+    ///
+    /// Vertices or indices consumed per primitive:
+    /// - `PointList`: 1 per point
+    /// - `LineList`: 2 per line
+    /// - `LineStrip`: 2 for the first line and 1 for each line after it
+    /// - `TriangleList`: 3 per triangle
+    /// - `TriangleStrip`: 3 for the first triangle and 1 for each triangle after it
+    /// - `TriangleFan`: 3 for the first triangle and 1 for each triangle after it
+    ///
+    /// Strips and fans drawn with an index buffer can be split with
+    /// the primitive restart index (`u16::MAX` or `u32::MAX`), see `with_indexed`.
+    pub fn with_topology(mut self, topology: PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// Will the pipeline be drawn with an index buffer.
+    ///
+    /// Enables primitive restart for strip and fan topologies.
+    pub fn with_indexed(mut self, indexed: bool) -> Self {
+        self.indexed = indexed;
+        self
+    }
+
+    /// Defaults to the `DepthMode` of the renderer,
+    /// set it when drawing into a `RenderTexture` with another mode
+    pub fn with_depth_mode(mut self, depth_mode: DepthMode) -> Self {
+        self.depth_mode = depth_mode;
+        self
+    }
+
+    /// Defaults to testing and writing, see `DepthConfig`
+    pub fn with_depth(mut self, depth: DepthConfig) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Write the depth of drawn fragments, defaults to `true`
+    ///
+    /// The depth test stays enabled, disable the writes
+    /// for translucent geometry drawn after the opaque geometry.
+    pub fn with_depth_write(mut self, depth_write: bool) -> Self {
+        self.depth.write = depth_write;
+        self
+    }
+
+    /// Defaults to `BlendMode::Opaque`
+    pub fn with_blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    /// Defaults to `CullMode::Back`, `CullMode::None` draws both sides
+    pub fn with_cull_mode(mut self, cull_mode: CullMode) -> Self {
+        self.cull_mode = cull_mode;
+        self
+    }
+
+    /// Defaults to `FrontFace::CounterClockwise`, the winding of most exporters
+    pub fn with_front_face(mut self, front_face: FrontFace) -> Self {
+        self.front_face = front_face;
+        self
+    }
+
+    /// Make a uniform buffer binding `UNIFORM_BUFFER_DYNAMIC`
+    ///
+    /// See `UniformArrayBuffer`.
+    pub fn with_dynamic_uniform(mut self, set: u32, binding: u32) -> Self {
+        self.dynamic_uniforms.push((set, binding));
+        self
+    }
+
+    /// Values of the `layout(constant_id = N)` constants of the vertex shader
+    ///
+    /// `vulkano_shaders::shader!` generates a `SpecializationConstants`
+    /// struct for each shader module, the defaults come from the shader.
+    pub fn with_vertex_specialization<S>(self, vs: S) -> GPipelineBuilder<'a, S, Gs, Fs> {
+        self.specialize(|(_, gs, fs)| (vs, gs, fs))
+    }
+
+    /// See `with_vertex_specialization`
+    pub fn with_geometry_specialization<S>(self, gs: S) -> GPipelineBuilder<'a, Vs, S, Fs> {
+        self.specialize(|(vs, _, fs)| (vs, gs, fs))
+    }
+
+    /// See `with_vertex_specialization`
+    pub fn with_fragment_specialization<S>(self, fs: S) -> GPipelineBuilder<'a, Vs, Gs, S> {
+        self.specialize(|(vs, gs, _)| (vs, gs, fs))
+    }
+
+    pub fn build(self) -> Result<Arc<GraphicsPipeline>, GraphicsPipelineCreationError>
+    where
+        Vs: SpecializationConstants,
+        Gs: SpecializationConstants,
+        Fs: SpecializationConstants,
+    {
+        let (vs, gs, fs) = self.specialization;
+        let restart = self.indexed
+            && matches!(
+                self.topology,
+                PrimitiveTopology::LineStrip
+                    | PrimitiveTopology::TriangleStrip
+                    | PrimitiveTopology::TriangleFan
+            );
+        let mut input_assembly = InputAssemblyState::new().topology(self.topology);
+        if restart {
+            input_assembly = input_assembly.primitive_restart_enable();
+        }
+
+        let depth_stencil = if self.subpass.has_depth() {
+            self.depth.depth_stencil_state(self.depth_mode)
+        } else {
+            DepthStencilState::disabled()
+        };
+        let color_blend = self
+            .blend
+            .color_blend_state(self.subpass.num_color_attachments());
+
+        let builder = GraphicsPipeline::start()
+            //
+            .input_assembly_state(input_assembly)
+            //
+            .vertex_input_state(self.vertex_input)
+            .vertex_shader(self.vert.expect("Vertex shader is mandatory"), vs)
+            .viewport_state(ViewportState::viewport_dynamic_scissor_dynamic(1))
+            //
+            .fragment_shader(self.frag.expect("Fragment shader is mandatory"), fs)
+            .depth_stencil_state(depth_stencil)
+            .color_blend_state(color_blend)
+            //
+            .rasterization_state(
+                RasterizationState::new()
+                    .cull_mode(self.cull_mode)
+                    .front_face(self.front_face),
+            )
+            .render_pass(self.subpass);
+
+        let dynamic_uniforms = self.dynamic_uniforms;
+        let layout = move |sets: &mut [DescriptorSetLayoutCreateInfo]| {
+            for &(set, binding) in dynamic_uniforms.iter() {
+                if let Some(binding) = sets
+                    .get_mut(set as usize)
+                    .and_then(|set| set.bindings.get_mut(&binding))
+                {
+                    binding.descriptor_type = DescriptorType::UniformBufferDynamic;
+                } else {
+                    log::warn!("Dynamic uniform (set: {set}, binding: {binding}) not found");
+                }
+            }
+        };
+
+        if let Some(geom) = self.geom {
+            builder
+                .geometry_shader(geom, gs)
+                .with_auto_layout(self.device, layout)
+        } else {
+            builder.with_auto_layout(self.device, layout)
+        }
+    }
+
+    fn specialize<Vs2, Gs2, Fs2, F>(self, f: F) -> GPipelineBuilder<'a, Vs2, Gs2, Fs2>
+    where
+        F: FnOnce((Vs, Gs, Fs)) -> (Vs2, Gs2, Fs2),
+    {
+        GPipelineBuilder {
+            device: self.device,
+            subpass: self.subpass,
+
+            vert: self.vert,
+            geom: self.geom,
+            frag: self.frag,
+
+            vertex_input: self.vertex_input,
+            topology: self.topology,
+            indexed: self.indexed,
+            depth_mode: self.depth_mode,
+            depth: self.depth,
+            blend: self.blend,
+            cull_mode: self.cull_mode,
+            front_face: self.front_face,
+
+            dynamic_uniforms: self.dynamic_uniforms,
+
+            specialization: f(self.specialization),
+        }
+    }
+}
+
+impl<K: Eq + Hash> Default for PipelineVariants<K> {
+    fn default() -> Self {
+        Self {
+            pipelines: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Eq + Hash> PipelineVariants<K> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The variant of `key`, built with `build` the first time
+    pub fn get_or_build<F>(
+        &mut self,
+        key: K,
+        build: F,
+    ) -> Result<Arc<GraphicsPipeline>, GraphicsPipelineCreationError>
+    where
+        F: FnOnce(&K) -> Result<Arc<GraphicsPipeline>, GraphicsPipelineCreationError>,
+    {
+        if let Some(pipeline) = self.pipelines.get(&key) {
+            return Ok(pipeline.clone());
+        }
+        let pipeline = build(&key)?;
+        self.pipelines.insert(key, pipeline.clone());
+        Ok(pipeline)
+    }
+
+    pub fn get(&self, key: &K) -> Option<&Arc<GraphicsPipeline>> {
+        self.pipelines.get(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.is_empty()
+    }
+
+    /// Drop every variant, e.g. after the render pass changed
+    pub fn clear(&mut self) {
+        self.pipelines.clear();
     }
-} */
+}
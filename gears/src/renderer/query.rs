@@ -2,7 +2,10 @@ use super::{device::Dev, Recorder};
 use std::{sync::Arc, time::Duration};
 use vulkano::{
     command_buffer::AutoCommandBufferBuilder,
-    query::{GetResultsError, QueryPool, QueryResultFlags, QueryType},
+    query::{
+        GetResultsError, QueryControlFlags, QueryPipelineStatisticFlags, QueryPool,
+        QueryResultFlags, QueryType,
+    },
     sync::PipelineStage,
 };
 
@@ -23,6 +26,31 @@ pub trait RecordPerf {
     fn end_perf(&mut self, perf: &PerfQuery) -> &'_ mut Self;
 }
 
+/// Counts of the work the GPU did between `begin_stats` and `end_stats`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PipelineStats {
+    /// Vertices read by the input assembly
+    pub vertices: u64,
+    /// Primitives read by the input assembly
+    pub primitives: u64,
+    /// Fragment shader invocations
+    pub fragments: u64,
+}
+
+/// Opt-in pipeline statistics query
+///
+/// Does nothing if the device does not
+/// support `pipeline_statistics_query`.
+pub struct PipelineStatsQuery {
+    query_pool: Option<Arc<QueryPool>>,
+}
+
+pub trait RecordPipelineStats {
+    fn reset_stats(&mut self, stats: &PipelineStatsQuery) -> &'_ mut Self;
+    fn begin_stats(&mut self, stats: &PipelineStatsQuery) -> &'_ mut Self;
+    fn end_stats(&mut self, stats: &PipelineStatsQuery) -> &'_ mut Self;
+}
+
 //
 
 impl PerfQuery {
@@ -97,3 +125,109 @@ impl<L, P> RecordPerf for AutoCommandBufferBuilder<L, P> {
         self
     }
 }
+
+impl PipelineStatsQuery {
+    pub fn new_with_device(device: &Dev) -> Self {
+        if !device
+            .logical()
+            .enabled_features()
+            .pipeline_statistics_query
+        {
+            log::warn!("Pipeline statistics queries are not supported");
+            return Self { query_pool: None };
+        }
+
+        let flags = QueryPipelineStatisticFlags {
+            input_assembly_vertices: true,
+            input_assembly_primitives: true,
+            fragment_shader_invocations: true,
+            ..QueryPipelineStatisticFlags::none()
+        };
+        let query_pool = QueryPool::new(
+            device.logical().clone(),
+            QueryType::PipelineStatistics(flags),
+            1,
+        )
+        .expect("Could not create a query pool");
+
+        Self {
+            query_pool: Some(query_pool),
+        }
+    }
+
+    pub fn is_supported(&self) -> bool {
+        self.query_pool.is_some()
+    }
+
+    pub fn reset(&self, recorder: &mut Recorder<false>) {
+        recorder.record().reset_stats(self);
+    }
+
+    pub fn begin(&self, recorder: &mut Recorder<false>) {
+        recorder.record().begin_stats(self);
+    }
+
+    pub fn end(&self, recorder: &mut Recorder<false>) {
+        recorder.record().end_stats(self);
+    }
+
+    /// `None` if the results are not available yet
+    /// or if the query is not supported
+    pub fn get(&self) -> Option<PipelineStats> {
+        let query_pool = self.query_pool.as_ref()?;
+
+        // results are in the order of the flag bits
+        let mut data = [0_u64; 3];
+        match query_pool.queries_range(0..1).unwrap().get_results(
+            &mut data,
+            QueryResultFlags {
+                wait: false,
+                with_availability: false,
+                partial: false,
+            },
+        ) {
+            Ok(true) => {}
+            Ok(false) => return None,
+            Err(err) => panic!("{}", err),
+        };
+
+        Some(PipelineStats {
+            vertices: data[0],
+            primitives: data[1],
+            fragments: data[2],
+        })
+    }
+}
+
+impl<L, P> RecordPipelineStats for AutoCommandBufferBuilder<L, P> {
+    fn reset_stats(&mut self, stats: &PipelineStatsQuery) -> &'_ mut Self {
+        if let Some(query_pool) = stats.query_pool.as_ref() {
+            // TODO: get rid of this unsafe
+            unsafe {
+                self.reset_query_pool(query_pool.clone(), 0..1).unwrap();
+            }
+        }
+        self
+    }
+
+    fn begin_stats(&mut self, stats: &PipelineStatsQuery) -> &'_ mut Self {
+        if let Some(query_pool) = stats.query_pool.as_ref() {
+            // TODO: get rid of this unsafe
+            unsafe {
+                self.begin_query(query_pool.clone(), 0, QueryControlFlags { precise: false })
+                    .unwrap();
+            }
+        }
+        self
+    }
+
+    fn end_stats(&mut self, stats: &PipelineStatsQuery) -> &'_ mut Self {
+        if let Some(query_pool) = stats.query_pool.as_ref() {
+            // TODO: get rid of this unsafe
+            unsafe {
+                self.end_query(query_pool.clone(), 0).unwrap();
+            }
+        }
+        self
+    }
+}
@@ -13,23 +13,24 @@ use winit::window::Window;
 pub struct QueueFamilies<'a> {
     pub present: QueueFamily<'a>,
     pub graphics: QueueFamily<'a>,
-    /* pub transfer: QueueFamily<'a>, */
+    /// dedicated transfer queue family if available, graphics otherwise
+    pub transfer: QueueFamily<'a>,
 }
 
 pub struct Queues {
     pub present: Arc<Queue>,
     pub graphics: Arc<Queue>,
-    /* pub transfer: Arc<Queue>, */
+    pub transfer: Arc<Queue>,
 }
 
 impl<'a> QueueFamilies<'a> {
     pub fn new(
-        surface: &Arc<Surface<Window>>,
+        surface: &Arc<Surface<Arc<Window>>>,
         p_device: PhysicalDevice<'a>,
     ) -> Result<Option<Self>, ContextError> {
         let mut present = None;
         let mut graphics = None;
-        /* let mut transfer = None; */
+        let mut transfer = None;
 
         let queue_family_properties = p_device.queue_families();
 
@@ -39,7 +40,8 @@ impl<'a> QueueFamilies<'a> {
                 .map_err(ContextError::CapabilitiesError)?;
 
             let graphics_support = queue_family_property.supports_graphics();
-            /* let transfer_support = queue_family_property.explicitly_supports_transfers(); */
+            let transfer_support = queue_family_property.explicitly_supports_transfers()
+                && !queue_family_property.supports_graphics();
 
             if present_support && present.is_none() {
                 present = Some(queue_family_property);
@@ -47,21 +49,22 @@ impl<'a> QueueFamilies<'a> {
             if graphics_support && graphics.is_none() {
                 graphics = Some(queue_family_property);
             }
-            /* if transfer_support && transfer.is_none() {
+            if transfer_support && transfer.is_none() {
                 transfer = Some(queue_family_property);
-            } */
-
-            if let (Some(present), Some(graphics) /* , Some(transfer) */) =
-                (present, graphics /* , transfer */)
-            {
-                return Ok(Some(Self {
-                    present,
-                    graphics,
-                    /* transfer, */
-                }));
             }
         }
 
+        if let (Some(present), Some(graphics)) = (present, graphics) {
+            // fall back to the graphics queue if there is no dedicated transfer queue
+            let transfer = transfer.unwrap_or(graphics);
+
+            return Ok(Some(Self {
+                present,
+                graphics,
+                transfer,
+            }));
+        }
+
         log::debug!(
             "{} is not suitable: (present, graphics) = {:?}",
             p_device.properties().device_name.blue(),
@@ -71,27 +74,57 @@ impl<'a> QueueFamilies<'a> {
         Ok(None)
     }
 
+    /// Graphics and transfer families without a surface,
+    /// presenting uses the graphics family
+    pub fn headless(p_device: PhysicalDevice<'a>) -> Option<Self> {
+        let graphics = p_device
+            .queue_families()
+            .find(|queue_family| queue_family.supports_graphics())?;
+        let transfer = p_device
+            .queue_families()
+            .find(|queue_family| {
+                queue_family.explicitly_supports_transfers() && !queue_family.supports_graphics()
+            })
+            .unwrap_or(graphics);
+
+        Some(Self {
+            present: graphics,
+            graphics,
+            transfer,
+        })
+    }
+
     pub fn get(&self) -> Vec<QueueCreateInfo<'_>> {
-        if self.present == self.graphics {
-            vec![QueueCreateInfo::family(self.present)]
-        } else {
-            vec![
-                QueueCreateInfo::family(self.present),
-                QueueCreateInfo::family(self.graphics),
-            ]
+        self.unique()
+            .into_iter()
+            .map(QueueCreateInfo::family)
+            .collect()
+    }
+
+    pub fn get_queues(&self, queue_iter: impl ExactSizeIterator<Item = Arc<Queue>>) -> Queues {
+        let queues = queue_iter.collect::<Vec<_>>();
+        let find = |family: QueueFamily| {
+            queues
+                .iter()
+                .find(|queue| queue.family() == family)
+                .expect("Missing queue")
+                .clone()
+        };
+
+        Queues {
+            present: find(self.present),
+            graphics: find(self.graphics),
+            transfer: find(self.transfer),
         }
     }
 
-    pub fn get_queues(&self, mut queue_iter: impl ExactSizeIterator<Item = Arc<Queue>>) -> Queues {
-        let present = queue_iter.next().expect("Missing queue");
-        if self.present == self.graphics {
-            Queues {
-                present: present.clone(),
-                graphics: present,
+    fn unique(&self) -> Vec<QueueFamily<'a>> {
+        let mut families = vec![self.present];
+        for family in [self.graphics, self.transfer] {
+            if !families.contains(&family) {
+                families.push(family);
             }
-        } else {
-            let graphics = queue_iter.next().expect("Missing queue");
-            Queues { present, graphics }
         }
+        families
     }
 }
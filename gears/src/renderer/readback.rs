@@ -0,0 +1,66 @@
+use anyhow::{anyhow, Result};
+use parking_lot::Mutex;
+use std::{sync::Arc, time::Duration};
+use vulkano::{
+    buffer::CpuAccessibleBuffer,
+    sync::{FenceSignalFuture, GpuFuture},
+};
+
+//
+
+pub(super) type FrameFuture = FenceSignalFuture<Box<dyn GpuFuture>>;
+
+/// Fence of the frame that a readback was recorded in
+///
+/// Set when the recorder is submitted.
+#[derive(Clone, Default)]
+pub(super) struct ReadbackFence {
+    future: Arc<Mutex<Option<Arc<FrameFuture>>>>,
+}
+
+/// Device to host copy recorded with `StagedBuffer::read_back`
+pub struct ReadbackHandle<T> {
+    buffer: Arc<CpuAccessibleBuffer<[T]>>,
+    fence: ReadbackFence,
+}
+
+//
+
+impl ReadbackFence {
+    pub(super) fn signal(&self, future: Arc<FrameFuture>) {
+        *self.future.lock() = Some(future);
+    }
+}
+
+impl<T> ReadbackHandle<T>
+where
+    T: Copy + Send + Sync + 'static,
+{
+    pub(super) fn new(buffer: Arc<CpuAccessibleBuffer<[T]>>, fence: ReadbackFence) -> Self {
+        Self { buffer, fence }
+    }
+
+    /// Non blocking check if the frame has finished
+    pub fn is_ready(&self) -> bool {
+        match self.fence.future.lock().as_ref() {
+            Some(future) => future.wait(Some(Duration::from_secs(0))).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Blocks until the frame has finished and copies the data out
+    ///
+    /// Fails if the frame was never submitted.
+    pub fn wait(self) -> Result<Vec<T>> {
+        let future = self
+            .fence
+            .future
+            .lock()
+            .clone()
+            .ok_or_else(|| anyhow!("The frame with the readback was not submitted"))?;
+        future.wait(None)?;
+
+        let data = self.buffer.read()?.to_vec();
+        Ok(data)
+    }
+}
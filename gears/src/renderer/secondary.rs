@@ -0,0 +1,67 @@
+use anyhow::Result;
+use std::sync::Arc;
+use vulkano::{
+    command_buffer::{
+        AutoCommandBufferBuilder, CommandBufferInheritanceInfo,
+        CommandBufferInheritanceRenderPassInfo, CommandBufferUsage, SecondaryAutoCommandBuffer,
+    },
+    device::Queue,
+    render_pass::{Framebuffer, Subpass},
+};
+
+//
+
+pub type SecondaryBuilder = AutoCommandBufferBuilder<SecondaryAutoCommandBuffer>;
+
+/// Records draws into the render pass of a `Recorder<true>`
+/// from any thread, see `Recorder::secondary`
+///
+/// Command buffer builders can't be sent between threads,
+/// so this holds only the render pass info and each
+/// `record` call allocates its builder on the calling thread.
+#[derive(Clone)]
+pub struct SecondaryRecorder {
+    queue: Arc<Queue>,
+    subpass: Subpass,
+    framebuffer: Arc<Framebuffer>,
+}
+
+//
+
+impl SecondaryRecorder {
+    pub(super) fn new(queue: Arc<Queue>, subpass: Subpass, framebuffer: Arc<Framebuffer>) -> Self {
+        Self {
+            queue,
+            subpass,
+            framebuffer,
+        }
+    }
+
+    /// Record a command buffer for `Recorder::execute_secondaries`
+    ///
+    /// Dynamic state is not inherited from the primary,
+    /// the viewport and scissor have to be set again.
+    pub fn record<F>(&self, f: F) -> Result<SecondaryAutoCommandBuffer>
+    where
+        F: FnOnce(&mut SecondaryBuilder) -> Result<()>,
+    {
+        let mut builder = AutoCommandBufferBuilder::secondary(
+            self.queue.device().clone(),
+            self.queue.family(),
+            CommandBufferUsage::OneTimeSubmit,
+            CommandBufferInheritanceInfo {
+                render_pass: Some(
+                    CommandBufferInheritanceRenderPassInfo {
+                        subpass: self.subpass.clone(),
+                        framebuffer: Some(self.framebuffer.clone()),
+                    }
+                    .into(),
+                ),
+                ..Default::default()
+            },
+        )?;
+
+        f(&mut builder)?;
+        Ok(builder.build()?)
+    }
+}
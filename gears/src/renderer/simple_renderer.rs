@@ -1,34 +1,44 @@
 use super::{
+    capture::{CaptureBuffer, FrameCapture},
     device::Dev,
+    memory::{self, Allocation, MemoryStats},
     query::{PerfQuery, RecordPerf},
-    target::window::{SwapchainImages, WindowTarget},
+    readback::{FrameFuture, ReadbackFence},
+    target::{
+        headless::HeadlessRenderer,
+        window::{SwapchainImages, WindowTarget},
+    },
+    upload::Uploader,
+    viewport::Rect,
     BeginInfoRecorder, Recorder,
 };
 use crate::{
-    context::ContextError,
+    context::{Context, ContextError},
+    format,
     frame::Frame,
     game_loop::State,
     renderer::{device::RenderDevice, target::window::WindowTargetBuilder},
+    report::FrameStats,
+    DepthMode, SyncMode,
 };
-use parking_lot::{Mutex, MutexGuard};
-use std::{
-    sync::{
-        atomic::{AtomicU8, Ordering},
-        Arc,
-    },
-    time::Duration,
-};
+use parking_lot::Mutex;
+use std::{error::Error, fmt, iter, sync::Arc, time::Duration};
 use vulkano::{
-    command_buffer::{
-        AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, SubpassContents,
-    },
+    buffer::{BufferUsage, CpuAccessibleBuffer},
+    command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer},
     format::{ClearValue, Format},
-    image::{view::ImageView, AttachmentImage, ImageAccess, SwapchainImage},
+    image::{
+        view::{ImageView, ImageViewAbstract},
+        AttachmentImage, ImageAccess, ImageLayout, ImageUsage, SampleCount,
+    },
     pipeline::graphics::viewport::{Scissor, Viewport},
-    render_pass::{Framebuffer, RenderPass},
-    single_pass_renderpass,
-    swapchain::SwapchainAcquireFuture,
-    sync::{self, FenceSignalFuture, FlushError, GpuFuture, JoinFuture},
+    render_pass::{
+        AttachmentDesc, Framebuffer, LoadOp, RenderPass, RenderPassDesc, StoreOp,
+        SubpassDependencyDesc, SubpassDesc,
+    },
+    swapchain::{AcquireError, SwapchainAcquireFuture},
+    sync::{self, AccessFlags, FlushError, GpuFuture, JoinFuture, PipelineStages},
+    DeviceSize,
 };
 use winit::window::Window;
 
@@ -40,54 +50,112 @@ struct SwapchainObjects {
 }
 
 #[allow(unused)]
-struct RenderTarget {
+pub(super) struct RenderTarget {
     // the actual render target
-    framebuffer: Arc<Framebuffer>,
+    pub(super) framebuffer: Arc<Framebuffer>,
+    // the first color attachment
+    pub(super) color_image: Arc<dyn ImageAccess>,
+    pub(super) color_view: Arc<dyn ImageViewAbstract>,
+    pub(super) color_attachments: usize,
+    pub(super) depth_view: Arc<ImageView<AttachmentImage>>,
+
+    // `RenderPassConfig::extra_attachments`, also in `color_attachments`
+    pub(super) extra: Vec<Arc<ImageView<AttachmentImage>>>,
 
     // performance debugging
-    perf: Arc<PerfQuery>,
-    triangles: usize,
+    pub(super) perf: Arc<PerfQuery>,
+
+    _depth_allocation: Allocation,
+    _extra_allocations: Vec<Allocation>,
 }
 
 //
 
 impl RenderTarget {
-    fn new(
+    pub(super) fn new<I>(
         device: Dev,
         render_pass: Arc<RenderPass>,
-        color_image: Arc<SwapchainImage<Window>>,
+        color_image_view: Arc<ImageView<I>>,
+    ) -> Self
+    where
+        I: ImageAccess + 'static,
+    {
+        Self::with_color_attachments(device, render_pass, &[color_image_view as _])
+    }
+
+    /// The render pass has the color attachments
+    /// in the same order followed by the depth attachment
+    pub(super) fn with_color_attachments(
+        device: Dev,
+        render_pass: Arc<RenderPass>,
+        color_image_views: &[Arc<dyn ImageViewAbstract>],
     ) -> Self {
         // images
-        let color_image = color_image;
-        let depth_image = AttachmentImage::new(
+        let first = color_image_views
+            .first()
+            .expect("Render target without color attachments");
+        let [width, height] = first.image().dimensions().width_height();
+        let depth_format = device.depth_format();
+        // sampled for post-processing if the format allows it
+        let depth_sampled = device
+            .physical()
+            .format_properties(depth_format)
+            .optimal_tiling_features
+            .sampled_image;
+        let depth_image = AttachmentImage::with_usage(
             device.logical().clone(),
-            color_image.dimensions().width_height(),
-            Format::D24_UNORM_S8_UINT,
+            [width, height],
+            depth_format,
+            ImageUsage {
+                depth_stencil_attachment: true,
+                sampled: depth_sampled,
+                ..ImageUsage::none()
+            },
         )
         .unwrap();
+        let depth_allocation = device.memory().track(
+            memory::IMAGES,
+            width as u64 * height as u64 * depth_format.block_size().unwrap_or(4),
+        );
 
         // image views
-        let color_image_view = ImageView::new(color_image).unwrap();
-        let depth_image_view = ImageView::new(depth_image).unwrap();
+        let color_image = first.image();
+        let color_view = first.clone();
+        let depth_view = ImageView::new(depth_image).unwrap();
 
         // framebuffer
-        let framebuffer = Framebuffer::start(render_pass)
-            .add(color_image_view)
-            .unwrap()
-            .add(depth_image_view)
+        let mut framebuffer = Framebuffer::start(render_pass);
+        for color_image_view in color_image_views {
+            framebuffer = framebuffer.add(color_image_view.clone()).unwrap();
+        }
+        let framebuffer = framebuffer
+            .add(depth_view.clone())
             .unwrap()
             .build()
             .unwrap();
 
         Self {
             framebuffer,
+            color_image,
+            color_view,
+            color_attachments: color_image_views.len(),
+            depth_view,
+
+            extra: Vec::new(),
 
             perf: Arc::new(PerfQuery::new_with_device(&device)),
-            triangles: 0,
+
+            _depth_allocation: depth_allocation,
+            _extra_allocations: Vec::new(),
         }
     }
 }
 
+/// Waits for the GPU when dropped.
+///
+/// Struct fields are dropped in declaration order,
+/// so declare the `Renderer` before anything that
+/// records GPU work with it (buffers, pipelines, ...).
 pub struct Renderer {
     swapchain_objects: SwapchainObjects,
 
@@ -97,16 +165,115 @@ pub struct Renderer {
     // future for the previous frame
     previous_frame: Option<Box<dyn GpuFuture>>,
 
-    frame_in_flight: AtomicU8,
-    frame_fences: [Option<Arc<Future>>; Renderer::frame_count()],
+    // requested present mode, the swapchain is
+    // recreated on the next frame if it was changed
+    sync: SyncMode,
+    sync_changed: bool,
+
+    depth_mode: DepthMode,
+    render_pass_config: RenderPassConfig,
+
+    // set by a DeviceLost error, `rebuild` clears it
+    device_lost: bool,
+
+    // index of the next frame in flight
+    // and one fence per frame in flight
+    frame_in_flight: usize,
+    frame_fences: Vec<Option<Arc<FrameFuture>>>,
+
+    // draws of the last `end_frame`
+    last_stats: FrameStats,
+
+    // frame captures, one per frame in flight
+    capture: bool,
+    captures: Vec<Option<CaptureBuffer>>,
+    last_capture: Option<usize>,
 
     pub device: Dev,
 }
 
-type Future = FenceSignalFuture<Box<dyn GpuFuture>>;
-
 pub struct RendererBuilder<'f> {
     frame: &'f Frame,
+    device: Option<Dev>,
+    depth_mode: DepthMode,
+    depth_format: Option<Format>,
+    frames_in_flight: usize,
+    render_pass_config: RenderPassConfig,
+}
+
+/// Subpasses of the main render pass, in the order they are recorded
+///
+/// Every subpass draws into the swapchain image. The depth buffer is
+/// shared by all subpasses and preserved through the ones without it.
+/// Defaults to a single subpass with depth.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderPassConfig {
+    subpasses: Vec<SubpassConfig>,
+    extra_attachments: Vec<ExtraAttachment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubpassConfig {
+    /// Depth test against the shared depth buffer
+    pub depth: bool,
+
+    /// How the subpass uses the extra attachments
+    pub extra: ExtraAccess,
+}
+
+/// Offscreen color attachment of every render target,
+/// resized with the swapchain
+///
+/// See `RendererBuilder::with_extra_attachment`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtraAttachment {
+    pub name: &'static str,
+    pub format: Format,
+    /// Color and input attachment usages are always added
+    pub usage: ImageUsage,
+}
+
+/// Use of the extra attachments in a subpass
+///
+/// An attachment cannot be sampled in the render pass that writes it,
+/// later subpasses read it as an input attachment (`subpassLoad`).
+/// Other render passes can sample it with `Renderer::extra_attachment`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtraAccess {
+    /// Preserved through the subpass
+    None,
+
+    /// Color attachments after the swapchain image,
+    /// `layout(location = 1..)` in the fragment shader
+    Write,
+
+    /// Input attachments in the order they were added,
+    /// `layout(input_attachment_index = 0..)` in the fragment shader
+    Read,
+}
+
+/// Errors from `Renderer::begin_frame`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderError {
+    /// The swapchain could not be recreated in `Renderer::MAX_RETRIES`
+    /// tries, e.g. while the window is minimized.
+    ///
+    /// Skip the frame and try again on the next one.
+    OutOfDate,
+
+    /// The window surface was lost, e.g. the display was disconnected
+    ///
+    /// Call `Frame::recreate_surface` and `Renderer::rebuild_surface`,
+    /// the game loop calls `Runnable::surface_lost` for it if `draw`
+    /// returns this.
+    SurfaceLost,
+
+    /// The device was lost, e.g. a driver reset or a removed GPU
+    ///
+    /// Call `Renderer::rebuild` and recreate everything that
+    /// was created with the old `Renderer::device`, the game loop
+    /// calls `Runnable::device_lost` for it if `draw` returns this.
+    DeviceLost,
 }
 
 #[must_use]
@@ -118,7 +285,7 @@ pub struct FrameData {
 
     pub image_index: usize,
     pub frame_in_flight: usize,
-    pub future: JoinFuture<Box<dyn GpuFuture>, SwapchainAcquireFuture<Window>>,
+    pub future: JoinFuture<Box<dyn GpuFuture>, SwapchainAcquireFuture<Arc<Window>>>,
 }
 
 impl FrameData {
@@ -129,62 +296,290 @@ impl FrameData {
     pub fn viewport_and_scissor(&self) -> (Viewport, Scissor) {
         (self.viewport.clone(), self.scissor)
     }
+
+    /// Whole frame in pixels, see `SplitScreen` for splitting it
+    pub fn rect(&self) -> Rect {
+        Rect::new(self.scissor.origin, self.scissor.dimensions)
+    }
+
+    pub fn extent(&self) -> [u32; 2] {
+        self.scissor.dimensions
+    }
+
+    /// Draw calls recorded into this frame so far, see `Recorder::stats`
+    pub fn stats(&self) -> FrameStats {
+        self.recorder.stats()
+    }
 }
 
 impl Renderer {
+    /// Render into an off-screen image without a window or surface
+    ///
+    /// See `HeadlessRenderer`.
+    pub fn headless(
+        context: &Context,
+        width: u32,
+        height: u32,
+    ) -> anyhow::Result<HeadlessRenderer> {
+        HeadlessRenderer::new(context, [width, height])
+    }
+
     pub fn builder(frame: &Frame) -> RendererBuilder {
-        RendererBuilder { frame }
+        RendererBuilder {
+            frame,
+            device: None,
+            depth_mode: DepthMode::default(),
+            depth_format: None,
+            frames_in_flight: RendererBuilder::DEFAULT_FRAMES_IN_FLIGHT,
+            render_pass_config: RenderPassConfig::default(),
+        }
     }
 
     pub fn render_pass(&self) -> Arc<RenderPass> {
         self.swapchain_objects.render_pass.clone()
     }
 
+    /// Subpasses in the main render pass, see `RenderPassConfig`
+    pub fn subpass_count(&self) -> u32 {
+        self.swapchain_objects.render_pass.desc().subpasses().len() as u32
+    }
+
+    /// Depth convention of the render pass and `GPipelineBuilder`
+    pub fn depth_mode(&self) -> DepthMode {
+        self.depth_mode
+    }
+
+    /// Format of the depth attachments, see `RendererBuilder::with_depth_format`
+    pub fn depth_format(&self) -> Format {
+        self.device.depth_format()
+    }
+
+    /// Sizes of the buffers and images allocated through gears
+    pub fn memory_stats(&self) -> MemoryStats {
+        self.device.memory().stats()
+    }
+
+    /// Bytes uploaded since `begin_frame`, see `UploadTracker`
+    ///
+    /// The warning threshold is set with `device.uploads().set_threshold`.
+    pub fn frame_upload_bytes(&self) -> u64 {
+        self.device.uploads().frame_bytes()
+    }
+
+    /// Swapchain color format.
+    pub fn format(&self) -> Format {
+        self.swapchain_objects.window_target.format.0
+    }
+
+    /// Does the swapchain encode colors to sRGB on write
+    ///
+    /// Shaders writing sRGB encoded colors have to linearize
+    /// them first if it does, see `format::Color::encode`.
+    pub fn surface_is_srgb(&self) -> bool {
+        format::is_srgb(self.format())
+    }
+
+    /// Records buffer copies for the transfer queue
+    /// without stalling the frames.
+    pub fn uploader(&self) -> anyhow::Result<Uploader> {
+        Uploader::new(&self.device)
+    }
+
+    /// Copy every frame into a host visible buffer
+    /// so that it can be read back with `capture_frame`.
+    pub fn set_capture(&mut self, capture: bool) {
+        self.capture = capture;
+        if !capture {
+            self.captures.iter_mut().for_each(|capture| *capture = None);
+            self.last_capture = None;
+        }
+    }
+
+    /// RGBA8 pixels of the last presented frame.
+    ///
+    /// Waits for the frame to finish rendering.
+    /// Capturing has to be enabled with `set_capture`
+    /// before the frame was rendered.
+    pub fn capture_frame(&mut self) -> anyhow::Result<FrameCapture> {
+        let frame_in_flight = self
+            .last_capture
+            .ok_or_else(|| anyhow::anyhow!("No captured frames, see `Renderer::set_capture`"))?;
+
+        if let Some(fence) = self.frame_fences[frame_in_flight].as_ref() {
+            fence.wait(None)?;
+        }
+
+        let capture = self.captures[frame_in_flight].as_ref().unwrap();
+        let pixels = capture.buffer.read()?.to_vec();
+        FrameCapture::from_raw(capture.extent, self.format(), pixels)
+    }
+
+    /// Blocks until all submitted frames have finished.
+    pub fn wait_idle(&mut self) {
+        for fence in self.frame_fences.iter() {
+            if let Some(Err(err)) = fence.as_ref().map(|fence| fence.wait(None)) {
+                log::error!("Failed to wait for a frame: {err}");
+            }
+        }
+
+        if let Some(previous_frame) = self.previous_frame.take() {
+            if let Err(err) = previous_frame
+                .then_signal_fence_and_flush()
+                .map_err(|err| err.to_string())
+                .and_then(|future| future.wait(None).map_err(|err| err.to_string()))
+            {
+                log::error!("Failed to wait for the previous frame: {err}");
+            }
+        }
+        self.previous_frame = Some(sync::now(self.device.logical().clone()).boxed());
+    }
+
+    /// Requested present mode, see `set_sync`
+    pub fn sync(&self) -> SyncMode {
+        self.sync
+    }
+
+    /// Present mode the swapchain was created with,
+    /// after the fallback for unsupported modes
+    pub fn current_sync_mode(&self) -> SyncMode {
+        self.swapchain_objects.window_target.sync
+    }
+
+    /// Change the present mode
+    ///
+    /// The swapchain is recreated at the next `begin_frame`.
+    /// Unsupported modes fall back to Fifo.
+    pub fn set_sync(&mut self, sync: SyncMode) {
+        if sync != self.sync {
+            self.sync = sync;
+            self.sync_changed = true;
+        }
+    }
+
     /// Swapchain images.
     pub fn image_count(&self) -> usize {
         self.render_targets.len()
     }
 
-    /// Frames in flight.
+    /// Swapchain image of the render target `image_index`, see `FrameData::image_index`
+    ///
+    /// The render targets are recreated with the swapchain,
+    /// so the views should not be kept over frames.
+    pub fn color_attachment(&self, image_index: usize) -> Option<Arc<dyn ImageViewAbstract>> {
+        Some(
+            self.render_targets
+                .get(image_index)?
+                .lock()
+                .color_view
+                .clone(),
+        )
+    }
+
+    /// Depth attachment of the render target `image_index`
+    ///
+    /// Sampled usage if the depth format supports it.
+    pub fn depth_attachment(&self, image_index: usize) -> Option<Arc<ImageView<AttachmentImage>>> {
+        Some(
+            self.render_targets
+                .get(image_index)?
+                .lock()
+                .depth_view
+                .clone(),
+        )
+    }
+
+    /// Extra attachment `name` of the render target `image_index`,
+    /// see `RendererBuilder::with_extra_attachment`
+    pub fn extra_attachment(
+        &self,
+        image_index: usize,
+        name: &str,
+    ) -> Option<Arc<ImageView<AttachmentImage>>> {
+        let index = self
+            .render_pass_config
+            .extra_attachments()
+            .iter()
+            .position(|extra| extra.name == name)?;
+        self.render_targets
+            .get(image_index)?
+            .lock()
+            .extra
+            .get(index)
+            .cloned()
+    }
+
+    /// Frames in flight, see `RendererBuilder::with_frames_in_flight`.
     /// Any changing buffers should have this many duplicates.
-    /// This count is always two.
-    pub const fn frame_count() -> usize {
-        2
+    pub fn frame_count(&self) -> usize {
+        self.frame_fences.len()
     }
 
-    pub fn begin_frame(&mut self, state: &mut State) -> FrameData {
-        loop {
-            match self.try_begin_frame(state) {
-                Some(frame_data) => break frame_data,
-                None => continue,
+    /// Swapchain recreations `begin_frame` tries before giving up on a frame
+    pub const MAX_RETRIES: usize = 5;
+
+    /// Acquire the next swapchain image and begin recording
+    ///
+    /// Out of date swapchains are recreated and lost surfaces are rebuilt,
+    /// up to `MAX_RETRIES` times.
+    pub fn begin_frame(&mut self, state: &mut State) -> Result<FrameData, RenderError> {
+        self.device.uploads().reset();
+        for retry in 1..=Self::MAX_RETRIES {
+            match self.try_begin_frame(state)? {
+                Some(frame_data) => return Ok(frame_data),
+                None => log::debug!("Swapchain recreated, retry {retry}/{}", Self::MAX_RETRIES),
             }
         }
+        Err(RenderError::OutOfDate)
     }
 
-    pub fn try_begin_frame(&mut self, state: &mut State) -> Option<FrameData> {
+    /// Single `begin_frame` attempt, `None` if the swapchain had to be recreated
+    pub fn try_begin_frame(&mut self, state: &mut State) -> Result<Option<FrameData>, RenderError> {
+        if self.device_lost {
+            return Err(RenderError::DeviceLost);
+        }
+
         self.previous_frame.as_mut().unwrap().cleanup_finished();
 
-        // frame in flight can be 0 or 1
-        // xor:ing with 1 swaps it between these two
-        //   xor 0,0 = 0
-        //   xor 1,0 = 1
-        //   xor 0,1 = 1
-        //   xor 1,1 = 0
-        // so with fetch_xor 1 we can cycle the frame in flight
-        // and get the index for this frame
-        let frame_in_flight = self.frame_in_flight.fetch_xor(1, Ordering::SeqCst) as usize;
+        if self.sync_changed {
+            if !self.try_recreate_swapchain() {
+                return Ok(None);
+            }
+            self.sync_changed = false;
+        }
+
+        // wait for the fence set up in the last same frame_in_flight
+        // waiting is necessary to unlock any resources it uses
+        // and it keeps the cpu at most `frame_count` frames ahead
+        let frame_in_flight = self.frame_in_flight;
+        if let Some(fence) = self.frame_fences[frame_in_flight].as_ref() {
+            match fence.wait(None) {
+                Ok(()) => {}
+                Err(FlushError::DeviceLost) => return Err(self.lose_device()),
+                Err(err) => log::error!("Failed to wait for a frame: {err}"),
+            }
+        }
 
         // acquire the target image (future) and its index
         let (image_index, acquire_future) =
             match self.swapchain_objects.window_target.acquire_image() {
-                Some(v) => v,
-                None => {
-                    // log::debug!("Failed to acquire image");
-                    self.recreate_swapchain().unwrap();
-                    return None;
+                Ok(Some(v)) => v,
+                Ok(None) => {
+                    self.try_recreate_swapchain();
+                    return Ok(None);
+                }
+                Err(AcquireError::DeviceLost) => return Err(self.lose_device()),
+                // a swapchain for the lost surface would fail again
+                Err(AcquireError::SurfaceLost) => return Err(RenderError::SurfaceLost),
+                Err(err) => {
+                    log::warn!("Failed to acquire a swapchain image: {err}");
+                    self.try_recreate_swapchain();
+                    return Ok(None);
                 }
             };
 
+        // cycle the frame in flight only after a successful acquire
+        self.frame_in_flight = (frame_in_flight + 1) % self.frame_count();
+
         // join the last frame and this frame
         let future = self.previous_frame.take().unwrap().join(acquire_future);
 
@@ -193,21 +588,17 @@ impl Renderer {
 
         // begin recording a render command buffer
         let (recorder, perf, gpu_time) =
-            Self::begin_record(&self.device, &mut target.lock(), image_index);
+            Self::begin_record(&self.device, &target.lock(), self.depth_mode, image_index);
         if let Some(gpu_time) = gpu_time {
             state.gpu_frame_reporter.manual(gpu_time);
         }
 
         // setup default dynamic state
-        let extent = self.swapchain_objects.window_target.base.extent;
-        let viewport = Viewport {
-            origin: [0.0, 0.0],
-            dimensions: [extent[0] as f32, extent[1] as f32],
-            depth_range: 0.0..1.0,
-        };
-        let scissor = Scissor::irrelevant();
+        let rect = Rect::from_extent(self.swapchain_objects.window_target.base.extent);
+        let viewport = rect.viewport();
+        let scissor = rect.scissor();
 
-        Some(FrameData {
+        Ok(Some(FrameData {
             recorder,
             viewport,
             scissor,
@@ -216,20 +607,91 @@ impl Renderer {
             image_index,
             frame_in_flight,
             future,
-        })
+        }))
+    }
+
+    /// Recreate the device, swapchain and render targets after `RenderError::DeviceLost`
+    ///
+    /// Reuses the `Context` of `frame` and the settings of this renderer.
+    /// Everything created with the old `device` (buffers, pipelines,
+    /// descriptor sets, ...) has to be recreated too. Renderers that
+    /// shared the lost device get a new device of their own.
+    pub fn rebuild(&mut self, frame: &Frame) -> Result<(), ContextError> {
+        log::warn!("Rebuilding the renderer");
+        let mut renderer = Renderer::builder(frame)
+            .with_depth_mode(self.depth_mode)
+            .with_depth_format(self.depth_format())
+            .with_frames_in_flight(self.frame_count())
+            .with_render_pass_config(self.render_pass_config.clone())
+            .build()?;
+        renderer.set_sync(self.sync);
+        renderer.set_capture(self.capture);
+
+        // the old renderer waits for its (lost) frames when dropped
+        *self = renderer;
+        log::info!("Renderer rebuilt");
+        Ok(())
+    }
+
+    /// New swapchain and render targets on the surface of `frame`
+    /// after `RenderError::SurfaceLost`
+    ///
+    /// Call `Frame::recreate_surface` first. The device and
+    /// everything created with it stay valid.
+    pub fn rebuild_surface(&mut self, frame: &Frame) -> Result<(), ContextError> {
+        log::warn!("Rebuilding the swapchain for a new surface");
+        let surface = frame.surface();
+        let supported = surface
+            .is_supported(self.device.queues.present.family())
+            .map_err(ContextError::CapabilitiesError)?;
+        if !supported {
+            return Err(ContextError::SurfaceNotSupported);
+        }
+        self.wait_idle();
+
+        // the old swapchain images are not used after this
+        self.render_targets.clear();
+        let (target, color_images) =
+            WindowTargetBuilder::new(surface)?.build(&self.device, self.sync)?;
+
+        self.render_targets = RendererBuilder::create_render_targets(
+            color_images,
+            &self.device,
+            &self.swapchain_objects.render_pass,
+            self.render_pass_config.extra_attachments(),
+        );
+        self.swapchain_objects.window_target = target;
+
+        Ok(())
     }
 
-    pub fn end_frame(&mut self, frame_data: FrameData) {
+    /// Draw calls of the last frame given to `end_frame`
+    pub fn last_frame_stats(&self) -> FrameStats {
+        self.last_stats
+    }
+
+    pub fn end_frame(&mut self, mut frame_data: FrameData) {
+        self.last_stats = frame_data.stats();
+
+        // copy the frame for `capture_frame`
+        let captured = self.capture
+            && match self.record_capture(
+                &mut frame_data.recorder,
+                frame_data.image_index,
+                frame_data.frame_in_flight,
+            ) {
+                Ok(_) => true,
+                Err(err) => {
+                    log::error!("Frame capture error: {err}");
+                    false
+                }
+            };
+
         // end recording
-        let cb = Self::end_record(frame_data.recorder);
+        let (cb, readbacks) = Self::end_record(frame_data.recorder);
 
         // rendering
 
-        // wait for the fence set up in the last same frame_in_flight
-        // waiting is necessary to unlock any resources it uses
-        if let Some(fence) = self.frame_fences[frame_data.frame_in_flight].as_ref() {
-            fence.wait(None).unwrap();
-        }
         // signal fence to wait for unlocking resources
         // wrap to Arc so that it can be cloned
         let future = match frame_data
@@ -245,6 +707,12 @@ impl Renderer {
         };
         // store the fence and wait for it the next time this same frame_in_flight is used
         self.frame_fences[frame_data.frame_in_flight] = Some(future.clone());
+        for readback in readbacks {
+            readback.signal(future.clone());
+        }
+        if captured {
+            self.last_capture = Some(frame_data.frame_in_flight);
+        }
 
         // presenting
         let future = future
@@ -259,6 +727,10 @@ impl Renderer {
         match future {
             Ok(future) => self.previous_frame = Some(future.boxed()),
             Err(FlushError::OutOfDate) => (),
+            // reported by the next begin_frame
+            Err(FlushError::DeviceLost) => {
+                self.lose_device();
+            }
             Err(err) => log::error!("Frame error: {}", err),
         }
 
@@ -267,9 +739,46 @@ impl Renderer {
         }
     }
 
-    fn begin_record(
+    fn record_capture(
+        &mut self,
+        recorder: &mut Recorder<false>,
+        image_index: usize,
+        frame_in_flight: usize,
+    ) -> anyhow::Result<()> {
+        let image = self.render_targets[image_index].lock().color_image.clone();
+        let extent = image.dimensions().width_height();
+
+        // reuse the buffer unless the swapchain was resized
+        let reuse =
+            matches!(&self.captures[frame_in_flight], Some(capture) if capture.extent == extent);
+        if !reuse {
+            let block_size = self.format().block_size().unwrap_or(4);
+            let len = extent[0] as DeviceSize * extent[1] as DeviceSize * block_size;
+            let buffer = unsafe {
+                CpuAccessibleBuffer::uninitialized_array(
+                    self.device.logical().clone(),
+                    len,
+                    BufferUsage::transfer_destination(),
+                    true,
+                )?
+            };
+            self.captures[frame_in_flight] = Some(CaptureBuffer { buffer, extent });
+        }
+
+        let buffer = self.captures[frame_in_flight]
+            .as_ref()
+            .unwrap()
+            .buffer
+            .clone();
+        recorder.record().copy_image_to_buffer(image, buffer)?;
+
+        Ok(())
+    }
+
+    pub(super) fn begin_record(
         device: &Dev,
-        render_target: &mut MutexGuard<RenderTarget>,
+        render_target: &RenderTarget,
+        depth_mode: DepthMode,
         image_index: usize,
         /* frame_in_flight: usize, */
     ) -> (Recorder<false>, Arc<PerfQuery>, Option<Duration>) {
@@ -282,16 +791,20 @@ impl Renderer {
         .unwrap();
 
         let fb = render_target.framebuffer.clone();
-        let begin_render_pass_lambda = move |(cb, cc): BeginInfoRecorder| {
+        let secondary_fb = fb.clone();
+        let srgb_target = format::is_srgb(render_target.color_image.format());
+        let color_attachments = render_target.color_attachments;
+        let depth_clear =
+            format::depth_clear_value(device.depth_format(), depth_mode.clear_value());
+        let begin_render_pass_lambda = move |(cb, cc, contents): BeginInfoRecorder| {
+            // cc.c is `clear color get color`, clearly
+            let color_clear = ClearValue::Float(cc.c(srgb_target));
             cb.begin_render_pass(
                 fb.clone(),
-                SubpassContents::Inline,
-                [
-                    ClearValue::Float(cc.c()), // cc.c is `clear color get color`, clearly
-                    ClearValue::DepthStencil((1.0, 0)),
-                ]
-                .iter()
-                .cloned(),
+                contents,
+                iter::repeat(color_clear)
+                    .take(color_attachments)
+                    .chain(iter::once(depth_clear)),
             )
             .unwrap();
         };
@@ -307,44 +820,175 @@ impl Renderer {
                 begin_render_pass_lambda,
                 image_index,
                 /* frame_in_flight, */
-            ),
+            )
+            .with_secondary(device.queues.graphics.clone(), secondary_fb)
+            .with_srgb_target(srgb_target),
             perf,
             gpu_time,
         )
     }
 
-    fn end_record(recorder: Recorder<false>) -> PrimaryAutoCommandBuffer {
+    pub(super) fn end_record(
+        recorder: Recorder<false>,
+    ) -> (PrimaryAutoCommandBuffer, Vec<ReadbackFence>) {
         // end, build and return the command buffer
-        recorder.inner.command_buffer.build().unwrap()
+        (
+            recorder.inner.command_buffer.build().unwrap(),
+            recorder.inner.readbacks,
+        )
+    }
+
+    fn lose_device(&mut self) -> RenderError {
+        if !self.device_lost {
+            log::error!("Device lost, the renderer has to be rebuilt");
+        }
+        self.device_lost = true;
+        RenderError::DeviceLost
+    }
+
+    /// `false` if the swapchain could not be recreated, e.g. while minimized
+    fn try_recreate_swapchain(&mut self) -> bool {
+        match self.recreate_swapchain() {
+            Ok(()) => true,
+            Err(err) => {
+                log::debug!("Failed to recreate the swapchain: {err}");
+                false
+            }
+        }
     }
 
     fn recreate_swapchain(&mut self) -> Result<(), ContextError> {
-        let color_images = self.swapchain_objects.window_target.recreate()?;
+        let color_images = self
+            .swapchain_objects
+            .window_target
+            .recreate(&self.device, self.sync)?;
 
         self.render_targets = RendererBuilder::create_render_targets(
             color_images,
             &self.device,
             &self.swapchain_objects.render_pass,
+            self.render_pass_config.extra_attachments(),
         );
 
         Ok(())
     }
 }
 
+impl Drop for Renderer {
+    fn drop(&mut self) {
+        self.wait_idle();
+        log::debug!("Renderer dropped");
+    }
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::OutOfDate => write!(f, "The swapchain could not be recreated"),
+            RenderError::SurfaceLost => write!(f, "The window surface was lost"),
+            RenderError::DeviceLost => write!(f, "The device was lost"),
+        }
+    }
+}
+
+impl Error for RenderError {}
+
 impl<'f> RendererBuilder<'f> {
+    const DEFAULT_FRAMES_IN_FLIGHT: usize = 2;
+    const MAX_FRAMES_IN_FLIGHT: usize = 3;
+
+    /// Frames the cpu can record ahead of the gpu, clamped to `1..=3`
+    ///
+    /// Defaults to 2. More frames hide gpu stalls better,
+    /// 1 has the lowest input latency.
+    pub fn with_frames_in_flight(mut self, frames_in_flight: usize) -> Self {
+        self.frames_in_flight = frames_in_flight.clamp(1, Self::MAX_FRAMES_IN_FLIGHT);
+        self
+    }
+
+    /// Defaults to a single subpass with depth
+    pub fn with_render_pass_config(mut self, config: RenderPassConfig) -> Self {
+        self.render_pass_config = config;
+        self
+    }
+
+    /// Defaults to `DepthMode::Standard`
+    pub fn with_depth_mode(mut self, depth_mode: DepthMode) -> Self {
+        self.depth_mode = depth_mode;
+        self
+    }
+
+    /// Add an offscreen color attachment to every render target
+    ///
+    /// Shortcut for `RenderPassConfig::with_extra_attachment`, call it after
+    /// `with_render_pass_config`. The subpasses use it with `SubpassConfig::extra`.
+    pub fn with_extra_attachment(
+        mut self,
+        name: &'static str,
+        format: Format,
+        usage: ImageUsage,
+    ) -> Self {
+        self.render_pass_config = self
+            .render_pass_config
+            .with_extra_attachment(ExtraAttachment {
+                name,
+                format,
+                usage,
+            });
+        self
+    }
+
+    /// Preferred depth attachment format
+    ///
+    /// Falls back to the first supported one of `RenderDevice::DEPTH_FORMATS`
+    /// (`D32_SFLOAT` first) if the GPU does not support it.
+    pub fn with_depth_format(mut self, depth_format: Format) -> Self {
+        self.depth_format = Some(depth_format);
+        self
+    }
+
+    /// Share the device of another `Renderer`, e.g. for a secondary `Frame`
+    ///
+    /// Buffers, textures and pipelines created with the device can be used
+    /// with both renderers, as long as the render passes are compatible.
+    /// The depth format of the shared device is used, `with_depth_format`
+    /// is ignored. The device has to be able to present to the frame.
+    pub fn with_device(mut self, device: Dev) -> Self {
+        self.device = Some(device);
+        self
+    }
+
     pub fn build(self) -> Result<Renderer, ContextError> {
         // device
-        let device = RenderDevice::from_frame(self.frame)?;
+        let device = match self.device {
+            Some(device) => {
+                let supported = self
+                    .frame
+                    .surface()
+                    .is_supported(device.queues.present.family())
+                    .map_err(ContextError::CapabilitiesError)?;
+                if !supported {
+                    return Err(ContextError::SurfaceNotSupported);
+                }
+                device
+            }
+            None => RenderDevice::from_frame_with_depth_format(self.frame, self.depth_format)?,
+        };
 
         // swapchain + images
         let (target, color_images) =
             WindowTargetBuilder::new(self.frame.surface())?.build(&device, self.frame.sync())?;
 
         // main render pass
-        let render_pass = Self::create_render_pass(&device, &target);
+        let render_pass = Self::create_render_pass(&device, &target, &self.render_pass_config);
 
         // render targets (framebuffers, command buffers, ...)
-        let render_targets = Self::create_render_targets(color_images, &device, &render_pass);
+        let render_targets = Self::create_render_targets(
+            color_images,
+            &device,
+            &render_pass,
+            self.render_pass_config.extra_attachments(),
+        );
 
         // swapchain + renderpass
         let swapchain_objects = SwapchainObjects {
@@ -353,8 +997,9 @@ impl<'f> RendererBuilder<'f> {
         };
 
         let previous_frame = Some(sync::now(device.logical().clone()).boxed());
-        let frame_in_flight = AtomicU8::new(0);
-        let frame_fences = [None, None];
+        let frame_in_flight = 0;
+        let frame_fences = vec![None; self.frames_in_flight];
+        let captures = (0..self.frames_in_flight).map(|_| None).collect();
 
         log::debug!("Renderer created");
 
@@ -365,39 +1010,141 @@ impl<'f> RendererBuilder<'f> {
 
             previous_frame,
 
+            sync: self.frame.sync(),
+            sync_changed: false,
+
+            depth_mode: self.depth_mode,
+            render_pass_config: self.render_pass_config,
+
+            device_lost: false,
+
             frame_in_flight,
             frame_fences,
 
+            last_stats: FrameStats::default(),
+
+            capture: false,
+            captures,
+            last_capture: None,
+
             device,
         })
     }
 
-    fn create_render_pass(device: &Dev, target: &WindowTarget) -> Arc<RenderPass> {
-        // AttachmentDesc
+    fn create_render_pass(
+        device: &Dev,
+        target: &WindowTarget,
+        config: &RenderPassConfig,
+    ) -> Arc<RenderPass> {
+        const COLOR: usize = 0;
+        let extra = config.extra_attachments();
+        let extra_ids = 1..1 + extra.len();
+        let depth = 1 + extra.len();
+
+        let extra_desc = |extra: &ExtraAttachment| AttachmentDesc {
+            format: extra.format,
+            samples: SampleCount::Sample1,
+            load: LoadOp::Clear,
+            store: StoreOp::Store,
+            stencil_load: LoadOp::DontCare,
+            stencil_store: StoreOp::DontCare,
+            initial_layout: ImageLayout::Undefined,
+            final_layout: ImageLayout::ShaderReadOnlyOptimal,
+        };
 
-        single_pass_renderpass!(device.logical().clone(),
-            attachments: {
-                c: {
-                    load: Clear,
-                    store: Store,
-                    format: target.format.0,
-                    samples: 1,
-                    initial_layout: ImageLayout::Undefined,
-                    final_layout: ImageLayout::PresentSrc,
-                },
-                d: {
-                    load: Clear,
-                    store: DontCare,
-                    format: Format::D24_UNORM_S8_UINT,
-                    samples: 1,
-                    initial_layout: ImageLayout::Undefined,
-                    final_layout: ImageLayout::DepthStencilAttachmentOptimal,
+        let attachments = iter::once(AttachmentDesc {
+            format: target.format.0,
+            samples: SampleCount::Sample1,
+            load: LoadOp::Clear,
+            store: StoreOp::Store,
+            stencil_load: LoadOp::DontCare,
+            stencil_store: StoreOp::DontCare,
+            initial_layout: ImageLayout::Undefined,
+            final_layout: ImageLayout::PresentSrc,
+        })
+        .chain(extra.iter().map(extra_desc))
+        .chain(iter::once(AttachmentDesc {
+            format: device.depth_format(),
+            samples: SampleCount::Sample1,
+            load: LoadOp::Clear,
+            store: StoreOp::DontCare,
+            stencil_load: LoadOp::Clear,
+            stencil_store: StoreOp::DontCare,
+            initial_layout: ImageLayout::Undefined,
+            final_layout: ImageLayout::DepthStencilAttachmentOptimal,
+        }))
+        .collect();
+
+        let subpasses = config
+            .subpasses()
+            .iter()
+            .map(|subpass| {
+                let mut color_attachments = vec![(COLOR, ImageLayout::ColorAttachmentOptimal)];
+                let mut input_attachments = vec![];
+                let mut preserve_attachments = if subpass.depth { vec![] } else { vec![depth] };
+                match subpass.extra {
+                    ExtraAccess::None => preserve_attachments.extend(extra_ids.clone()),
+                    ExtraAccess::Write => color_attachments.extend(
+                        extra_ids
+                            .clone()
+                            .map(|i| (i, ImageLayout::ColorAttachmentOptimal)),
+                    ),
+                    ExtraAccess::Read => input_attachments.extend(
+                        extra_ids
+                            .clone()
+                            .map(|i| (i, ImageLayout::ShaderReadOnlyOptimal)),
+                    ),
                 }
-            },
-            pass: {
-                color: [ c ],
-                depth_stencil: { d }
-            }
+
+                SubpassDesc {
+                    color_attachments,
+                    depth_stencil: subpass
+                        .depth
+                        .then(|| (depth, ImageLayout::DepthStencilAttachmentOptimal)),
+                    input_attachments,
+                    resolve_attachments: vec![],
+                    preserve_attachments,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // each subpass draws over the previous one or reads
+        // its extra attachments, so wait for its color and depth writes
+        let dependencies = (1..subpasses.len())
+            .map(|i| SubpassDependencyDesc {
+                source_subpass: i - 1,
+                destination_subpass: i,
+                source_stages: PipelineStages {
+                    color_attachment_output: true,
+                    late_fragment_tests: true,
+                    ..PipelineStages::none()
+                },
+                destination_stages: PipelineStages {
+                    color_attachment_output: true,
+                    early_fragment_tests: true,
+                    fragment_shader: true,
+                    ..PipelineStages::none()
+                },
+                source_access: AccessFlags {
+                    color_attachment_write: true,
+                    depth_stencil_attachment_write: true,
+                    ..AccessFlags::none()
+                },
+                destination_access: AccessFlags {
+                    input_attachment_read: true,
+                    color_attachment_read: true,
+                    color_attachment_write: true,
+                    depth_stencil_attachment_read: true,
+                    depth_stencil_attachment_write: true,
+                    ..AccessFlags::none()
+                },
+                by_region: true,
+            })
+            .collect();
+
+        RenderPass::new(
+            device.logical().clone(),
+            RenderPassDesc::new(attachments, subpasses, dependencies),
         )
         .unwrap()
     }
@@ -406,16 +1153,123 @@ impl<'f> RendererBuilder<'f> {
         color_images: SwapchainImages,
         device: &Dev,
         render_pass: &Arc<RenderPass>,
+        extra_attachments: &[ExtraAttachment],
     ) -> Box<[Arc<Mutex<RenderTarget>>]> {
         color_images
             .iter()
             .map(|image| {
-                Arc::new(Mutex::new(RenderTarget::new(
+                let extent = image.dimensions().width_height();
+                let (extra, extra_allocations): (Vec<_>, Vec<_>) = extra_attachments
+                    .iter()
+                    .map(|extra| {
+                        let image = AttachmentImage::with_usage(
+                            device.logical().clone(),
+                            extent,
+                            extra.format,
+                            ImageUsage {
+                                color_attachment: true,
+                                input_attachment: true,
+                                ..extra.usage
+                            },
+                        )
+                        .unwrap();
+                        let allocation = device.memory().track(
+                            memory::IMAGES,
+                            extent[0] as u64
+                                * extent[1] as u64
+                                * extra.format.block_size().unwrap_or(4),
+                        );
+                        (ImageView::new(image).unwrap(), allocation)
+                    })
+                    .unzip();
+
+                // the swapchain image first, then the extra attachments
+                let views: Vec<Arc<dyn ImageViewAbstract>> =
+                    iter::once(ImageView::new(image.clone()).unwrap() as _)
+                        .chain(extra.iter().map(|view| view.clone() as _))
+                        .collect();
+
+                let mut target = RenderTarget::with_color_attachments(
                     device.clone(),
                     render_pass.clone(),
-                    image.clone(),
-                )))
+                    &views,
+                );
+                target.extra = extra;
+                target._extra_allocations = extra_allocations;
+                Arc::new(Mutex::new(target))
             })
             .collect()
     }
 }
+
+impl Default for RenderPassConfig {
+    fn default() -> Self {
+        Self::new().with_subpass(SubpassConfig::depth())
+    }
+}
+
+impl RenderPassConfig {
+    /// No subpasses, add at least one with `with_subpass`
+    pub fn new() -> Self {
+        Self {
+            subpasses: Vec::new(),
+            extra_attachments: Vec::new(),
+        }
+    }
+
+    /// Append a subpass, subpass indices start from 0
+    pub fn with_subpass(mut self, subpass: SubpassConfig) -> Self {
+        self.subpasses.push(subpass);
+        self
+    }
+
+    /// Add an offscreen color attachment to every render target, see `ExtraAccess`
+    pub fn with_extra_attachment(mut self, extra_attachment: ExtraAttachment) -> Self {
+        self.extra_attachments.push(extra_attachment);
+        self
+    }
+
+    pub fn extra_attachments(&self) -> &[ExtraAttachment] {
+        &self.extra_attachments
+    }
+
+    /// A config with no subpasses falls back to the default single subpass
+    pub fn subpasses(&self) -> &[SubpassConfig] {
+        const DEFAULT: [SubpassConfig; 1] = [SubpassConfig::depth()];
+        if self.subpasses.is_empty() {
+            &DEFAULT
+        } else {
+            &self.subpasses
+        }
+    }
+}
+
+impl SubpassConfig {
+    /// Subpass with depth testing, e.g. opaque 3D content
+    pub const fn depth() -> Self {
+        Self {
+            depth: true,
+            extra: ExtraAccess::None,
+        }
+    }
+
+    /// Subpass without depth, e.g. an unlit overlay or ui
+    pub const fn no_depth() -> Self {
+        Self {
+            depth: false,
+            extra: ExtraAccess::None,
+        }
+    }
+
+    /// Write or read the extra attachments, see `RenderPassConfig::with_extra_attachment`
+    pub const fn with_extra(mut self, extra: ExtraAccess) -> Self {
+        self.extra = extra;
+        self
+    }
+}
+
+impl Default for ExtraAccess {
+    fn default() -> Self {
+        Self::None
+    }
+}
@@ -0,0 +1,356 @@
+use super::{
+    memory::UploadTracker, simple_renderer::Renderer, texture::Texture, viewport::Rect, Recorder,
+};
+use anyhow::{anyhow, Result};
+use glam::{Mat4, Vec2, Vec4};
+use std::{any, collections::HashMap, mem, sync::Arc};
+use vulkano::{
+    buffer::CpuBufferPool,
+    descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
+    pipeline::{
+        graphics::{
+            color_blend::ColorBlendState,
+            depth_stencil::DepthStencilState,
+            input_assembly::{InputAssemblyState, PrimitiveTopology},
+            rasterization::RasterizationState,
+            vertex_input::BuffersDefinition,
+            viewport::ViewportState,
+        },
+        GraphicsPipeline, Pipeline, PipelineBindPoint,
+    },
+    render_pass::Subpass,
+};
+
+//
+
+/// Textured and colored quad of a `SpriteBatch`
+#[derive(Clone)]
+pub struct Sprite {
+    /// Center of the quad
+    pub position: Vec2,
+    pub size: Vec2,
+    /// Radians around the center
+    pub rotation: f32,
+    /// Texture coordinates of the `-size / 2` and `size / 2` corners
+    pub region: [Vec2; 2],
+    /// Multiplied with the texture
+    pub color: Vec4,
+    /// `None` draws the plain `color`
+    pub texture: Option<Arc<Texture>>,
+}
+
+/// Collects `Sprite`s and draws them with one instanced
+/// draw call per texture
+///
+/// Sprites are drawn with no depth test. Sprites with the same texture are
+/// drawn in the order they were queued, textures in no particular order.
+///
+/// `end` draws in pixel coordinates, `flush` with any projection.
+pub struct SpriteBatch {
+    pipeline: Arc<GraphicsPipeline>,
+    instance_pool: CpuBufferPool<SpriteInstance>,
+    uploads: UploadTracker,
+
+    white: Arc<Texture>,
+    // keyed by the texture address,
+    // the texture is kept alive as long as its set is cached
+    sets: HashMap<usize, (Arc<Texture>, Arc<PersistentDescriptorSet>)>,
+
+    sprites: Vec<Sprite>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[repr(C)]
+struct SpriteInstance {
+    i_position: [f32; 2],
+    i_size: [f32; 2],
+    i_rotation: f32,
+    i_region: [f32; 4],
+    i_color: [f32; 4],
+}
+
+vulkano::impl_vertex!(
+    SpriteInstance,
+    i_position,
+    i_size,
+    i_rotation,
+    i_region,
+    i_color
+);
+
+mod vert {
+    #![allow(clippy::needless_question_mark)]
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+#version 420
+
+layout(location = 0) in vec2 i_position;
+layout(location = 1) in vec2 i_size;
+layout(location = 2) in float i_rotation;
+layout(location = 3) in vec4 i_region;
+layout(location = 4) in vec4 i_color;
+
+layout(location = 0) out vec2 fi_uv;
+layout(location = 1) out vec4 fi_color;
+
+layout(push_constant) uniform PushConstants {
+	mat4 view_proj;
+} pc;
+
+void main() {
+	// triangle strip: (0, 0), (1, 0), (0, 1), (1, 1)
+	vec2 corner = vec2(gl_VertexIndex & 1, gl_VertexIndex >> 1);
+	vec2 local = (corner - 0.5) * i_size;
+	float s = sin(i_rotation);
+	float c = cos(i_rotation);
+	vec2 pos = i_position + vec2(c * local.x - s * local.y, s * local.x + c * local.y);
+
+	gl_Position = pc.view_proj * vec4(pos, 0.0, 1.0);
+	fi_uv = mix(i_region.xy, i_region.zw, corner);
+	fi_color = i_color;
+}"
+    }
+}
+
+mod frag {
+    #![allow(clippy::needless_question_mark)]
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 420
+
+layout(location = 0) in vec2 fi_uv;
+layout(location = 1) in vec4 fi_color;
+
+layout(location = 0) out vec4 color;
+
+layout(binding = 0) uniform sampler2D tex;
+
+void main() {
+	color = fi_color * texture(tex, fi_uv);
+}"
+    }
+}
+
+//
+
+/// Orthographic projection of pixel coordinates in an `extent` sized viewport
+///
+/// (0, 0) is the top left corner and +Y is down.
+pub fn pixel_projection(extent: [u32; 2]) -> Mat4 {
+    Mat4::orthographic_rh(0.0, extent[0] as f32, 0.0, extent[1] as f32, -1.0, 1.0)
+}
+
+impl Sprite {
+    /// White untextured quad with no rotation
+    pub fn new(position: Vec2, size: Vec2) -> Self {
+        Self {
+            position,
+            size,
+            rotation: 0.0,
+            region: [Vec2::ZERO, Vec2::ONE],
+            color: Vec4::ONE,
+            texture: None,
+        }
+    }
+
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Part of the texture in texture coordinates, defaults to the whole texture
+    pub fn with_region(mut self, min: Vec2, max: Vec2) -> Self {
+        self.region = [min, max];
+        self
+    }
+
+    /// Part of the texture in texels, e.g. a tile of a sprite sheet
+    ///
+    /// Set the texture first, the region is relative to its extent.
+    pub fn with_texel_region(self, min: [u32; 2], max: [u32; 2]) -> Self {
+        let extent = self
+            .texture
+            .as_ref()
+            .map_or([1, 1], |texture| texture.extent());
+        let extent = Vec2::new(extent[0] as f32, extent[1] as f32);
+        let texels = |p: [u32; 2]| Vec2::new(p[0] as f32, p[1] as f32) / extent;
+        self.with_region(texels(min), texels(max))
+    }
+
+    pub fn with_color(mut self, color: Vec4) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_texture(mut self, texture: Arc<Texture>) -> Self {
+        self.texture = Some(texture);
+        self
+    }
+
+    fn instance(&self) -> SpriteInstance {
+        let [min, max] = self.region;
+        SpriteInstance {
+            i_position: self.position.to_array(),
+            i_size: self.size.to_array(),
+            i_rotation: self.rotation,
+            i_region: [min.x, min.y, max.x, max.y],
+            i_color: self.color.to_array(),
+        }
+    }
+}
+
+impl SpriteBatch {
+    pub fn new(renderer: &Renderer) -> Result<Self> {
+        let device = renderer.device.logical().clone();
+
+        let vert = vert::load(device.clone())?;
+        let frag = frag::load(device.clone())?;
+        let pipeline = GraphicsPipeline::start()
+            .input_assembly_state(
+                InputAssemblyState::new().topology(PrimitiveTopology::TriangleStrip),
+            )
+            .vertex_input_state(BuffersDefinition::new().instance::<SpriteInstance>())
+            .vertex_shader(
+                vert.entry_point("main")
+                    .ok_or_else(|| anyhow!("Sprite vertex shader has no main"))?,
+                (),
+            )
+            .viewport_state(ViewportState::viewport_dynamic_scissor_dynamic(1))
+            .fragment_shader(
+                frag.entry_point("main")
+                    .ok_or_else(|| anyhow!("Sprite fragment shader has no main"))?,
+                (),
+            )
+            .depth_stencil_state(DepthStencilState::disabled())
+            .color_blend_state(ColorBlendState::new(1).blend_alpha())
+            .rasterization_state(RasterizationState::new())
+            .render_pass(
+                Subpass::from(renderer.render_pass(), 0)
+                    .ok_or_else(|| anyhow!("Render pass has no subpasses"))?,
+            )
+            .build(device.clone())?;
+
+        Ok(Self {
+            pipeline,
+            instance_pool: CpuBufferPool::vertex_buffer(device),
+            uploads: renderer.device.uploads().clone(),
+
+            white: Arc::new(Texture::white(&renderer.device)?),
+            sets: HashMap::new(),
+
+            sprites: Vec::new(),
+        })
+    }
+
+    /// Drop the sprites queued since the last draw
+    pub fn begin(&mut self) -> &mut Self {
+        self.sprites.clear();
+        self
+    }
+
+    /// Queue a sprite for the next `end` or `flush`
+    pub fn draw(&mut self, sprite: Sprite) -> &mut Self {
+        self.sprites.push(sprite);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    /// Draw and clear the queued sprites in pixel coordinates of `rect`
+    ///
+    /// Sets the viewport and scissor to `rect`, see `pixel_projection`.
+    pub fn end(&mut self, recorder: &mut Recorder<true>, rect: Rect) -> Result<()> {
+        recorder.set_viewport_and_scissor(rect);
+        self.flush(recorder, &pixel_projection(rect.extent))
+    }
+
+    /// Draw and clear the queued sprites
+    ///
+    /// Uses the current viewport and scissor of the recorder.
+    pub fn flush(&mut self, recorder: &mut Recorder<true>, view_proj: &Mat4) -> Result<()> {
+        if self.sprites.is_empty() {
+            return Ok(());
+        }
+
+        // group by texture, the sort is stable so each group keeps its order
+        let mut sprites = std::mem::take(&mut self.sprites);
+        let white = self.white.clone();
+        let texture = |sprite: &Sprite| sprite.texture.clone().unwrap_or_else(|| white.clone());
+        sprites.sort_by_key(|sprite| Arc::as_ptr(&texture(sprite)) as usize);
+
+        let instances = self
+            .instance_pool
+            .chunk(sprites.iter().map(Sprite::instance))?;
+        self.uploads.upload(
+            any::type_name::<SpriteInstance>(),
+            (sprites.len() * mem::size_of::<SpriteInstance>()) as u64,
+        );
+
+        let layout = self.pipeline.layout().clone();
+        recorder
+            .record()
+            .bind_pipeline_graphics(self.pipeline.clone())
+            .push_constants(
+                layout.clone(),
+                0,
+                vert::ty::PushConstants {
+                    view_proj: view_proj.to_cols_array_2d(),
+                },
+            )
+            .bind_vertex_buffers(0, instances);
+
+        let mut first = 0;
+        while first < sprites.len() {
+            let group = texture(&sprites[first]);
+            let count = sprites[first..]
+                .iter()
+                .take_while(|sprite| Arc::ptr_eq(&texture(sprite), &group))
+                .count();
+
+            let set = self.set(group)?;
+            recorder
+                .record()
+                .bind_descriptor_sets(PipelineBindPoint::Graphics, layout.clone(), 0, set)
+                .draw(4, count as u32, 0, first as u32)?;
+            first += count;
+        }
+
+        // drop the sets of textures that only the cache holds
+        self.sets
+            .retain(|_, (texture, _)| Arc::strong_count(texture) > 1);
+
+        sprites.clear();
+        self.sprites = sprites;
+
+        Ok(())
+    }
+
+    /// Cached descriptor set of `texture`
+    fn set(&mut self, texture: Arc<Texture>) -> Result<Arc<PersistentDescriptorSet>> {
+        let key = Arc::as_ptr(&texture) as usize;
+        if let Some((_, set)) = self.sets.get(&key) {
+            return Ok(set.clone());
+        }
+
+        let layout = self.pipeline.layout().descriptor_set_layouts()[0].clone();
+        let set = PersistentDescriptorSet::new(
+            layout,
+            [WriteDescriptorSet::image_view_sampler(
+                0,
+                texture.image().clone(),
+                texture.sampler().clone(),
+            )],
+        )?;
+        self.sets.insert(key, (texture, set.clone()));
+
+        Ok(set)
+    }
+}
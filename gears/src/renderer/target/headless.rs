@@ -0,0 +1,126 @@
+use super::texture::RenderTexture;
+use crate::{
+    context::Context,
+    renderer::{
+        capture::FrameCapture,
+        device::{Dev, RenderDevice},
+        readback::ReadbackHandle,
+        viewport::Rect,
+        Recorder,
+    },
+    DepthMode,
+};
+use anyhow::Result;
+use std::sync::Arc;
+use vulkano::{
+    buffer::{BufferUsage, CpuAccessibleBuffer},
+    format::Format,
+    render_pass::RenderPass,
+    DeviceSize,
+};
+
+//
+
+/// Renders into a `RenderTexture` without a window or surface,
+/// e.g. for image tests in CI or thumbnails on a server
+///
+/// Every frame is read back, see `HeadlessFrame`. Build pipelines
+/// with `GPipelineBuilder::new_with_render_pass`.
+pub struct HeadlessRenderer {
+    pub device: Dev,
+    target: RenderTexture,
+}
+
+/// Color attachment of a submitted headless frame
+pub struct HeadlessFrame {
+    pixels: ReadbackHandle<u8>,
+    extent: [u32; 2],
+    format: Format,
+}
+
+//
+
+impl HeadlessRenderer {
+    pub const FORMAT: Format = Format::R8G8B8A8_SRGB;
+
+    pub fn new(context: &Context, extent: [u32; 2]) -> Result<Self> {
+        let device = RenderDevice::headless(context, None)?;
+        let target = RenderTexture::new(&device, extent, Self::FORMAT)?;
+        log::debug!("Headless renderer created");
+
+        Ok(Self { device, target })
+    }
+
+    /// Defaults to `DepthMode::Standard`
+    pub fn with_depth_mode(mut self, depth_mode: DepthMode) -> Self {
+        self.target = self.target.with_depth_mode(depth_mode);
+        self
+    }
+
+    pub fn depth_mode(&self) -> DepthMode {
+        self.target.depth_mode()
+    }
+
+    pub fn format(&self) -> Format {
+        Self::FORMAT
+    }
+
+    pub fn render_pass(&self) -> Arc<RenderPass> {
+        self.target.render_pass()
+    }
+
+    pub fn extent(&self) -> [u32; 2] {
+        self.target.extent()
+    }
+
+    pub fn rect(&self) -> Rect {
+        self.target.rect()
+    }
+
+    /// Waits for the previous frame to finish
+    pub fn begin_frame(&mut self) -> Result<Recorder<false>> {
+        self.target.begin()
+    }
+
+    /// Submit the frame and read its color attachment back
+    pub fn end_frame(&mut self, mut recorder: Recorder<false>) -> Result<HeadlessFrame> {
+        let extent = self.extent();
+        let len = extent[0] as DeviceSize
+            * extent[1] as DeviceSize
+            * Self::FORMAT.block_size().unwrap_or(4);
+        let buffer = unsafe {
+            CpuAccessibleBuffer::uninitialized_array(
+                self.device.logical().clone(),
+                len,
+                BufferUsage::transfer_destination(),
+                true,
+            )?
+        };
+
+        let color = self.target.color()?;
+        recorder
+            .record()
+            .copy_image_to_buffer(color.image().clone(), buffer.clone())?;
+        let fence = recorder.readback_fence();
+        self.target.end(recorder)?;
+
+        Ok(HeadlessFrame {
+            pixels: ReadbackHandle::new(buffer, fence),
+            extent,
+            format: Self::FORMAT,
+        })
+    }
+}
+
+impl HeadlessFrame {
+    /// Non blocking check if the frame has finished
+    pub fn is_ready(&self) -> bool {
+        self.pixels.is_ready()
+    }
+
+    /// Blocks until the frame has finished and converts it to RGBA8
+    pub fn wait(self) -> Result<FrameCapture> {
+        let pixels = self.pixels.wait()?;
+        FrameCapture::from_raw(self.extent, self.format, pixels)
+    }
+}
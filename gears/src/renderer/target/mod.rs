@@ -0,0 +1,3 @@
+pub mod headless;
+pub mod texture;
+pub mod window;
@@ -0,0 +1,245 @@
+use crate::renderer::{
+    device::Dev,
+    memory::{self, Allocation},
+    query::PerfQuery,
+    readback::FrameFuture,
+    simple_renderer::{RenderTarget, Renderer},
+    viewport::Rect,
+    Recorder,
+};
+use crate::DepthMode;
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+use vulkano::{
+    format::Format,
+    image::{
+        view::{ImageView, ImageViewAbstract},
+        AttachmentImage, ImageLayout, ImageUsage, SampleCount,
+    },
+    pipeline::graphics::viewport::{Scissor, Viewport},
+    render_pass::{AttachmentDesc, LoadOp, RenderPass, RenderPassDesc, StoreOp, SubpassDesc},
+    sync::{self, GpuFuture},
+};
+
+//
+
+/// Off-screen render target
+///
+/// The color attachments can be sampled in later passes
+/// (post-processing, mirrors, minimaps, ...).
+///
+/// `new_with_formats` creates one color attachment per format,
+/// e.g. albedo, normal and position for deferred shading.
+/// The fragment shader writes `layout(location = N) out`
+/// into the attachment N.
+pub struct RenderTexture {
+    device: Dev,
+    render_pass: Arc<RenderPass>,
+    target: RenderTarget,
+
+    colors: Vec<Arc<ImageView<AttachmentImage>>>,
+    extent: [u32; 2],
+    depth_mode: DepthMode,
+
+    // future for the previous render
+    previous: Option<Arc<FrameFuture>>,
+
+    _color_allocations: Vec<Allocation>,
+}
+
+//
+
+impl RenderTexture {
+    pub fn new(device: &Dev, extent: [u32; 2], format: Format) -> Result<Self> {
+        Self::new_with_formats(device, extent, &[format])
+    }
+
+    /// One color attachment per format, in the same order
+    pub fn new_with_formats(device: &Dev, extent: [u32; 2], formats: &[Format]) -> Result<Self> {
+        if formats.is_empty() {
+            return Err(anyhow!("RenderTexture needs at least one color format"));
+        }
+        let render_pass = Self::create_render_pass(device, formats)?;
+
+        let [width, height] = extent;
+        let mut colors = Vec::with_capacity(formats.len());
+        let mut color_allocations = Vec::with_capacity(formats.len());
+        for &format in formats {
+            let color_image = AttachmentImage::with_usage(
+                device.logical().clone(),
+                extent,
+                format,
+                ImageUsage {
+                    color_attachment: true,
+                    sampled: true,
+                    transfer_source: true,
+                    ..ImageUsage::none()
+                },
+            )?;
+            colors.push(ImageView::new(color_image)?);
+            color_allocations.push(device.memory().track(
+                memory::IMAGES,
+                width as u64 * height as u64 * format.block_size().unwrap_or(4),
+            ));
+        }
+
+        let views: Vec<Arc<dyn ImageViewAbstract>> =
+            colors.iter().map(|color| color.clone() as _).collect();
+        let target =
+            RenderTarget::with_color_attachments(device.clone(), render_pass.clone(), &views);
+
+        Ok(Self {
+            device: device.clone(),
+            render_pass,
+            target,
+
+            colors,
+            extent,
+            depth_mode: DepthMode::default(),
+
+            previous: None,
+
+            _color_allocations: color_allocations,
+        })
+    }
+
+    /// Same color format and depth mode as the swapchain
+    pub fn from_renderer(renderer: &Renderer, extent: [u32; 2]) -> Result<Self> {
+        Ok(Self::new(&renderer.device, extent, renderer.format())?
+            .with_depth_mode(renderer.depth_mode()))
+    }
+
+    /// Defaults to `DepthMode::Standard`
+    pub fn with_depth_mode(mut self, depth_mode: DepthMode) -> Self {
+        self.depth_mode = depth_mode;
+        self
+    }
+
+    pub fn depth_mode(&self) -> DepthMode {
+        self.depth_mode
+    }
+
+    /// Render pass for pipelines drawing into this texture
+    pub fn render_pass(&self) -> Arc<RenderPass> {
+        self.render_pass.clone()
+    }
+
+    pub fn extent(&self) -> [u32; 2] {
+        self.extent
+    }
+
+    pub fn rect(&self) -> Rect {
+        Rect::from_extent(self.extent)
+    }
+
+    pub fn viewport_and_scissor(&self) -> (Viewport, Scissor) {
+        let rect = self.rect();
+        (rect.viewport(), rect.scissor())
+    }
+
+    pub fn perf(&self) -> Arc<PerfQuery> {
+        self.target.perf.clone()
+    }
+
+    /// First color attachment for sampling
+    ///
+    /// Waits for the previous render into this texture to finish.
+    pub fn color(&mut self) -> Result<Arc<ImageView<AttachmentImage>>> {
+        self.color_attachment(0)
+    }
+
+    /// Color attachment `index` for sampling, see `new_with_formats`
+    ///
+    /// Waits for the previous render into this texture to finish.
+    pub fn color_attachment(&mut self, index: usize) -> Result<Arc<ImageView<AttachmentImage>>> {
+        self.wait()?;
+        self.colors.get(index).cloned().ok_or_else(|| {
+            anyhow!(
+                "RenderTexture has {} color attachments, {index} is out of range",
+                self.colors.len()
+            )
+        })
+    }
+
+    pub fn color_attachments(&self) -> usize {
+        self.colors.len()
+    }
+
+    /// Begin recording render commands into this texture
+    ///
+    /// Waits for the previous render into this texture to finish.
+    pub fn begin(&mut self) -> Result<Recorder<false>> {
+        self.wait()?;
+        let (recorder, _, _) =
+            Renderer::begin_record(&self.device, &self.target, self.depth_mode, 0);
+        Ok(recorder)
+    }
+
+    /// Submit the recorded commands to the graphics queue
+    pub fn end(&mut self, recorder: Recorder<false>) -> Result<()> {
+        let (cb, readbacks) = Renderer::end_record(recorder);
+
+        let future = sync::now(self.device.logical().clone())
+            .then_execute(self.device.queues.graphics.clone(), cb)?
+            .boxed()
+            .then_signal_fence_and_flush()?;
+        let future = Arc::new(future);
+
+        for readback in readbacks {
+            readback.signal(future.clone());
+        }
+        self.previous = Some(future);
+        Ok(())
+    }
+
+    /// Blocks until the previous render into this texture has finished
+    pub fn wait(&mut self) -> Result<()> {
+        if let Some(future) = self.previous.take() {
+            future.wait(None)?;
+        }
+        Ok(())
+    }
+
+    fn create_render_pass(device: &Dev, formats: &[Format]) -> Result<Arc<RenderPass>> {
+        let color = |format| AttachmentDesc {
+            format,
+            samples: SampleCount::Sample1,
+            load: LoadOp::Clear,
+            store: StoreOp::Store,
+            stencil_load: LoadOp::DontCare,
+            stencil_store: StoreOp::DontCare,
+            initial_layout: ImageLayout::Undefined,
+            final_layout: ImageLayout::ShaderReadOnlyOptimal,
+        };
+        let depth = AttachmentDesc {
+            format: device.depth_format(),
+            samples: SampleCount::Sample1,
+            load: LoadOp::Clear,
+            store: StoreOp::DontCare,
+            stencil_load: LoadOp::Clear,
+            stencil_store: StoreOp::DontCare,
+            initial_layout: ImageLayout::Undefined,
+            final_layout: ImageLayout::DepthStencilAttachmentOptimal,
+        };
+        let attachments = formats
+            .iter()
+            .map(|&format| color(format))
+            .chain(Some(depth))
+            .collect();
+
+        let subpass = SubpassDesc {
+            color_attachments: (0..formats.len())
+                .map(|i| (i, ImageLayout::ColorAttachmentOptimal))
+                .collect(),
+            depth_stencil: Some((formats.len(), ImageLayout::DepthStencilAttachmentOptimal)),
+            input_attachments: vec![],
+            resolve_attachments: vec![],
+            preserve_attachments: vec![],
+        };
+
+        Ok(RenderPass::new(
+            device.logical().clone(),
+            RenderPassDesc::new(attachments, vec![subpass], vec![]),
+        )?)
+    }
+}
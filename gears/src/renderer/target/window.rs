@@ -0,0 +1,240 @@
+use crate::{context::ContextError, renderer::device::Dev, SyncMode};
+use std::sync::Arc;
+use vulkano::{
+    format::Format,
+    image::{ImageUsage, SwapchainImage},
+    swapchain::{
+        acquire_next_image, AcquireError, Capabilities, ColorSpace, CompositeAlpha, Surface,
+        SurfaceTransform, Swapchain, SwapchainAcquireFuture,
+    },
+    sync::SharingMode,
+};
+use winit::window::Window;
+
+//
+
+pub struct SwapchainInfo {
+    format: (Format, ColorSpace),
+    sync: SyncMode,
+    len: u32,
+    extent: [u32; 2],
+    usage: ImageUsage,
+    transform: SurfaceTransform,
+    composite_alpha: CompositeAlpha,
+}
+
+pub struct WindowTargetBuilder {
+    pub extent: [u32; 2],
+    pub surface: Arc<Surface<Arc<Window>>>,
+}
+
+//
+
+pub type SwapchainImages = Vec<Arc<SwapchainImage<Arc<Window>>>>;
+
+//
+
+impl WindowTargetBuilder {
+    pub fn new(surface: Arc<Surface<Arc<Window>>>) -> Result<Self, ContextError> {
+        let size = surface.window().inner_size();
+        Ok(Self {
+            extent: [size.width, size.height],
+            surface,
+        })
+    }
+
+    pub fn build(
+        mut self,
+        device: &Dev,
+        sync: SyncMode,
+    ) -> Result<(WindowTarget, SwapchainImages), ContextError> {
+        let info = self.swapchain_info(device, sync)?;
+
+        let sharing = if device.queues.present == device.queues.graphics {
+            SharingMode::Exclusive
+        } else {
+            SharingMode::from(&[&device.queues.present, &device.queues.graphics][..])
+        };
+
+        let (swapchain, images) = Swapchain::start(device.logical().clone(), self.surface.clone())
+            .num_images(info.len)
+            .format(info.format.0)
+            .color_space(info.format.1)
+            .dimensions(info.extent)
+            .usage(info.usage)
+            .sharing_mode(sharing)
+            .transform(info.transform)
+            .composite_alpha(info.composite_alpha)
+            .present_mode(info.sync.present_mode())
+            .clipped(true)
+            .layers(1)
+            .build()
+            .map_err(ContextError::SwapchainCreationError)?;
+
+        Ok((
+            WindowTarget {
+                base: self,
+                format: info.format,
+                sync: info.sync,
+                swapchain,
+            },
+            images,
+        ))
+    }
+
+    fn swapchain_info(
+        &mut self,
+        device: &Dev,
+        sync: SyncMode,
+    ) -> Result<SwapchainInfo, ContextError> {
+        let caps = self.capabilities(device)?;
+        Ok(SwapchainInfo {
+            format: self.pick_format(&caps)?,
+            sync: self.pick_sync(&caps, sync),
+
+            len: self.swapchain_len(&caps),
+            extent: self.swapchain_extent(&caps),
+            usage: self.swapchain_usage(&caps),
+            transform: self.swapchain_transform(&caps),
+            composite_alpha: self.swapchain_composite_alpha(&caps),
+        })
+    }
+
+    fn capabilities(&self, device: &Dev) -> Result<Capabilities, ContextError> {
+        self.surface
+            .capabilities(device.physical())
+            .map_err(ContextError::CapabilitiesError)
+    }
+
+    fn pick_format(
+        &self,
+        surface_caps: &Capabilities,
+    ) -> Result<(Format, ColorSpace), ContextError> {
+        let format = surface_caps
+            .supported_formats
+            .iter()
+            .find(|(format, color_space)| {
+                format == &Format::R8G8B8A8_SRGB && color_space == &ColorSpace::SrgbNonLinear
+            })
+            .unwrap_or(&surface_caps.supported_formats[0]);
+        let format = *format;
+
+        log::debug!(
+            "Surface format chosen: {:?} from {:?}",
+            format,
+            surface_caps.supported_formats
+        );
+
+        Ok(format)
+    }
+
+    fn pick_sync(&self, surface_caps: &Capabilities, sync: SyncMode) -> SyncMode {
+        let picked = sync.or_fallback(&surface_caps.present_modes);
+        if picked != sync {
+            log::warn!(
+                "Requested present mode: '{:?}' not supported",
+                sync.present_mode()
+            );
+        }
+
+        log::debug!("Surface present mode chosen: {:?}", picked.present_mode());
+
+        picked
+    }
+
+    fn swapchain_len(&self, surface_caps: &Capabilities) -> u32 {
+        let preferred = surface_caps.min_image_count + 1;
+
+        if let Some(max_image_count) = surface_caps.max_image_count {
+            preferred.min(max_image_count)
+        } else {
+            preferred
+        }
+    }
+
+    fn swapchain_extent(&mut self, surface_caps: &Capabilities) -> [u32; 2] {
+        if let Some(extent) = surface_caps.current_extent {
+            self.extent = extent;
+        } else {
+            for i in 0..=1 {
+                self.extent[i] = self.extent[i]
+                    .max(surface_caps.min_image_extent[i])
+                    .min(surface_caps.max_image_extent[i]);
+            }
+        };
+
+        self.extent
+    }
+
+    fn swapchain_usage(&self, surface_caps: &Capabilities) -> ImageUsage {
+        // transfer source for frame captures
+        ImageUsage {
+            transfer_source: surface_caps.supported_usage_flags.transfer_source,
+            ..ImageUsage::color_attachment()
+        }
+    }
+
+    fn swapchain_transform(&self, surface_caps: &Capabilities) -> SurfaceTransform {
+        if surface_caps.supported_transforms.identity {
+            SurfaceTransform::Identity
+        } else {
+            surface_caps.current_transform
+        }
+    }
+
+    fn swapchain_composite_alpha(&self, surface_caps: &Capabilities) -> CompositeAlpha {
+        if surface_caps.supported_composite_alpha.opaque {
+            CompositeAlpha::Opaque
+        } else {
+            CompositeAlpha::Inherit
+        }
+    }
+}
+
+pub struct WindowTarget {
+    pub base: WindowTargetBuilder,
+    pub format: (Format, ColorSpace),
+    /// Present mode after the fallback
+    pub sync: SyncMode,
+    pub swapchain: Arc<Swapchain<Arc<Window>>>,
+}
+
+impl WindowTarget {
+    /// `None` if the swapchain is out of date or suboptimal and has to be recreated
+    pub fn acquire_image(
+        &self,
+    ) -> Result<Option<(usize, SwapchainAcquireFuture<Arc<Window>>)>, AcquireError> {
+        match acquire_next_image(self.swapchain.clone(), None) {
+            Ok((image_index, false, future)) => Ok(Some((image_index, future))),
+            Ok((_, true, _)) | Err(AcquireError::OutOfDate) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn extent(&mut self, device: &Dev) -> Result<[u32; 2], ContextError> {
+        let surface_caps = self.base.capabilities(device)?;
+        Ok(self.base.swapchain_extent(&surface_caps))
+    }
+
+    /// Recreate with the current surface size and the present mode picked from `sync`
+    pub fn recreate(
+        &mut self,
+        device: &Dev,
+        sync: SyncMode,
+    ) -> Result<SwapchainImages, ContextError> {
+        let surface_caps = self.base.capabilities(device)?;
+        let sync = self.base.pick_sync(&surface_caps, sync);
+
+        let (swapchain, images) = self
+            .swapchain
+            .recreate()
+            .present_mode(sync.present_mode())
+            .build()
+            .map_err(ContextError::SwapchainCreationError)?;
+
+        self.sync = sync;
+        self.base.extent = swapchain.dimensions();
+        self.swapchain = swapchain;
+        Ok(images)
+    }
+}
@@ -0,0 +1,99 @@
+/// Width of a glyph in font pixels
+pub(super) const WIDTH: u32 = 5;
+/// Height of a glyph in font pixels
+pub(super) const HEIGHT: u32 = 7;
+
+/// Glyphs are laid out on this grid, leaving one pixel between them
+pub(super) const ADVANCE: [u32; 2] = [WIDTH + 1, HEIGHT + 1];
+
+pub(super) const COUNT: usize = GLYPHS.len();
+
+/// 5x7 glyphs for ASCII 0x20 (space) ..= 0x5F (underscore)
+///
+/// Bit `y * 5 + x` is the font pixel at column `x` and row `y`,
+/// row 0 being the top one.
+const GLYPHS: [u64; 64] = [
+    0x000000000, // ' '
+    0x100421084, // '!'
+    0x00000294a, // '"'
+    0x295f57d4a, // '#'
+    0x11f4717c4, // '$'
+    0x632222263, // '%'
+    0x593511526, // '&'
+    0x000001084, // "'"
+    0x208210888, // '('
+    0x088842082, // ')'
+    0x009575480, // '*'
+    0x0084f9080, // '+'
+    0x088600000, // ','
+    0x0000f8000, // '-'
+    0x18c000000, // '.'
+    0x002222200, // '/'
+    0x3a33ae62e, // '0'
+    0x3884210c4, // '1'
+    0x7c444422e, // '2'
+    0x3a304111f, // '3'
+    0x211f4a988, // '4'
+    0x3a3083c3f, // '5'
+    0x3a317844c, // '6'
+    0x08422221f, // '7'
+    0x3a317462e, // '8'
+    0x1910f462e, // '9'
+    0x00c6018c0, // ':'
+    0x0886018c0, // ';'
+    0x208208888, // '<'
+    0x001f07c00, // '='
+    0x088882082, // '>'
+    0x10044422e, // '?'
+    0x3ab5b422e, // '@'
+    0x4631fc62e, // 'A'
+    0x3e317c62f, // 'B'
+    0x3a210862e, // 'C'
+    0x1d318c527, // 'D'
+    0x7c217843f, // 'E'
+    0x04217843f, // 'F'
+    0x7a31e862e, // 'G'
+    0x4631fc631, // 'H'
+    0x38842108e, // 'I'
+    0x19284211c, // 'J'
+    0x452519531, // 'K'
+    0x7c2108421, // 'L'
+    0x4631ad771, // 'M'
+    0x4639ace31, // 'N'
+    0x3a318c62e, // 'O'
+    0x04217c62f, // 'P'
+    0x59358c62e, // 'Q'
+    0x45257c62f, // 'R'
+    0x3e107043e, // 'S'
+    0x10842109f, // 'T'
+    0x3a318c631, // 'U'
+    0x11518c631, // 'V'
+    0x2ab5ac631, // 'W'
+    0x462a22a31, // 'X'
+    0x108454631, // 'Y'
+    0x7c222221f, // 'Z'
+    0x38421084e, // '['
+    0x020820820, // '\\'
+    0x39084210e, // ']'
+    0x000004544, // '^'
+    0x7c0000000, // '_'
+];
+
+//
+
+/// Index of the glyph for `c`
+///
+/// Lowercase letters use the uppercase glyphs,
+/// characters without a glyph are drawn as `?`
+pub(super) fn index(c: char) -> usize {
+    let c = c.to_ascii_uppercase();
+    match c {
+        ' '..='_' => c as usize - ' ' as usize,
+        _ => '?' as usize - ' ' as usize,
+    }
+}
+
+/// Is the font pixel at column `x` and row `y` of glyph `index` set
+pub(super) fn pixel(index: usize, x: u32, y: u32) -> bool {
+    (GLYPHS[index] >> (y * WIDTH + x)) & 1 != 0
+}
@@ -0,0 +1,385 @@
+use super::{
+    device::Dev,
+    memory::{self, Allocation, UploadTracker},
+    simple_renderer::Renderer,
+    viewport::Rect,
+    Recorder,
+};
+use anyhow::{anyhow, Result};
+use glam::{Vec2, Vec4};
+use std::{any, mem, sync::Arc};
+use vulkano::{
+    buffer::CpuBufferPool,
+    descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
+    format::Format,
+    image::{view::ImageView, ImageDimensions, ImmutableImage, MipmapsCount},
+    pipeline::{
+        graphics::{
+            color_blend::ColorBlendState, depth_stencil::DepthStencilState,
+            input_assembly::InputAssemblyState, rasterization::RasterizationState,
+            vertex_input::BuffersDefinition, viewport::ViewportState,
+        },
+        GraphicsPipeline, Pipeline, PipelineBindPoint,
+    },
+    render_pass::Subpass,
+    sampler::{Filter, Sampler, SamplerAddressMode},
+    sync::GpuFuture,
+};
+
+//
+
+mod font;
+
+//
+
+/// Texture with the glyphs of the embedded 5x7 bitmap font
+///
+/// Covers ASCII from space to underscore, lowercase
+/// letters are drawn with the uppercase glyphs.
+pub struct FontAtlas {
+    image: Arc<ImageView<ImmutableImage>>,
+    sampler: Arc<Sampler>,
+    _allocation: Allocation,
+}
+
+/// Glyph quads of any number of strings, drawn with a single draw call
+///
+/// Positions and sizes are in pixels from the top left corner of the frame.
+#[derive(Debug, Default, Clone)]
+pub struct TextBatch {
+    glyphs: Vec<Glyph>,
+}
+
+/// Draws `TextBatch`es with an internal pipeline
+///
+/// Text is drawn over everything with no depth test,
+/// so draw it after the rest of the frame.
+pub struct TextRenderer {
+    atlas: FontAtlas,
+    pipeline: Arc<GraphicsPipeline>,
+    set: Arc<PersistentDescriptorSet>,
+    vertex_pool: CpuBufferPool<TextVertex>,
+    uploads: UploadTracker,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Glyph {
+    index: usize,
+    position: Vec2,
+    scale: f32,
+    color: Vec4,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[repr(C)]
+struct TextVertex {
+    vi_pos: [f32; 2],
+    vi_uv: [f32; 2],
+    vi_color: [f32; 4],
+}
+
+vulkano::impl_vertex!(TextVertex, vi_pos, vi_uv, vi_color);
+
+mod vert {
+    #![allow(clippy::needless_question_mark)]
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+#version 420
+
+layout(location = 0) in vec2 vi_pos;
+layout(location = 1) in vec2 vi_uv;
+layout(location = 2) in vec4 vi_color;
+
+layout(location = 0) out vec2 fi_uv;
+layout(location = 1) out vec4 fi_color;
+
+void main() {
+	gl_Position = vec4(vi_pos, 0.0, 1.0);
+	fi_uv = vi_uv;
+	fi_color = vi_color;
+}"
+    }
+}
+
+mod frag {
+    #![allow(clippy::needless_question_mark)]
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 420
+
+layout(location = 0) in vec2 fi_uv;
+layout(location = 1) in vec4 fi_color;
+
+layout(location = 0) out vec4 color;
+
+layout(binding = 0) uniform sampler2D atlas;
+
+void main() {
+	if (texture(atlas, fi_uv).r < 0.5) {
+		discard;
+	}
+	color = fi_color;
+}"
+    }
+}
+
+//
+
+/// Glyphs per atlas row
+const ATLAS_COLUMNS: u32 = 16;
+
+impl FontAtlas {
+    /// Upload the embedded font, waits for the upload to finish
+    pub fn embedded(device: &Dev) -> Result<Self> {
+        let [width, height] = Self::size();
+        let mut texels = vec![0_u8; (width * height) as usize];
+        for index in 0..font::COUNT {
+            let [cell_x, cell_y] = Self::cell(index);
+            for y in 0..font::HEIGHT {
+                for x in 0..font::WIDTH {
+                    if font::pixel(index, x, y) {
+                        texels[((cell_y + y) * width + cell_x + x) as usize] = u8::MAX;
+                    }
+                }
+            }
+        }
+
+        let (image, future) = ImmutableImage::from_iter(
+            texels.into_iter(),
+            ImageDimensions::Dim2d {
+                width,
+                height,
+                array_layers: 1,
+            },
+            MipmapsCount::One,
+            Format::R8_UNORM,
+            device.queues.graphics.clone(),
+        )?;
+        future.then_signal_fence_and_flush()?.wait(None)?;
+        let allocation = device
+            .memory()
+            .track(memory::IMAGES, width as u64 * height as u64);
+
+        let image = ImageView::new(image)?;
+        let sampler = Sampler::start(device.logical().clone())
+            .filter(Filter::Nearest)
+            .address_mode(SamplerAddressMode::ClampToEdge)
+            .build()?;
+
+        Ok(Self {
+            image,
+            sampler,
+            _allocation: allocation,
+        })
+    }
+
+    /// Size of a glyph in font pixels
+    pub fn glyph_size(&self) -> [u32; 2] {
+        [font::WIDTH, font::HEIGHT]
+    }
+
+    /// Distance between glyphs and lines in font pixels
+    pub fn advance(&self) -> [u32; 2] {
+        font::ADVANCE
+    }
+
+    /// Atlas size in texels
+    fn size() -> [u32; 2] {
+        let rows = (font::COUNT as u32 + ATLAS_COLUMNS - 1) / ATLAS_COLUMNS;
+        [ATLAS_COLUMNS * font::ADVANCE[0], rows * font::ADVANCE[1]]
+    }
+
+    /// Top left texel of a glyph
+    fn cell(index: usize) -> [u32; 2] {
+        let index = index as u32;
+        [
+            index % ATLAS_COLUMNS * font::ADVANCE[0],
+            index / ATLAS_COLUMNS * font::ADVANCE[1],
+        ]
+    }
+}
+
+impl TextBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue `text` with its top left corner at `position`
+    ///
+    /// `scale` is the size of a font pixel in pixels,
+    /// `\n` starts a new line.
+    pub fn push(&mut self, text: &str, position: Vec2, scale: f32, color: Vec4) -> &mut Self {
+        let advance = Vec2::new(font::ADVANCE[0] as f32, font::ADVANCE[1] as f32) * scale;
+        for (row, line) in text.lines().enumerate() {
+            for (col, c) in line.chars().enumerate() {
+                if c == ' ' {
+                    continue;
+                }
+                self.glyphs.push(Glyph {
+                    index: font::index(c),
+                    position: position + advance * Vec2::new(col as f32, row as f32),
+                    scale,
+                    color,
+                });
+            }
+        }
+        self
+    }
+
+    /// Size of `text` in pixels, excluding the spacing after the last glyph and line
+    pub fn measure(text: &str, scale: f32) -> Vec2 {
+        let (cols, rows) = text.lines().fold((0, 0), |(cols, rows), line| {
+            (cols.max(line.chars().count() as u32), rows + 1)
+        });
+        let size = |n: u32, advance: u32| n.saturating_mul(advance).saturating_sub(1) as f32;
+        Vec2::new(size(cols, font::ADVANCE[0]), size(rows, font::ADVANCE[1])) * scale
+    }
+
+    pub fn clear(&mut self) {
+        self.glyphs.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.glyphs.is_empty()
+    }
+
+    /// Two triangles per glyph in normalized device coordinates
+    fn vertices(&self, extent: [u32; 2]) -> Vec<TextVertex> {
+        let extent = Vec2::new(extent[0].max(1) as f32, extent[1].max(1) as f32);
+        let [atlas_w, atlas_h] = FontAtlas::size();
+        let atlas = Vec2::new(atlas_w as f32, atlas_h as f32);
+        let glyph = Vec2::new(font::WIDTH as f32, font::HEIGHT as f32);
+
+        let mut vertices = Vec::with_capacity(self.glyphs.len() * 6);
+        for g in self.glyphs.iter() {
+            let [cell_x, cell_y] = FontAtlas::cell(g.index);
+            let cell = Vec2::new(cell_x as f32, cell_y as f32);
+            let color = g.color.to_array();
+            for corner in [
+                Vec2::new(0.0, 0.0),
+                Vec2::new(1.0, 0.0),
+                Vec2::new(1.0, 1.0),
+                Vec2::new(0.0, 0.0),
+                Vec2::new(1.0, 1.0),
+                Vec2::new(0.0, 1.0),
+            ] {
+                let pos = g.position + corner * glyph * g.scale;
+                vertices.push(TextVertex {
+                    vi_pos: (pos / extent * 2.0 - 1.0).to_array(),
+                    vi_uv: ((cell + corner * glyph) / atlas).to_array(),
+                    vi_color: color,
+                });
+            }
+        }
+        vertices
+    }
+}
+
+impl TextRenderer {
+    pub fn new(renderer: &Renderer) -> Result<Self> {
+        Self::with_subpass(renderer, 0)
+    }
+
+    /// Draw text in another subpass of the main render pass, see `RenderPassConfig`
+    pub fn with_subpass(renderer: &Renderer, subpass: u32) -> Result<Self> {
+        let device = renderer.device.logical().clone();
+        let atlas = FontAtlas::embedded(&renderer.device)?;
+
+        let vert = vert::load(device.clone())?;
+        let frag = frag::load(device.clone())?;
+        let pipeline = GraphicsPipeline::start()
+            .input_assembly_state(InputAssemblyState::new())
+            .vertex_input_state(BuffersDefinition::new().vertex::<TextVertex>())
+            .vertex_shader(
+                vert.entry_point("main")
+                    .ok_or_else(|| anyhow!("Text vertex shader has no main"))?,
+                (),
+            )
+            .viewport_state(ViewportState::viewport_dynamic_scissor_dynamic(1))
+            .fragment_shader(
+                frag.entry_point("main")
+                    .ok_or_else(|| anyhow!("Text fragment shader has no main"))?,
+                (),
+            )
+            .depth_stencil_state(DepthStencilState::disabled())
+            .color_blend_state(ColorBlendState::new(1).blend_alpha())
+            .rasterization_state(RasterizationState::new())
+            .render_pass(
+                Subpass::from(renderer.render_pass(), subpass)
+                    .ok_or_else(|| anyhow!("Render pass has no subpass {subpass}"))?,
+            )
+            .build(device.clone())?;
+
+        let layout = pipeline.layout().descriptor_set_layouts()[0].clone();
+        let set = PersistentDescriptorSet::new(
+            layout,
+            [WriteDescriptorSet::image_view_sampler(
+                0,
+                atlas.image.clone(),
+                atlas.sampler.clone(),
+            )],
+        )?;
+
+        Ok(Self {
+            atlas,
+            pipeline,
+            set,
+            vertex_pool: CpuBufferPool::vertex_buffer(device),
+            uploads: renderer.device.uploads().clone(),
+        })
+    }
+
+    pub fn atlas(&self) -> &FontAtlas {
+        &self.atlas
+    }
+
+    /// Draw a single string, see `TextBatch::push`
+    pub fn draw(
+        &self,
+        recorder: &mut Recorder<true>,
+        text: &str,
+        position: Vec2,
+        scale: f32,
+        color: Vec4,
+    ) -> Result<()> {
+        let mut batch = TextBatch::new();
+        batch.push(text, position, scale, color);
+        self.draw_batch(recorder, &batch)
+    }
+
+    /// Draw every string in `batch` with one draw call
+    ///
+    /// Sets the viewport and scissor to the whole frame.
+    pub fn draw_batch(&self, recorder: &mut Recorder<true>, batch: &TextBatch) -> Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let extent = recorder
+            .extent()
+            .ok_or_else(|| anyhow!("Recorder has no framebuffer to draw text into"))?;
+        let vertices = batch.vertices(extent);
+        let count = vertices.len() as u32;
+        let vertices = self.vertex_pool.chunk(vertices)?;
+        self.uploads.upload(
+            any::type_name::<TextVertex>(),
+            count as u64 * mem::size_of::<TextVertex>() as u64,
+        );
+
+        recorder
+            .set_viewport_and_scissor(Rect::from_extent(extent))
+            .bind_pipeline_graphics(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.pipeline.layout().clone(),
+                0,
+                self.set.clone(),
+            )
+            .bind_vertex_buffers(0, vertices)
+            .draw(count, 1, 0, 0)?;
+
+        Ok(())
+    }
+}
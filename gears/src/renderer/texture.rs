@@ -0,0 +1,119 @@
+use super::{
+    device::Dev,
+    memory::{self, Allocation},
+};
+use anyhow::{anyhow, bail, Result};
+use std::sync::Arc;
+use vulkano::{
+    format::Format,
+    image::{view::ImageView, ImageDimensions, ImmutableImage, MipmapsCount},
+    sampler::{Filter, Sampler, SamplerAddressMode},
+    sync::GpuFuture,
+};
+
+//
+
+/// Sampled RGBA8 sRGB image
+pub struct Texture {
+    image: Arc<ImageView<ImmutableImage>>,
+    sampler: Arc<Sampler>,
+    filter: Filter,
+    extent: [u32; 2],
+    _allocation: Allocation,
+}
+
+//
+
+impl Texture {
+    /// Upload `pixels` (4 bytes per texel, rows from the top), waits for the upload to finish
+    pub fn from_rgba(
+        device: &Dev,
+        extent: [u32; 2],
+        pixels: &[u8],
+        filter: Filter,
+    ) -> Result<Self> {
+        let [width, height] = extent;
+        if pixels.len() != (width * height * 4) as usize {
+            return Err(anyhow!(
+                "Texture of {width}x{height} expects {} bytes, got {}",
+                width * height * 4,
+                pixels.len()
+            ));
+        }
+
+        let (image, future) = ImmutableImage::from_iter(
+            pixels.iter().copied(),
+            ImageDimensions::Dim2d {
+                width,
+                height,
+                array_layers: 1,
+            },
+            MipmapsCount::One,
+            Format::R8G8B8A8_SRGB,
+            device.queues.graphics.clone(),
+        )?;
+        future.then_signal_fence_and_flush()?.wait(None)?;
+        let allocation = device.memory().track(memory::IMAGES, pixels.len() as u64);
+
+        let image = ImageView::new(image)?;
+        let sampler = Sampler::start(device.logical().clone())
+            .filter(filter)
+            .address_mode(SamplerAddressMode::ClampToEdge)
+            .build()?;
+
+        Ok(Self {
+            image,
+            sampler,
+            filter,
+            extent,
+            _allocation: allocation,
+        })
+    }
+
+    /// Single white texel, for drawing untextured with a textured pipeline
+    pub fn white(device: &Dev) -> Result<Self> {
+        Self::from_rgba(device, [1, 1], &[u8::MAX; 4], Filter::Nearest)
+    }
+
+    #[cfg(feature = "image")]
+    pub fn load<P: AsRef<std::path::Path>>(device: &Dev, path: P, filter: Filter) -> Result<Self> {
+        let image = image::open(path)?.to_rgba8();
+        Self::from_rgba(
+            device,
+            [image.width(), image.height()],
+            image.as_raw(),
+            filter,
+        )
+    }
+
+    /// Sample with up to `max` anisotropic samples, e.g. for ground textures
+    ///
+    /// Needs the `sampler_anisotropy` feature, see `Context::with_features`.
+    /// `max` is clamped to the `max_sampler_anisotropy` limit.
+    pub fn with_anisotropy(mut self, device: &Dev, max: f32) -> Result<Self> {
+        if !device.enabled_features().sampler_anisotropy {
+            bail!("Anisotropic filtering needs the sampler_anisotropy device feature");
+        }
+        let limit = device.physical().properties().max_sampler_anisotropy;
+
+        self.sampler = Sampler::start(device.logical().clone())
+            .filter(self.filter)
+            .address_mode(SamplerAddressMode::ClampToEdge)
+            .anisotropy(Some(max.clamp(1.0, limit)))
+            .build()?;
+        Ok(self)
+    }
+
+    pub fn image(&self) -> &Arc<ImageView<ImmutableImage>> {
+        &self.image
+    }
+
+    pub fn sampler(&self) -> &Arc<Sampler> {
+        &self.sampler
+    }
+
+    /// Size in texels
+    pub fn extent(&self) -> [u32; 2] {
+        self.extent
+    }
+}
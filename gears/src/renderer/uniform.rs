@@ -0,0 +1,251 @@
+use super::{device::Dev, memory::UploadTracker};
+use anyhow::{anyhow, Result};
+use std::{any, marker::PhantomData, mem, sync::Arc};
+use vulkano::{
+    buffer::{
+        cpu_pool::CpuBufferPoolChunk, BufferSlice, BufferUsage, CpuAccessibleBuffer, CpuBufferPool,
+        TypedBufferAccess,
+    },
+    descriptor_set::{
+        layout::DescriptorSetLayout, single_layout_pool::SingleLayoutDescSet,
+        PersistentDescriptorSet, SingleLayoutDescSetPool, WriteDescriptorSet,
+    },
+    memory::pool::StdMemoryPool,
+    pipeline::{GraphicsPipeline, Pipeline},
+    DeviceSize,
+};
+
+//
+
+/// First element of an uploaded `UniformArrayBuffer`,
+/// other elements are selected with dynamic offsets
+pub type UniformArray = Arc<BufferSlice<[u8], Arc<CpuBufferPoolChunk<u8, Arc<StdMemoryPool>>>>>;
+
+/// Array of uniform structs for `UNIFORM_BUFFER_DYNAMIC` descriptors
+///
+/// Elements are aligned to `minUniformBufferOffsetAlignment`.
+/// The pipeline has to mark the binding dynamic with
+/// `GPipelineBuilder::with_dynamic_uniform`.
+pub struct UniformArrayBuffer<T> {
+    pool: CpuBufferPool<u8>,
+    stride: DeviceSize,
+    uploads: UploadTracker,
+    _p: PhantomData<T>,
+}
+
+/// Uniform buffer and descriptor set pools of a pipeline
+/// whose descriptor set has a single uniform buffer
+///
+/// Replaces the `buffer_pool.next`, `descriptor_set_layouts()[0]`,
+/// `WriteDescriptorSet::buffer(0, ..)` steps with one `next` call.
+pub struct UniformSets<T> {
+    buffer_pool: CpuBufferPool<T>,
+    desc_pool: SingleLayoutDescSetPool,
+    binding: u32,
+    uploads: UploadTracker,
+}
+
+/// One uniform buffer and descriptor set per frame in flight
+///
+/// Like `UniformSets`, but `update` rewrites the buffer of the
+/// frame in flight instead of allocating a new buffer and set
+/// every frame. The renderer waits for the previous use of a
+/// frame in flight in `Renderer::begin_frame`, see
+/// `Renderer::frame_count`.
+pub struct FrameUniformSets<T> {
+    device: Dev,
+    layout: Arc<DescriptorSetLayout>,
+    binding: u32,
+    frames: Vec<Vec<Option<FrameUniform<T>>>>,
+}
+
+struct FrameUniform<T> {
+    buffer: Arc<CpuAccessibleBuffer<T>>,
+    set: Arc<PersistentDescriptorSet>,
+}
+
+//
+
+impl<T> UniformArrayBuffer<T>
+where
+    T: Copy + Send + Sync + 'static,
+{
+    pub fn new(device: &Dev) -> Self {
+        let alignment = device
+            .physical()
+            .properties()
+            .min_uniform_buffer_offset_alignment
+            .max(1);
+        let size = mem::size_of::<T>() as DeviceSize;
+        let stride = (size + alignment - 1) / alignment * alignment;
+
+        Self {
+            pool: CpuBufferPool::new(device.logical().clone(), BufferUsage::uniform_buffer()),
+            stride,
+            uploads: device.uploads().clone(),
+            _p: PhantomData,
+        }
+    }
+
+    /// Bytes between two elements
+    pub fn stride(&self) -> DeviceSize {
+        self.stride
+    }
+
+    /// Dynamic offset of the element at `index`
+    pub fn offset(&self, index: usize) -> u32 {
+        (self.stride * index as DeviceSize) as u32
+    }
+
+    /// Upload the elements into a new chunk
+    pub fn next<I>(&self, data: I) -> Result<UniformArray>
+    where
+        I: ExactSizeIterator<Item = T>,
+    {
+        let stride = self.stride as usize;
+        let mut bytes = vec![0u8; data.len().max(1) * stride];
+        for (i, element) in data.enumerate() {
+            let dst = bytes[i * stride..].as_mut_ptr() as *mut T;
+            // SAFETY: each element has `stride >= size_of::<T>()` bytes of space
+            unsafe { dst.write_unaligned(element) };
+        }
+
+        self.uploads
+            .upload(any::type_name::<Self>(), bytes.len() as u64);
+        let chunk = self.pool.chunk(bytes)?;
+        chunk
+            .slice(0..mem::size_of::<T>() as DeviceSize)
+            .ok_or_else(|| anyhow!("Uniform array chunk is smaller than one element"))
+    }
+}
+
+impl<T> UniformSets<T>
+where
+    T: Send + Sync + 'static,
+{
+    /// Uniform buffer at set 0, binding 0 of `pipeline`
+    pub fn new(device: &Dev, pipeline: &GraphicsPipeline) -> Result<Self> {
+        Self::with_binding(device, pipeline, 0, 0)
+    }
+
+    /// Uniform buffer at `set`, `binding` of `pipeline`
+    ///
+    /// The set must not have any other bindings.
+    pub fn with_binding(
+        device: &Dev,
+        pipeline: &GraphicsPipeline,
+        set: usize,
+        binding: u32,
+    ) -> Result<Self> {
+        let layout = pipeline
+            .layout()
+            .descriptor_set_layouts()
+            .get(set)
+            .ok_or_else(|| anyhow!("Pipeline has no descriptor set {set}"))?
+            .clone();
+
+        Ok(Self {
+            buffer_pool: CpuBufferPool::uniform_buffer(device.logical().clone()),
+            desc_pool: SingleLayoutDescSetPool::new(layout),
+            binding,
+            uploads: device.uploads().clone(),
+        })
+    }
+
+    /// Upload `data` and write it to a new descriptor set
+    pub fn next(&mut self, data: T) -> Result<Arc<SingleLayoutDescSet>> {
+        let buffer = self.buffer_pool.next(data)?;
+        self.uploads
+            .upload(any::type_name::<Self>(), mem::size_of::<T>() as u64);
+        let set = self
+            .desc_pool
+            .next([WriteDescriptorSet::buffer(self.binding, buffer)])?;
+        Ok(set)
+    }
+}
+
+impl<T> FrameUniformSets<T>
+where
+    T: Copy + Send + Sync + 'static,
+{
+    /// Uniform buffer at set 0, binding 0 of `pipeline`
+    pub fn new(device: &Dev, pipeline: &GraphicsPipeline) -> Result<Self> {
+        Self::with_binding(device, pipeline, 0, 0)
+    }
+
+    /// Uniform buffer at `set`, `binding` of `pipeline`
+    ///
+    /// The set must not have any other bindings.
+    pub fn with_binding(
+        device: &Dev,
+        pipeline: &GraphicsPipeline,
+        set: usize,
+        binding: u32,
+    ) -> Result<Self> {
+        let layout = pipeline
+            .layout()
+            .descriptor_set_layouts()
+            .get(set)
+            .ok_or_else(|| anyhow!("Pipeline has no descriptor set {set}"))?
+            .clone();
+
+        Ok(Self {
+            device: device.clone(),
+            layout,
+            binding,
+            frames: Vec::new(),
+        })
+    }
+
+    /// Write `data` to the buffer of `frame_in_flight` and return its descriptor set
+    ///
+    /// The buffer and set are created on the first use of a frame in flight.
+    pub fn update(
+        &mut self,
+        frame_in_flight: usize,
+        data: T,
+    ) -> Result<Arc<PersistentDescriptorSet>> {
+        self.update_nth(frame_in_flight, 0, data)
+    }
+
+    /// `update` for the `n`th of several sets used in the same frame
+    pub fn update_nth(
+        &mut self,
+        frame_in_flight: usize,
+        n: usize,
+        data: T,
+    ) -> Result<Arc<PersistentDescriptorSet>> {
+        if self.frames.len() <= frame_in_flight {
+            self.frames.resize_with(frame_in_flight + 1, Vec::new);
+        }
+        let frame = &mut self.frames[frame_in_flight];
+        if frame.len() <= n {
+            frame.resize_with(n + 1, || None);
+        }
+
+        if let Some(uniform) = frame[n].as_ref() {
+            // still locked if the gpu has not released the buffer,
+            // a new buffer replaces it then
+            if let Ok(mut lock) = uniform.buffer.write() {
+                *lock = data;
+                return Ok(uniform.set.clone());
+            }
+        }
+
+        let buffer = CpuAccessibleBuffer::from_data(
+            self.device.logical().clone(),
+            BufferUsage::uniform_buffer(),
+            false,
+            data,
+        )?;
+        let set = PersistentDescriptorSet::new(
+            self.layout.clone(),
+            [WriteDescriptorSet::buffer(self.binding, buffer.clone())],
+        )?;
+        frame[n] = Some(FrameUniform {
+            buffer,
+            set: set.clone(),
+        });
+        Ok(set)
+    }
+}
@@ -0,0 +1,93 @@
+use super::device::Dev;
+use anyhow::Result;
+use parking_lot::Mutex;
+use std::{sync::Arc, time::Duration};
+use vulkano::{
+    command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer},
+    sync::{self, FenceSignalFuture, GpuFuture},
+};
+
+//
+
+type UploadFuture = FenceSignalFuture<Box<dyn GpuFuture + Send + Sync>>;
+
+/// Records buffer copies into a separate command buffer
+/// that gets submitted to the transfer queue.
+///
+/// The transfer queue is the graphics queue if the
+/// device has no dedicated transfer queue family.
+pub struct Uploader {
+    device: Dev,
+    command_buffer: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    handle: UploadHandle,
+}
+
+/// Shared handle to a submitted (or to be submitted) upload
+#[derive(Clone, Default)]
+pub struct UploadHandle {
+    future: Arc<Mutex<Option<UploadFuture>>>,
+}
+
+//
+
+impl Uploader {
+    pub fn new(device: &Dev) -> Result<Self> {
+        let command_buffer = AutoCommandBufferBuilder::primary(
+            device.logical().clone(),
+            device.queues.transfer.family(),
+            CommandBufferUsage::OneTimeSubmit,
+        )?;
+
+        Ok(Self {
+            device: device.clone(),
+            command_buffer,
+            handle: UploadHandle::default(),
+        })
+    }
+
+    pub fn device(&self) -> &Dev {
+        &self.device
+    }
+
+    pub fn record(&mut self) -> &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer> {
+        &mut self.command_buffer
+    }
+
+    /// Handle that becomes ready after this uploader
+    /// has been submitted and the upload has finished
+    pub fn handle(&self) -> UploadHandle {
+        self.handle.clone()
+    }
+
+    pub fn submit(self) -> Result<UploadHandle> {
+        let command_buffer = self.command_buffer.build()?;
+
+        let future = sync::now(self.device.logical().clone())
+            .then_execute(self.device.queues.transfer.clone(), command_buffer)?
+            .boxed_send_sync()
+            .then_signal_fence_and_flush()?;
+
+        *self.handle.future.lock() = Some(future);
+        Ok(self.handle)
+    }
+}
+
+impl UploadHandle {
+    /// Non blocking check if the upload has finished
+    pub fn is_ready(&self) -> bool {
+        match self.future.lock().as_ref() {
+            Some(future) => future.wait(Some(Duration::from_secs(0))).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Blocks until the upload has finished
+    ///
+    /// Returns immediately if the uploader was never submitted
+    pub fn wait(&self) -> Result<()> {
+        if let Some(future) = self.future.lock().as_ref() {
+            future.wait(None)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+use vulkano::pipeline::graphics::viewport::{Scissor, Viewport};
+
+//
+
+/// Pixel rectangle of a render target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rect {
+    pub origin: [u32; 2],
+    pub extent: [u32; 2],
+}
+
+/// Splits a frame into multiple viewports
+///
+/// Viewports are ordered left to right, top to bottom.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SplitScreen {
+    pub extent: [u32; 2],
+}
+
+//
+
+impl Rect {
+    pub fn new(origin: [u32; 2], extent: [u32; 2]) -> Self {
+        Self { origin, extent }
+    }
+
+    pub fn from_extent(extent: [u32; 2]) -> Self {
+        Self {
+            origin: [0, 0],
+            extent,
+        }
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.extent[0] as f32 / self.extent[1].max(1) as f32
+    }
+
+    pub fn viewport(&self) -> Viewport {
+        Viewport {
+            origin: [self.origin[0] as f32, self.origin[1] as f32],
+            dimensions: [self.extent[0] as f32, self.extent[1] as f32],
+            depth_range: 0.0..1.0,
+        }
+    }
+
+    pub fn scissor(&self) -> Scissor {
+        Scissor {
+            origin: self.origin,
+            dimensions: self.extent,
+        }
+    }
+}
+
+impl SplitScreen {
+    pub fn new(extent: [u32; 2]) -> Self {
+        Self { extent }
+    }
+
+    /// `n` viewports side by side
+    pub fn columns(&self, n: usize) -> Vec<Rect> {
+        self.grid_with(n as u32, 1, n)
+    }
+
+    /// `n` viewports on top of each other
+    pub fn rows(&self, n: usize) -> Vec<Rect> {
+        self.grid_with(1, n as u32, n)
+    }
+
+    /// `n` viewports in a grid that is as square as possible
+    ///
+    /// 2 viewports are placed side by side, 3 and 4 in a 2x2 grid, ...
+    pub fn grid(&self, n: usize) -> Vec<Rect> {
+        let columns = (n as f32).sqrt().ceil().max(1.0) as u32;
+        let rows = ((n as u32) + columns - 1) / columns;
+        self.grid_with(columns, rows, n)
+    }
+
+    fn grid_with(&self, columns: u32, rows: u32, n: usize) -> Vec<Rect> {
+        let columns = columns.max(1);
+        let rows = rows.max(1);
+        let cell = [self.extent[0] / columns, self.extent[1] / rows];
+
+        (0..n as u32)
+            .map(|i| {
+                let (x, y) = (i % columns, i / columns);
+                let origin = [x * cell[0], y * cell[1]];
+
+                // the last column and row take the leftover pixels
+                let extent = [
+                    if x + 1 == columns {
+                        self.extent[0] - origin[0]
+                    } else {
+                        cell[0]
+                    },
+                    if y + 1 == rows {
+                        self.extent[1] - origin[1]
+                    } else {
+                        cell[1]
+                    },
+                ];
+
+                Rect { origin, extent }
+            })
+            .collect()
+    }
+}
@@ -1,136 +1,204 @@
 use std::{
-    ops::Deref,
-    time::{Duration, Instant},
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::AddAssign,
+    path::Path,
+    time::Duration,
 };
 
-pub struct Reporter {
-    count: u32,
-    elapsed: Duration,
-    report_timer: Instant,
-    report_interval: Duration,
+/// Draw counts of a single frame
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DrawStats {
+    pub visible: u32,
+    pub culled: u32,
+    pub triangles: u64,
+}
 
-    last_interval: Option<Duration>,
-    last_per_second: Option<f64>,
+/// Draw calls recorded into a frame, see `Recorder::count_draw`
+///
+/// Triangles assume triangle lists.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FrameStats {
+    pub draw_calls: u32,
+    pub triangles: u64,
+    pub vertices: u64,
 }
 
-pub struct Timer {
-    begin: Instant,
+/// Collects the `DrawStats` of the frame being drawn
+#[derive(Debug, Default)]
+pub struct DrawReporter {
+    frame: DrawStats,
+    last: DrawStats,
 }
 
-impl Default for Reporter {
-    fn default() -> Self {
-        Self::new_with_interval(Duration::from_secs(3))
+/// CPU and GPU time of a single frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameSample {
+    pub frame_index: u64,
+    pub cpu: Duration,
+    /// `None` if the GPU time was not available
+    pub gpu: Option<Duration>,
+}
+
+/// Keeps the frame times of the last `capacity` frames
+/// for exporting them as CSV or JSON
+#[derive(Debug)]
+pub struct PerfRecorder {
+    samples: VecDeque<FrameSample>,
+    capacity: usize,
+    frame_index: u64,
+}
+
+impl AddAssign for DrawStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.visible += rhs.visible;
+        self.culled += rhs.culled;
+        self.triangles += rhs.triangles;
     }
 }
 
-impl Reporter {
-    pub fn new() -> Self {
-        Self::default()
+impl AddAssign for FrameStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.draw_calls += rhs.draw_calls;
+        self.triangles += rhs.triangles;
+        self.vertices += rhs.vertices;
     }
+}
 
-    pub fn new_with_interval(report_interval: Duration) -> Self {
+impl FrameStats {
+    /// One draw call of `vertices` vertices or indices per instance
+    pub fn draw(vertices: u32, instances: u32) -> Self {
+        let instances = instances as u64;
         Self {
-            count: 0_u32,
-            elapsed: Duration::default(),
-            report_timer: Instant::now(),
-            report_interval,
-
-            last_interval: None,
-            last_per_second: None,
+            draw_calls: 1,
+            triangles: (vertices / 3) as u64 * instances,
+            vertices: vertices as u64 * instances,
         }
     }
+}
 
-    pub fn begin(&self) -> Timer {
-        Timer {
-            begin: Instant::now(),
-        }
+impl DrawReporter {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    pub fn end(&mut self, timer: Timer) -> bool {
-        self.elapsed += timer.begin.elapsed();
-        self.count += 1;
-
-        let should_report = self.should_report();
-        if should_report {
-            self.reset();
-        }
-        should_report
+    pub fn add(&mut self, stats: DrawStats) {
+        self.frame += stats;
     }
 
-    pub fn manual(&mut self, elapsed: Duration) -> bool {
-        self.elapsed += elapsed;
-        self.count += 1;
+    /// Called by the game loop after `Runnable::draw`
+    pub fn end_frame(&mut self) {
+        self.last = std::mem::take(&mut self.frame);
+    }
 
-        let should_report = self.should_report();
-        if should_report {
-            self.reset();
-        }
-        should_report
+    /// Stats of the last complete frame
+    pub fn last(&self) -> DrawStats {
+        self.last
     }
 
-    pub fn should_report(&self) -> bool {
-        self.report_timer.elapsed() >= self.report_interval
+    pub fn last_string(&self) -> String {
+        format!(
+            "{} visible, {} culled, {} triangles",
+            self.last.visible, self.last.culled, self.last.triangles
+        )
     }
+}
 
-    pub fn report_interval(&self) -> Duration {
-        self.report_interval
+impl Default for PerfRecorder {
+    fn default() -> Self {
+        Self::with_capacity(1024)
     }
+}
 
-    /* pub fn report(&mut self, interval_name: &str, per_second_name: &str) {
-        let avg = self.elapsed / self.count;
-        let fps = self.count as f64 / self.report_interval.as_secs_f64();
+impl PerfRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-        log::debug!(
-            "Report ({interval}s)\nAVG {interval_name}: {per_second:.2?}\nRESP {per_second_name}: {resp_fps:.2}\nREAL {per_second_name}: {real_fps:.2}",
-            interval = self.report_interval.as_secs_f64(),
-            per_second = avg,
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            frame_index: 0,
+        }
+    }
 
-            resp_fps = 1.0 / avg.as_secs_f64(),
-            real_fps = fps,
+    /// Called by the game loop after every frame
+    pub fn push(&mut self, cpu: Duration, gpu: Option<Duration>) {
+        if self.capacity != 0 {
+            if self.samples.len() == self.capacity {
+                self.samples.pop_front();
+            }
+            self.samples.push_back(FrameSample {
+                frame_index: self.frame_index,
+                cpu,
+                gpu,
+            });
+        }
+        self.frame_index += 1;
+    }
 
-            interval_name = interval_name,
-            per_second_name = per_second_name,
-        );
-    } */
+    /// Oldest first
+    pub fn samples(&self) -> impl Iterator<Item = &FrameSample> {
+        self.samples.iter()
+    }
 
-    pub fn reset(&mut self) {
-        let avg = self.elapsed / self.count;
-        let fps = self.count as f64 / self.report_interval.as_secs_f64();
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
 
-        self.count = 0;
-        self.elapsed = Duration::default();
-        self.report_timer = Instant::now();
-        self.last_interval = Some(avg);
-        self.last_per_second = Some(fps);
+    /// Columns: `frame_index,cpu_ms,gpu_ms`
+    ///
+    /// `gpu_ms` is empty for frames without a GPU time.
+    pub fn write_csv<W: Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(w, "frame_index,cpu_ms,gpu_ms")?;
+        for sample in self.samples.iter() {
+            write!(w, "{},{}", sample.frame_index, as_ms(sample.cpu))?;
+            match sample.gpu {
+                Some(gpu) => writeln!(w, ",{}", as_ms(gpu))?,
+                None => writeln!(w, ",")?,
+            }
+        }
+        Ok(())
     }
 
-    /* pub fn report_maybe(&mut self, interval_name: &str, per_second_name: &str) {
-        if self.should_report() {
-            self.report(interval_name, per_second_name);
-            self.reset();
+    /// An array of `{ "frame_index", "cpu_ms", "gpu_ms" }` objects
+    ///
+    /// `gpu_ms` is `null` for frames without a GPU time.
+    pub fn write_json<W: Write>(&self, mut w: W) -> io::Result<()> {
+        write!(w, "[")?;
+        for (i, sample) in self.samples.iter().enumerate() {
+            if i != 0 {
+                write!(w, ",")?;
+            }
+            write!(
+                w,
+                "\n  {{\"frame_index\":{},\"cpu_ms\":{},\"gpu_ms\":",
+                sample.frame_index,
+                as_ms(sample.cpu)
+            )?;
+            match sample.gpu {
+                Some(gpu) => write!(w, "{}}}", as_ms(gpu))?,
+                None => write!(w, "null}}")?,
+            }
         }
-    } */
+        writeln!(w, "\n]")
+    }
 
-    pub fn last(&self) -> Option<(Duration, f64)> {
-        Some((self.last_interval?, self.last_per_second?))
+    pub fn dump_csv<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_csv(&mut w)?;
+        w.flush()
     }
 
-    pub fn last_string(&self) -> (String, String) {
-        (
-            self.last_interval
-                .map(|ft| format!("{:3.3?}", ft))
-                .unwrap_or_else(|| "...".into()),
-            self.last_per_second
-                .map(|fps| format!("{:3.3}", fps))
-                .unwrap_or_else(|| "...".into()),
-        )
+    pub fn dump_json<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_json(&mut w)?;
+        w.flush()
     }
 }
 
-impl Deref for Timer {
-    type Target = Instant;
-
-    fn deref(&self) -> &Self::Target {
-        &self.begin
-    }
+fn as_ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
 }
@@ -0,0 +1,227 @@
+use crate::{frame::Frame, DepthMode};
+use glam::{Mat3, Mat4, Quat, Vec3, Vec4};
+
+//
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PerspectiveConfig {
+    /// Vertical field of view in radians
+    pub fov_y: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrthographicConfig {
+    /// Visible height in world units,
+    /// the width comes from the aspect ratio
+    pub height: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective(PerspectiveConfig),
+    Orthographic(OrthographicConfig),
+}
+
+/// View and projection matrices of a camera or a camera controller
+pub trait CameraView {
+    /// World to view space
+    fn view(&self) -> Mat4;
+
+    /// View to Vulkan clip space, see `Projection::matrix`
+    fn projection(&self, aspect: f32) -> Mat4;
+
+    /// Projection * view
+    fn view_projection(&self, aspect: f32) -> Mat4 {
+        self.projection(aspect) * self.view()
+    }
+}
+
+/// Camera with a right handed +Y up world
+///
+/// The projection flips Y for the Vulkan clip space,
+/// so no negative up vectors are needed. Front faces
+/// are counter clockwise, like `GPipelineBuilder` expects.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub projection: Projection,
+    pub position: Vec3,
+    /// Camera looks towards -Z of this rotation
+    pub rotation: Quat,
+    pub aspect: f32,
+    /// Has to match the `DepthMode` of the renderer
+    pub depth_mode: DepthMode,
+}
+
+//
+
+impl Default for PerspectiveConfig {
+    fn default() -> Self {
+        Self {
+            fov_y: 1.0,
+            near: 0.01,
+            far: 100.0,
+        }
+    }
+}
+
+impl Default for OrthographicConfig {
+    fn default() -> Self {
+        Self {
+            height: 2.0,
+            near: -1.0,
+            far: 1.0,
+        }
+    }
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Projection::Perspective(PerspectiveConfig::default())
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::perspective(PerspectiveConfig::default())
+    }
+}
+
+/// Right handed perspective projection with depth 1 at `near`
+/// and depth 0 at infinity, for `DepthMode::ReversedZ`
+///
+/// Like `Mat4::perspective_rh`, Y is not flipped.
+pub fn perspective_reversed_rh(fov_y: f32, aspect: f32, near: f32) -> Mat4 {
+    Mat4::perspective_infinite_reverse_rh(fov_y, aspect, near)
+}
+
+impl Projection {
+    /// Vulkan clip space projection (Y down)
+    ///
+    /// With `DepthMode::ReversedZ` the perspective far plane is at infinity.
+    pub fn matrix(&self, aspect: f32, depth_mode: DepthMode) -> Mat4 {
+        let projection = match *self {
+            Projection::Perspective(PerspectiveConfig { fov_y, near, far }) => match depth_mode {
+                DepthMode::Standard => Mat4::perspective_rh(fov_y, aspect, near, far),
+                DepthMode::ReversedZ => perspective_reversed_rh(fov_y, aspect, near),
+            },
+            Projection::Orthographic(OrthographicConfig { height, near, far }) => {
+                let (x, y) = (height * aspect * 0.5, height * 0.5);
+                match depth_mode {
+                    DepthMode::Standard => Mat4::orthographic_rh(-x, x, -y, y, near, far),
+                    DepthMode::ReversedZ => Mat4::orthographic_rh(-x, x, -y, y, far, near),
+                }
+            }
+        };
+
+        // the only Y flip between the +Y up world and Vulkan
+        Mat4::from_scale(Vec3::new(1.0, -1.0, 1.0)) * projection
+    }
+}
+
+impl Camera {
+    pub fn perspective(config: PerspectiveConfig) -> Self {
+        Self {
+            projection: Projection::Perspective(config),
+            position: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            aspect: 1.0,
+            depth_mode: DepthMode::Standard,
+        }
+    }
+
+    pub fn orthographic(config: OrthographicConfig) -> Self {
+        Self {
+            projection: Projection::Orthographic(config),
+            ..Self::perspective(PerspectiveConfig::default())
+        }
+    }
+
+    pub fn with_position(mut self, position: Vec3) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: Quat) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_aspect(mut self, aspect: f32) -> Self {
+        self.aspect = aspect;
+        self
+    }
+
+    /// Use `Renderer::depth_mode`
+    pub fn with_depth_mode(mut self, depth_mode: DepthMode) -> Self {
+        self.depth_mode = depth_mode;
+        self
+    }
+
+    /// Aspect ratio from the window size
+    pub fn update_aspect(&mut self, frame: &Frame) {
+        self.aspect = frame.aspect();
+    }
+
+    /// Rotate towards `target`
+    pub fn look_at(&mut self, target: Vec3, up: Vec3) {
+        let view = Mat4::look_at_rh(self.position, target, up);
+        self.rotation = Quat::from_mat3(&Mat3::from_mat4(view).transpose());
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        self.rotation * -Vec3::Z
+    }
+
+    pub fn view(&self) -> Mat4 {
+        Mat4::from_rotation_translation(self.rotation, self.position).inverse()
+    }
+
+    pub fn projection_matrix(&self) -> Mat4 {
+        self.projection.matrix(self.aspect, self.depth_mode)
+    }
+
+    /// Projection * view
+    pub fn vp(&self) -> Mat4 {
+        self.projection_matrix() * self.view()
+    }
+
+    /// Left, right, bottom, top, near and far planes
+    /// in clip space order
+    ///
+    /// Points with `plane.xyz.dot(p) + plane.w >= 0.0`
+    /// for every plane are inside of the frustum.
+    pub fn frustum_planes(&self) -> [Vec4; 6] {
+        let vp = self.vp();
+        let (x, y, z, w) = (vp.row(0), vp.row(1), vp.row(2), vp.row(3));
+
+        // depth is 0..1, so the plane at depth 0 is just z
+        let (near, far) = match self.depth_mode {
+            DepthMode::Standard => (z, w - z),
+            DepthMode::ReversedZ => (w - z, z),
+        };
+
+        [w + x, w - x, w + y, w - y, near, far].map(|plane| {
+            let length = plane.truncate().length();
+            if length > 0.0 {
+                plane / length
+            } else {
+                // the far plane at infinity, nothing is behind it
+                Vec4::W
+            }
+        })
+    }
+}
+
+impl CameraView for Camera {
+    fn view(&self) -> Mat4 {
+        Camera::view(self)
+    }
+
+    fn projection(&self, aspect: f32) -> Mat4 {
+        self.projection.matrix(aspect, self.depth_mode)
+    }
+}
@@ -0,0 +1,240 @@
+use glam::{Mat4, Quat, Vec3};
+
+//
+
+pub mod camera;
+
+pub use camera::{Camera, CameraView, OrthographicConfig, PerspectiveConfig, Projection};
+
+//
+
+/// Local translation, rotation and scale of a node
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+pub type NodeId = usize;
+
+/// Transform hierarchy
+///
+/// Nodes refer to their parents by index. World matrices
+/// are cached and only recomputed for nodes whose own
+/// transform or any ancestor's transform has changed.
+#[derive(Debug, Default)]
+pub struct SceneGraph {
+    nodes: Vec<Node>,
+    /// Parents before children
+    order: Vec<NodeId>,
+    order_dirty: bool,
+}
+
+#[derive(Debug)]
+struct Node {
+    parent: Option<NodeId>,
+    local: Transform,
+    world: Mat4,
+    dirty: bool,
+    changed: bool,
+}
+
+//
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    pub fn from_translation(translation: Vec3) -> Self {
+        Self {
+            translation,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn from_rotation(rotation: Quat) -> Self {
+        Self {
+            rotation,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn from_scale(scale: Vec3) -> Self {
+        Self {
+            scale,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn with_translation(mut self, translation: Vec3) -> Self {
+        self.translation = translation;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: Quat) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Vec3) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Scale, then rotate, then translate
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// New node without a parent
+    pub fn add(&mut self, local: Transform) -> NodeId {
+        self.nodes.push(Node {
+            parent: None,
+            local,
+            world: Mat4::IDENTITY,
+            dirty: true,
+            changed: false,
+        });
+        self.order_dirty = true;
+        self.nodes.len() - 1
+    }
+
+    pub fn add_child(&mut self, parent: NodeId, local: Transform) -> NodeId {
+        let node = self.add(local);
+        self.nodes[node].parent = Some(parent);
+        node
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn parent(&self, node: NodeId) -> Option<NodeId> {
+        self.nodes[node].parent
+    }
+
+    /// Returns false and keeps the old parent
+    /// if `node` would become its own ancestor
+    pub fn set_parent(&mut self, node: NodeId, parent: Option<NodeId>) -> bool {
+        let mut ancestor = parent;
+        while let Some(id) = ancestor {
+            if id == node {
+                return false;
+            }
+            ancestor = self.nodes[id].parent;
+        }
+
+        self.nodes[node].parent = parent;
+        self.nodes[node].dirty = true;
+        self.order_dirty = true;
+        true
+    }
+
+    pub fn transform(&self, node: NodeId) -> &Transform {
+        &self.nodes[node].local
+    }
+
+    /// Marks the node and its children dirty
+    pub fn transform_mut(&mut self, node: NodeId) -> &mut Transform {
+        let node = &mut self.nodes[node];
+        node.dirty = true;
+        &mut node.local
+    }
+
+    pub fn set_transform(&mut self, node: NodeId, local: Transform) {
+        *self.transform_mut(node) = local;
+    }
+
+    /// Recompute the dirty world matrices in one pass
+    ///
+    /// Returns the number of recomputed matrices.
+    pub fn update(&mut self) -> usize {
+        if self.order_dirty {
+            self.rebuild_order();
+        }
+
+        let mut recomputed = 0;
+        for &id in self.order.iter() {
+            let (parent_world, parent_changed) = match self.nodes[id].parent {
+                Some(parent) => (self.nodes[parent].world, self.nodes[parent].changed),
+                None => (Mat4::IDENTITY, false),
+            };
+
+            let node = &mut self.nodes[id];
+            node.changed = node.dirty || parent_changed;
+            if node.changed {
+                node.world = parent_world * node.local.matrix();
+                node.dirty = false;
+                recomputed += 1;
+            }
+        }
+        recomputed
+    }
+
+    /// Cached world matrix, call `update` after modifying transforms
+    pub fn world_matrix(&self, node: NodeId) -> Mat4 {
+        self.nodes[node].world
+    }
+
+    /// Updates and iterates `(node, world matrix)` in hierarchy order
+    pub fn iter(&mut self) -> impl ExactSizeIterator<Item = (NodeId, Mat4)> + '_ {
+        self.update();
+        let nodes = &self.nodes;
+        self.order.iter().map(move |&id| (id, nodes[id].world))
+    }
+
+    /// `iter` with the world matrices multiplied by `view_projection`
+    pub fn iter_mvp(
+        &mut self,
+        view_projection: Mat4,
+    ) -> impl ExactSizeIterator<Item = (NodeId, Mat4)> + '_ {
+        self.iter()
+            .map(move |(id, world)| (id, view_projection * world))
+    }
+
+    /// `iter_mvp` with the view projection of `camera`
+    pub fn iter_camera(
+        &mut self,
+        camera: &Camera,
+    ) -> impl ExactSizeIterator<Item = (NodeId, Mat4)> + '_ {
+        self.iter_mvp(camera.vp())
+    }
+
+    fn rebuild_order(&mut self) {
+        let mut children = vec![Vec::new(); self.nodes.len()];
+        let mut stack = Vec::new();
+        for (id, node) in self.nodes.iter().enumerate().rev() {
+            match node.parent {
+                Some(parent) => children[parent].push(id),
+                None => stack.push(id),
+            }
+        }
+
+        self.order.clear();
+        while let Some(id) = stack.pop() {
+            self.order.push(id);
+            stack.extend(children[id].iter().copied());
+        }
+        self.order_dirty = false;
+    }
+}
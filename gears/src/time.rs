@@ -0,0 +1,416 @@
+use crate::UpdateRate;
+use std::{
+    ops::{Add, Deref},
+    time::{Duration, Instant},
+};
+
+//
+
+const AVERAGE_SAMPLES: usize = 32;
+
+/// Measures the time since it was started and between laps
+#[derive(Debug, Clone, Copy)]
+pub struct Stopwatch {
+    start: Instant,
+    lap: Instant,
+}
+
+/// Values that `MovingAverage` can average
+pub trait Average: Copy + Default + Add<Output = Self> {
+    fn div_count(self, count: u32) -> Self;
+}
+
+/// Average of the last `N` samples
+#[derive(Debug, Clone)]
+pub struct MovingAverage<T, const N: usize> {
+    samples: [T; N],
+    len: usize,
+    next: usize,
+}
+
+/// Counts samples and reports their average and rate
+/// once per report interval
+///
+/// The game loop keeps its frame and update
+/// counters in `State` with these.
+pub struct RateReporter {
+    count: u32,
+    elapsed: Duration,
+    report_timer: Instant,
+    report_interval: Duration,
+
+    last_interval: Option<Duration>,
+    last_per_second: Option<f64>,
+
+    sample: Option<Duration>,
+    average: MovingAverage<Duration, AVERAGE_SAMPLES>,
+}
+
+pub struct Timer {
+    begin: Instant,
+}
+
+/// Adds the time from `new` to drop into a `RateReporter`
+///
+/// ```ignore
+/// {
+///     let _timer = ScopedTimer::new("remesh", state.span_reporter("remesh"));
+///     self.re_mesh();
+/// }
+/// ```
+pub struct ScopedTimer<'a> {
+    name: &'static str,
+    reporter: &'a mut RateReporter,
+    stopwatch: Stopwatch,
+}
+
+/// Counts the fixed updates of an `UpdateRate` that are due
+///
+/// The time is kept in whole nanoseconds times the updates per
+/// period, so rates without a whole nanosecond interval, e.g.
+/// `PerSecond(144)`, do not drift from the requested rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpdateAccumulator {
+    // `count` updates every `period` nanoseconds
+    period: u128,
+    count: u128,
+    // elapsed nanoseconds times `count`
+    lag: u128,
+}
+
+//
+
+impl Default for Stopwatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Stopwatch {
+    /// Started stopwatch
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            start: now,
+            lap: now,
+        }
+    }
+
+    /// Time since `new` or `reset`
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Time since the previous lap, starts the next one
+    pub fn lap(&mut self) -> Duration {
+        let now = Instant::now();
+        let lap = now - self.lap;
+        self.lap = now;
+        lap
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl Average for f32 {
+    fn div_count(self, count: u32) -> Self {
+        self / count as f32
+    }
+}
+
+impl Average for f64 {
+    fn div_count(self, count: u32) -> Self {
+        self / count as f64
+    }
+}
+
+impl Average for Duration {
+    fn div_count(self, count: u32) -> Self {
+        self / count
+    }
+}
+
+impl<T: Average, const N: usize> Default for MovingAverage<T, N> {
+    fn default() -> Self {
+        Self {
+            samples: [T::default(); N],
+            len: 0,
+            next: 0,
+        }
+    }
+}
+
+impl<T: Average, const N: usize> MovingAverage<T, N> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces the oldest sample once `N` samples have been pushed
+    pub fn push(&mut self, sample: T) {
+        if N == 0 {
+            return;
+        }
+        self.samples[self.next] = sample;
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+    }
+
+    /// `None` before the first sample
+    pub fn average(&self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        let sum = self.samples[..self.len]
+            .iter()
+            .fold(T::default(), |sum, &sample| sum + sample);
+        Some(sum.div_count(self.len as u32))
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.next = 0;
+    }
+}
+
+impl Default for RateReporter {
+    fn default() -> Self {
+        Self::new_with_interval(Duration::from_secs(3))
+    }
+}
+
+impl RateReporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn new_with_interval(report_interval: Duration) -> Self {
+        Self {
+            count: 0_u32,
+            elapsed: Duration::default(),
+            report_timer: Instant::now(),
+            report_interval,
+
+            last_interval: None,
+            last_per_second: None,
+
+            sample: None,
+            average: MovingAverage::new(),
+        }
+    }
+
+    pub fn begin(&self) -> Timer {
+        Timer {
+            begin: Instant::now(),
+        }
+    }
+
+    pub fn end(&mut self, timer: Timer) -> bool {
+        self.manual(timer.begin.elapsed())
+    }
+
+    pub fn manual(&mut self, elapsed: Duration) -> bool {
+        self.elapsed += elapsed;
+        self.count += 1;
+        self.sample = Some(elapsed);
+        self.average.push(elapsed);
+
+        let should_report = self.should_report();
+        if should_report {
+            self.reset();
+        }
+        should_report
+    }
+
+    pub fn should_report(&self) -> bool {
+        self.report_timer.elapsed() >= self.report_interval
+    }
+
+    pub fn report_interval(&self) -> Duration {
+        self.report_interval
+    }
+
+    pub fn reset(&mut self) {
+        let avg = self.elapsed / self.count.max(1);
+        let fps = self.count as f64 / self.report_interval.as_secs_f64();
+
+        self.count = 0;
+        self.elapsed = Duration::default();
+        self.report_timer = Instant::now();
+        self.last_interval = Some(avg);
+        self.last_per_second = Some(fps);
+    }
+
+    /// Average and rate of the previous report interval
+    pub fn last(&self) -> Option<(Duration, f64)> {
+        Some((self.last_interval?, self.last_per_second?))
+    }
+
+    /// Average of the last 32 measurements,
+    /// updates with every measurement unlike `last`
+    pub fn average(&self) -> Option<Duration> {
+        self.average.average()
+    }
+
+    /// The latest single measurement, if there was one after the previous take
+    pub fn take_sample(&mut self) -> Option<Duration> {
+        self.sample.take()
+    }
+
+    pub fn last_string(&self) -> (String, String) {
+        (
+            self.last_interval
+                .map(|ft| format!("{:3.3?}", ft))
+                .unwrap_or_else(|| "...".into()),
+            self.last_per_second
+                .map(|fps| format!("{:3.3}", fps))
+                .unwrap_or_else(|| "...".into()),
+        )
+    }
+}
+
+impl Deref for Timer {
+    type Target = Instant;
+
+    fn deref(&self) -> &Self::Target {
+        &self.begin
+    }
+}
+
+impl<'a> ScopedTimer<'a> {
+    pub fn new(name: &'static str, reporter: &'a mut RateReporter) -> Self {
+        Self {
+            name,
+            reporter,
+            stopwatch: Stopwatch::new(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Time since `new`
+    pub fn elapsed(&self) -> Duration {
+        self.stopwatch.elapsed()
+    }
+}
+
+impl UpdateAccumulator {
+    pub fn new(rate: UpdateRate) -> Self {
+        let (period, count) = match rate {
+            UpdateRate::PerSecond(n) => (1_000_000_000, n as u128),
+            UpdateRate::PerMinute(n) => (60_000_000_000, n as u128),
+            UpdateRate::Interval(interval) => (interval.as_nanos(), 1),
+        };
+        Self {
+            period: period.max(1),
+            count: count.max(1),
+            lag: 0,
+        }
+    }
+
+    pub fn add(&mut self, elapsed: Duration) {
+        self.lag += elapsed.as_nanos() * self.count;
+    }
+
+    /// Take one due update
+    pub fn tick(&mut self) -> bool {
+        let due = self.lag >= self.period;
+        if due {
+            self.lag -= self.period;
+        }
+        due
+    }
+
+    /// Updates due
+    pub fn due(&self) -> u64 {
+        (self.lag / self.period) as u64
+    }
+
+    /// Drop `count` due updates
+    pub fn skip(&mut self, count: u64) {
+        self.lag = self.lag.saturating_sub(self.period * count as u128);
+    }
+
+    /// Drop every due update, keeping the progress towards the next one
+    pub fn skip_due(&mut self) {
+        self.lag %= self.period;
+    }
+
+    pub fn reset(&mut self) {
+        self.lag = 0;
+    }
+
+    /// Progress towards the next update, above 1.0 if updates are due
+    pub fn alpha(&self) -> f32 {
+        (self.lag as f64 / self.period as f64) as f32
+    }
+}
+
+impl Drop for ScopedTimer<'_> {
+    fn drop(&mut self) {
+        let elapsed = self.stopwatch.elapsed();
+        log::debug!("{} took {:?}", self.name, elapsed);
+        self.reporter.manual(elapsed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ticks over `seconds` simulated seconds of 1 ms frames
+    fn run(rate: UpdateRate, seconds: u64) -> u64 {
+        let mut accumulator = UpdateAccumulator::new(rate);
+        let mut ticks = 0;
+        for _ in 0..seconds * 1000 {
+            accumulator.add(Duration::from_millis(1));
+            while accumulator.tick() {
+                ticks += 1;
+            }
+        }
+        ticks
+    }
+
+    #[test]
+    fn per_second_exact() {
+        assert_eq!(run(UpdateRate::PerSecond(144), 1), 144);
+        assert_eq!(run(UpdateRate::PerSecond(60), 1), 60);
+        assert_eq!(run(UpdateRate::PerSecond(7), 1), 7);
+    }
+
+    #[test]
+    fn per_second_no_drift() {
+        // the rounded interval of 6944444 ns runs 64 ns ahead every second
+        let mut accumulator = UpdateAccumulator::new(UpdateRate::PerSecond(144));
+        accumulator.add(Duration::from_secs(3600));
+        assert_eq!(accumulator.due(), 144 * 3600);
+        accumulator.skip_due();
+        assert_eq!(accumulator.alpha(), 0.0);
+    }
+
+    #[test]
+    fn per_minute_exact() {
+        assert_eq!(run(UpdateRate::PerMinute(7), 60), 7);
+    }
+
+    #[test]
+    fn skip_due_keeps_fraction() {
+        let mut accumulator = UpdateAccumulator::new(UpdateRate::PerSecond(4));
+        accumulator.add(Duration::from_millis(1125));
+        assert_eq!(accumulator.due(), 4);
+        accumulator.skip_due();
+        assert_eq!(accumulator.due(), 0);
+        assert!((accumulator.alpha() - 0.5).abs() < 1e-6);
+    }
+}
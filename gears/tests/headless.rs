@@ -0,0 +1,65 @@
+//! Renderer tests that need a Vulkan capable GPU
+//!
+//! Ignored by default, run them with `cargo test -- --ignored`.
+
+use anyhow::Result;
+use gears::{
+    context::{Context, ContextGPUPick, ContextValidation},
+    debug::{DebugConfig, OnError},
+    renderer::{
+        buffer::{BufferUsage, StagedBuffer},
+        target::headless::HeadlessRenderer,
+    },
+};
+
+//
+
+fn context() -> Result<Context> {
+    // count the errors instead of panicking on the first one
+    let debug = DebugConfig::new().with_on_error(OnError::Log);
+    Ok(Context::new_with_debug(
+        ContextGPUPick::Automatic,
+        ContextValidation::WithValidation,
+        debug,
+    )?)
+}
+
+#[test]
+#[ignore]
+fn frame_without_validation_errors() -> Result<()> {
+    let context = context()?;
+    let mut renderer = HeadlessRenderer::new(&context, [64, 64])?;
+
+    let recorder = renderer.begin_frame()?;
+    let recorder = recorder.begin_render_pass().end_render_pass();
+    let capture = renderer.end_frame(recorder)?.wait()?;
+    assert_eq!((capture.width(), capture.height()), (64, 64));
+
+    // the teardown is validated too
+    drop(renderer);
+    assert_eq!(context.validation_error_count(), 0);
+    Ok(())
+}
+
+#[test]
+#[ignore]
+fn vertex_buffer_read_back() -> Result<()> {
+    const VERTICES: [[f32; 3]; 3] = [[0.0, -0.5, 0.0], [0.5, 0.5, 0.25], [-0.5, 0.5, 1.0]];
+
+    let context = context()?;
+    let mut renderer = HeadlessRenderer::new(&context, [64, 64])?;
+    let buffer = StagedBuffer::from_iter(
+        &renderer.device,
+        BufferUsage::vertex_buffer(),
+        VERTICES.iter().copied(),
+    )?;
+
+    let mut recorder = renderer.begin_frame()?;
+    buffer.update(&mut recorder)?;
+    let mut recorder = recorder.begin_render_pass().end_render_pass();
+    let readback = buffer.read_back(&mut recorder)?;
+    renderer.end_frame(recorder)?.wait()?;
+
+    assert_eq!(readback.wait()?, VERTICES);
+    Ok(())
+}